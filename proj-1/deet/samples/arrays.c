@@ -0,0 +1,21 @@
+#include <stdio.h>
+
+int numbers[8] = {1, 2, 3};
+char greeting[16] = "hi \"there\"\n";
+int grid[2][3] = {{1, 2, 3}, {4, 5, 6}};
+
+struct Buffer {
+    int len;
+    char data[4];
+};
+
+int main() {
+    int squares[300];
+    for (int i = 0; i < 300; i++) {
+        squares[i] = i * i;
+    }
+    int *p = squares + 2;
+    struct Buffer buffer = {3, "abc"};
+    printf("%d %d %s %d %s\n", numbers[2], *p, greeting, grid[1][2], buffer.data);
+    return 0;
+}
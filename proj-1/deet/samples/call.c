@@ -0,0 +1,27 @@
+#include <stdio.h>
+
+int calls = 0;
+
+int add(int a, int b) {
+    calls++;
+    return a + b;
+}
+
+long length(const char *s) {
+    long n = 0;
+    while (s[n] != '\0') {
+        n++;
+    }
+    return n;
+}
+
+void reset(void) {
+    calls = 0;
+}
+
+int main() {
+    const char *greeting = "hello";
+    int sum = add(1, 2);
+    printf("sum = %d, length = %ld, calls = %d\n", sum, length(greeting), calls);
+    return 0;
+}
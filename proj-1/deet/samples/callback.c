@@ -0,0 +1,13 @@
+#include <stdio.h>
+
+int apply(int (*func)(int), int value);
+
+int callback(int value) {
+    int result = value * 2;
+    printf("callback(%d) = %d\n", value, result);
+    return result;
+}
+
+int main() {
+    printf("apply returned %d\n", apply(callback, 21));
+}
@@ -0,0 +1,22 @@
+#include <stdio.h>
+
+/* Adds up the numbers below n. */
+int sum_below(int n)
+{
+    int total = 0;
+
+    // Count down so that the loop ends at zero
+    for (int i = n - 1; i > 0; i--) {
+        total += i;
+    }
+
+    /*
+     * The result is n * (n - 1) / 2.
+     */
+    return total;
+}
+
+int main() {
+    printf("%d\n", sum_below(10));
+    return 0;
+}
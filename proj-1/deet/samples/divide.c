@@ -0,0 +1,10 @@
+#include <stdio.h>
+
+int divide(int a, int b) {
+    return a / b;
+}
+
+int main() {
+    printf("About to divide by zero...\n");
+    printf("%d\n", divide(42, 0));
+}
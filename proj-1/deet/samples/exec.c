@@ -0,0 +1,12 @@
+#include <stdio.h>
+#include <unistd.h>
+
+int main(int argc, char *argv[]) {
+    if (argc < 2) {
+        fprintf(stderr, "usage: %s program [args...]\n", argv[0]);
+        return 3;
+    }
+    execv(argv[1], &argv[1]);
+    perror("execv");
+    return 1;
+}
@@ -0,0 +1,15 @@
+#include <stdio.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+int main() {
+    pid_t pid = fork();
+    if (pid == 0) {
+        printf("Hello from the child\n");
+        return 3;
+    }
+    int status;
+    waitpid(pid, &status, 0);
+    printf("Child exited with status %d\n", WEXITSTATUS(status));
+    return WEXITSTATUS(status);
+}
@@ -0,0 +1,4 @@
+int work(int n) {
+    int doubled = n * 2;
+    return doubled + 1;
+}
@@ -0,0 +1,15 @@
+#include <stdio.h>
+
+long weigh(int count, long scale, char tag) {
+    int doubled = count * 2;
+    long total = doubled * scale;
+    char label[8] = "weights";
+    label[0] = tag;
+    printf("%s: %ld\n", label, total);
+    return total;
+}
+
+int main() {
+    weigh(21, 1000, 'W');
+    return 0;
+}
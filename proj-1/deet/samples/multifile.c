@@ -0,0 +1,12 @@
+#include <stdio.h>
+
+int work(int n);
+
+int main() {
+    int total = 0;
+    for (int i = 0; i < 3; i++) {
+        total += work(i);
+    }
+    printf("total = %d\n", total);
+    return 0;
+}
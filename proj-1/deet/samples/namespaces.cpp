@@ -0,0 +1,18 @@
+#include <cstdio>
+
+namespace geometry {
+
+int scale(int value, int factor) {
+    return value * factor;
+}
+
+int scale(int value) {
+    return scale(value, 2);
+}
+
+}  // namespace geometry
+
+int main() {
+    printf("%d\n", geometry::scale(21));
+    return 0;
+}
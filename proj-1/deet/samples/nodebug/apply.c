@@ -0,0 +1,4 @@
+// Built without -g (see the Makefile), so deet has no debug info for this frame.
+int apply(int (*func)(int), int value) {
+    return func(value) + 1;
+}
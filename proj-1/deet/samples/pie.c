@@ -0,0 +1,13 @@
+#include <stdio.h>
+
+int counter = 0;
+
+void bump(int amount) {
+    counter += amount;
+    printf("counter = %d\n", counter);
+}
+
+int main() {
+    bump(3);
+    bump(4);
+}
@@ -0,0 +1,22 @@
+#include <stdio.h>
+
+__attribute__((noinline)) int bottom(int depth) {
+    printf("Reached the bottom at depth %d\n", depth);
+    return depth;
+}
+
+__attribute__((noinline)) int recurse(int n, int depth) {
+    int result;
+    if (n == 0) {
+        result = bottom(depth);
+    } else {
+        result = recurse(n - 1, depth + 1);
+    }
+    printf("Returning from depth %d\n", depth);
+    return result + n;
+}
+
+int main() {
+    printf("Total: %d\n", recurse(3, 0));
+    return 0;
+}
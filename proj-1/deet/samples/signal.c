@@ -0,0 +1,16 @@
+#include <signal.h>
+#include <stdio.h>
+#include <unistd.h>
+
+volatile sig_atomic_t handled = 0;
+
+void handler(int sig) {
+    handled = 1;
+}
+
+int main() {
+    signal(SIGUSR1, handler);
+    kill(getpid(), SIGUSR1);
+    printf("handler %s\n", handled ? "ran" : "did not run");
+    return handled ? 0 : 1;
+}
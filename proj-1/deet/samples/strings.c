@@ -0,0 +1,13 @@
+#include <stdio.h>
+
+const char *greeting = "Hello, world!";
+const char *dangling = (const char *)0x10;
+
+void greet(const char *name) {
+    printf("%s, %s\n", greeting, name);
+}
+
+int main() {
+    greet("deet");
+    return 0;
+}
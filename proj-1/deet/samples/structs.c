@@ -0,0 +1,45 @@
+#include <stdio.h>
+
+struct Point {
+    int x;
+    int y;
+};
+
+struct Rect {
+    struct Point origin;
+    struct Point size;
+    char label;
+};
+
+struct Node {
+    int value;
+    struct Node *next;
+};
+
+struct Flags {
+    unsigned ready : 1;
+    unsigned mode : 3;
+};
+
+union Number {
+    int i;
+    float f;
+};
+
+struct Rect global_rect = {{1, 2}, {30, 40}, 'r'};
+struct Node *no_node = NULL;
+
+int area(struct Rect *rect) {
+    return rect->size.x * rect->size.y;
+}
+
+int main() {
+    struct Point point = {3, 7};
+    struct Node tail = {2, NULL};
+    struct Node head = {1, &tail};
+    struct Point *ptr = &point;
+    struct Flags flags = {1, 5};
+    union Number number = {42};
+    printf("%d %d %d %d\n", ptr->x + head.next->value, flags.mode, number.i, area(&global_rect));
+    return 0;
+}
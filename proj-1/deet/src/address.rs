@@ -0,0 +1,115 @@
+//! Address expressions of the form `<base>[+|-<offset>]`, e.g. `0x401136`, `main+0x1a`,
+//! `buffer+8` or `$rsp-16`. The base is a symbol, a register or a hex address; the offset is a
+//! decimal or `0x`-prefixed hexadecimal integer.
+
+use crate::condition;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AddressExpr {
+    pub base: String,
+    pub offset: i64,
+}
+
+/// Parses a hex address, with or without a `0x` prefix.
+fn parse_address(addr: &str) -> Option<usize> {
+    let addr_without_0x = if addr.to_lowercase().starts_with("0x") {
+        &addr[2..]
+    } else {
+        addr
+    };
+    usize::from_str_radix(addr_without_0x, 16).ok()
+}
+
+impl AddressExpr {
+    pub fn parse(expr: &str) -> Result<AddressExpr, String> {
+        // The first character can't be the operator: it belongs to the base
+        let (base, offset) = match expr
+            .char_indices()
+            .skip(1)
+            .find(|&(_, c)| c == '+' || c == '-')
+        {
+            Some((i, sign)) => {
+                let digits = expr[i + 1..].trim();
+                let offset = condition::parse_literal(digits)
+                    .filter(|_| !digits.starts_with('-'))
+                    .ok_or_else(|| format!("Failed to parse offset \"{}\" in {}.", digits, expr))?;
+                (&expr[..i], if sign == '-' { -offset } else { offset })
+            }
+            None => (expr, 0),
+        };
+        let base = base.trim();
+        if base.is_empty() || base == "$" || base.starts_with(['+', '-']) {
+            return Err(format!("Missing address before the offset in {}.", expr));
+        }
+        Ok(AddressExpr {
+            base: base.to_string(),
+            offset,
+        })
+    }
+
+    /// Computes the address, looking up symbols and registers through the given functions. A
+    /// base that isn't a known symbol is read as a hex address.
+    pub fn evaluate<S, R>(&self, lookup_symbol: S, read_register: R) -> Result<usize, String>
+    where
+        S: Fn(&str) -> Option<usize>,
+        R: Fn(&str) -> Result<usize, String>,
+    {
+        let base = if let Some(register) = self.base.strip_prefix('$') {
+            read_register(register)?
+        } else if let Some(addr) = lookup_symbol(&self.base) {
+            addr
+        } else {
+            parse_address(&self.base).ok_or_else(|| {
+                format!(
+                    "No symbol \"{}\" in the debug info, and it isn't a valid address.",
+                    self.base
+                )
+            })?
+        };
+        Ok(base.wrapping_add(self.offset as usize))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn eval(expr: &str) -> Result<usize, String> {
+        AddressExpr::parse(expr)?.evaluate(
+            |symbol| (symbol == "main").then_some(0x401136),
+            |register| match register {
+                "rsp" => Ok(0x7ffc1000),
+                _ => Err(format!("Unknown register ${}", register)),
+            },
+        )
+    }
+
+    #[test]
+    fn test_parse_offsets() {
+        assert_eq!(
+            AddressExpr::parse("main+0x1a").unwrap(),
+            AddressExpr {
+                base: "main".to_string(),
+                offset: 0x1a
+            }
+        );
+        assert_eq!(AddressExpr::parse("$rsp - 16").unwrap().offset, -16);
+        assert_eq!(AddressExpr::parse("0x401136").unwrap().offset, 0);
+        assert!(AddressExpr::parse("main+").is_err());
+        assert!(AddressExpr::parse("main+-4").is_err());
+        assert!(AddressExpr::parse("+4").is_err());
+    }
+
+    #[test]
+    fn test_evaluate() {
+        assert_eq!(eval("main+0x1a"), Ok(0x401150));
+        assert_eq!(eval("main+26"), Ok(0x401150));
+        assert_eq!(eval("$rsp-16"), Ok(0x7ffc0ff0));
+        // Bare hex, as before symbols were supported
+        assert_eq!(eval("401136"), Ok(0x401136));
+        assert_eq!(eval("0x401136+4"), Ok(0x40113a));
+        let err = eval("nosuch+4").unwrap_err();
+        assert!(err.contains("\"nosuch\""), "{}", err);
+        assert!(eval("$foo+4").unwrap_err().contains("$foo"));
+    }
+}
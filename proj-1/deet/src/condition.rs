@@ -0,0 +1,172 @@
+//! Breakpoint conditions, which are expressions (see `expression`) such as `$rdi == 5`,
+//! `n * 2 == 50` or `*p > 0x10`. A condition holds when its value is nonzero.
+
+use crate::expression::{Context, Expr};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Condition {
+    /// The condition as it was typed, for showing it back
+    text: String,
+    expr: Expr,
+}
+
+/// Parses a decimal or `0x`-prefixed hexadecimal integer, optionally negative. Returns None for
+/// values that don't fit in an i64 (including hex literals above `i64::MAX`).
+pub fn parse_literal(token: &str) -> Option<i64> {
+    let (negative, digits) = match token.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, token),
+    };
+    // The parsers below would accept a sign of their own
+    if digits.contains(['+', '-']) {
+        return None;
+    }
+    let value = if let Some(hex) = digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        i64::from_str_radix(hex, 16).ok()?
+    } else {
+        digits.parse::<i64>().ok()?
+    };
+    if negative {
+        value.checked_neg()
+    } else {
+        Some(value)
+    }
+}
+
+impl Condition {
+    /// Parses a condition such as `$rdi == 5`.
+    pub fn parse(condition: &str) -> Result<Condition, String> {
+        let expr = Expr::parse(condition)?;
+        if let Expr::Repeat(..) = expr {
+            return Err("Cannot use an artificial array as a condition.".to_string());
+        }
+        Ok(Condition {
+            text: condition.trim().to_string(),
+            expr,
+        })
+    }
+
+    /// Evaluates the condition, reading its operands through `context`.
+    pub fn evaluate(&self, context: &dyn Context) -> Result<bool, String> {
+        Ok(self.expr.evaluate(context)? != 0)
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A process with %rdi = `rdi`, and `x` = `x` stored at 0x1000.
+    struct FakeProcess {
+        rdi: i64,
+        x: i64,
+    }
+
+    impl Context for FakeProcess {
+        fn variable(&self, name: &str) -> Result<i64, String> {
+            self.read_word(self.address_of(name)?)
+        }
+
+        fn address_of(&self, name: &str) -> Result<i64, String> {
+            match name {
+                "x" => Ok(0x1000),
+                _ => Err(format!("No symbol \"{}\" in current context.", name)),
+            }
+        }
+
+        fn register(&self, name: &str) -> Result<i64, String> {
+            match name {
+                "rdi" => Ok(self.rdi),
+                _ => Err(format!("Unknown register ${}", name)),
+            }
+        }
+
+        fn read_word(&self, addr: i64) -> Result<i64, String> {
+            match addr {
+                0x1000 => Ok(self.x),
+                _ => Err(format!("Cannot access memory at address {:#x}", addr)),
+            }
+        }
+
+        fn element(&self, base: &Expr, index: i64) -> Result<i64, String> {
+            self.read_word(base.evaluate(self)? + index * 8)
+        }
+    }
+
+    fn eval(condition: &str, rdi: i64, x: i64) -> bool {
+        Condition::parse(condition)
+            .expect("Failed to parse condition")
+            .evaluate(&FakeProcess { rdi, x })
+            .expect("Failed to evaluate condition")
+    }
+
+    #[test]
+    fn test_parse_keeps_text() {
+        let condition = Condition::parse(" x!=-3 ").unwrap();
+        assert_eq!(condition.to_string(), "x!=-3");
+        assert_eq!(condition, Condition::parse("x!=-3").unwrap());
+        assert_ne!(condition, Condition::parse("x != -3").unwrap());
+    }
+
+    #[test]
+    fn test_hex_and_decimal_literals() {
+        assert_eq!(parse_literal("0x1f"), Some(31));
+        assert_eq!(parse_literal("0X10"), Some(16));
+        assert_eq!(parse_literal("42"), Some(42));
+        assert_eq!(parse_literal("-0x2"), Some(-2));
+        assert_eq!(parse_literal("0xzz"), None);
+        assert_eq!(parse_literal("0x7fffffffffffffff"), Some(i64::MAX));
+        assert_eq!(parse_literal("0x8000000000000000"), None);
+        assert_eq!(parse_literal("-0x8000000000000000"), None);
+        assert_eq!(parse_literal("0xffffffffffffffff"), None);
+        assert_eq!(parse_literal("0x-5"), None);
+        assert_eq!(parse_literal("--5"), None);
+    }
+
+    #[test]
+    fn test_comparison_operators() {
+        assert!(eval("$rdi == 5", 5, 0));
+        assert!(!eval("$rdi == 5", 4, 0));
+        assert!(eval("$rdi != 5", 4, 0));
+        assert!(!eval("$rdi != 5", 5, 0));
+        assert!(eval("x < 10", 0, 9));
+        assert!(!eval("x < 10", 0, 10));
+        assert!(eval("x > 10", 0, 11));
+        assert!(!eval("x > 10", 0, 10));
+        assert!(eval("x <= 10", 0, 10));
+        assert!(eval("x >= 10", 0, 10));
+        assert!(eval("$rdi > x", 3, 2));
+        assert!(eval("$rdi == 0x10", 16, 0));
+        assert!(eval("x == 16", 0, 16));
+    }
+
+    #[test]
+    fn test_expressions() {
+        assert!(eval("x * 2 == 50", 0, 25));
+        assert!(!eval("x * 2 == 50", 0, 24));
+        assert!(eval("*&x == 3", 0, 3));
+        assert!(eval("$rdi + 1 > x & x != 0", 5, 5));
+        assert!(eval("x", 0, 1));
+        assert!(!eval("x", 0, 0));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(Condition::parse("$rdi 5").is_err());
+        assert!(Condition::parse("$ == 5").is_err());
+        assert!(Condition::parse("x == 0xg").is_err());
+        assert!(Condition::parse("x = 5").is_err());
+        assert!(Condition::parse("x == ").is_err());
+        assert!(Condition::parse("x@2").is_err());
+    }
+}
@@ -0,0 +1,147 @@
+//! Parsing of deet's command line.
+
+/// How a debugging session should be set up, as given on the command line
+#[derive(Debug, PartialEq)]
+pub struct DebuggerConfig {
+    /// Path of the program to debug
+    pub target: String,
+    /// Arguments to pass to the program when it is started (what `set args` changes)
+    pub args: Vec<String>,
+    /// Core file to examine instead of running the program
+    pub core: Option<String>,
+    /// Command files to run (`-x`), in the order given
+    pub scripts: Vec<String>,
+    /// Whether to run the commands in the `.deetrc` files
+    pub load_init_files: bool,
+    /// Whether to exit once the command files have run, instead of prompting for commands
+    pub batch: bool,
+}
+
+/// What deet was asked to do
+#[derive(Debug, PartialEq)]
+pub enum Invocation {
+    Debug(DebuggerConfig),
+    Help,
+    Version,
+}
+
+impl DebuggerConfig {
+    /// Returns the configuration for debugging `target` with no arguments and default settings.
+    pub fn new(target: &str) -> DebuggerConfig {
+        DebuggerConfig {
+            target: target.to_string(),
+            args: Vec::new(),
+            core: None,
+            scripts: Vec::new(),
+            load_init_files: true,
+            batch: false,
+        }
+    }
+
+    /// Parses the command-line arguments that follow the program name. Everything after
+    /// `--args <program>` is passed to the program as is, even if it looks like an option.
+    pub fn parse(args: &[String]) -> Result<Invocation, String> {
+        let mut config = DebuggerConfig::new("");
+        let mut target = None;
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            let mut value = || {
+                iter.next()
+                    .cloned()
+                    .ok_or_else(|| format!("Option {} requires an argument.", arg))
+            };
+            match arg.as_str() {
+                "-h" | "--help" => return Ok(Invocation::Help),
+                "-v" | "--version" => return Ok(Invocation::Version),
+                "--core" => config.core = Some(value()?),
+                "-x" => config.scripts.push(value()?),
+                "--no-init" => config.load_init_files = false,
+                "--batch" => config.batch = true,
+                "--args" => {
+                    if let Some(target) = target {
+                        return Err(format!("Program {} given before --args.", target));
+                    }
+                    target = Some(value()?);
+                    config.args = iter.by_ref().cloned().collect();
+                }
+                _ if arg.starts_with('-') && arg.len() > 1 => {
+                    return Err(format!("Unrecognized option {}.", arg))
+                }
+                _ if target.is_none() => target = Some(arg.clone()),
+                _ => {
+                    return Err(format!(
+                        "Unexpected argument {} (use --args to pass arguments to the program).",
+                        arg
+                    ))
+                }
+            }
+        }
+        config.target = target.ok_or("No program to debug was given.")?;
+        if config.core.is_some() && !config.args.is_empty() {
+            return Err("A core file can't be examined with program arguments.".to_string());
+        }
+        Ok(Invocation::Debug(config))
+    }
+}
+
+/// Returns the `--help` text, with `program` as the name deet was run as.
+pub fn usage(program: &str) -> String {
+    format!(
+        "Usage: {0} [options] <program> [--core <core file>]
+       {0} [options] --args <program> [arguments...]
+
+Options:
+  --args <program> ...  Debug <program>, passing it the arguments that follow
+  --core <file>         Examine a core file of the program instead of running it
+  -x <file>             Run the commands in <file> (may be given more than once)
+  --no-init             Don't run the commands in ~/.deetrc and ./.deetrc
+  --batch               Exit after running the command files instead of prompting
+  -h, --help            Print this help and exit
+  -v, --version         Print the version of deet and exit",
+        program
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(line: &str) -> Result<Invocation, String> {
+        let args: Vec<String> = line.split_whitespace().map(String::from).collect();
+        DebuggerConfig::parse(&args)
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            parse("samples/hello"),
+            Ok(Invocation::Debug(DebuggerConfig::new("samples/hello")))
+        );
+        assert_eq!(
+            parse("--no-init -x a.deet --batch -x b.deet --core core.1 prog"),
+            Ok(Invocation::Debug(DebuggerConfig {
+                core: Some("core.1".to_string()),
+                scripts: vec!["a.deet".to_string(), "b.deet".to_string()],
+                load_init_files: false,
+                batch: true,
+                ..DebuggerConfig::new("prog")
+            }))
+        );
+        assert_eq!(
+            parse("--batch --args ./prog -x --help arg"),
+            Ok(Invocation::Debug(DebuggerConfig {
+                args: vec!["-x".to_string(), "--help".to_string(), "arg".to_string()],
+                batch: true,
+                ..DebuggerConfig::new("./prog")
+            }))
+        );
+        assert_eq!(parse("prog --help"), Ok(Invocation::Help));
+        assert_eq!(parse("--version"), Ok(Invocation::Version));
+        assert!(parse("").is_err());
+        assert!(parse("--verbose prog").is_err());
+        assert!(parse("prog arg").is_err());
+        assert!(parse("prog -x").is_err());
+        assert!(parse("prog --args other").is_err());
+        assert!(parse("--core core.1 --args prog arg").is_err());
+    }
+}
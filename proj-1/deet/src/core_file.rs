@@ -0,0 +1,712 @@
+use crate::inferior::{self, Inferior, MemoryRegion, Target};
+use crate::proc_info;
+use nix::libc::{siginfo_t, user_regs_struct};
+use nix::sys::signal::Signal;
+use nix::unistd::{sysconf, Pid, SysconfVar};
+use object::elf::{
+    FileHeader64, ELFCLASS64, ELFDATA2LSB, ELFOSABI_NONE, EM_X86_64, ET_CORE, EV_CURRENT, NT_AUXV,
+    NT_FILE, NT_PRPSINFO, NT_PRSTATUS, NT_SIGINFO, PF_R, PF_W, PF_X, PT_LOAD, PT_NOTE,
+};
+use object::read::elf::{FileHeader, ProgramHeader};
+use object::Endianness;
+use std::fs::File;
+use std::mem::size_of;
+use std::os::unix::fs::FileExt;
+use std::path::Path;
+
+// Offsets into the x86-64 `struct elf_prstatus` and `struct elf_prpsinfo` (see
+// <linux/elfcore.h>)
+const PRSTATUS_CURSIG_OFFSET: usize = 12;
+const PRSTATUS_PID_OFFSET: usize = 32;
+const PRSTATUS_PPID_OFFSET: usize = 36;
+const PRSTATUS_PGRP_OFFSET: usize = 40;
+const PRSTATUS_SID_OFFSET: usize = 44;
+const PRSTATUS_REGS_OFFSET: usize = 112;
+const PRSTATUS_SIZE: usize = 336;
+const PRPSINFO_STATE_OFFSET: usize = 0;
+const PRPSINFO_SNAME_OFFSET: usize = 1;
+const PRPSINFO_ZOMB_OFFSET: usize = 2;
+const PRPSINFO_NICE_OFFSET: usize = 3;
+const PRPSINFO_FLAG_OFFSET: usize = 8;
+const PRPSINFO_UID_OFFSET: usize = 16;
+const PRPSINFO_GID_OFFSET: usize = 20;
+const PRPSINFO_PID_OFFSET: usize = 24;
+const PRPSINFO_PPID_OFFSET: usize = 28;
+const PRPSINFO_PGRP_OFFSET: usize = 32;
+const PRPSINFO_SID_OFFSET: usize = 36;
+const PRPSINFO_FNAME_OFFSET: usize = 40;
+const PRPSINFO_FNAME_LEN: usize = 16;
+const PRPSINFO_PSARGS_OFFSET: usize = 56;
+const PRPSINFO_PSARGS_LEN: usize = 80;
+const PRPSINFO_SIZE: usize = 136;
+
+// Sizes of the ELF structures `write_core` lays out
+const ELF_HEADER_SIZE: usize = 64;
+const PROGRAM_HEADER_SIZE: usize = 56;
+
+/// Mappings larger than this are left out of cores written by `gcore`
+const MAX_DUMPED_MAPPING: usize = 1 << 30;
+
+#[derive(Debug)]
+pub enum CoreFileError {
+    /// The core file could not be opened or mapped
+    Open(std::io::Error),
+    /// The file is not an x86-64 core dump, or is malformed
+    Format(String),
+}
+
+impl From<object::read::Error> for CoreFileError {
+    fn from(err: object::read::Error) -> Self {
+        CoreFileError::Format(err.to_string())
+    }
+}
+
+/// A PT_LOAD segment: `mem_len` bytes of the process's memory at `addr`, of which the first
+/// `file_len` were dumped at `offset` in the core file. Mappings that the kernel didn't dump
+/// (such as the program's code) have a `file_len` of 0.
+struct Segment {
+    addr: usize,
+    mem_len: usize,
+    offset: usize,
+    file_len: usize,
+    flags: u32,
+}
+
+/// A file mapped into the process, from the NT_FILE note.
+struct MappedFile {
+    start: usize,
+    end: usize,
+    offset: usize,
+    path: String,
+}
+
+/// A core dump left behind by a crashed process. Only the thread that crashed (the first
+/// NT_PRSTATUS note) is available.
+pub struct CoreFile {
+    data: memmap2::Mmap,
+    segments: Vec<Segment>,
+    mapped_files: Vec<MappedFile>,
+    registers: user_regs_struct,
+    siginfo: Option<siginfo_t>,
+    signal: i32,
+    pid: i32,
+    /// The command line the process was started with (truncated to 80 bytes by the kernel)
+    command: String,
+    load_base: usize,
+}
+
+/// Copies a C struct out of a note's description, if the description is large enough.
+fn read_struct<T>(desc: &[u8], offset: usize) -> Option<T> {
+    let bytes = desc.get(offset..offset + size_of::<T>())?;
+    // Safety: T is a plain C struct (all of whose bit patterns are valid), and bytes holds
+    // exactly size_of::<T>() bytes
+    Some(unsafe { std::ptr::read_unaligned(bytes.as_ptr() as *const T) })
+}
+
+fn read_word(desc: &[u8], index: usize) -> Option<usize> {
+    read_struct::<u64>(desc, index * 8).map(|word| word as usize)
+}
+
+/// Parses an NT_FILE note: a count and page size, then (start, end, page offset) for each mapped
+/// file, then their NUL-terminated paths.
+fn parse_mapped_files(desc: &[u8]) -> Option<Vec<MappedFile>> {
+    let count = read_word(desc, 0)?;
+    let page_size = read_word(desc, 1)?;
+    let mut paths = desc.get(8 * (2 + 3 * count)..)?.split(|&byte| byte == 0);
+    (0..count)
+        .map(|i| {
+            Some(MappedFile {
+                start: read_word(desc, 2 + 3 * i)?,
+                end: read_word(desc, 3 + 3 * i)?,
+                offset: read_word(desc, 4 + 3 * i)? * page_size,
+                path: String::from_utf8_lossy(paths.next()?).into_owned(),
+            })
+        })
+        .collect()
+}
+
+impl CoreFile {
+    /// Loads the core dump at `path`, which was left behind by a crash of `executable`.
+    pub fn open(path: &str, executable: &str) -> Result<CoreFile, CoreFileError> {
+        let file = File::open(path).map_err(CoreFileError::Open)?;
+        let data = unsafe { memmap2::Mmap::map(&file).map_err(CoreFileError::Open)? };
+        let header = FileHeader64::<Endianness>::parse(&*data)?;
+        let endian = header.endian()?;
+        if header.e_type(endian) != ET_CORE {
+            return Err(CoreFileError::Format(format!(
+                "{} is not a core dump",
+                path
+            )));
+        }
+
+        let mut segments = Vec::new();
+        let mut mapped_files = Vec::new();
+        let mut prstatus = None;
+        let mut siginfo = None;
+        let mut command = String::new();
+        for phdr in header.program_headers(endian, &*data)? {
+            match phdr.p_type(endian) {
+                PT_LOAD => segments.push(Segment {
+                    addr: phdr.p_vaddr(endian) as usize,
+                    mem_len: phdr.p_memsz(endian) as usize,
+                    offset: phdr.p_offset(endian) as usize,
+                    file_len: phdr.p_filesz(endian) as usize,
+                    flags: phdr.p_flags(endian),
+                }),
+                PT_NOTE => {
+                    let mut notes = match phdr.notes(endian, &*data)? {
+                        Some(notes) => notes,
+                        None => continue,
+                    };
+                    while let Some(note) = notes.next()? {
+                        if note.name() != b"CORE" {
+                            continue;
+                        }
+                        let desc = note.desc();
+                        match note.n_type(endian) {
+                            // The first thread listed is the one that crashed
+                            NT_PRSTATUS if prstatus.is_none() => prstatus = Some(desc),
+                            NT_SIGINFO if siginfo.is_none() => siginfo = read_struct(desc, 0),
+                            NT_PRPSINFO => {
+                                let args = desc
+                                    .get(PRPSINFO_PSARGS_OFFSET..)
+                                    .unwrap_or_default()
+                                    .iter()
+                                    .take(PRPSINFO_PSARGS_LEN)
+                                    .take_while(|&&byte| byte != 0)
+                                    .copied()
+                                    .collect::<Vec<u8>>();
+                                command = String::from_utf8_lossy(&args).trim_end().to_string();
+                            }
+                            NT_FILE => {
+                                mapped_files = parse_mapped_files(desc).ok_or_else(|| {
+                                    CoreFileError::Format("Malformed NT_FILE note".to_string())
+                                })?
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let prstatus = prstatus
+            .ok_or_else(|| CoreFileError::Format("No NT_PRSTATUS note in core".to_string()))?;
+        let malformed = || CoreFileError::Format("Malformed NT_PRSTATUS note".to_string());
+        let registers = read_struct(prstatus, PRSTATUS_REGS_OFFSET).ok_or_else(malformed)?;
+        let signal = read_struct::<u16>(prstatus, PRSTATUS_CURSIG_OFFSET).ok_or_else(malformed)?;
+        let pid = read_struct::<i32>(prstatus, PRSTATUS_PID_OFFSET).ok_or_else(malformed)?;
+
+        // Like a live process, a position-independent executable is loaded at the start of its
+        // first mapping
+        let exe_name = Path::new(executable).file_name();
+        let load_base = if inferior::is_position_independent(executable) {
+            mapped_files
+                .iter()
+                .filter(|mapped| Path::new(&mapped.path).file_name() == exe_name)
+                .map(|mapped| mapped.start)
+                .min()
+                .unwrap_or(0)
+        } else {
+            0
+        };
+
+        Ok(CoreFile {
+            data,
+            segments,
+            mapped_files,
+            registers,
+            siginfo,
+            signal: signal as i32,
+            pid,
+            command,
+            load_base,
+        })
+    }
+
+    /// Returns the process ID of the crashed process.
+    pub fn pid(&self) -> i32 {
+        self.pid
+    }
+
+    /// Returns the signal that killed the process, if it was one nix knows about.
+    pub fn signal(&self) -> Option<Signal> {
+        Signal::try_from(self.signal).ok()
+    }
+
+    /// Returns the command line of the crashed process.
+    pub fn command(&self) -> &str {
+        &self.command
+    }
+
+    /// Reads up to `len` bytes of memory at `addr`, stopping at the end of the segment or mapped
+    /// file that holds them. Returns None if `addr` is neither dumped nor backed by a readable
+    /// file.
+    fn read_chunk(&self, addr: usize, len: usize) -> Option<Vec<u8>> {
+        if let Some(segment) = self
+            .segments
+            .iter()
+            .find(|segment| segment.addr <= addr && addr < segment.addr + segment.file_len)
+        {
+            let start = segment.offset + (addr - segment.addr);
+            let end = start + len.min(segment.file_len - (addr - segment.addr));
+            return self.data.get(start..end).map(<[u8]>::to_vec);
+        }
+        // Read-only file mappings (like the program's code) usually aren't dumped, but their
+        // contents are still in the files they were mapped from
+        let mapped = self
+            .mapped_files
+            .iter()
+            .find(|mapped| mapped.start <= addr && addr < mapped.end)?;
+        let mut bytes = vec![0; len.min(mapped.end - addr)];
+        let file_offset = mapped.offset + (addr - mapped.start);
+        let read = File::open(&mapped.path)
+            .ok()?
+            .read_at(&mut bytes, file_offset as u64)
+            .ok()?;
+        if read == 0 {
+            return None;
+        }
+        bytes.truncate(read);
+        Some(bytes)
+    }
+}
+
+impl Target for CoreFile {
+    fn load_base(&self) -> usize {
+        self.load_base
+    }
+
+    fn memory_regions(&self) -> Option<Vec<MemoryRegion>> {
+        let flag = |set: bool, c: char| if set { c } else { '-' };
+        Some(
+            self.segments
+                .iter()
+                .map(|segment| MemoryRegion {
+                    start: segment.addr,
+                    end: segment.addr + segment.mem_len,
+                    perms: format!(
+                        "{}{}{}p",
+                        flag(segment.flags & PF_R != 0, 'r'),
+                        flag(segment.flags & PF_W != 0, 'w'),
+                        flag(segment.flags & PF_X != 0, 'x')
+                    ),
+                    offset: self
+                        .mapped_files
+                        .iter()
+                        .find(|mapped| mapped.start == segment.addr)
+                        .map_or(0, |mapped| mapped.offset),
+                    path: self
+                        .mapped_files
+                        .iter()
+                        .find(|mapped| mapped.start == segment.addr)
+                        .map(|mapped| mapped.path.clone())
+                        .unwrap_or_default(),
+                })
+                .collect(),
+        )
+    }
+
+    fn get_registers(&self) -> Result<user_regs_struct, nix::Error> {
+        Ok(self.registers)
+    }
+
+    fn read_memory(&self, addr: usize, len: usize) -> Result<Vec<u8>, nix::Error> {
+        let mut bytes = Vec::with_capacity(len);
+        while bytes.len() < len {
+            let chunk = self
+                .read_chunk(addr + bytes.len(), len - bytes.len())
+                .ok_or(nix::Error::EIO)?;
+            bytes.extend(chunk);
+        }
+        Ok(bytes)
+    }
+
+    fn get_siginfo(&self) -> Result<siginfo_t, nix::Error> {
+        self.siginfo.ok_or(nix::Error::ENODATA)
+    }
+}
+
+/// Views a plain C struct as its bytes.
+fn struct_bytes<T>(value: &T) -> &[u8] {
+    // Safety: any initialized value can be read as size_of::<T>() bytes
+    unsafe { std::slice::from_raw_parts(value as *const T as *const u8, size_of::<T>()) }
+}
+
+fn put(desc: &mut [u8], offset: usize, bytes: &[u8]) {
+    desc[offset..offset + bytes.len()].copy_from_slice(bytes);
+}
+
+fn align_up(value: usize, align: usize) -> usize {
+    value.div_ceil(align) * align
+}
+
+/// Appends a note named `CORE`, like the kernel's, to the contents of a PT_NOTE segment.
+fn push_note(notes: &mut Vec<u8>, n_type: u32, desc: &[u8]) {
+    notes.extend(5u32.to_le_bytes());
+    notes.extend((desc.len() as u32).to_le_bytes());
+    notes.extend(n_type.to_le_bytes());
+    notes.extend(b"CORE\0\0\0\0");
+    notes.extend(desc);
+    notes.resize(align_up(notes.len(), 4), 0);
+}
+
+/// Builds the NT_PRSTATUS note of thread `tid`. Times and signal masks are left at zero.
+fn prstatus(
+    tid: Pid,
+    registers: &user_regs_struct,
+    signal: i32,
+    stat: &proc_info::Stat,
+) -> Vec<u8> {
+    let mut desc = vec![0; PRSTATUS_SIZE];
+    // pr_info.si_signo comes first
+    put(&mut desc, 0, &signal.to_le_bytes());
+    put(
+        &mut desc,
+        PRSTATUS_CURSIG_OFFSET,
+        &(signal as u16).to_le_bytes(),
+    );
+    put(&mut desc, PRSTATUS_PID_OFFSET, &tid.as_raw().to_le_bytes());
+    put(&mut desc, PRSTATUS_PPID_OFFSET, &stat.ppid.to_le_bytes());
+    put(&mut desc, PRSTATUS_PGRP_OFFSET, &stat.pgrp.to_le_bytes());
+    put(&mut desc, PRSTATUS_SID_OFFSET, &stat.session.to_le_bytes());
+    put(&mut desc, PRSTATUS_REGS_OFFSET, struct_bytes(registers));
+    desc
+}
+
+/// Builds the NT_PRPSINFO note of process `pid`.
+fn prpsinfo(pid: Pid, stat: &proc_info::Stat, ids: (u32, u32), command: &str) -> Vec<u8> {
+    let mut desc = vec![0; PRPSINFO_SIZE];
+    let state = "RSDTZW".find(stat.state).unwrap_or(0) as u8;
+    put(&mut desc, PRPSINFO_STATE_OFFSET, &[state]);
+    put(&mut desc, PRPSINFO_SNAME_OFFSET, &[stat.state as u8]);
+    put(
+        &mut desc,
+        PRPSINFO_ZOMB_OFFSET,
+        &[(stat.state == 'Z') as u8],
+    );
+    put(&mut desc, PRPSINFO_NICE_OFFSET, &[stat.nice as i8 as u8]);
+    put(&mut desc, PRPSINFO_FLAG_OFFSET, &stat.flags.to_le_bytes());
+    put(&mut desc, PRPSINFO_UID_OFFSET, &ids.0.to_le_bytes());
+    put(&mut desc, PRPSINFO_GID_OFFSET, &ids.1.to_le_bytes());
+    put(&mut desc, PRPSINFO_PID_OFFSET, &pid.as_raw().to_le_bytes());
+    put(&mut desc, PRPSINFO_PPID_OFFSET, &stat.ppid.to_le_bytes());
+    put(&mut desc, PRPSINFO_PGRP_OFFSET, &stat.pgrp.to_le_bytes());
+    put(&mut desc, PRPSINFO_SID_OFFSET, &stat.session.to_le_bytes());
+    // Both strings keep a NUL terminator
+    let fname = stat.comm.as_bytes();
+    put(
+        &mut desc,
+        PRPSINFO_FNAME_OFFSET,
+        &fname[..fname.len().min(PRPSINFO_FNAME_LEN - 1)],
+    );
+    let psargs = command.as_bytes();
+    put(
+        &mut desc,
+        PRPSINFO_PSARGS_OFFSET,
+        &psargs[..psargs.len().min(PRPSINFO_PSARGS_LEN - 1)],
+    );
+    desc
+}
+
+/// Builds an NT_FILE note listing the file-backed mappings among `regions`.
+fn file_note(regions: &[MemoryRegion], page_size: usize) -> Vec<u8> {
+    let files: Vec<&MemoryRegion> = regions
+        .iter()
+        .filter(|region| region.path.starts_with('/'))
+        .collect();
+    let mut desc = Vec::new();
+    desc.extend((files.len() as u64).to_le_bytes());
+    desc.extend((page_size as u64).to_le_bytes());
+    for region in &files {
+        for word in [region.start, region.end, region.offset / page_size] {
+            desc.extend((word as u64).to_le_bytes());
+        }
+    }
+    for region in &files {
+        desc.extend(region.path.as_bytes());
+        desc.push(0);
+    }
+    desc
+}
+
+/// Returns why `region` shouldn't be dumped, if it shouldn't: reading device memory may have side
+/// effects, and huge mappings (such as sanitizers' shadow memory) make for useless cores.
+fn skip_reason(region: &MemoryRegion) -> Option<String> {
+    if region.path.starts_with("/dev/") {
+        return Some("device mapping".to_string());
+    }
+    let len = region.end - region.start;
+    if len > MAX_DUMPED_MAPPING {
+        return Some(format!("{} MiB mapping", len >> 20));
+    }
+    None
+}
+
+/// Writes a core file of the stopped `inferior` to `path`, in the layout the kernel uses: a
+/// PT_NOTE segment with the registers of every thread (the current one first), the process's
+/// details, the pending signal, the auxiliary vector and the mapped files, then a PT_LOAD segment
+/// for every mapping. Mappings that are unreadable, device-backed or huge are recorded without
+/// their contents. Planted breakpoints are left out of the dumped code. Returns warnings about
+/// the mappings that were left out.
+pub fn write_core(inferior: &Inferior, path: &str) -> Result<Vec<String>, String> {
+    let pid = inferior.pid();
+    let regions = inferior
+        .memory_regions()
+        .ok_or("Cannot read the process's memory mappings.")?;
+    let stat =
+        proc_info::stat(pid).map_err(|err| format!("Cannot read process status: {}", err))?;
+    let ids = proc_info::ids(pid).map_err(|err| format!("Cannot read process status: {}", err))?;
+    let command = proc_info::cmdline(pid).unwrap_or_default().join(" ");
+    let page_size = sysconf(SysconfVar::PAGE_SIZE)
+        .ok()
+        .flatten()
+        .map_or(4096, |size| size as usize);
+
+    let mut warnings = Vec::new();
+    let mut contents = Vec::with_capacity(regions.len());
+    for region in &regions {
+        if !region.perms.starts_with('r') {
+            contents.push(Vec::new());
+            continue;
+        }
+        if let Some(reason) = skip_reason(region) {
+            warnings.push(format!(
+                "Not dumping {:#x}-{:#x} {} ({}).",
+                region.start, region.end, region.path, reason
+            ));
+            contents.push(Vec::new());
+            continue;
+        }
+        let len = region.end - region.start;
+        match inferior.read_code(inferior.to_link_time(region.start), len) {
+            Ok(bytes) => contents.push(bytes),
+            Err(err) => {
+                // The kernel never lets anyone read [vvar], so there is no point in mentioning it
+                if !region.path.starts_with("[vvar") {
+                    warnings.push(format!(
+                        "Cannot read {:#x}-{:#x} {}: {}",
+                        region.start, region.end, region.path, err
+                    ));
+                }
+                contents.push(Vec::new());
+            }
+        }
+    }
+
+    let signal = inferior.pending_signal().map_or(0, |signal| signal as i32);
+    let current = inferior.current_thread();
+    let threads = std::iter::once(current).chain(
+        inferior
+            .threads()
+            .iter()
+            .copied()
+            .filter(|&tid| tid != current),
+    );
+    let mut notes = Vec::new();
+    for (i, tid) in threads.enumerate() {
+        let registers = inferior
+            .thread_registers(tid)
+            .map_err(|err| format!("Cannot read the registers of thread {}: {}", tid, err))?;
+        push_note(
+            &mut notes,
+            NT_PRSTATUS,
+            &prstatus(tid, &registers, signal, &stat),
+        );
+        // As in the kernel's cores, the process-wide notes follow the first thread's
+        if i > 0 {
+            continue;
+        }
+        push_note(
+            &mut notes,
+            NT_PRPSINFO,
+            &prpsinfo(pid, &stat, ids, &command),
+        );
+        if signal != 0 {
+            if let Ok(siginfo) = inferior.get_siginfo() {
+                push_note(&mut notes, NT_SIGINFO, struct_bytes(&siginfo));
+            }
+        }
+        if let Ok(auxv) = proc_info::auxv(pid) {
+            push_note(&mut notes, NT_AUXV, &auxv);
+        }
+        push_note(&mut notes, NT_FILE, &file_note(&regions, page_size));
+    }
+
+    let num_headers = 1 + regions.len();
+    let notes_offset = ELF_HEADER_SIZE + num_headers * PROGRAM_HEADER_SIZE;
+    let mut header = Vec::with_capacity(notes_offset + notes.len());
+    header.extend(b"\x7fELF");
+    header.extend([ELFCLASS64, ELFDATA2LSB, EV_CURRENT, ELFOSABI_NONE]);
+    header.resize(16, 0);
+    header.extend(ET_CORE.to_le_bytes());
+    header.extend(EM_X86_64.to_le_bytes());
+    header.extend(u32::from(EV_CURRENT).to_le_bytes());
+    header.extend(0u64.to_le_bytes()); // e_entry
+    header.extend((ELF_HEADER_SIZE as u64).to_le_bytes()); // e_phoff
+    header.extend(0u64.to_le_bytes()); // e_shoff
+    header.extend(0u32.to_le_bytes()); // e_flags
+    header.extend((ELF_HEADER_SIZE as u16).to_le_bytes());
+    header.extend((PROGRAM_HEADER_SIZE as u16).to_le_bytes());
+    header.extend((num_headers as u16).to_le_bytes());
+    header.extend([0; 6]); // e_shentsize, e_shnum, e_shstrndx
+
+    let mut program_header = |p_type: u32,
+                              flags: u32,
+                              offset: usize,
+                              addr: usize,
+                              file_len: usize,
+                              mem_len: usize,
+                              align: usize| {
+        header.extend(p_type.to_le_bytes());
+        header.extend(flags.to_le_bytes());
+        for word in [offset, addr, 0, file_len, mem_len, align] {
+            header.extend((word as u64).to_le_bytes());
+        }
+    };
+    program_header(PT_NOTE, 0, notes_offset, 0, notes.len(), 0, 4);
+    let mut offset = align_up(notes_offset + notes.len(), page_size);
+    let mut offsets = Vec::with_capacity(regions.len());
+    for (region, bytes) in regions.iter().zip(&contents) {
+        let flag = |c: char, flag: u32| if region.perms.contains(c) { flag } else { 0 };
+        let flags = flag('r', PF_R) | flag('w', PF_W) | flag('x', PF_X);
+        program_header(
+            PT_LOAD,
+            flags,
+            offset,
+            region.start,
+            bytes.len(),
+            region.end - region.start,
+            page_size,
+        );
+        offsets.push(offset);
+        offset = align_up(offset + bytes.len(), page_size);
+    }
+    header.extend(&notes);
+
+    let write_error = |err: std::io::Error| format!("Cannot write {}: {}", path, err);
+    let file = File::create(path).map_err(write_error)?;
+    file.write_all_at(&header, 0).map_err(write_error)?;
+    for (bytes, offset) in contents.iter().zip(offsets) {
+        file.write_all_at(bytes, offset as u64)
+            .map_err(write_error)?;
+    }
+    Ok(warnings)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dwarf_data::DwarfData;
+    use std::os::unix::process::{CommandExt, ExitStatusExt};
+    use std::process::Command;
+
+    /// Runs samples/segfault in a scratch directory with core dumps enabled, and returns the
+    /// path of the core it leaves behind, or None if the system sends cores elsewhere (e.g. to
+    /// systemd-coredump).
+    fn dump_core() -> Option<String> {
+        let program = format!("{}/samples/segfault", env!("CARGO_MANIFEST_DIR"));
+        let dir = std::env::temp_dir().join(format!("deet-core-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let status = unsafe {
+            Command::new(&program)
+                .current_dir(&dir)
+                .stdout(std::process::Stdio::null())
+                .pre_exec(|| {
+                    let unlimited = nix::libc::rlimit {
+                        rlim_cur: nix::libc::RLIM_INFINITY,
+                        rlim_max: nix::libc::RLIM_INFINITY,
+                    };
+                    nix::libc::setrlimit(nix::libc::RLIMIT_CORE, &unlimited);
+                    Ok(())
+                })
+                .status()
+                .expect("Could not run samples/segfault. Have you run make?")
+        };
+        assert_eq!(status.signal(), Some(nix::libc::SIGSEGV));
+        let core = std::fs::read_dir(&dir)
+            .unwrap()
+            .filter_map(Result::ok)
+            .find(|entry| entry.file_name().to_string_lossy().starts_with("core"))?;
+        Some(core.path().to_string_lossy().into_owned())
+    }
+
+    #[test]
+    fn test_backtrace_from_core() {
+        let core_path = match dump_core() {
+            Some(core_path) => core_path,
+            None => {
+                println!("No core dump was written; check /proc/sys/kernel/core_pattern");
+                return;
+            }
+        };
+        let core = CoreFile::open(&core_path, "samples/segfault").unwrap();
+        std::fs::remove_dir_all(Path::new(&core_path).parent().unwrap()).unwrap();
+        assert_eq!(core.signal(), Some(Signal::SIGSEGV));
+        assert!(core.command().ends_with("samples/segfault"));
+        assert_eq!(
+            core.describe_fault(Signal::SIGSEGV).unwrap().unwrap(),
+            "Segmentation fault: invalid access at 0x0000000000000000"
+        );
+
+        let dwarf_data = DwarfData::from_file("samples/segfault").unwrap();
+        let frames = core.stack_frames(&dwarf_data, 10).unwrap();
+        let formatted: Vec<String> = frames
+            .iter()
+            .enumerate()
+            .map(|(num, frame)| core.format_frame(frame, num, &dwarf_data))
+            .collect();
+        assert_eq!(formatted.len(), 3, "unexpected backtrace: {:?}", formatted);
+        assert!(formatted[0].contains(" in func2 (a=2) at "));
+        assert!(formatted[1].contains(" in func1 (a=42) at "));
+        assert!(formatted[2].contains(" in main () at "));
+
+        // Code isn't dumped, so it has to come from the executable
+        let main_addr = dwarf_data.get_addr_for_function(None, "main").unwrap();
+        assert_eq!(core.read_memory(main_addr, 1).unwrap(), [0x55]);
+        assert!(core.read_memory(0x10, 1).is_err());
+    }
+
+    #[test]
+    fn test_write_core() {
+        let mut inferior = Inferior::new(
+            "samples/segfault",
+            &[],
+            inferior::InferiorStdio::default(),
+            true,
+        )
+        .expect("Could not run samples/segfault. Have you run make?");
+        assert!(matches!(
+            inferior.wake_and_wait(),
+            Ok(inferior::Status::Stopped(Signal::SIGSEGV, _))
+        ));
+        let path = std::env::temp_dir().join(format!("deet-gcore-test-{}", std::process::id()));
+        let path = path.to_str().unwrap();
+        write_core(&inferior, path).unwrap();
+        let core = CoreFile::open(path, "samples/segfault").unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(core.pid(), inferior.pid().as_raw());
+        assert_eq!(core.signal(), Some(Signal::SIGSEGV));
+        assert!(core.command().ends_with("samples/segfault"));
+        assert_eq!(
+            core.get_registers().unwrap().rip,
+            inferior.get_registers().unwrap().rip
+        );
+        assert_eq!(
+            core.describe_fault(Signal::SIGSEGV).unwrap().unwrap(),
+            "Segmentation fault: invalid access at 0x0000000000000000"
+        );
+
+        let dwarf_data = DwarfData::from_file("samples/segfault").unwrap();
+        let frames = core.stack_frames(&dwarf_data, 10).unwrap();
+        assert_eq!(frames.len(), 3);
+        assert!(core
+            .format_frame(&frames[1], 1, &dwarf_data)
+            .contains(" in func1 (a=42) at "));
+        // Unlike the kernel, gcore dumps code too
+        let main_addr = dwarf_data.get_addr_for_function(None, "main").unwrap();
+        assert_eq!(core.read_memory(main_addr, 1).unwrap(), [0x55]);
+        let _ = inferior.kill();
+    }
+}
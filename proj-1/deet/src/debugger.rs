@@ -1,31 +1,345 @@
-use crate::debugger_command::DebuggerCommand;
-use crate::dwarf_data::{DwarfData, Error as DwarfError};
-use crate::inferior::{Inferior, Status};
+use crate::address::AddressExpr;
+use crate::condition::{self, Condition};
+use crate::config::DebuggerConfig;
+use crate::core_file::{self, CoreFile, CoreFileError};
+use crate::debugger_command::{self, DebuggerCommand, FindFormat, MemoryFormat, Redirection};
+use crate::demangle;
+use crate::disassemble;
+use crate::dwarf_data::{
+    DwarfData, Encoding, Error as DwarfError, Line, LineLookupError, Location, Type, TypeKind,
+    Variable,
+};
+use crate::expression::{self, Expr};
+use crate::inferior::{
+    ptrace_event_name, Frame, Inferior, InferiorError, InferiorStdio, Status, Target,
+    MAX_BACKTRACE_FRAMES, MAX_CALL_ARGS, MAX_WATCHPOINTS,
+};
+use crate::memory_search;
+use crate::output::{self, outputln, Output};
+use crate::proc_info;
+use crate::registers::RegisterFile;
+use crate::shared_library::SharedLibrary;
+use crate::signal_policy::{self, SignalTable};
+use crate::source::{self, SourceCache};
+use crate::syscalls;
+use crate::value;
+use nix::sys::signal::Signal;
+use nix::unistd::Pid;
 use rustyline::error::ReadlineError;
 use rustyline::history::FileHistory;
 use rustyline::Editor;
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
 
-fn parse_address(addr: &str) -> Option<usize> {
-    let addr_without_0x = if addr.to_lowercase().starts_with("0x") {
-        &addr[2..]
-    } else {
-        &addr
-    };
-    usize::from_str_radix(addr_without_0x, 16).ok()
+/// Most instructions shown by one `disassemble` (or `disassemble more`)
+const DISASSEMBLE_LIMIT: usize = 32;
+
+/// How deeply `source` commands may nest, so that a script sourcing itself can't recurse forever
+const MAX_SOURCE_DEPTH: usize = 8;
+
+/// How many stops in a row may run breakpoint commands that resume the inferior before deet
+/// returns to the prompt, so that commands which keep hitting their own breakpoint can't loop
+/// forever
+const MAX_BREAKPOINT_COMMAND_CHAIN: usize = 1000;
+
+/// Script run at startup, from the home directory and then the working directory
+const INIT_FILE_NAME: &str = ".deetrc";
+
+/// Where commands come from: a terminal, with line editing and history, or anything else (a
+/// pipe or a file), read line by line without a prompt.
+enum CommandInput {
+    Terminal {
+        readline: Box<Editor<(), FileHistory>>,
+        history_path: String,
+    },
+    Piped,
+}
+
+/// Largest artificial array (`p@n`) that `print` reads, in bytes
+const MAX_VALUE_SIZE: usize = 65536;
+
+/// Longest string read from the inferior by `x/s`, `print/s`, or when printing a `char *`
+const MAX_STRING_LEN: usize = 200;
+
+/// Renders the NUL-terminated string at `addr` as a C string literal, or as
+/// `<unreadable at addr>` if the process's memory there can't be read.
+fn format_cstring(process: &dyn Target, addr: usize) -> String {
+    match process.read_cstring(addr, MAX_STRING_LEN) {
+        Ok(bytes) => value::format_cstring(&bytes, MAX_STRING_LEN),
+        Err(_) => format!("<unreadable at {:#x}>", addr),
+    }
+}
+
+/// Reads register `name` (without the `$`) of the current thread of `process`.
+fn read_register(process: &dyn Target, name: &str) -> Result<usize, String> {
+    let regs = process.get_registers().map_err(|err| err.to_string())?;
+    RegisterFile::new(regs)
+        .get(name)
+        .map(|value| value as usize)
+}
+
+/// Splits a `file:line` or `library:function` location at its last colon, skipping the `::` in
+/// C++ and Rust names like `geometry::scale`.
+fn split_location(location: &str) -> Option<(&str, &str)> {
+    let bytes = location.as_bytes();
+    let colon = (0..bytes.len()).rev().find(|&i| {
+        bytes[i] == b':' && bytes.get(i + 1) != Some(&b':') && (i == 0 || bytes[i - 1] != b':')
+    })?;
+    Some((&location[..colon], &location[colon + 1..]))
+}
+
+/// Returns true if `name` matches `pattern`, a regular expression made of literal characters,
+/// `.`, `*` and the anchors `^` and `$`. Without anchors, the pattern may match anywhere in the
+/// name, so a plain substring works as a pattern too.
+fn matches_pattern(pattern: &str, name: &str) -> bool {
+    fn match_here(pattern: &[char], text: &[char]) -> bool {
+        match pattern {
+            [] => true,
+            [c, '*', rest @ ..] => {
+                // Try every number of repetitions of `c`, shortest first
+                let mut text = text;
+                loop {
+                    if match_here(rest, text) {
+                        return true;
+                    }
+                    match text.split_first() {
+                        Some((&first, remaining)) if *c == '.' || *c == first => text = remaining,
+                        _ => return false,
+                    }
+                }
+            }
+            ['$'] => text.is_empty(),
+            [c, rest @ ..] => match text.split_first() {
+                Some((&first, remaining)) if *c == '.' || *c == first => {
+                    match_here(rest, remaining)
+                }
+                _ => false,
+            },
+        }
+    }
+
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = name.chars().collect();
+    if let Some(('^', anchored)) = pattern.split_first() {
+        return match_here(anchored, &text);
+    }
+    (0..=text.len()).any(|start| match_here(&pattern, &text[start..]))
+}
+
+/// Names system call `num`, falling back to its number if it isn't in the table.
+fn syscall_display_name(num: usize) -> String {
+    match syscalls::syscall_name(num) {
+        Some(name) => name.to_string(),
+        None => format!("syscall_{}", num),
+    }
+}
+
+pub struct Breakpoint {
+    /// Location the breakpoint was set at, as given to `break`
+    pub location: String,
+    /// Link-time address, or None while the breakpoint is pending because its location doesn't
+    /// resolve (yet)
+    pub addr: Option<usize>,
+    /// If set, the inferior only stops here when the condition evaluates to true
+    pub condition: Option<Condition>,
+    /// Temporary breakpoints are deleted the first time they are hit
+    pub temporary: bool,
+    /// Number of upcoming hits to skip without stopping
+    pub ignore_count: usize,
+    /// Number of hits skipped due to the ignore count since the last time we stopped here
+    pub skipped_hits: usize,
+    /// Commands run whenever the inferior stops here, set with `commands`
+    pub commands: Vec<String>,
+}
+
+pub struct Watchpoint {
+    /// What is being watched: a variable name or `*<address>`
+    pub expression: String,
+    /// Run-time address of the watched location
+    pub addr: usize,
+    /// Type used to print the watched value
+    pub entity_type: Type,
+    /// True if the expression is a local variable, whose address is only valid in one frame
+    pub local: bool,
+    /// Value of the location when the inferior was last resumed
+    pub old_value: Vec<u8>,
+    /// Debug register slot the watchpoint occupies in the running inferior, or None for a
+    /// software watchpoint, which is checked by single-stepping
+    pub slot: Option<usize>,
+}
+
+/// A value read from the inferior for `print`, with its type and, if it is in memory, its address
+struct TypedValue {
+    addr: Option<usize>,
+    bytes: Vec<u8>,
+    entity_type: Type,
+}
+
+/// A snapshot of the inferior saved by `checkpoint`
+pub struct Checkpoint {
+    /// A fork of the inferior, kept stopped where the inferior was
+    pub inferior: Inferior,
+    /// Where that is, as described by `describe_stop`
+    pub location: String,
+}
+
+/// A function call started by `call` that hasn't returned yet
+struct PendingCall {
+    /// The call as shown to the user, e.g. `add(1, 2)`
+    expression: String,
+    /// Type of the return value, or None if the function returns void
+    return_type: Option<Type>,
+}
+
+/// Returns true if values of type `entity_type` are passed and returned in a general-purpose
+/// register, which are the only ones `call` supports.
+fn is_word_type(entity_type: &Type) -> bool {
+    entity_type.size <= 8
+        && match entity_type.kind {
+            TypeKind::Base(encoding) => encoding != Encoding::Float,
+            TypeKind::Pointer(_) => true,
+            _ => false,
+        }
+}
+
+/// Which system calls the inferior stops at, as set with `catch syscall`
+#[derive(Clone, Copy, PartialEq)]
+enum SyscallCatch {
+    Off,
+    All,
+    /// Only the system call with this number
+    Only(usize),
+}
+
+/// Events the inferior stops at when a catchpoint is set on them with `catch`
+#[derive(Clone, Copy, PartialEq)]
+enum CatchEvent {
+    Exec,
+    Exit,
+}
+
+impl CatchEvent {
+    fn name(self) -> &'static str {
+        match self {
+            CatchEvent::Exec => "exec",
+            CatchEvent::Exit => "exit",
+        }
+    }
+}
+
+/// What executing a command did, for code that drives the debugger rather than a user reading
+/// its output (which is printed either way)
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CommandOutcome {
+    /// The command reported an error
+    pub failed: bool,
+    /// The debugger should exit
+    pub quit: bool,
+    /// How the inferior last stopped or terminated while the command ran it
+    pub stop: Option<Status>,
+    /// The breakpoint that stopped the inferior, if any
+    pub breakpoint: Option<usize>,
+    /// The stack frames shown by `backtrace`
+    pub frames: Option<Vec<Frame>>,
+}
+
+/// What to do about a status reported by the woken inferior
+enum StopAction {
+    /// Wake the inferior up again without reporting anything (e.g. a breakpoint whose condition
+    /// doesn't hold)
+    Wake,
+    /// Report the stop; contains the breakpoint that was hit, if any, and whether it is temporary
+    Report(Option<(usize, bool)>),
+}
+
+/// How to go on with an inferior running in the background
+#[derive(Clone, Copy)]
+enum BackgroundStep {
+    Wake,
+    /// Check on it without blocking
+    Poll,
+    /// Block until it stops
+    Wait,
 }
 
 pub struct Debugger {
     target: String,
-    history_path: String,
-    readline: Editor<(), FileHistory>,
+    /// Arguments for the inferior, remembered between runs
+    args: Vec<String>,
+    /// Whether new inferiors are started without address space randomization
+    disable_aslr: bool,
+    /// If set, programs started by deet keep running should deet die without killing them
+    detach_on_exit: bool,
+    /// Whether to keep tracing the child rather than the parent when the inferior forks
+    follow_fork_child: bool,
+    /// Whether to report how long the inferior ran when it exits
+    show_timing: bool,
+    /// Most elements of an array that `print` shows, or None for no limit
+    max_elements: Option<usize>,
+    syscall_catch: SyscallCatch,
+    /// What to do when the inferior receives each signal
+    signal_table: SignalTable,
+    input: CommandInput,
+    /// Whether to print each command read from non-terminal input, prefixed with `+ `
+    echo_commands: bool,
+    output: Output,
     inferior: Option<Inferior>,
+    /// Whether the inferior was woken by `run &` or `continue &` and hasn't been seen to stop
+    running_in_background: bool,
+    /// Process and current thread of the inferior when it was last woken up
+    woken_thread: (Pid, Pid),
+    /// Core dump being inspected instead of a live process, if deet was started with `--core`
+    core: Option<CoreFile>,
     dwarf_data: DwarfData,
-    breakpoints: Vec<usize>,
+    /// Breakpoints keyed by their (stable) breakpoint number
+    breakpoints: BTreeMap<usize, Breakpoint>,
+    /// Hardware watchpoints, numbered along with the breakpoints
+    watchpoints: BTreeMap<usize, Watchpoint>,
+    /// Catchpoints set with `catch exec` and `catch exit`, numbered along with the breakpoints
+    catchpoints: BTreeMap<usize, CatchEvent>,
+    next_breakpoint_num: usize,
+    /// Expressions printed every time the inferior stops, keyed by their display number. They
+    /// are kept across runs.
+    displays: BTreeMap<usize, String>,
+    next_display_num: usize,
+    /// Snapshots of the inferior, keyed by their checkpoint number
+    checkpoints: BTreeMap<usize, Checkpoint>,
+    next_checkpoint_num: usize,
+    /// How the last inferior ended (e.g. "exited with status 0"), if it ended on its own
+    last_exit: Option<String>,
+    /// The same as an exit code, the way a shell reports it: 128 + the signal number if the
+    /// inferior was killed by a signal
+    last_exit_code: Option<i32>,
+    /// Exit code given to `quit`
+    quit_code: Option<i32>,
+    /// Index of the stack frame that `print`, `set`, etc. operate on (0 is innermost)
+    selected_frame: usize,
+    source_cache: SourceCache,
+    /// File and last line printed by `list`, so that a bare `list` continues from there
+    last_listing: Option<(String, usize)>,
+    /// Whether the user has been told about `directory` after a source file couldn't be found
+    source_hint_shown: bool,
+    /// Where the last `disassemble` stopped, so that `disassemble more` can go on from there: the
+    /// next address, and the end of the function being disassembled (if known)
+    last_disassembly: Option<(usize, Option<usize>)>,
+    /// What the command being executed (or the last one executed) did
+    outcome: CommandOutcome,
+    /// Number of scripts being run by (nested) `source` commands
+    source_depth: usize,
+    /// Breakpoint whose command list is being typed after `commands`, and the lines so far
+    recording_commands: Option<(usize, Vec<String>)>,
+    /// Commands of the breakpoint that stopped the inferior, still to be run
+    triggered_commands: Option<Vec<String>>,
+    /// Function call made by `call` that stopped before returning
+    pending_call: Option<PendingCall>,
 }
 
 impl Debugger {
-    /// Initializes the debugger.
-    pub fn new(target: &str) -> Debugger {
+    /// Initializes the debugger for the program and arguments in `config`.
+    pub fn new(config: &DebuggerConfig) -> Debugger {
+        let target = config.target.as_str();
         // Milestone 3: initialize the DwarfData
         let debug_data = match DwarfData::from_file(target) {
             Ok(val) => val,
@@ -39,163 +353,4942 @@ impl Debugger {
             }
         };
 
-        // FOR TEST
-        debug_data.print();
-
-        let history_path = format!("{}/.deet_history", std::env::var("HOME").unwrap());
-        let mut readline = Editor::<(), FileHistory>::new().expect("Failed to create Editor");
-        // Attempt to load history from ~/.deet_history if it exists
-        let _ = readline.load_history(&history_path);
+        // Commands piped in (from a script or an autograder) don't go through rustyline, which
+        // misbehaves without a terminal, and are kept out of the history file
+        let input = if std::io::stdin().is_terminal() {
+            let history_path = format!("{}/.deet_history", std::env::var("HOME").unwrap());
+            let mut readline = Editor::<(), FileHistory>::new().expect("Failed to create Editor");
+            // Attempt to load history from ~/.deet_history if it exists
+            let _ = readline.load_history(&history_path);
+            CommandInput::Terminal {
+                readline: Box::new(readline),
+                history_path,
+            }
+        } else {
+            CommandInput::Piped
+        };
+        let echo_commands = matches!(input, CommandInput::Piped);
 
         Debugger {
             target: target.to_string(),
-            history_path,
-            readline,
+            args: config.args.clone(),
+            disable_aslr: true,
+            detach_on_exit: false,
+            follow_fork_child: false,
+            show_timing: false,
+            max_elements: Some(value::DEFAULT_MAX_ELEMENTS),
+            syscall_catch: SyscallCatch::Off,
+            signal_table: SignalTable::new(),
+            input,
+            echo_commands,
+            output: Output::default(),
             inferior: None,
+            running_in_background: false,
+            woken_thread: (Pid::from_raw(0), Pid::from_raw(0)),
+            core: None,
             dwarf_data: debug_data,
-            breakpoints: Vec::new(),
+            breakpoints: BTreeMap::new(),
+            watchpoints: BTreeMap::new(),
+            catchpoints: BTreeMap::new(),
+            next_breakpoint_num: 0,
+            displays: BTreeMap::new(),
+            next_display_num: 0,
+            checkpoints: BTreeMap::new(),
+            next_checkpoint_num: 0,
+            last_exit: None,
+            last_exit_code: None,
+            quit_code: None,
+            selected_frame: 0,
+            source_cache: SourceCache::new(),
+            last_listing: None,
+            source_hint_shown: false,
+            last_disassembly: None,
+            outcome: CommandOutcome::default(),
+            source_depth: 0,
+            recording_commands: None,
+            triggered_commands: None,
+            pending_call: None,
+        }
+    }
+
+    /// Loads a core dump left behind by the target for post-mortem debugging, and shows where
+    /// the process crashed. Commands that only read state then work against the core.
+    pub fn load_core(&mut self, path: &str) {
+        let core = match CoreFile::open(path, &self.target) {
+            Ok(core) => core,
+            Err(CoreFileError::Open(err)) => {
+                outputln!(self.output, "Could not open core file {}: {}", path, err);
+                std::process::exit(1);
+            }
+            Err(CoreFileError::Format(err)) => {
+                outputln!(self.output, "Could not read core file {}: {}", path, err);
+                std::process::exit(1);
+            }
+        };
+        outputln!(
+            self.output,
+            "Core was generated by `{}' (pid {}).",
+            core.command(),
+            core.pid()
+        );
+        if let Some(signal) = core.signal() {
+            outputln!(
+                self.output,
+                "Program terminated with signal {}.",
+                signal.as_str()
+            );
+            if let Ok(Some(fault)) = core.describe_fault(signal) {
+                outputln!(self.output, "{}", fault);
+            }
+        }
+        self.core = Some(core);
+        self.select_frame(0);
+        if let Some(line) = self.current_line() {
+            self.print_source_line(line.address);
+        }
+        self.print_displays();
+    }
+
+    /// Runs the commands in `~/.deetrc` and then `./.deetrc`, if they exist, so that settings for
+    /// a project can build on (or override) personal ones. Returns false if one of them quit the
+    /// debugger.
+    pub fn load_init_files(&mut self) -> bool {
+        let mut paths = Vec::new();
+        if let Ok(home) = std::env::var("HOME") {
+            paths.push(Path::new(&home).join(INIT_FILE_NAME));
+        }
+        paths.push(PathBuf::from(INIT_FILE_NAME));
+        let mut loaded = Vec::new();
+        for path in paths {
+            // Missing files are skipped, and run from the home directory, both paths are the
+            // same file
+            let canonical = match fs::canonicalize(&path) {
+                Ok(canonical) => canonical,
+                Err(_) => continue,
+            };
+            if loaded.contains(&canonical) {
+                continue;
+            }
+            loaded.push(canonical);
+            if !self.source_script(&path.to_string_lossy(), false) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Runs the commands in the script at `path` as if they had been typed at the prompt,
+    /// echoing each one first if `echo` is set. Blank lines and `#` comments are skipped. The
+    /// script stops at the first command that fails. Returns false if the script quit the
+    /// debugger.
+    pub fn source_script(&mut self, path: &str, echo: bool) -> bool {
+        if self.source_depth >= MAX_SOURCE_DEPTH {
+            self.report_error(format!(
+                "Cannot source {}: scripts are nested more than {} deep.",
+                path, MAX_SOURCE_DEPTH
+            ));
+            return true;
+        }
+        let script = match fs::read_to_string(path) {
+            Ok(script) => script,
+            Err(err) => {
+                self.report_error(format!("{}: {}.", path, err));
+                return true;
+            }
+        };
+        self.source_depth += 1;
+        let mut keep_going = true;
+        for (num, line) in script.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if echo {
+                outputln!(self.output, "(deet) {}", line);
+            }
+            if self.record_command_line(line) {
+                continue;
+            }
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            keep_going = match DebuggerCommand::from_tokens(&tokens) {
+                Ok(command) => self.dispatch(command),
+                Err(err) => {
+                    self.report_error(err);
+                    true
+                }
+            };
+            if !keep_going {
+                break;
+            }
+            // The failure stays recorded, so that any script sourcing this one stops too
+            if self.outcome.failed {
+                outputln!(self.output, "Script {} stopped at line {}.", path, num + 1);
+                break;
+            }
         }
+        // A command list left open at the end of the script ends with it
+        self.finish_recording_commands();
+        self.source_depth -= 1;
+        keep_going
     }
 
     pub fn run(&mut self) {
         loop {
-            match self.get_next_command() {
-                DebuggerCommand::Quit => {
-                    self.clean();
-                    return;
+            let command = self.get_next_command();
+            if self.execute(command).quit {
+                return;
+            }
+        }
+    }
+
+    /// Executes a single command, printing its output as at the prompt, and returns what it did.
+    pub fn execute(&mut self, command: DebuggerCommand) -> CommandOutcome {
+        self.outcome = CommandOutcome::default();
+        self.outcome.quit = !self.dispatch(command);
+        self.outcome.clone()
+    }
+
+    /// Carries out `command`, followed by the commands of any breakpoint it stops at. Returns
+    /// false if the debugger should exit.
+    fn dispatch(&mut self, command: DebuggerCommand) -> bool {
+        self.dispatch_command(command) && self.run_breakpoint_commands()
+    }
+
+    /// Runs the commands of the breakpoint that last stopped the inferior. If they resume it and
+    /// it stops at another breakpoint with commands, those run next, and so on. Each list ends
+    /// early at a command that fails or resumes the inferior. Returns false if one of the
+    /// commands quit the debugger.
+    fn run_breakpoint_commands(&mut self) -> bool {
+        let mut chain = 0;
+        while let Some(commands) = self.triggered_commands.take() {
+            chain += 1;
+            if chain > MAX_BREAKPOINT_COMMAND_CHAIN {
+                outputln!(
+                    self.output,
+                    "Breakpoint commands resumed the program {} times in a row; returning to the \
+                     prompt.",
+                    MAX_BREAKPOINT_COMMAND_CHAIN
+                );
+                break;
+            }
+            for line in commands {
+                let tokens: Vec<&str> = line.split_whitespace().collect();
+                let command = match DebuggerCommand::from_tokens(&tokens) {
+                    Ok(command) => command,
+                    Err(err) => {
+                        self.report_error(err);
+                        break;
+                    }
+                };
+                let last_stop = self.outcome.stop.take();
+                if !self.dispatch_command(command) {
+                    return false;
+                }
+                // A command that resumed the inferior ends the list
+                if self.outcome.stop.is_some() {
+                    break;
+                }
+                self.outcome.stop = last_stop;
+                if self.outcome.failed {
+                    break;
+                }
+            }
+        }
+        true
+    }
+
+    /// While the command list of a breakpoint is being typed, adds `line` to it (or finishes it,
+    /// at `end`) and returns true. Returns false otherwise.
+    fn record_command_line(&mut self, line: &str) -> bool {
+        let (_, commands) = match &mut self.recording_commands {
+            Some(recording) => recording,
+            None => return false,
+        };
+        if line != "end" {
+            commands.push(line.to_string());
+            return true;
+        }
+        self.finish_recording_commands();
+        true
+    }
+
+    /// Stores the command list being typed on its breakpoint.
+    fn finish_recording_commands(&mut self) {
+        if let Some((num, commands)) = self.recording_commands.take() {
+            match self.breakpoints.get_mut(&num) {
+                Some(bp) => bp.commands = commands,
+                None => self.report_error(format!("No breakpoint number {}.", num)),
+            }
+        }
+    }
+
+    /// Carries out `command` alone. Returns false if the debugger should exit.
+    fn dispatch_command(&mut self, command: DebuggerCommand) -> bool {
+        self.outcome.failed = false;
+        self.poll_background();
+        if self.running_in_background && !Debugger::allowed_while_running(&command) {
+            self.report_error(
+                "Cannot execute this command while the program is running; use \"interrupt\" to \
+                 stop it first.",
+            );
+            return true;
+        }
+        match command {
+            DebuggerCommand::Quit(code) => {
+                self.clean();
+                self.delete_checkpoints();
+                self.quit_code = code;
+                return false;
+            }
+            DebuggerCommand::Run(..) | DebuggerCommand::Start(_) if self.core.is_some() => {
+                self.report_error(format!(
+                    "Cannot run {} while inspecting a core dump.",
+                    self.target
+                ));
+            }
+            DebuggerCommand::Run(args, background) => {
+                self.clean();
+                // Checkpoints belong to the process being replaced
+                self.delete_checkpoints();
+                if !args.is_empty() {
+                    self.args = args;
+                }
+                let (args, stdio) = match debugger_command::split_redirections(&self.args).and_then(
+                    |(args, redirections)| Ok((args, Debugger::open_redirections(&redirections)?)),
+                ) {
+                    Ok(result) => result,
+                    Err(err) => {
+                        self.report_error(err);
+                        return true;
+                    }
+                };
+
+                let randomization = if self.disable_aslr {
+                    "disabled"
+                } else {
+                    "enabled"
+                };
+                if args.is_empty() {
+                    outputln!(
+                        self.output,
+                        "Starting {} (address space randomization {})",
+                        self.target,
+                        randomization
+                    );
+                } else {
+                    outputln!(
+                        self.output,
+                        "Starting {} with args: {} (address space randomization {})",
+                        self.target,
+                        args.join(" "),
+                        randomization
+                    );
                 }
-                DebuggerCommand::Run(args) => {
-                    self.clean();
 
-                    if let Some(inferior) = Inferior::new(&self.target, &args, &self.breakpoints) {
+                match Inferior::new(&self.target, &args, stdio, self.disable_aslr) {
+                    Ok(mut inferior) => {
+                        inferior.set_catch_syscalls(self.syscall_catch != SyscallCatch::Off);
+                        inferior.set_follow_fork_child(self.follow_fork_child);
+                        if let Err(err) = inferior.set_kill_on_exit(!self.detach_on_exit) {
+                            outputln!(
+                                self.output,
+                                "Warning: failed to set ptrace options: {}",
+                                err
+                            );
+                        }
                         // Create the inferior
                         self.inferior = Some(inferior);
-                        self.wake_and_wait();
-                    } else {
-                        println!("Error starting subprocess");
+                        self.sync_catch_exit();
+                        // Hits skipped in the previous process don't count against this one
+                        for bp in self.breakpoints.values_mut() {
+                            bp.skipped_hits = 0;
+                        }
+                        self.resolve_pending_breakpoints();
+                        self.sync_breakpoints();
+                        self.install_watchpoints();
+                        self.last_exit = None;
+                        self.last_exit_code = None;
+                        if background {
+                            self.wake_in_background();
+                        } else {
+                            self.wake_and_wait();
+                        }
+                    }
+                    Err(InferiorError::Spawn(err)) => {
+                        self.report_error(format!("Failed to start {}: {}", self.target, err));
+                    }
+                    Err(InferiorError::Wait(err)) => {
+                        self.report_error(format!(
+                            "Failed to wait for {} to start: {}",
+                            self.target, err
+                        ));
+                    }
+                    Err(InferiorError::UnexpectedWaitStatus(status)) => {
+                        self.report_error(format!(
+                            "{} did not stop as expected after starting: {:?}",
+                            self.target, status
+                        ));
                     }
                 }
-                DebuggerCommand::Continue => {
-                    if self.inferior.is_none() {
-                        println!("Inferior is not running");
-                    } else {
-                        self.wake_and_wait();
+            }
+            DebuggerCommand::Attach(_) if self.core.is_some() => {
+                self.report_error("Cannot attach to a process while inspecting a core dump.");
+            }
+            DebuggerCommand::Attach(pid) => self.attach(Pid::from_raw(pid)),
+            DebuggerCommand::Detach => match self.running_inferior_mut() {
+                Ok(inferior) => {
+                    let pid = inferior.pid();
+                    match inferior.detach() {
+                        Ok(()) => outputln!(self.output, "Detached from process {}", pid),
+                        Err(err) => self.report_error(format!("Failed to detach: {}", err)),
                     }
+                    self.inferior = None;
                 }
-                DebuggerCommand::Backtrace => {
-                    let _ = self
-                        .inferior
-                        .as_mut()
-                        .unwrap()
-                        .print_backtrace(&self.dwarf_data);
+                Err(err) => self.report_error(err),
+            },
+            DebuggerCommand::Checkpoint => {
+                if let Err(err) = self.checkpoint() {
+                    self.report_error(err);
                 }
-                DebuggerCommand::Break(arg) => {
-                    let addr = if arg.starts_with("*") {
-                        parse_address(&arg[1..])
-                    } else if let Ok(line_number) = usize::from_str_radix(&arg, 10) {
-                        self.dwarf_data.get_addr_for_line(None, line_number)
-                    } else {
-                        self.dwarf_data.get_addr_for_function(None, &arg)
-                    };
-
-                    if let Some(addr) = addr {
-                        println!("Set breakpoint {} at {:#x}", self.breakpoints.len(), addr);
-                        self.breakpoints.push(addr);
-
-                        if let Some(inferior) = self.inferior.as_mut() {
-                            if let Err(err) = inferior.set_breakpoint(addr) {
-                                println!("Failed to set breakpoint in running inferior: {}", err);
-                            }
+            }
+            DebuggerCommand::Restore(num) => {
+                if let Err(err) = self.restore_checkpoint(num) {
+                    self.report_error(err);
+                }
+            }
+            DebuggerCommand::InfoCheckpoints => self.print_checkpoints(),
+            DebuggerCommand::InfoProc
+            | DebuggerCommand::InfoProcCmdline
+            | DebuggerCommand::InfoProcEnviron => {
+                if let Err(err) = self.print_proc_info(&command) {
+                    self.report_error(err);
+                }
+            }
+            DebuggerCommand::Handle(signal, actions) => {
+                if let Err(err) = self.handle_signal(&signal, &actions) {
+                    self.report_error(err);
+                }
+            }
+            DebuggerCommand::Gcore(path) => {
+                if let Err(err) = self.gcore(path) {
+                    self.report_error(err);
+                }
+            }
+            DebuggerCommand::InfoSignals(signal) => {
+                if let Err(err) = self.print_signals(signal.as_deref()) {
+                    self.report_error(err);
+                }
+            }
+            DebuggerCommand::Continue(deliver_signal, background) => {
+                match self.running_inferior_mut() {
+                    Err(err) => self.report_error(err),
+                    Ok(inferior) => {
+                        if !deliver_signal {
+                            inferior.discard_pending_signal();
+                        } else if let Some(signal) = inferior.pending_signal() {
+                            outputln!(self.output, "Continuing with signal {}.", signal.as_str());
+                        }
+                        if background {
+                            self.wake_in_background();
+                        } else {
+                            self.wake_and_wait();
                         }
-                    } else {
-                        println!(
-                            "Failed to parse {} as valid address, line number or function name.",
-                            arg
-                        );
                     }
                 }
             }
-        }
-    }
-
-    fn wake_and_wait(&mut self) {
-        // Milestone 1: make the inferior run
-        // You may use self.inferior.as_mut().unwrap() to get a mutable reference
-        // to the Inferior object
-        match self
-            .inferior
-            .as_mut()
-            .unwrap()
-            .wake_and_wait(&self.breakpoints)
-        {
-            Ok(status) => match status {
-                Status::Stopped(signal, instruction_ptr) => {
-                    println!("Child stopped (signal {})", signal.as_str());
-                    if let Some(line_number) = self.dwarf_data.get_line_from_addr(instruction_ptr) {
-                        println!("Stopped at {}", line_number);
-                    }
+            DebuggerCommand::Interrupt => {
+                if !self.running_in_background {
+                    self.report_error("The program is not running in the background.");
+                } else if let Err(err) = self.inferior.as_mut().unwrap().interrupt() {
+                    self.report_error(format!("Failed to interrupt the program: {}", err));
+                } else {
+                    self.drive_background(BackgroundStep::Wait);
                 }
-                Status::Exited(code) => {
-                    println!("Child exited (status {})", code);
+            }
+            DebuggerCommand::Status => self.print_status(),
+            DebuggerCommand::Call(call) => {
+                if let Err(err) = self.call_function(&call) {
+                    self.report_error(err);
                 }
-                Status::Signaled(signal) => {
-                    println!("Child signaled (signal {})", signal.as_str());
+            }
+            DebuggerCommand::Jump(location) => {
+                if let Err(err) = self.jump(&location) {
+                    self.report_error(err);
                 }
+            }
+            DebuggerCommand::Backtrace(limit) => match self.stopped_process() {
+                Ok(process) => match process.print_backtrace(&self.dwarf_data, limit, &self.output)
+                {
+                    Ok(frames) => self.outcome.frames = Some(frames),
+                    Err(err) => self.report_error(format!("Failed to walk the stack: {}", err)),
+                },
+                Err(err) => self.report_error(err),
             },
-            Err(_) => println!("Error waking up the inferior and waiting"),
-        }
-    }
-
-    /// Kills any existing inferiors
-    fn clean(&mut self) {
-        if self.inferior.is_some() {
-            let inferior_refmut = self.inferior.as_mut().unwrap();
-            println!("Killing running inferior (pid {})", inferior_refmut.pid());
-            match inferior_refmut.kill() {
-                Ok(_) => println!("Killed"),
-                Err(e) => println!("Failed to kill: {}", e),
+            DebuggerCommand::InfoFunctions(pattern, raw) => {
+                self.print_functions(pattern.as_deref(), raw)
             }
-        }
-    }
-
-    /// This function prompts the user to enter a command, and continues re-prompting until the user
-    /// enters a valid command. It uses DebuggerCommand::from_tokens to do the command parsing.
-    ///
-    /// You don't need to read, understand, or modify this function.
-    fn get_next_command(&mut self) -> DebuggerCommand {
-        loop {
-            // Print prompt and get next line of user input
-            match self.readline.readline("(deet) ") {
-                Err(ReadlineError::Interrupted) => {
-                    // User pressed ctrl+c. We're going to ignore it
-                    println!("Type \"quit\" to exit");
+            DebuggerCommand::InfoVariables(pattern) => self.print_variables(pattern.as_deref()),
+            DebuggerCommand::InfoSources => {
+                outputln!(self.output, "Source files with debug info:");
+                for file in self.dwarf_data.get_source_files() {
+                    outputln!(self.output, "  {}", file);
                 }
-                Err(ReadlineError::Eof) => {
-                    // User pressed ctrl+d, which is the equivalent of "quit" for our purposes
-                    return DebuggerCommand::Quit;
+            }
+            DebuggerCommand::InfoLine(location) => {
+                if let Err(err) = self.print_line_info(&location) {
+                    self.report_error(err);
                 }
-                Err(err) => {
-                    panic!("Unexpected I/O error: {:?}", err);
+            }
+            DebuggerCommand::InfoBreakpoints => self.print_breakpoint_list(),
+            DebuggerCommand::InfoLocals => {
+                if let Err(err) = self.print_locals() {
+                    self.report_error(err);
+                }
+            }
+            DebuggerCommand::InfoThreads => match self.running_inferior() {
+                Ok(inferior) => self.print_threads(inferior),
+                Err(err) => self.report_error(err),
+            },
+            DebuggerCommand::Thread(tid) => {
+                if let Err(err) = self.select_thread(tid) {
+                    self.report_error(err);
+                }
+            }
+            DebuggerCommand::Restart => {
+                return self.dispatch(DebuggerCommand::Run(Vec::new(), false));
+            }
+            DebuggerCommand::Start(args) => {
+                let location = if self
+                    .dwarf_data
+                    .get_addr_for_function(None, "main")
+                    .is_some()
+                {
+                    "main".to_string()
+                } else {
+                    let entry = self.dwarf_data.get_entry_point();
+                    outputln!(
+                        self.output,
+                        "Warning: no debug info for main; stopping at the entry point {:#x} \
+                         instead.",
+                        entry
+                    );
+                    format!("*{:#x}", entry)
+                };
+                self.add_breakpoint(&location, None, true);
+                return self.dispatch(DebuggerCommand::Run(args, false));
+            }
+            DebuggerCommand::Break(location, condition) => {
+                self.add_breakpoint(&location, condition.as_deref(), false);
+            }
+            DebuggerCommand::TemporaryBreak(location, condition) => {
+                self.add_breakpoint(&location, condition.as_deref(), true);
+            }
+            DebuggerCommand::Watch(expression) => {
+                if let Err(err) = self.add_watchpoint(&expression, true) {
+                    self.report_error(err);
                 }
-                Ok(line) => {
-                    if line.trim().len() == 0 {
-                        continue;
+            }
+            DebuggerCommand::SoftwareWatch(expression) => {
+                if let Err(err) = self.add_watchpoint(&expression, false) {
+                    self.report_error(err);
+                }
+            }
+            DebuggerCommand::CatchSyscall(name) => {
+                let catch = match name {
+                    None => {
+                        outputln!(self.output, "Catchpoint set on all system calls.");
+                        SyscallCatch::All
                     }
-                    let _ = self.readline.add_history_entry(line.as_str());
-                    if let Err(err) = self.readline.save_history(&self.history_path) {
-                        println!(
-                            "Warning: failed to save history file at {}: {}",
-                            self.history_path, err
+                    Some(name) => match syscalls::syscall_number(&name) {
+                        Some(num) => {
+                            outputln!(
+                                self.output,
+                                "Catchpoint set on system call {} ({}).",
+                                name,
+                                num
+                            );
+                            SyscallCatch::Only(num)
+                        }
+                        None => {
+                            self.report_error(format!("Unknown system call name '{}'.", name));
+                            return true;
+                        }
+                    },
+                };
+                self.set_syscall_catch(catch);
+            }
+            DebuggerCommand::CatchSyscallOff => {
+                outputln!(self.output, "No longer catching system calls.");
+                self.set_syscall_catch(SyscallCatch::Off);
+            }
+            DebuggerCommand::CatchExec => self.add_catchpoint(CatchEvent::Exec),
+            DebuggerCommand::CatchExit => self.add_catchpoint(CatchEvent::Exit),
+            DebuggerCommand::Commands(num) => {
+                let num = num.or_else(|| self.next_breakpoint_num.checked_sub(1));
+                match num.filter(|num| self.breakpoints.contains_key(num)) {
+                    Some(num) => {
+                        outputln!(
+                            self.output,
+                            "Type commands for breakpoint {}, one per line.\nEnd with a line \
+                             saying just \"end\".",
+                            num
                         );
+                        self.recording_commands = Some((num, Vec::new()));
                     }
-                    let tokens: Vec<&str> = line.split_whitespace().collect();
-                    if let Some(cmd) = DebuggerCommand::from_tokens(&tokens) {
-                        return cmd;
-                    } else {
-                        println!("Unrecognized command.");
+                    None => self.report_error(match num {
+                        Some(num) => format!("No breakpoint number {}.", num),
+                        None => "No breakpoints specified.".to_string(),
+                    }),
+                }
+            }
+            DebuggerCommand::Condition(num, condition) => {
+                if !self.breakpoints.contains_key(&num) {
+                    self.report_error(format!("No breakpoint number {}.", num));
+                    return true;
+                }
+                // An invalid condition leaves the old one in place
+                let condition = match condition.as_deref().map(Condition::parse).transpose() {
+                    Ok(condition) => condition,
+                    Err(err) => {
+                        self.report_error(err);
+                        return true;
                     }
+                };
+                if condition.is_none() {
+                    outputln!(self.output, "Breakpoint {} now unconditional.", num);
                 }
+                self.breakpoints.get_mut(&num).unwrap().condition = condition;
             }
-        }
+            DebuggerCommand::Ignore(num, count) => match self.breakpoints.get_mut(&num) {
+                Some(bp) => {
+                    bp.ignore_count = count;
+                    bp.skipped_hits = 0;
+                    match count {
+                        0 => outputln!(
+                            self.output,
+                            "Will stop next time breakpoint {} is reached.",
+                            num
+                        ),
+                        1 => outputln!(
+                            self.output,
+                            "Will ignore next crossing of breakpoint {}.",
+                            num
+                        ),
+                        _ => outputln!(
+                            self.output,
+                            "Will ignore next {} crossings of breakpoint {}.",
+                            count,
+                            num
+                        ),
+                    }
+                }
+                None => self.report_error(format!("No breakpoint number {}.", num)),
+            },
+            DebuggerCommand::Print(name, format) => {
+                if let Err(err) = self.print_variable(&name, format) {
+                    self.report_error(err);
+                }
+            }
+            DebuggerCommand::PrintString(target) => {
+                if let Err(err) = self.print_string(&target) {
+                    self.report_error(err);
+                }
+            }
+            DebuggerCommand::Examine(memory_format, addr) => {
+                if let Err(err) = self.examine_memory(memory_format, &addr) {
+                    self.report_error(err);
+                }
+            }
+            DebuggerCommand::Find(find_format, args) => {
+                if let Err(err) = self.find_memory(find_format, &args) {
+                    self.report_error(err);
+                }
+            }
+            DebuggerCommand::SetVariable(target, value) => {
+                if let Err(err) = self.set_variable(&target, &value) {
+                    self.report_error(err);
+                }
+            }
+            DebuggerCommand::Frame(num) => {
+                self.select_frame(num.unwrap_or(self.selected_frame));
+            }
+            DebuggerCommand::SetArgs(args) => self.args = args,
+            DebuggerCommand::ShowArgs => outputln!(
+                self.output,
+                "Argument list to give program being debugged when it is started is \"{}\".",
+                self.args.join(" ")
+            ),
+            DebuggerCommand::SetDisableAslr(disable) => self.disable_aslr = disable,
+            DebuggerCommand::SetTiming(show_timing) => self.show_timing = show_timing,
+            DebuggerCommand::ShowTiming => outputln!(
+                self.output,
+                "Reporting the program's run time on exit is {}.",
+                if self.show_timing { "on" } else { "off" }
+            ),
+            DebuggerCommand::SetPrintElements(max_elements) => self.max_elements = max_elements,
+            DebuggerCommand::SetPrintDemangle(demangle) => self.dwarf_data.set_demangle(demangle),
+            DebuggerCommand::ShowPrintDemangle => outputln!(
+                self.output,
+                "Demangling of C++ and Rust names is {}.",
+                if self.dwarf_data.demangles() {
+                    "on"
+                } else {
+                    "off"
+                }
+            ),
+            DebuggerCommand::ShowPrintElements => match self.max_elements {
+                Some(max) => outputln!(self.output, "Limit on array elements to print is {}.", max),
+                None => outputln!(
+                    self.output,
+                    "Limit on array elements to print is unlimited."
+                ),
+            },
+            DebuggerCommand::SetEcho(echo) => self.echo_commands = echo,
+            DebuggerCommand::ShowEcho => outputln!(
+                self.output,
+                "Echoing commands read from non-terminal input is {}.",
+                if self.echo_commands { "on" } else { "off" }
+            ),
+            DebuggerCommand::SetLogging(true, path) => {
+                let path = path.as_deref().unwrap_or(output::DEFAULT_LOG_PATH);
+                match self.output.start_log(path) {
+                    Ok(()) => outputln!(self.output, "Copying output to {}.", path),
+                    Err(err) => self.report_error(err),
+                }
+            }
+            DebuggerCommand::SetLogging(false, _) => match self.output.stop_log() {
+                Some(path) => outputln!(self.output, "Done logging to {}.", path),
+                None => outputln!(self.output, "Logging is already off."),
+            },
+            DebuggerCommand::ShowLogging => match self.output.log_path() {
+                Some(path) => outputln!(self.output, "Logging to {}.", path),
+                None => outputln!(self.output, "Logging is off."),
+            },
+            DebuggerCommand::SetDetachOnExit(detach) => {
+                self.detach_on_exit = detach;
+                match self.inferior.as_mut() {
+                    Some(inferior) if !inferior.is_attached() => {
+                        if let Err(err) = inferior.set_kill_on_exit(!detach) {
+                            self.report_error(format!("Failed to set ptrace options: {}", err));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            DebuggerCommand::ShowDetachOnExit => outputln!(
+                self.output,
+                "Leaving the program running if deet dies is {}.",
+                if self.detach_on_exit { "on" } else { "off" }
+            ),
+            DebuggerCommand::ShowDisableAslr => outputln!(
+                self.output,
+                "Disabling randomization of debuggee's virtual address space is {}.",
+                if self.disable_aslr { "on" } else { "off" }
+            ),
+            DebuggerCommand::SetFollowForkMode(follow_child) => {
+                self.follow_fork_child = follow_child;
+                if let Some(inferior) = self.inferior.as_mut() {
+                    inferior.set_follow_fork_child(follow_child);
+                }
+            }
+            DebuggerCommand::ShowFollowForkMode => outputln!(
+                self.output,
+                "Debugger response to a program call of fork or vfork is \"{}\".",
+                if self.follow_fork_child {
+                    "child"
+                } else {
+                    "parent"
+                }
+            ),
+            DebuggerCommand::List(line) => {
+                if let Err(err) = self.list_source(line) {
+                    self.report_error(err);
+                }
+            }
+            DebuggerCommand::ListFunction(name) => {
+                if let Err(err) = self.list_function(&name) {
+                    self.report_error(err);
+                }
+            }
+            DebuggerCommand::ListRange(file, first, last) => {
+                if let Err(err) = self.list_range(file.as_deref(), first, last) {
+                    self.report_error(err);
+                }
+            }
+            DebuggerCommand::Directory(path) => {
+                self.source_cache.add_directory(&path);
+                outputln!(
+                    self.output,
+                    "Source directories searched: {}",
+                    self.source_cache.directories().join(":")
+                );
+            }
+            DebuggerCommand::ShowDirectories => outputln!(
+                self.output,
+                "Source directories searched: {}",
+                self.source_cache.directories().join(":")
+            ),
+            DebuggerCommand::Disassemble(function) => {
+                if let Err(err) = self.disassemble(function.as_deref()) {
+                    self.report_error(err);
+                }
+            }
+            DebuggerCommand::DisassembleMore => match self.last_disassembly {
+                Some((start, end)) => {
+                    if let Err(err) = self.print_instructions(start, end) {
+                        self.report_error(err);
+                    }
+                }
+                None => outputln!(self.output, "Nothing more to disassemble."),
+            },
+            DebuggerCommand::Up => self.select_frame(self.selected_frame + 1),
+            DebuggerCommand::Source(path) => return self.source_script(&path, true),
+            DebuggerCommand::Down => {
+                if self.selected_frame == 0 {
+                    self.report_error("Bottom (innermost) frame selected; you cannot go down.");
+                } else {
+                    self.select_frame(self.selected_frame - 1);
+                }
+            }
+            DebuggerCommand::Display(Some(expression)) => {
+                let num = self.next_display_num;
+                self.next_display_num += 1;
+                self.displays.insert(num, expression);
+                // Like gdb, show it right away if there is something to evaluate it in
+                if self.stopped_process().is_ok() {
+                    self.print_display(num);
+                }
+            }
+            DebuggerCommand::Display(None) => self.print_displays(),
+            DebuggerCommand::Undisplay(Some(num)) => {
+                if self.displays.remove(&num).is_none() {
+                    self.report_error(format!("No display number {}.", num));
+                }
+            }
+            DebuggerCommand::Undisplay(None) => {
+                self.displays.clear();
+                outputln!(self.output, "Deleted all displays.");
+            }
+            DebuggerCommand::InfoDisplay => self.print_display_list(),
+            DebuggerCommand::Delete(num) => match num {
+                Some(num) => {
+                    if !self.delete_breakpoint(num) {
+                        self.report_error(format!("No breakpoint number {}.", num));
+                    }
+                }
+                None => {
+                    let nums: Vec<usize> = self
+                        .breakpoints
+                        .keys()
+                        .chain(self.watchpoints.keys())
+                        .chain(self.catchpoints.keys())
+                        .copied()
+                        .collect();
+                    for num in nums {
+                        self.delete_breakpoint(num);
+                    }
+                    outputln!(self.output, "Deleted all breakpoints.");
+                }
+            },
+        }
+        true
+    }
+
+    /// Implements `jump <location>`: moves the current thread to `location` (resolved like a
+    /// breakpoint location) and continues from there.
+    fn jump(&mut self, location: &str) -> Result<(), String> {
+        let inferior = self.running_inferior()?;
+        let addr = self.resolve_location(location)?;
+        let instruction_ptr = inferior
+            .thread_instruction_ptr(inferior.current_thread())
+            .map_err(|err| err.to_string())?;
+        let function_at = |addr| {
+            self.dwarf_data
+                .get_function_containing(addr)
+                .map_or("??".to_string(), |func| self.dwarf_data.function_name(func))
+        };
+        let (from, to) = (function_at(instruction_ptr), function_at(addr));
+        if from != to {
+            outputln!(
+                self.output,
+                "Warning: jumping from {} into {}. The stack still holds the frame of {}, so the \
+                 program will most likely crash.",
+                from,
+                to,
+                from
+            );
+        }
+        self.running_inferior_mut()?
+            .set_instruction_ptr(addr)
+            .map_err(|err| format!("Failed to set the instruction pointer: {}", err))?;
+        outputln!(self.output, "Continuing at {:#x}.", addr);
+        self.wake_and_wait();
+        Ok(())
+    }
+
+    /// Returns true if `command` can be executed while the inferior runs in the background, i.e.
+    /// it doesn't need to look at the inferior.
+    fn allowed_while_running(command: &DebuggerCommand) -> bool {
+        matches!(
+            command,
+            DebuggerCommand::Quit(_)
+                | DebuggerCommand::Run(..)
+                | DebuggerCommand::Start(_)
+                | DebuggerCommand::Restart
+                | DebuggerCommand::Interrupt
+                | DebuggerCommand::Status
+                | DebuggerCommand::Source(_)
+                | DebuggerCommand::SetArgs(_)
+                | DebuggerCommand::ShowArgs
+                | DebuggerCommand::SetDisableAslr(_)
+                | DebuggerCommand::ShowDisableAslr
+                | DebuggerCommand::SetTiming(_)
+                | DebuggerCommand::ShowTiming
+                | DebuggerCommand::SetPrintElements(_)
+                | DebuggerCommand::ShowPrintElements
+                | DebuggerCommand::SetPrintDemangle(_)
+                | DebuggerCommand::ShowPrintDemangle
+                | DebuggerCommand::SetEcho(_)
+                | DebuggerCommand::ShowEcho
+                | DebuggerCommand::SetLogging(..)
+                | DebuggerCommand::ShowLogging
+                | DebuggerCommand::ShowDetachOnExit
+                | DebuggerCommand::ShowFollowForkMode
+                | DebuggerCommand::Directory(_)
+                | DebuggerCommand::ShowDirectories
+                | DebuggerCommand::InfoBreakpoints
+                | DebuggerCommand::InfoDisplay
+                | DebuggerCommand::InfoFunctions(..)
+                | DebuggerCommand::InfoVariables(_)
+                | DebuggerCommand::InfoSources
+                | DebuggerCommand::InfoLine(_)
+                | DebuggerCommand::InfoCheckpoints
+                | DebuggerCommand::InfoProc
+                | DebuggerCommand::InfoProcCmdline
+                | DebuggerCommand::InfoProcEnviron
+                | DebuggerCommand::Handle(..)
+                | DebuggerCommand::InfoSignals(_)
+        )
+    }
+
+    /// Implements `status`: says whether the program is running in the background, stopped (and
+    /// where), or not running at all.
+    fn print_status(&self) {
+        let inferior = match &self.inferior {
+            Some(inferior) => inferior,
+            None => {
+                outputln!(self.output, "{}", self.not_running_message());
+                return;
+            }
+        };
+        if self.running_in_background {
+            outputln!(
+                self.output,
+                "Process {} is running in the background.",
+                inferior.pid()
+            );
+            return;
+        }
+        match inferior.thread_instruction_ptr(inferior.current_thread()) {
+            Ok(instruction_ptr) => outputln!(
+                self.output,
+                "Process {} is stopped {}.",
+                inferior.pid(),
+                self.describe_stop(instruction_ptr)
+            ),
+            Err(_) => outputln!(self.output, "Process {} is stopped.", inferior.pid()),
+        }
+    }
+
+    /// Prints an error message for the command being executed, and notes that it failed (so that
+    /// a script running it stops).
+    fn report_error(&mut self, err: impl std::fmt::Display) {
+        outputln!(self.output, "{}", err);
+        self.outcome.failed = true;
+    }
+
+    /// Attaches to the running process `pid`, which must be running the binary being debugged,
+    /// and installs the current breakpoints in it.
+    fn attach(&mut self, pid: Pid) {
+        // The debug info is only meaningful for the binary it was loaded from
+        let exe = fs::canonicalize(format!("/proc/{}/exe", pid));
+        let target = fs::canonicalize(&self.target);
+        match (exe, target) {
+            (Ok(exe), Ok(target)) if exe == target => {}
+            (Ok(exe), _) => {
+                self.report_error(format!(
+                    "Process {} is running {}, not {}; refusing to attach.",
+                    pid,
+                    exe.display(),
+                    self.target
+                ));
+                return;
+            }
+            (Err(err), _) => {
+                self.report_error(format!("Cannot attach to process {}: {}", pid, err));
+                return;
+            }
+        }
+
+        self.clean();
+        match Inferior::attach(pid) {
+            Ok(mut inferior) => {
+                inferior.set_catch_syscalls(self.syscall_catch != SyscallCatch::Off);
+                inferior.set_follow_fork_child(self.follow_fork_child);
+                outputln!(self.output, "Attached to process {}", pid);
+                self.inferior = Some(inferior);
+                self.sync_catch_exit();
+                self.resolve_pending_breakpoints();
+                self.sync_breakpoints();
+                self.install_watchpoints();
+                self.last_exit = None;
+                self.last_exit_code = None;
+                self.selected_frame = 0;
+                self.last_listing = None;
+                let inferior = self.inferior.as_ref().unwrap();
+                if let Ok(regs) = inferior.get_registers() {
+                    let location =
+                        self.describe_stop((regs.rip as usize).wrapping_sub(inferior.load_base()));
+                    outputln!(self.output, "Stopped {}", location);
+                }
+            }
+            Err(err) => self.report_error(format!("Failed to attach to process {}: {}", pid, err)),
+        }
+    }
+
+    /// Implements `checkpoint`: forks the stopped inferior, keeping the copy stopped so that
+    /// `restore` can go back to it.
+    fn checkpoint(&mut self) -> Result<(), String> {
+        let inferior = self.running_inferior_mut()?;
+        let mut snapshot = inferior.checkpoint().map_err(|err| match err {
+            nix::Error::ENOTSUP => {
+                "Checkpoints are only supported in single-threaded programs.".to_string()
+            }
+            err => format!("Failed to create a checkpoint: {}", err),
+        })?;
+        // A snapshot is of no use to anyone once deet is gone
+        if let Err(err) = snapshot.set_kill_on_exit(true) {
+            outputln!(
+                self.output,
+                "Warning: failed to set ptrace options: {}",
+                err
+            );
+        }
+        let location = match snapshot.get_registers() {
+            Ok(regs) => self.describe_stop(snapshot.to_link_time(regs.rip as usize)),
+            Err(err) => format!("<cannot read registers: {}>", err),
+        };
+        let num = self.next_checkpoint_num;
+        self.next_checkpoint_num += 1;
+        outputln!(
+            self.output,
+            "Checkpoint {}: process {} {}",
+            num,
+            snapshot.pid(),
+            location
+        );
+        self.checkpoints.insert(
+            num,
+            Checkpoint {
+                inferior: snapshot,
+                location,
+            },
+        );
+        Ok(())
+    }
+
+    /// Implements `restore <num>`: kills the inferior and carries on debugging from checkpoint
+    /// `num` instead. What runs is a fork of the snapshot, so the checkpoint can be restored
+    /// again later.
+    fn restore_checkpoint(&mut self, num: usize) -> Result<(), String> {
+        if self.core.is_some() {
+            return Err("Cannot restore a checkpoint while inspecting a core dump.".to_string());
+        }
+        let checkpoint = self
+            .checkpoints
+            .get_mut(&num)
+            .ok_or_else(|| format!("No checkpoint number {}.", num))?;
+        let mut inferior = checkpoint
+            .inferior
+            .checkpoint()
+            .map_err(|err| format!("Failed to restore checkpoint {}: {}", num, err))?;
+        let location = checkpoint.location.clone();
+        self.clean();
+        if let Err(err) = inferior.set_kill_on_exit(!self.detach_on_exit) {
+            outputln!(
+                self.output,
+                "Warning: failed to set ptrace options: {}",
+                err
+            );
+        }
+        outputln!(
+            self.output,
+            "Switching to process {} (checkpoint {}) {}",
+            inferior.pid(),
+            num,
+            location
+        );
+        // Debug registers aren't copied by fork
+        for (num, wp) in self.watchpoints.iter_mut() {
+            if wp.slot.is_some() {
+                wp.slot = match inferior.set_watchpoint(wp.addr, wp.entity_type.size) {
+                    Ok(slot) => Some(slot),
+                    Err(err) => {
+                        outputln!(
+                            self.output,
+                            "Warning: cannot insert watchpoint {}; checking it by single-stepping \
+                             instead: {}",
+                            num,
+                            err
+                        );
+                        None
+                    }
+                };
+            }
+        }
+        self.inferior = Some(inferior);
+        self.sync_catch_exit();
+        self.sync_breakpoints();
+        self.refresh_watched_values();
+        self.last_exit = None;
+        self.last_exit_code = None;
+        self.selected_frame = 0;
+        self.last_listing = None;
+        if let Some(line) = self.current_line() {
+            self.print_source_line(line.address);
+        }
+        Ok(())
+    }
+
+    /// Implements `info proc`, `info proc cmdline` and `info proc environ`, from what /proc says
+    /// about the inferior.
+    fn print_proc_info(&self, command: &DebuggerCommand) -> Result<(), String> {
+        let pid = self.running_inferior()?.pid();
+        match command {
+            DebuggerCommand::InfoProcCmdline => {
+                let args = proc_info::cmdline(pid).map_err(|err| {
+                    format!("Cannot read the command line of process {}: {}", pid, err)
+                })?;
+                let args: Vec<String> = args.iter().map(|arg| proc_info::quote(arg)).collect();
+                outputln!(self.output, "{}", args.join(" "));
+            }
+            DebuggerCommand::InfoProcEnviron => {
+                let vars = proc_info::environ(pid).map_err(|err| {
+                    format!("Cannot read the environment of process {}: {}", pid, err)
+                })?;
+                for var in vars {
+                    outputln!(self.output, "{}", var);
+                }
+            }
+            _ => {
+                // Fields that can't be read say why, in place of their value
+                let field = |value: Result<String, String>| {
+                    value.unwrap_or_else(|err| format!("<error: {}>", err))
+                };
+                outputln!(
+                    self.output,
+                    "process {}: exe {}, state {}, cwd {}",
+                    pid,
+                    field(proc_info::exe(pid).map(|path| proc_info::quote(&path))),
+                    field(proc_info::state(pid)),
+                    field(proc_info::cwd(pid).map(|path| proc_info::quote(&path)))
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Implements `info checkpoints`.
+    fn print_checkpoints(&self) {
+        if self.checkpoints.is_empty() {
+            outputln!(self.output, "No checkpoints.");
+            return;
+        }
+        outputln!(self.output, "{:<8}{:<12}Location", "Num", "Process");
+        for (num, checkpoint) in &self.checkpoints {
+            outputln!(
+                self.output,
+                "{:<8}{:<12}{}",
+                num,
+                checkpoint.inferior.pid(),
+                checkpoint.location
+            );
+        }
+    }
+
+    /// Kills the processes kept as checkpoints.
+    fn delete_checkpoints(&mut self) {
+        for (_, mut checkpoint) in std::mem::take(&mut self.checkpoints) {
+            checkpoint.inferior.kill_and_reap();
+        }
+    }
+
+    /// Retries resolving the locations of pending breakpoints. Called whenever a new inferior
+    /// starts, before the breakpoints are planted in it.
+    fn resolve_pending_breakpoints(&mut self) {
+        let pending: Vec<(usize, String)> = self
+            .breakpoints
+            .iter()
+            .filter(|(_, bp)| bp.addr.is_none())
+            .map(|(&num, bp)| (num, bp.location.clone()))
+            .collect();
+        for (num, location) in pending {
+            if let Ok(addr) = self.resolve_location(&location) {
+                outputln!(
+                    self.output,
+                    "Pending breakpoint {} ({}) resolved to {:#x}",
+                    num,
+                    location,
+                    addr
+                );
+                self.breakpoints.get_mut(&num).unwrap().addr = Some(addr);
+            }
+        }
+    }
+
+    /// Returns the code deet should exit with: the one given to `quit`, or else in batch mode
+    /// (i.e. when driven by a script) the code of the last inferior, so that deet can stand in
+    /// for the program in a test harness. Interactive sessions exit with 0.
+    pub fn exit_code(&self, batch: bool) -> i32 {
+        match (self.quit_code, self.last_exit_code) {
+            (Some(code), _) => code,
+            (None, Some(code)) if batch => code,
+            _ => 0,
+        }
+    }
+
+    /// Brings the int3s planted in the running inferior (if any) in line with the breakpoint list.
+    /// This is the only way breakpoints get planted or removed, so that a new inferior is set up
+    /// from the list rather than from leftovers of the previous one.
+    fn sync_breakpoints(&mut self) {
+        let failed = match self.inferior.as_mut() {
+            Some(inferior) => inferior.sync_breakpoints(self.breakpoints.values()),
+            None => return,
+        };
+        for (addr, err) in failed {
+            if self.breakpoints.values().any(|bp| bp.addr == Some(addr)) {
+                self.report_error(format!("Cannot insert breakpoint at {:#x}: {}", addr, err));
+            } else {
+                self.report_error(format!("Cannot remove breakpoint at {:#x}: {}", addr, err));
+            }
+        }
+    }
+
+    /// Implements `catch exec` and `catch exit`.
+    fn add_catchpoint(&mut self, event: CatchEvent) {
+        let num = self.next_breakpoint_num;
+        self.next_breakpoint_num += 1;
+        self.catchpoints.insert(num, event);
+        outputln!(self.output, "Catchpoint {} ({})", num, event.name());
+        if event == CatchEvent::Exit {
+            self.sync_catch_exit();
+        }
+    }
+
+    /// Returns the number of a catchpoint on `event`, if there is one.
+    fn catchpoint_on(&self, event: CatchEvent) -> Option<usize> {
+        self.catchpoints
+            .iter()
+            .find(|(_, &caught)| caught == event)
+            .map(|(&num, _)| num)
+    }
+
+    /// Makes the inferior (if any) stop before exiting exactly when there is an exit catchpoint.
+    fn sync_catch_exit(&mut self) {
+        let catch_exit = self.catchpoint_on(CatchEvent::Exit).is_some();
+        if let Some(inferior) = self.inferior.as_mut() {
+            if let Err(err) = inferior.set_catch_exit(catch_exit) {
+                outputln!(
+                    self.output,
+                    "Warning: failed to set ptrace options: {}",
+                    err
+                );
+            }
+        }
+    }
+
+    /// Catches up with the inferior having exec'd a new program: loads the new program's debug
+    /// info if it is a different one, and plants the breakpoints afresh in it.
+    fn follow_exec(&mut self) {
+        let inferior = self.inferior.as_ref().unwrap();
+        let pid = inferior.pid();
+        let path = match inferior.executable_path() {
+            Some(path) => path,
+            None => return,
+        };
+        outputln!(
+            self.output,
+            "process {} is executing new program: {}",
+            pid,
+            path
+        );
+        // Whatever the breakpoints resolved to may not be code in the new program
+        for bp in self.breakpoints.values_mut() {
+            bp.addr = None;
+        }
+        let same_program = std::fs::canonicalize(&self.target)
+            .is_ok_and(|target| target.to_str() == Some(path.as_str()));
+        if !same_program {
+            match DwarfData::from_file(&path) {
+                Ok(mut dwarf_data) => {
+                    dwarf_data.set_demangle(self.dwarf_data.demangles());
+                    self.dwarf_data = dwarf_data;
+                    self.target = path;
+                }
+                Err(err) => {
+                    // Resolving against the old program's debug info would plant int3s at
+                    // random in the new one, so the breakpoints stay pending
+                    self.report_error(format!(
+                        "Could not load debugging symbols from {}: {:?}",
+                        path, err
+                    ));
+                    return;
+                }
+            }
+        }
+        self.resolve_pending_breakpoints();
+        self.sync_breakpoints();
+        self.install_watchpoints();
+    }
+
+    fn set_syscall_catch(&mut self, catch: SyscallCatch) {
+        self.syscall_catch = catch;
+        if let Some(inferior) = self.inferior.as_mut() {
+            inferior.set_catch_syscalls(catch != SyscallCatch::Off);
+        }
+    }
+
+    /// Re-arms the watchpoints in a newly started or attached inferior. Watchpoints on local
+    /// variables are deleted, since the frames they were set in are gone.
+    fn install_watchpoints(&mut self) {
+        let nums: Vec<usize> = self.watchpoints.keys().copied().collect();
+        for num in nums {
+            let wp = &self.watchpoints[&num];
+            if wp.local {
+                outputln!(
+                    self.output,
+                    "Watchpoint {} deleted because the program has restarted ({} was a local \
+                     variable).",
+                    num,
+                    wp.expression
+                );
+                self.watchpoints.remove(&num);
+                continue;
+            }
+            let inferior = self.inferior.as_ref().unwrap();
+            let result = self
+                .resolve_watch_expression(inferior, &wp.expression, None)
+                .and_then(|(addr, entity_type, _)| {
+                    let old_value = inferior
+                        .read_memory(addr, entity_type.size)
+                        .map_err(|err| err.to_string())?;
+                    Ok((addr, entity_type, old_value))
+                })
+                .and_then(|(addr, entity_type, old_value)| {
+                    if wp.slot.is_none() {
+                        return Ok((addr, old_value, None));
+                    }
+                    let slot = self
+                        .inferior
+                        .as_mut()
+                        .unwrap()
+                        .set_watchpoint(addr, entity_type.size)
+                        .map_err(|err| err.to_string())?;
+                    Ok((addr, old_value, Some(slot)))
+                });
+            match result {
+                Ok((addr, old_value, slot)) => {
+                    let wp = self.watchpoints.get_mut(&num).unwrap();
+                    wp.addr = addr;
+                    wp.old_value = old_value;
+                    wp.slot = slot;
+                }
+                Err(err) => {
+                    outputln!(
+                        self.output,
+                        "Warning: cannot insert watchpoint {}: {}",
+                        num,
+                        err
+                    );
+                    self.watchpoints.remove(&num);
+                }
+            }
+        }
+    }
+
+    /// Opens the files named by `run` redirections so they can be handed to the inferior.
+    fn open_redirections(redirections: &[Redirection]) -> Result<InferiorStdio, String> {
+        let open_error = |path: &str, err: std::io::Error| format!("{}: {}", path, err);
+        let mut stdio = InferiorStdio::default();
+        for redirection in redirections {
+            match redirection {
+                Redirection::Stdin(path) => {
+                    stdio.stdin = Some(File::open(path).map_err(|err| open_error(path, err))?);
+                }
+                Redirection::Stdout(path, append) => {
+                    let file = OpenOptions::new()
+                        .write(true)
+                        .create(true)
+                        .append(*append)
+                        .truncate(!*append)
+                        .open(path)
+                        .map_err(|err| open_error(path, err))?;
+                    stdio.stdout = Some(file);
+                }
+                Redirection::Stderr(path) => {
+                    stdio.stderr = Some(File::create(path).map_err(|err| open_error(path, err))?);
+                }
+            }
+        }
+        Ok(stdio)
+    }
+
+    /// Resolves `location`, records a new breakpoint there, and plants it in the running inferior
+    /// (if any). A function or source file the debug info doesn't know about makes a pending
+    /// breakpoint, which is resolved again when the next inferior starts.
+    fn add_breakpoint(&mut self, location: &str, condition: Option<&str>, temporary: bool) {
+        let condition = match condition.map(Condition::parse).transpose() {
+            Ok(condition) => condition,
+            Err(err) => {
+                self.report_error(err);
+                return;
+            }
+        };
+        let addr = match self.resolve_location(location) {
+            Ok(addr) => {
+                // A line without code snaps to the next one that has some
+                let requested_line = match split_location(location) {
+                    Some((_, line)) => line.parse::<usize>().ok(),
+                    None => location.parse::<usize>().ok(),
+                };
+                let actual_line = self.dwarf_data.get_line_from_addr(addr);
+                if let (Some(requested), Some(actual)) = (requested_line, actual_line) {
+                    if requested != actual.number {
+                        outputln!(
+                            self.output,
+                            "No code at line {}; breakpoint set at line {} instead.",
+                            requested,
+                            actual.number
+                        );
+                    }
+                }
+                Some(addr)
+            }
+            Err(err) if self.may_resolve_later(location) => {
+                outputln!(self.output, "{}", err);
+                None
+            }
+            Err(err) => {
+                self.report_error(err);
+                return;
+            }
+        };
+
+        // Breakpoints at the same address share the planted int3; it is only removed along with
+        // the last of them
+        let duplicates: Vec<String> = self
+            .breakpoints
+            .iter()
+            .filter(|(_, bp)| addr.is_some() && bp.addr == addr)
+            .map(|(num, _)| num.to_string())
+            .collect();
+        match duplicates.as_slice() {
+            [] => {}
+            [num] => outputln!(
+                self.output,
+                "Note: breakpoint {} also set at {:#x}.",
+                num,
+                addr.unwrap()
+            ),
+            nums => outputln!(
+                self.output,
+                "Note: breakpoints {} also set at {:#x}.",
+                nums.join(", "),
+                addr.unwrap()
+            ),
+        }
+
+        let num = self.next_breakpoint_num;
+        self.next_breakpoint_num += 1;
+        let kind = if temporary {
+            "Temporary breakpoint"
+        } else {
+            "Breakpoint"
+        };
+        let place = match addr {
+            Some(addr) => format!("at {}", self.describe_breakpoint(addr)),
+            None => format!("({}) pending", location),
+        };
+        match &condition {
+            Some(condition) => {
+                outputln!(self.output, "{} {} {} if {}", kind, num, place, condition)
+            }
+            None => outputln!(self.output, "{} {} {}", kind, num, place),
+        }
+        self.breakpoints.insert(
+            num,
+            Breakpoint {
+                location: location.to_string(),
+                addr,
+                condition,
+                temporary,
+                ignore_count: 0,
+                skipped_hits: 0,
+                commands: Vec::new(),
+            },
+        );
+
+        self.sync_breakpoints();
+    }
+
+    /// Sets a watchpoint on `expression`, a variable in the selected frame (or a global) or
+    /// `*<address>` (watched as a 4-byte int). Software watchpoints have no size or alignment
+    /// restrictions and no limit on their number, but make the inferior single-step.
+    fn add_watchpoint(&mut self, expression: &str, hardware: bool) -> Result<(), String> {
+        let hardware_count = self
+            .watchpoints
+            .values()
+            .filter(|wp| wp.slot.is_some())
+            .count();
+        if hardware && hardware_count >= MAX_WATCHPOINTS {
+            return Err(format!(
+                "Cannot set more than {} hardware watchpoints; delete one first, or use `swatch` \
+                 for a (slow) software watchpoint.",
+                MAX_WATCHPOINTS
+            ));
+        }
+        let inferior = self.running_inferior()?;
+        let frame = self.current_frame(inferior)?;
+        let (addr, entity_type, local) =
+            self.resolve_watch_expression(inferior, expression, Some(frame))?;
+        let old_value = inferior
+            .read_memory(addr, entity_type.size)
+            .map_err(|_| format!("Cannot access memory at address {:#x}", addr))?;
+        let slot = if hardware {
+            match self
+                .inferior
+                .as_mut()
+                .unwrap()
+                .set_watchpoint(addr, entity_type.size)
+            {
+                Ok(slot) => Some(slot),
+                Err(nix::Error::EINVAL) => {
+                    return Err(format!(
+                        "Cannot watch {}: hardware watchpoints only cover aligned locations of 1, \
+                         2, 4 or 8 bytes (use `swatch` instead)",
+                        expression
+                    ))
+                }
+                Err(err) => return Err(format!("Failed to set watchpoint: {}", err)),
+            }
+        } else {
+            None
+        };
+
+        let num = self.next_breakpoint_num;
+        self.next_breakpoint_num += 1;
+        if hardware {
+            outputln!(self.output, "Hardware watchpoint {}: {}", num, expression);
+        } else {
+            outputln!(self.output, "Software watchpoint {}: {}", num, expression);
+        }
+        self.watchpoints.insert(
+            num,
+            Watchpoint {
+                expression: expression.to_string(),
+                addr,
+                entity_type,
+                local,
+                old_value,
+                slot,
+            },
+        );
+        Ok(())
+    }
+
+    /// Works out the run-time address and type of a watch expression, and whether it is a local
+    /// variable. Without a frame, only globals and addresses can be resolved.
+    fn resolve_watch_expression(
+        &self,
+        inferior: &Inferior,
+        expression: &str,
+        frame: Option<Frame>,
+    ) -> Result<(usize, Type, bool), String> {
+        if let Some(addr) = expression.strip_prefix('*') {
+            let addr = self.resolve_address(inferior, addr)?;
+            let int_type = Type::new("int".to_string(), 4, TypeKind::Base(Encoding::Signed));
+            return Ok((addr, int_type, false));
+        }
+        let (var, frame_base) = match frame {
+            Some(frame) => (
+                self.lookup_variable(frame.instruction_ptr, expression)?,
+                frame.frame_base,
+            ),
+            None => (
+                self.dwarf_data
+                    .get_global_variable(expression)
+                    .ok_or_else(|| format!("No symbol \"{}\" in current context.", expression))?,
+                0,
+            ),
+        };
+        let addr = var
+            .location
+            .address(frame_base, inferior.load_base())
+            .ok_or_else(|| format!("Cannot watch {}: it is not in memory", expression))?;
+        let local = matches!(var.location, Location::FramePointerOffset(_));
+        Ok((addr, var.entity_type.clone(), local))
+    }
+
+    /// Removes breakpoint, watchpoint or catchpoint `num` from the list and from the running
+    /// inferior (if any). Returns false if there is no such breakpoint.
+    fn delete_breakpoint(&mut self, num: usize) -> bool {
+        if let Some(event) = self.catchpoints.remove(&num) {
+            if event == CatchEvent::Exit {
+                self.sync_catch_exit();
+            }
+            return true;
+        }
+        if let Some(wp) = self.watchpoints.remove(&num) {
+            if let (Some(slot), Some(inferior)) = (wp.slot, self.inferior.as_mut()) {
+                if let Err(err) = inferior.remove_watchpoint(slot) {
+                    outputln!(
+                        self.output,
+                        "Failed to remove watchpoint from running inferior: {}",
+                        err
+                    );
+                }
+            }
+            return true;
+        }
+        if self.breakpoints.remove(&num).is_none() {
+            return false;
+        }
+        // Another breakpoint may share the same address, in which case it stays planted
+        self.sync_breakpoints();
+        true
+    }
+
+    /// Returns true if `location` is a function or a line in a source file that the debug info
+    /// doesn't know about, so that it may still be found later (e.g. in a shared library).
+    fn may_resolve_later(&self, location: &str) -> bool {
+        if location.starts_with('*') {
+            return false;
+        }
+        match split_location(location) {
+            Some((file, line)) => {
+                line.parse::<usize>().is_ok() && self.check_source_file(file).is_err()
+            }
+            None => location.parse::<usize>().is_err(),
+        }
+    }
+
+    /// Resolves a breakpoint location to an address. Accepts `*<address>`, `<file>:<line>`,
+    /// `<library>:<function>`, a bare line number, or a function name (whose breakpoint goes
+    /// past the prologue, at its first statement).
+    fn resolve_location(&self, location: &str) -> Result<usize, String> {
+        if let Some(expr) = location.strip_prefix('*') {
+            // Breakpoints take link-time addresses, so registers are rebased
+            let process = self.stopped_process();
+            let load_base = process.as_ref().map_or(0, |process| process.load_base());
+            let addr = AddressExpr::parse(expr)?.evaluate(
+                |symbol| self.symbol_address(symbol),
+                |register| {
+                    let process = process.as_ref().map_err(|err| err.clone())?;
+                    Ok(read_register(*process, register)?.wrapping_sub(load_base))
+                },
+            )?;
+            // A live inferior's mappings also cover shared libraries
+            let executable = self
+                .inferior
+                .as_ref()
+                .and_then(|inferior| {
+                    inferior.is_executable_address(addr.wrapping_add(inferior.load_base()))
+                })
+                .unwrap_or_else(|| self.dwarf_data.is_executable_address(addr));
+            if !executable {
+                return Err(format!(
+                    "Address {:#x} is not in the target's text segment",
+                    addr
+                ));
+            }
+            return Ok(addr);
+        }
+
+        if let Some((library, function)) = split_location(location) {
+            if function.parse::<usize>().is_err()
+                && (library.starts_with("lib") || library.contains(".so"))
+            {
+                return self.resolve_library_function(library, function);
+            }
+        }
+
+        if let Some((file, line)) = split_location(location) {
+            let line_number = line
+                .parse::<usize>()
+                .map_err(|_| format!("Failed to parse {} as a valid line number.", line))?;
+            return self
+                .dwarf_data
+                .lookup_line(Some(file), line_number)
+                .map_err(|err| Debugger::line_lookup_message(err, file, line_number));
+        }
+
+        if let Ok(line_number) = location.parse::<usize>() {
+            let file = self.dwarf_data.get_default_file().unwrap_or("??");
+            let num_files = self.dwarf_data.num_source_files();
+            if num_files > 1 {
+                outputln!(
+                    self.output,
+                    "Note: line {} is taken from {}, one of {} source files; use <file>:<line> \
+                     to pick another.",
+                    line_number,
+                    file,
+                    num_files
+                );
+            }
+            return self
+                .dwarf_data
+                .lookup_line(None, line_number)
+                .map_err(|err| Debugger::line_lookup_message(err, file, line_number));
+        }
+        // Like gdb, stop once the arguments and locals are set up
+        self.dwarf_data
+            .get_addr_for_function(None, location)
+            .map(|addr| self.dwarf_data.skip_prologue(addr))
+            .ok_or_else(|| {
+                format!(
+                    "Failed to parse {} as valid address, line number or function name.",
+                    location
+                )
+            })
+    }
+
+    /// Implements `call`: runs a function of the program in the current thread, with integer or
+    /// pointer arguments, and prints what it returns. The thread's registers are restored once
+    /// the function returns, or if it is stopped by a signal.
+    fn call_function(&mut self, call: &str) -> Result<(), String> {
+        let (name, args) = debugger_command::split_call(call)?;
+        let inferior = self.running_inferior()?;
+        if let (true, Some(pending)) = (inferior.in_call(), &self.pending_call) {
+            return Err(format!(
+                "Already inside {}, called from deet; continue until it returns first.",
+                pending.expression
+            ));
+        }
+        if args.len() > MAX_CALL_ARGS {
+            return Err(format!(
+                "Cannot pass more than {} arguments.",
+                MAX_CALL_ARGS
+            ));
+        }
+        let frame = self.current_frame(inferior)?;
+        let context = FrameContext {
+            debugger: self,
+            process: inferior,
+            frame,
+        };
+        let values = args
+            .iter()
+            .map(|arg| Ok(Expr::parse(arg)?.evaluate(&context)? as u64))
+            .collect::<Result<Vec<u64>, String>>()?;
+        let (addr, return_type) = self.resolve_callee(&name, args.len())?;
+        self.inferior
+            .as_mut()
+            .unwrap()
+            .start_call(addr, &values)
+            .map_err(|err| match err {
+                nix::Error::EBUSY => {
+                    "Cannot call a function while stopped in a system call.".to_string()
+                }
+                err => format!("Cannot call {}: {}", name, err),
+            })?;
+        self.pending_call = Some(PendingCall {
+            expression: format!("{}({})", name, args.join(", ")),
+            return_type,
+        });
+        self.wake_and_wait();
+        Ok(())
+    }
+
+    /// Finds the function `name` (or `lib:function`) for `call`, which is given `num_args`
+    /// arguments. Returns its link-time address and return type.
+    fn resolve_callee(&self, name: &str, num_args: usize) -> Result<(usize, Option<Type>), String> {
+        if let Some((library, function)) = split_location(name) {
+            let addr = self.resolve_library_function(library, function)?;
+            let process = self.stopped_process()?;
+            let runtime_addr = process.to_runtime(addr);
+            if process
+                .memory_regions()
+                .and_then(|regions| SharedLibrary::containing(&regions, runtime_addr))
+                .is_some_and(|shared_library| shared_library.is_indirect_function(runtime_addr))
+            {
+                return Err(format!(
+                    "Cannot call {}: indirect functions (GNU ifuncs) aren't supported.",
+                    name
+                ));
+            }
+            // Without debug info, all there is to go on is the calling convention
+            let word = Type::new("long".to_string(), 8, TypeKind::Base(Encoding::Signed));
+            return Ok((addr, Some(word)));
+        }
+        let addr = self
+            .dwarf_data
+            .get_addr_for_function(None, name)
+            .ok_or_else(|| format!("No function named {} in the program.", name))?;
+        let func = self.dwarf_data.get_function_containing(addr).unwrap();
+        let params = self
+            .dwarf_data
+            .get_function_parameters(addr)
+            .unwrap_or_default();
+        // Variadic functions take more arguments than they have parameters
+        if num_args < params.len() {
+            return Err(format!(
+                "Too few arguments in call to {}: it takes {}.",
+                name,
+                params.len()
+            ));
+        }
+        if let Some(param) = params
+            .iter()
+            .find(|param| !is_word_type(&param.entity_type))
+        {
+            return Err(format!(
+                "Cannot pass {} of type {}: only integer and pointer arguments are supported.",
+                param.name, param.entity_type.name
+            ));
+        }
+        match &func.return_type {
+            Some(return_type) if !is_word_type(return_type) => Err(format!(
+                "Cannot call {}: returning {} is not supported.",
+                name, return_type.name
+            )),
+            return_type => Ok((addr, return_type.clone())),
+        }
+    }
+
+    /// Returns the (link-time) address of `function` in the shared library `library` (e.g.
+    /// `libc.so.6`, or just `libc`) loaded by the running inferior.
+    fn resolve_library_function(&self, library: &str, function: &str) -> Result<usize, String> {
+        let not_loaded = || format!("Shared library {} is not loaded.", library);
+        let process = self.stopped_process().map_err(|_| not_loaded())?;
+        let regions = process.memory_regions().ok_or_else(not_loaded)?;
+        let shared_library = SharedLibrary::named(&regions, library).ok_or_else(not_loaded)?;
+        let addr = shared_library.symbol_address(function).ok_or_else(|| {
+            format!(
+                "No function named {} in {}.",
+                function,
+                shared_library.file_name()
+            )
+        })?;
+        Ok(process.to_link_time(addr))
+    }
+
+    /// Checks that `file` names exactly one compilation unit.
+    fn check_source_file(&self, file: &str) -> Result<(), String> {
+        match self.dwarf_data.lookup_line(Some(file), 0) {
+            Ok(_) | Err(LineLookupError::NoCode) => Ok(()),
+            Err(err) => Err(Debugger::line_lookup_message(err, file, 0)),
+        }
+    }
+
+    /// Explains why line `line_number` of `file` couldn't be found.
+    fn line_lookup_message(err: LineLookupError, file: &str, line_number: usize) -> String {
+        match err {
+            LineLookupError::NoSuchFile => format!("No source file named {}.", file),
+            LineLookupError::AmbiguousFile(candidates) => {
+                let mut msg = format!("Source file name {} is ambiguous. Candidates:", file);
+                for candidate in candidates {
+                    msg.push_str(&format!("\n  {}", candidate));
+                }
+                msg
+            }
+            LineLookupError::NoCode => format!(
+                "Line {} of \"{}\" has no code, and neither do the lines after it.",
+                line_number, file
+            ),
+        }
+    }
+
+    /// Resolves a `line`, `file:line` or function name to a compilation unit and line number.
+    fn resolve_source_line(&self, location: &str) -> Result<(String, usize), String> {
+        if let Some((file, line)) = split_location(location) {
+            let line_number = line
+                .parse::<usize>()
+                .map_err(|_| format!("Failed to parse {} as a valid line number.", line))?;
+            self.check_source_file(file)?;
+            return Ok((file.to_string(), line_number));
+        }
+        if let Ok(line_number) = location.parse::<usize>() {
+            let file = self
+                .dwarf_data
+                .get_default_file()
+                .ok_or("No symbol table is loaded.")?;
+            return Ok((file.to_string(), line_number));
+        }
+        self.dwarf_data
+            .get_functions()
+            .into_iter()
+            .find(|func| func.name == location || demangle::matches(&func.raw_name, location))
+            .map(|func| (func.file, func.line_number))
+            .ok_or_else(|| format!("Function \"{}\" not defined.", location))
+    }
+
+    /// Describes a link-time code address as `<function+offset>`, if it lies in a known function.
+    fn symbolize(&self, addr: usize) -> Option<String> {
+        let func = self.dwarf_data.get_function_containing(addr)?;
+        let name = self.dwarf_data.function_name(func);
+        Some(match addr - func.address {
+            0 => format!("<{}>", name),
+            offset => format!("<{}+{}>", name, offset),
+        })
+    }
+
+    /// Prints the address ranges covered by a source line. For a line without code, points at the
+    /// next line that has some.
+    fn print_line_info(&self, location: &str) -> Result<(), String> {
+        let (file, line_number) = self.resolve_source_line(location)?;
+        let load_base = self
+            .stopped_process()
+            .map_or(0, |process| process.load_base());
+        let describe = |addr: usize| match self.symbolize(addr) {
+            Some(symbol) => format!("{:#x} {}", addr.wrapping_add(load_base), symbol),
+            None => format!("{:#x}", addr.wrapping_add(load_base)),
+        };
+        let ranges = self.dwarf_data.get_line_ranges(&file, line_number);
+        if ranges.is_empty() {
+            return match self.dwarf_data.get_next_line_with_code(&file, line_number) {
+                Some((next_line, addr)) => {
+                    outputln!(
+                        self.output,
+                        "Line {} of \"{}\" has no code; the next line with code is {}, at \
+                         address {}.",
+                        line_number,
+                        file,
+                        next_line,
+                        describe(addr)
+                    );
+                    Ok(())
+                }
+                None => Err(format!(
+                    "Line {} is out of range for \"{}\".",
+                    line_number, file
+                )),
+            };
+        }
+        for (start, end) in ranges {
+            outputln!(
+                self.output,
+                "Line {} of \"{}\" starts at address {} and ends at {}.",
+                line_number,
+                file,
+                describe(start),
+                describe(end)
+            );
+        }
+        Ok(())
+    }
+
+    fn wake_and_wait(&mut self) {
+        // Milestone 1: make the inferior run
+        // You may use self.inferior.as_mut().unwrap() to get a mutable reference
+        // to the Inferior object
+        let software_watch = self.prepare_to_wake();
+        let (result, hit) = loop {
+            let result = if software_watch {
+                self.step_until_watched_change()
+            } else {
+                self.inferior.as_mut().unwrap().wake_and_wait()
+            };
+            if let StopAction::Report(hit) = self.check_stop(&result) {
+                break (result, hit);
+            }
+        };
+        self.report_stop(result, hit, software_watch);
+    }
+
+    /// Implements `run &` and `continue &`: wakes the inferior and returns to the prompt right
+    /// away. Its next stop is reported before a later prompt.
+    fn wake_in_background(&mut self) {
+        if self.watchpoints.values().any(|wp| wp.slot.is_none()) {
+            outputln!(
+                self.output,
+                "Software watchpoints can't be checked in the background; running in the \
+                 foreground."
+            );
+            self.wake_and_wait();
+            return;
+        }
+        self.prepare_to_wake();
+        self.drive_background(BackgroundStep::Wake);
+    }
+
+    /// With `set timing on`, describes how long the inferior that just terminated ran (not
+    /// counting the time it was stopped), to be appended to the exit message.
+    fn timing_summary(&self) -> String {
+        let inferior = match (&self.inferior, self.show_timing) {
+            (Some(inferior), true) => inferior,
+            _ => return String::new(),
+        };
+        let mut summary = format!(" after {:.2}s wall", inferior.run_time().as_secs_f64());
+        if let Some((user, sys)) = inferior.cpu_time() {
+            summary.push_str(&format!(
+                ", {:.2}s user, {:.2}s sys",
+                user.as_secs_f64(),
+                sys.as_secs_f64()
+            ));
+        }
+        summary
+    }
+
+    /// Takes the next step with an inferior running in the background, going on until it is
+    /// running again or has stopped in a way that is reported.
+    fn drive_background(&mut self, mut step: BackgroundStep) {
+        loop {
+            let inferior = self.inferior.as_mut().unwrap();
+            let result = match step {
+                BackgroundStep::Wake => inferior.wake(),
+                BackgroundStep::Poll => inferior.poll(false),
+                BackgroundStep::Wait => inferior.poll(true),
+            };
+            let result = match result.transpose() {
+                Some(result) => result,
+                None => {
+                    self.running_in_background = true;
+                    return;
+                }
+            };
+            match self.check_stop(&result) {
+                StopAction::Wake => step = BackgroundStep::Wake,
+                StopAction::Report(hit) => {
+                    self.running_in_background = false;
+                    self.report_stop(result, hit, false);
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Reports what happened to an inferior running in the background since it was last looked
+    /// at, if anything. Called before each command.
+    fn poll_background(&mut self) {
+        if self.running_in_background {
+            self.drive_background(BackgroundStep::Poll);
+        }
+    }
+
+    /// Gets ready for the inferior to be woken up. Returns true if it must be single-stepped to
+    /// check software watchpoints.
+    fn prepare_to_wake(&mut self) -> bool {
+        self.selected_frame = 0;
+        self.last_listing = None;
+        self.refresh_watched_values();
+        let inferior = self.inferior.as_ref().unwrap();
+        self.woken_thread = (inferior.pid(), inferior.current_thread());
+        let software_watch = self.watchpoints.values().any(|wp| wp.slot.is_none());
+        if software_watch {
+            outputln!(
+                self.output,
+                "Single-stepping to check software watchpoints; this is slow (press Ctrl-C to \
+                 interrupt)."
+            );
+        }
+        software_watch
+    }
+
+    /// Decides whether a status from the woken inferior is reported, or the inferior is quietly
+    /// woken up again.
+    fn check_stop(&mut self, result: &Result<Status, nix::Error>) -> StopAction {
+        // The CPU reports every write, even one that stores the value already there
+        if let Ok(Status::Watchpoint(slot, _)) = result {
+            if self
+                .watchpoint_in_slot(*slot)
+                .and_then(|num| self.watched_value(num))
+                .is_some_and(|(old, new)| old == new)
+            {
+                return StopAction::Wake;
+            }
+        }
+        // Forks are reported, but don't stop the program
+        match result {
+            Ok(Status::Forked(new_pid, true)) => {
+                outputln!(
+                    self.output,
+                    "Following fork into child process {}.",
+                    new_pid
+                );
+                return StopAction::Wake;
+            }
+            Ok(Status::Forked(new_pid, false)) => {
+                outputln!(
+                    self.output,
+                    "Detaching after fork from child process {}.",
+                    new_pid
+                );
+                return StopAction::Wake;
+            }
+            _ => {}
+        }
+        if let Ok(Status::SyscallEntry(num) | Status::SyscallExit(num)) = result {
+            if matches!(self.syscall_catch, SyscallCatch::Only(wanted) if wanted != *num) {
+                return StopAction::Wake;
+            }
+        }
+        if let Ok(Status::Exec(_)) = result {
+            self.follow_exec();
+        }
+        let caught = match result {
+            Ok(Status::Exec(_)) => Some(CatchEvent::Exec),
+            Ok(Status::Exiting(..) | Status::Dying(..)) => Some(CatchEvent::Exit),
+            _ => None,
+        };
+        if let Some(event) = caught {
+            return match self.catchpoint_on(event) {
+                Some(num) => StopAction::Report(Some((num, false))),
+                None => StopAction::Wake,
+            };
+        }
+        if let Ok(Status::Breakpoint(instruction_ptr)) = result {
+            match self.triggered_breakpoint(*instruction_ptr) {
+                Some(num) => {
+                    let bp = self.breakpoints.get_mut(&num).unwrap();
+                    if bp.skipped_hits > 0 {
+                        outputln!(
+                            self.output,
+                            "Breakpoint {} hit after ignoring {} previous hit(s)",
+                            num,
+                            bp.skipped_hits
+                        );
+                        bp.skipped_hits = 0;
+                    }
+                    return StopAction::Report(Some((num, bp.temporary)));
+                }
+                // Breakpoints are here, but none of their conditions hold
+                None if self
+                    .breakpoints
+                    .values()
+                    .any(|bp| bp.addr == Some(*instruction_ptr)) =>
+                {
+                    return StopAction::Wake;
+                }
+                None => {}
+            }
+        }
+        if let Ok(Status::Stopped(signal, _)) = result {
+            if *signal != Signal::SIGTRAP {
+                let policy = self.signal_table.get(*signal);
+                if !policy.pass {
+                    self.inferior.as_mut().unwrap().discard_pending_signal();
+                }
+                if !policy.stop {
+                    if policy.print {
+                        outputln!(
+                            self.output,
+                            "Child received signal {} ({})",
+                            signal.as_str(),
+                            if policy.pass { "passed" } else { "discarded" }
+                        );
+                    }
+                    return StopAction::Wake;
+                }
+            }
+        }
+        StopAction::Report(None)
+    }
+
+    /// Reports why the woken inferior stopped (or that it terminated). `hit` is the breakpoint
+    /// that stopped it, if any, and whether that breakpoint is temporary.
+    fn report_stop(
+        &mut self,
+        result: Result<Status, nix::Error>,
+        hit: Option<(usize, bool)>,
+        software_watch: bool,
+    ) {
+        self.outcome.stop = result.as_ref().ok().copied();
+        self.outcome.breakpoint = hit.map(|(num, _)| num);
+        let (prev_pid, prev_thread) = self.woken_thread;
+        if let (
+            Ok(
+                Status::Stopped(..)
+                | Status::Breakpoint(_)
+                | Status::Watchpoint(..)
+                | Status::Interrupted(_)
+                | Status::Exiting(..)
+                | Status::Dying(..)
+                | Status::PtraceEvent(..),
+            ),
+            Some(inferior),
+        ) = (&result, &self.inferior)
+        {
+            // (a followed fork child is a new process, not a thread switch)
+            if inferior.pid() == prev_pid && inferior.current_thread() != prev_thread {
+                outputln!(
+                    self.output,
+                    "[Switching to thread {}]",
+                    inferior.current_thread()
+                );
+            }
+        }
+        // Taken before a temporary breakpoint is deleted, and run once the stop is reported
+        self.triggered_commands = hit
+            .and_then(|(num, _)| self.breakpoints.get(&num))
+            .filter(|bp| !bp.commands.is_empty())
+            .map(|bp| bp.commands.clone());
+        match result {
+            Ok(status) => match status {
+                Status::Breakpoint(instruction_ptr) => {
+                    let location = self.describe_stop(instruction_ptr);
+                    match hit {
+                        Some((num, true)) => {
+                            outputln!(self.output, "Temporary breakpoint {} hit {}", num, location);
+                            self.delete_breakpoint(num);
+                        }
+                        Some((num, false)) => {
+                            outputln!(self.output, "Breakpoint {} hit {}", num, location)
+                        }
+                        None => outputln!(self.output, "Stopped at a breakpoint {}", location),
+                    }
+                    // The step that reached the breakpoint may also have changed a watched value
+                    if let Some(num) = software_watch
+                        .then(|| self.changed_software_watchpoint())
+                        .flatten()
+                    {
+                        self.print_watchpoint_change(num);
+                    }
+                    self.print_source_line(instruction_ptr);
+                }
+                Status::Watchpoint(slot, instruction_ptr) => {
+                    self.report_watchpoint(self.watchpoint_in_slot(slot), instruction_ptr);
+                }
+                // Single steps only stop the stepping loop when a watched value changes
+                Status::Stopped(Signal::SIGTRAP, instruction_ptr) if software_watch => {
+                    self.report_watchpoint(self.changed_software_watchpoint(), instruction_ptr);
+                }
+                // Woken up again by check_stop, or never returned by a blocking wait
+                Status::Forked(_, _) | Status::Running => {}
+                Status::Interrupted(instruction_ptr) => {
+                    outputln!(
+                        self.output,
+                        "Interrupted {}",
+                        self.describe_stop(instruction_ptr)
+                    );
+                    self.print_source_line(instruction_ptr);
+                }
+                Status::ThreadExited(tid) => {
+                    outputln!(
+                        self.output,
+                        "[Thread {} exited; switching to main thread]",
+                        tid
+                    );
+                }
+                Status::Returned(value) => {
+                    let process = self.inferior.as_ref().unwrap();
+                    if let Some(PendingCall {
+                        expression,
+                        return_type: Some(return_type),
+                    }) = self.pending_call.take()
+                    {
+                        outputln!(
+                            self.output,
+                            "{} = {}",
+                            expression,
+                            self.format_bytes(
+                                process,
+                                &value.to_le_bytes()[..return_type.size],
+                                &return_type
+                            )
+                        );
+                    }
+                }
+                Status::Exec(instruction_ptr) => {
+                    let path = self.inferior.as_ref().unwrap().executable_path();
+                    let what = format!("exec'd {}", path.unwrap_or_default());
+                    self.report_catchpoint(&what, instruction_ptr);
+                }
+                Status::Exiting(code, instruction_ptr) => {
+                    let what = format!("process exiting with code {}", code);
+                    self.report_catchpoint(&what, instruction_ptr);
+                }
+                Status::Dying(signal, instruction_ptr) => {
+                    let what = format!("process exiting on signal {}", signal.as_str());
+                    self.report_catchpoint(&what, instruction_ptr);
+                }
+                Status::PtraceEvent(event, instruction_ptr) => {
+                    outputln!(
+                        self.output,
+                        "Child stopped at an unhandled ptrace event ({})",
+                        ptrace_event_name(event)
+                    );
+                    outputln!(
+                        self.output,
+                        "Stopped {}",
+                        self.describe_stop(instruction_ptr)
+                    );
+                    self.print_source_line(instruction_ptr);
+                }
+                Status::SyscallEntry(num) => {
+                    if let Ok(regs) = self.inferior.as_ref().unwrap().get_registers() {
+                        outputln!(
+                            self.output,
+                            "Syscall entry: {}({:#x}, {:#x}, {:#x}, {:#x}, ...)",
+                            syscall_display_name(num),
+                            regs.rdi,
+                            regs.rsi,
+                            regs.rdx,
+                            regs.r10
+                        );
+                    }
+                }
+                Status::SyscallExit(num) => {
+                    if let Ok(regs) = self.inferior.as_ref().unwrap().get_registers() {
+                        let result = regs.rax as i64;
+                        // Failing system calls return -errno
+                        if (-4095..0).contains(&result) {
+                            outputln!(
+                                self.output,
+                                "Syscall exit: {} = {} ({})",
+                                syscall_display_name(num),
+                                result,
+                                std::io::Error::from_raw_os_error(-result as i32)
+                            );
+                        } else {
+                            outputln!(
+                                self.output,
+                                "Syscall exit: {} = {}",
+                                syscall_display_name(num),
+                                result
+                            );
+                        }
+                    }
+                }
+                Status::Stopped(signal, instruction_ptr) => {
+                    outputln!(self.output, "Child stopped (signal {})", signal.as_str());
+                    let inferior = self.inferior.as_ref().unwrap();
+                    if let Ok(Some(fault)) = inferior.describe_fault(signal) {
+                        outputln!(self.output, "{}", fault);
+                        if let Ok(frames) = inferior.stack_frames(&self.dwarf_data, 1) {
+                            outputln!(
+                                self.output,
+                                "{}",
+                                inferior.format_frame(&frames[0], 0, &self.dwarf_data)
+                            );
+                        }
+                    } else {
+                        outputln!(
+                            self.output,
+                            "Stopped {}",
+                            self.describe_stop(instruction_ptr)
+                        );
+                    }
+                    self.print_source_line(instruction_ptr);
+                }
+                // The child has been reaped by waitpid, so there is nothing left to clean up
+                Status::Exited(code) => {
+                    outputln!(
+                        self.output,
+                        "Child exited (status {}){}",
+                        code,
+                        self.timing_summary()
+                    );
+                    self.inferior = None;
+                    self.last_exit = Some(format!("exited with status {}", code));
+                    self.last_exit_code = Some(code);
+                }
+                Status::Signaled(signal) => {
+                    outputln!(
+                        self.output,
+                        "Child signaled (signal {}){}",
+                        signal.as_str(),
+                        self.timing_summary()
+                    );
+                    self.inferior = None;
+                    self.last_exit = Some(format!("was killed by signal {}", signal.as_str()));
+                    self.last_exit_code = Some(128 + signal as i32);
+                }
+            },
+            Err(err) => outputln!(
+                self.output,
+                "Error waking up the inferior and waiting: {}",
+                err
+            ),
+        }
+        self.check_pending_call();
+        if self.inferior.is_some() {
+            self.print_displays();
+        }
+    }
+
+    /// Implements `handle <signal> [actions...]`, then shows what is now done with the signal.
+    fn handle_signal(&mut self, name: &str, actions: &[String]) -> Result<(), String> {
+        let signal = signal_policy::parse_signal(name)?;
+        self.signal_table.handle(signal, actions)?;
+        outputln!(self.output, "{}", signal_policy::TABLE_HEADER);
+        outputln!(
+            self.output,
+            "{}",
+            signal_policy::format_row(signal, self.signal_table.get(signal))
+        );
+        Ok(())
+    }
+
+    /// Implements `gcore [file]`: saves the stopped inferior as a core file, which deet (or gdb)
+    /// can load later.
+    fn gcore(&self, path: Option<String>) -> Result<(), String> {
+        let inferior = self.running_inferior()?;
+        let path = path.unwrap_or_else(|| format!("core.{}", inferior.pid()));
+        for warning in core_file::write_core(inferior, &path)? {
+            outputln!(self.output, "Warning: {}", warning);
+        }
+        outputln!(self.output, "Saved corefile {}", path);
+        Ok(())
+    }
+
+    /// Implements `info signals [signal]`.
+    fn print_signals(&mut self, name: Option<&str>) -> Result<(), String> {
+        let rows: Vec<String> = match name {
+            Some(name) => {
+                let signal = signal_policy::parse_signal(name)?;
+                vec![signal_policy::format_row(
+                    signal,
+                    self.signal_table.get(signal),
+                )]
+            }
+            None => self
+                .signal_table
+                .iter()
+                .map(|(signal, policy)| signal_policy::format_row(signal, policy))
+                .collect(),
+        };
+        outputln!(self.output, "{}", signal_policy::TABLE_HEADER);
+        for row in rows {
+            outputln!(self.output, "{}", row);
+        }
+        Ok(())
+    }
+
+    /// Deals with the inferior stopping inside a function called by `call`: the call is
+    /// abandoned if a signal stopped it, and goes on when the inferior is continued otherwise
+    /// (e.g. after a breakpoint in the function).
+    fn check_pending_call(&mut self) {
+        let inferior = match self.inferior.as_mut().filter(|inferior| inferior.in_call()) {
+            Some(inferior) => inferior,
+            None => {
+                self.pending_call = None;
+                return;
+            }
+        };
+        let expression = self
+            .pending_call
+            .as_ref()
+            .map_or_else(String::new, |call| call.expression.clone());
+        match self.outcome.stop {
+            Some(Status::Stopped(signal, _)) if signal != Signal::SIGTRAP => {
+                let result = inferior.end_call();
+                self.pending_call = None;
+                match result {
+                    Ok(()) => outputln!(
+                        self.output,
+                        "The program received {} in {}, called from deet. The call was \
+                         abandoned and the program's state restored.",
+                        signal.as_str(),
+                        expression
+                    ),
+                    Err(err) => self.report_error(format!(
+                        "Failed to restore the program's state after {}: {}",
+                        expression, err
+                    )),
+                }
+            }
+            _ => outputln!(
+                self.output,
+                "The program stopped in {}, called from deet; continue to let it return.",
+                expression
+            ),
+        }
+    }
+
+    /// Records the current value of every watched location, so that the next watchpoint hit can
+    /// show what it was before.
+    fn refresh_watched_values(&mut self) {
+        let inferior = self.inferior.as_ref().unwrap();
+        for wp in self.watchpoints.values_mut() {
+            if let Ok(bytes) = inferior.read_memory(wp.addr, wp.entity_type.size) {
+                wp.old_value = bytes;
+            }
+        }
+    }
+
+    /// Single-steps the inferior until the value watched by a software watchpoint changes, or
+    /// until it stops for any other reason: a breakpoint, a hardware watchpoint, a signal (such
+    /// as the SIGINT sent by Ctrl-C), or exiting.
+    fn step_until_watched_change(&mut self) -> Result<Status, nix::Error> {
+        loop {
+            match self.inferior.as_mut().unwrap().step()? {
+                Status::Stopped(Signal::SIGTRAP, _)
+                    if self.changed_software_watchpoint().is_none() => {}
+                status => return Ok(status),
+            }
+        }
+    }
+
+    /// Returns the number of the watchpoint in debug register slot `slot`.
+    fn watchpoint_in_slot(&self, slot: usize) -> Option<usize> {
+        self.watchpoints
+            .iter()
+            .find(|(_, wp)| wp.slot == Some(slot))
+            .map(|(&num, _)| num)
+    }
+
+    /// Returns the number of the first software watchpoint whose value has changed since the
+    /// inferior was resumed.
+    fn changed_software_watchpoint(&self) -> Option<usize> {
+        self.watchpoints
+            .iter()
+            .filter(|(_, wp)| wp.slot.is_none())
+            .map(|(&num, _)| num)
+            .find(|&num| self.watched_value(num).is_some_and(|(old, new)| old != new))
+    }
+
+    /// Returns the value of the location watched by watchpoint `num` when the inferior was
+    /// resumed, and its value now.
+    fn watched_value(&self, num: usize) -> Option<(Vec<u8>, Vec<u8>)> {
+        let wp = self.watchpoints.get(&num)?;
+        let new_value = self
+            .inferior
+            .as_ref()?
+            .read_memory(wp.addr, wp.entity_type.size)
+            .ok()?;
+        Some((wp.old_value.clone(), new_value))
+    }
+
+    /// Reports a stop caused by watchpoint `num` (None if the watchpoint can't be identified),
+    /// showing how the watched value changed and where.
+    fn report_watchpoint(&mut self, num: Option<usize>, instruction_ptr: usize) {
+        if let Some(num) = num {
+            self.print_watchpoint_change(num);
+        }
+        outputln!(
+            self.output,
+            "Stopped {}",
+            self.describe_stop(instruction_ptr)
+        );
+        self.print_source_line(instruction_ptr);
+    }
+
+    /// Prints watchpoint `num` with the old and new value of what it watches.
+    fn print_watchpoint_change(&self, num: usize) {
+        let wp = &self.watchpoints[&num];
+        let kind = if wp.slot.is_some() {
+            "Hardware"
+        } else {
+            "Software"
+        };
+        outputln!(
+            self.output,
+            "{} watchpoint {}: {}",
+            kind,
+            num,
+            wp.expression
+        );
+        if let Some((old_value, new_value)) = self.watched_value(num) {
+            outputln!(
+                self.output,
+                "Old value = {}",
+                value::format_value_limited(&old_value, &wp.entity_type, self.max_elements)
+            );
+            outputln!(
+                self.output,
+                "New value = {}",
+                value::format_value_limited(&new_value, &wp.entity_type, self.max_elements)
+            );
+        }
+    }
+
+    /// Describes `addr` as `func (file:line)`, falling back to the raw address for code without
+    /// debug info.
+    fn describe_location(&self, addr: usize) -> String {
+        match (
+            self.dwarf_data.get_function_from_addr(addr),
+            self.dwarf_data.get_line_from_addr(addr),
+        ) {
+            (Some(function), Some(line)) => format!("{} ({})", function, line),
+            (Some(function), None) => format!("{} ({:#x})", function, addr),
+            _ => match self.library_symbol(addr) {
+                Some((function, library)) => format!("{} ({})", function, library),
+                None => format!("{:#x}", addr),
+            },
+        }
+    }
+
+    /// Looks up link-time address `addr` in the shared libraries loaded by the stopped process,
+    /// returning e.g. `("puts+0x4", "/lib/x86_64-linux-gnu/libc.so.6")`.
+    fn library_symbol(&self, addr: usize) -> Option<(String, String)> {
+        let process = self.stopped_process().ok()?;
+        let runtime_addr = process.to_runtime(addr);
+        let library = SharedLibrary::containing(&process.memory_regions()?, runtime_addr)?;
+        let function = match library.symbolize(runtime_addr)? {
+            (name, 0) => self.dwarf_data.display_name(name),
+            (name, offset) => format!("{}+{:#x}", self.dwarf_data.display_name(name), offset),
+        };
+        Some((function, library.path))
+    }
+
+    /// Describes the address a breakpoint resolved to, e.g.
+    /// `0x401136: file main.c, line 27 (in compute)`.
+    fn describe_breakpoint(&self, addr: usize) -> String {
+        let line = match self.dwarf_data.get_line_from_addr(addr) {
+            Some(line) => line,
+            None => {
+                return match self.library_symbol(addr) {
+                    Some((function, library)) => {
+                        format!("{:#x} (in {} from {})", addr, function, library)
+                    }
+                    None => format!("{:#x}", addr),
+                }
+            }
+        };
+        let mut description = format!("{:#x}: file {}, line {}", addr, line.file, line.number);
+        if let Some(function) = self.dwarf_data.get_function_from_addr(addr) {
+            description.push_str(&format!(" (in {})", function));
+        }
+        description
+    }
+
+    /// Describes where the inferior stopped as `in func at file:line`, dropping whichever of the
+    /// two the debug info doesn't know and using the raw address in place of the line.
+    fn describe_stop(&self, addr: usize) -> String {
+        let place = match self.dwarf_data.get_line_from_addr(addr) {
+            Some(line) => line.to_string(),
+            None => format!("{:#x}", addr),
+        };
+        match self.dwarf_data.get_function_from_addr(addr) {
+            Some(function) => format!("in {} at {}", function, place),
+            None => match self.library_symbol(addr) {
+                Some((function, library)) => format!("in {} from {}", function, library),
+                None => format!("at {}", place),
+            },
+        }
+    }
+
+    /// Reports a stop at the catchpoint that was hit; `what` describes the event.
+    fn report_catchpoint(&mut self, what: &str, instruction_ptr: usize) {
+        outputln!(
+            self.output,
+            "Catchpoint {} ({})",
+            self.outcome.breakpoint.unwrap_or_default(),
+            what
+        );
+        outputln!(
+            self.output,
+            "Stopped {}",
+            self.describe_stop(instruction_ptr)
+        );
+        self.print_source_line(instruction_ptr);
+    }
+
+    /// Implements `info breakpoints`: lists breakpoints, watchpoints and catchpoints in order of
+    /// number.
+    fn print_breakpoint_list(&self) {
+        let mut nums: Vec<usize> = self
+            .breakpoints
+            .keys()
+            .chain(self.watchpoints.keys())
+            .chain(self.catchpoints.keys())
+            .copied()
+            .collect();
+        if nums.is_empty() {
+            outputln!(self.output, "No breakpoints or watchpoints.");
+            return;
+        }
+        nums.sort();
+        outputln!(
+            self.output,
+            "{:<8}{:<16}{:<20}What",
+            "Num",
+            "Type",
+            "Address"
+        );
+        for num in nums {
+            let (kind, addr, what) = if let Some(bp) = self.breakpoints.get(&num) {
+                let kind = if bp.temporary {
+                    "temp breakpoint"
+                } else {
+                    "breakpoint"
+                };
+                let (addr, mut what) = match bp.addr {
+                    Some(addr) => (format!("{:#018x}", addr), self.describe_location(addr)),
+                    None => ("<PENDING>".to_string(), bp.location.clone()),
+                };
+                if let Some(condition) = &bp.condition {
+                    what.push_str(&format!(" if {}", condition));
+                }
+                // The command list goes below, indented
+                for line in &bp.commands {
+                    what.push_str(&format!("\n{:<8}{}", "", line));
+                }
+                (kind, addr, what)
+            } else if let Some(event) = self.catchpoints.get(&num) {
+                ("catchpoint", String::new(), event.name().to_string())
+            } else {
+                let wp = &self.watchpoints[&num];
+                let kind = if wp.slot.is_some() {
+                    "hw watchpoint"
+                } else {
+                    "watchpoint"
+                };
+                (kind, format!("{:#018x}", wp.addr), wp.expression.clone())
+            };
+            outputln!(self.output, "{:<8}{:<16}{:<20}{}", num, kind, addr, what);
+        }
+    }
+
+    /// Lists the functions with debug info whose names match `pattern` (all of them if there is
+    /// no pattern), in order of address.
+    fn print_functions(&self, pattern: Option<&str>, raw: bool) {
+        match pattern {
+            Some(pattern) => outputln!(self.output, "All functions matching \"{}\":", pattern),
+            None => outputln!(self.output, "All defined functions:"),
+        }
+        let load_base = self
+            .stopped_process()
+            .map_or(0, |process| process.load_base());
+        for func in self.dwarf_data.get_functions() {
+            let name = if raw { &func.raw_name } else { &func.name };
+            if pattern.is_some_and(|pattern| !matches_pattern(pattern, name)) {
+                continue;
+            }
+            outputln!(
+                self.output,
+                "{:#018x}  {}  at {}:{}",
+                func.address.wrapping_add(load_base),
+                name,
+                func.file,
+                func.line_number
+            );
+        }
+    }
+
+    /// Lists the global variables with debug info whose names match `pattern` (all of them if
+    /// there is no pattern), in order of address.
+    fn print_variables(&self, pattern: Option<&str>) {
+        match pattern {
+            Some(pattern) => outputln!(self.output, "All variables matching \"{}\":", pattern),
+            None => outputln!(self.output, "All defined variables:"),
+        }
+        let load_base = self
+            .stopped_process()
+            .map_or(0, |process| process.load_base());
+        for var in self.dwarf_data.get_global_variables() {
+            if pattern.is_some_and(|pattern| !matches_pattern(pattern, &var.name)) {
+                continue;
+            }
+            let address = match var.address {
+                Some(address) => format!("{:#018x}", address.wrapping_add(load_base)),
+                None => "<optimized out>".to_string(),
+            };
+            outputln!(
+                self.output,
+                "{:<18}  {} {}  at {}:{}",
+                address,
+                var.type_name,
+                var.name,
+                var.file,
+                var.line_number
+            );
+        }
+    }
+
+    /// Lists the threads of `inferior`, each with the address and function it is stopped in. The
+    /// current thread is marked with an asterisk.
+    fn print_threads(&self, inferior: &Inferior) {
+        for &tid in inferior.threads() {
+            let marker = if tid == inferior.current_thread() {
+                '*'
+            } else {
+                ' '
+            };
+            match inferior.thread_instruction_ptr(tid) {
+                Ok(instruction_ptr) => {
+                    let location = match self.dwarf_data.get_function_from_addr(instruction_ptr) {
+                        Some(_) => self.describe_location(instruction_ptr),
+                        None => "??".to_string(),
+                    };
+                    outputln!(
+                        self.output,
+                        "{} Thread {}  {:#018x} in {}",
+                        marker,
+                        tid,
+                        instruction_ptr.wrapping_add(inferior.load_base()),
+                        location
+                    );
+                }
+                Err(err) => outputln!(
+                    self.output,
+                    "{} Thread {}  <cannot read registers: {}>",
+                    marker,
+                    tid,
+                    err
+                ),
+            }
+        }
+    }
+
+    /// Makes thread `tid` the one that frames, registers and single steps refer to, and shows
+    /// where it is stopped. With no `tid`, just shows which thread that is.
+    fn select_thread(&mut self, tid: Option<i32>) -> Result<(), String> {
+        let inferior = self.running_inferior_mut()?;
+        let tid = match tid {
+            Some(tid) => Pid::from_raw(tid),
+            None => {
+                let current = inferior.current_thread();
+                outputln!(self.output, "[Current thread is {}]", current);
+                return Ok(());
+            }
+        };
+        inferior
+            .select_thread(tid)
+            .map_err(|_| format!("Thread {} does not exist or has exited.", tid))?;
+        outputln!(self.output, "[Switching to thread {}]", tid);
+        self.select_frame(0);
+        Ok(())
+    }
+
+    /// Prints the source line containing `addr`, if the source file is available. If it isn't,
+    /// the location alone will have to do.
+    fn print_source_line(&mut self, addr: usize) {
+        if let Some(line) = self.dwarf_data.get_line_from_addr(addr) {
+            if self.source_cache.lines(&line.file).is_none() {
+                self.hint_source_directory();
+            }
+            if let Some(text) = self
+                .source_cache
+                .lines(&line.file)
+                .and_then(|lines| lines.get(line.number.checked_sub(1)?))
+            {
+                outputln!(
+                    self.output,
+                    "{}\t{}",
+                    line.number,
+                    source::render_line(text)
+                );
+            }
+        }
+    }
+
+    /// Returns the number of the breakpoint at `addr` that should stop the inferior, i.e. the
+    /// first one whose condition holds (or that has no condition) and whose ignore count is
+    /// used up. Breakpoints at `addr` that still have hits left to ignore are decremented.
+    /// Returns None if no breakpoint should stop the inferior.
+    fn triggered_breakpoint(&mut self, addr: usize) -> Option<usize> {
+        let nums: Vec<usize> = self
+            .breakpoints
+            .iter()
+            .filter(|(_, bp)| bp.addr == Some(addr))
+            .map(|(&num, _)| num)
+            .collect();
+        let mut triggered = None;
+        for num in nums {
+            let condition_holds = match &self.breakpoints[&num].condition {
+                None => true,
+                Some(condition) => match self.evaluate_condition(condition) {
+                    Ok(result) => result,
+                    Err(err) => {
+                        outputln!(
+                            self.output,
+                            "Error in condition for breakpoint {}: {}",
+                            num,
+                            err
+                        );
+                        true
+                    }
+                },
+            };
+            if !condition_holds {
+                continue;
+            }
+            let bp = self.breakpoints.get_mut(&num).unwrap();
+            if bp.ignore_count > 0 {
+                bp.ignore_count -= 1;
+                bp.skipped_hits += 1;
+            } else if triggered.is_none() {
+                triggered = Some(num);
+            } else {
+                // This stop is reported against the first triggered breakpoint
+                bp.skipped_hits = 0;
+            }
+        }
+        triggered
+    }
+
+    /// Returns the running inferior, or an error saying that there isn't one (and why).
+    fn running_inferior(&self) -> Result<&Inferior, String> {
+        self.inferior
+            .as_ref()
+            .ok_or_else(|| self.not_running_message())
+    }
+
+    fn running_inferior_mut(&mut self) -> Result<&mut Inferior, String> {
+        if self.inferior.is_none() {
+            return Err(self.not_running_message());
+        }
+        Ok(self.inferior.as_mut().unwrap())
+    }
+
+    /// Returns the process that commands which only read state operate on: the running inferior,
+    /// or else the core dump being inspected.
+    fn stopped_process(&self) -> Result<&dyn Target, String> {
+        match (&self.inferior, &self.core) {
+            (Some(inferior), _) => Ok(inferior),
+            (None, Some(core)) => Ok(core),
+            (None, None) => Err(self.not_running_message()),
+        }
+    }
+
+    fn not_running_message(&self) -> String {
+        if self.core.is_some() {
+            return "The program is not being run; only its core dump can be inspected."
+                .to_string();
+        }
+        match &self.last_exit {
+            Some(how) => format!("The program is not being run (it {}).", how),
+            None => "The program is not being run.".to_string(),
+        }
+    }
+
+    /// Evaluates a breakpoint condition in the selected frame of the stopped inferior.
+    fn evaluate_condition(&self, condition: &Condition) -> Result<bool, String> {
+        let process: &dyn Target = self.inferior.as_ref().unwrap();
+        let frame = self.current_frame(process)?;
+        condition.evaluate(&FrameContext {
+            debugger: self,
+            process,
+            frame,
+        })
+    }
+
+    /// Returns the stack frame selected with `frame`/`up`/`down`.
+    fn current_frame(&self, process: &dyn Target) -> Result<Frame, String> {
+        let frames = process
+            .stack_frames(&self.dwarf_data, MAX_BACKTRACE_FRAMES)
+            .map_err(|err| format!("Failed to walk the stack: {}", err))?;
+        frames
+            .get(self.selected_frame)
+            .copied()
+            .ok_or_else(|| format!("No frame at level {}.", self.selected_frame))
+    }
+
+    /// Selects stack frame `num` and prints it.
+    fn select_frame(&mut self, num: usize) {
+        let process = match self.stopped_process() {
+            Ok(process) => process,
+            Err(err) => {
+                self.report_error(err);
+                return;
+            }
+        };
+        let frames = match process.stack_frames(&self.dwarf_data, MAX_BACKTRACE_FRAMES) {
+            Ok(frames) => frames,
+            Err(err) => {
+                self.report_error(format!("Failed to walk the stack: {}", err));
+                return;
+            }
+        };
+        let frame = match frames.get(num) {
+            Some(frame) => frame,
+            None if num > self.selected_frame => {
+                self.report_error("Initial frame selected; you cannot go up.");
+                return;
+            }
+            None => {
+                self.report_error(format!("No frame at level {}.", num));
+                return;
+            }
+        };
+        outputln!(
+            self.output,
+            "{}",
+            process.format_frame(frame, num, &self.dwarf_data)
+        );
+        self.selected_frame = num;
+        self.last_listing = None;
+    }
+
+    /// Returns the source line of the selected frame, or of `main` if the program isn't running.
+    fn current_line(&self) -> Option<Line> {
+        let addr = match self.stopped_process().ok() {
+            Some(process) => self.current_frame(process).ok()?.instruction_ptr,
+            None => self.dwarf_data.get_addr_for_function(None, "main")?,
+        };
+        self.dwarf_data.get_line_from_addr(addr)
+    }
+
+    /// Disassembles `function`, or else the function of the selected frame (or the code from the
+    /// frame's instruction on, if it isn't in a known function). At most `DISASSEMBLE_LIMIT`
+    /// instructions are shown, chosen to include the frame's instruction if possible.
+    fn disassemble(&mut self, function: Option<&str>) -> Result<(), String> {
+        let inferior = self.running_inferior()?;
+        let frame_addr = self.current_frame(inferior)?.instruction_ptr;
+        let (mut start, end, name) = match function {
+            Some(name) => {
+                let addr = self
+                    .dwarf_data
+                    .get_addr_for_function(None, name)
+                    .ok_or_else(|| format!("No function \"{}\" in this program.", name))?;
+                let end = self
+                    .dwarf_data
+                    .get_function_containing(addr)
+                    .map(|func| func.address + func.text_length);
+                (addr, end, Some(name.to_string()))
+            }
+            None => match self.dwarf_data.get_function_containing(frame_addr) {
+                Some(func) => (
+                    func.address,
+                    Some(func.address + func.text_length),
+                    Some(self.dwarf_data.function_name(func)),
+                ),
+                None => (frame_addr, None, None),
+            },
+        };
+        if (start..end.unwrap_or(start)).contains(&frame_addr) {
+            // Keep a few instructions of context before the frame's instruction
+            const CONTEXT_INSTRUCTIONS: usize = 8;
+            let addrs = self.instruction_addresses(start, frame_addr)?;
+            if addrs.len() + 1 > DISASSEMBLE_LIMIT {
+                start = addrs[addrs.len() - CONTEXT_INSTRUCTIONS];
+            }
+        }
+        match name {
+            Some(name) => outputln!(self.output, "Dump of assembler code for function {}:", name),
+            None => outputln!(
+                self.output,
+                "Dump of assembler code from {:#x}:",
+                start.wrapping_add(inferior.load_base())
+            ),
+        }
+        self.print_instructions(start, end)
+    }
+
+    /// Returns the addresses of the instructions from `start` up to (not including) `end`.
+    fn instruction_addresses(&self, start: usize, end: usize) -> Result<Vec<usize>, String> {
+        let inferior = self.running_inferior()?;
+        let bytes = inferior
+            .read_code(start, end - start)
+            .map_err(|err| format!("Cannot access memory at {:#x}: {}", start, err))?;
+        let mut addrs = Vec::new();
+        let mut offset = 0;
+        while offset < bytes.len() {
+            addrs.push(start + offset);
+            offset += disassemble::decode(&bytes[offset..], start + offset).len;
+        }
+        Ok(addrs)
+    }
+
+    /// Prints up to `DISASSEMBLE_LIMIT` instructions starting at `start`, stopping at `end` (the
+    /// end of the function) if given. The instruction of the selected frame is marked with `=>`.
+    fn print_instructions(&mut self, start: usize, end: Option<usize>) -> Result<(), String> {
+        let inferior = self.running_inferior()?;
+        let load_base = inferior.load_base();
+        let frame_addr = self.current_frame(inferior)?.instruction_ptr;
+        let mut addr = start;
+        for _ in 0..DISASSEMBLE_LIMIT {
+            if end.is_some_and(|end| addr >= end) {
+                break;
+            }
+            let max_len = match end {
+                Some(end) => disassemble::MAX_INSTRUCTION_LEN.min(end - addr),
+                None => disassemble::MAX_INSTRUCTION_LEN,
+            };
+            let bytes = inferior.read_code(addr, max_len).map_err(|err| {
+                format!(
+                    "Cannot access memory at {:#x}: {}",
+                    addr.wrapping_add(load_base),
+                    err
+                )
+            })?;
+            // Decode with run-time addresses, so that branch targets are run-time addresses too
+            let instruction = disassemble::decode(&bytes, addr.wrapping_add(load_base));
+            let offset = match self.dwarf_data.get_function_containing(addr) {
+                Some(func) => format!(" <+{}>", addr - func.address),
+                None => String::new(),
+            };
+            let mut text = instruction.text();
+            if let Some(target) = instruction.branch_target {
+                if let Some(symbol) = self.symbolize(target.wrapping_sub(load_base)) {
+                    text.push_str(&format!(" {}", symbol));
+                }
+            }
+            if let Some(target) = instruction.memory_target {
+                text.push_str(&format!("        # {:#x}", target));
+            }
+            let hex_bytes: Vec<String> = bytes[..instruction.len]
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect();
+            outputln!(
+                self.output,
+                "{} {:#018x}{}:\t{:<20}\t{}",
+                if addr == frame_addr { "=>" } else { "  " },
+                addr.wrapping_add(load_base),
+                offset,
+                hex_bytes.join(" "),
+                text
+            );
+            addr += instruction.len;
+        }
+        if end.is_some_and(|end| addr >= end) {
+            self.last_disassembly = None;
+            outputln!(self.output, "End of assembler dump.");
+        } else {
+            self.last_disassembly = Some((addr, end));
+            outputln!(self.output, "(use \"disassemble more\" to continue)");
+        }
+        Ok(())
+    }
+
+    /// Prints lines of source code. With a line number, centers the listing on that line of the
+    /// file being listed; otherwise continues after the previous listing, or centers on the
+    /// current line if there was none. The current line is marked with `=>`.
+    fn list_source(&mut self, line_number: Option<usize>) -> Result<(), String> {
+        const CONTEXT_LINES: usize = 5;
+        let current_line = self.current_line();
+        let (file, first) = match (line_number, &self.last_listing) {
+            (Some(number), Some((file, _))) => (file.clone(), number.saturating_sub(CONTEXT_LINES)),
+            (None, Some((file, last))) => (file.clone(), last + 1),
+            (_, None) => {
+                let line = current_line
+                    .clone()
+                    .ok_or("No source information for the current location.")?;
+                let center = line_number.unwrap_or(line.number);
+                (line.file, center.saturating_sub(CONTEXT_LINES))
+            }
+        };
+        let first = first.max(1);
+        self.print_listing(&file, first, first + 2 * CONTEXT_LINES)
+    }
+
+    /// Lists function `name` from its first line to its closing brace (or, if the line table
+    /// doesn't say where it ends, a fixed number of lines).
+    fn list_function(&mut self, name: &str) -> Result<(), String> {
+        const FALLBACK_LINES: usize = 10;
+        let func = self
+            .dwarf_data
+            .get_functions()
+            .into_iter()
+            .find(|func| func.name == name || demangle::matches(&func.raw_name, name))
+            .ok_or_else(|| format!("Function \"{}\" not defined.", name))?;
+        // The line table has the path as compiled, which the compilation unit name may not
+        let file = self
+            .dwarf_data
+            .get_line_from_addr(func.address)
+            .map_or(func.file, |line| line.file);
+        let last = self
+            .dwarf_data
+            .get_function_last_line(func.address)
+            .filter(|&last| last >= func.line_number)
+            .unwrap_or(func.line_number + FALLBACK_LINES - 1);
+        self.print_listing(&file, func.line_number, last)
+    }
+
+    /// Lists lines `first` to `last` of `file`, or of the file being listed if no file is given.
+    fn list_range(&mut self, file: Option<&str>, first: usize, last: usize) -> Result<(), String> {
+        if first > last {
+            return Err(format!("Line range {},{} is backwards.", first, last));
+        }
+        let file = match file {
+            Some(file) => self.source_path(file)?,
+            None => match &self.last_listing {
+                Some((file, _)) => file.clone(),
+                None => {
+                    self.current_line()
+                        .ok_or("No source information for the current location.")?
+                        .file
+                }
+            },
+        };
+        self.print_listing(&file, first, last)
+    }
+
+    /// Returns the path of source file `file` as the line table records it, so that it can be
+    /// opened (a compilation unit may be named relative to a directory deet isn't running in).
+    fn source_path(&self, file: &str) -> Result<String, String> {
+        self.check_source_file(file)?;
+        let path = self
+            .dwarf_data
+            .lookup_line(Some(file), 0)
+            .ok()
+            .and_then(|addr| self.dwarf_data.get_line_from_addr(addr))
+            .map(|line| line.file);
+        Ok(path.unwrap_or_else(|| self.dwarf_data.get_matching_files(file)[0].to_string()))
+    }
+
+    /// Prints lines `first` to `last` of `file` (as far as it goes), marking the current line,
+    /// and remembers where the listing stopped.
+    fn print_listing(&mut self, file: &str, first: usize, last: usize) -> Result<(), String> {
+        let first = first.max(1);
+        let current_line = self.current_line();
+        if self.source_cache.lines(file).is_none() {
+            self.hint_source_directory();
+            return Err(format!("Cannot read source file {}.", file));
+        }
+        let lines = self.source_cache.lines(file).unwrap();
+        if first > lines.len() {
+            return Err(format!(
+                "Line number {} out of range; \"{}\" has {} lines.",
+                first,
+                file,
+                lines.len()
+            ));
+        }
+        let last = last.min(lines.len());
+        for number in first..=last {
+            let is_current = current_line
+                .as_ref()
+                .is_some_and(|line| line.file == file && line.number == number);
+            let marker = if is_current { "=>" } else { "" };
+            outputln!(
+                self.output,
+                "{:<3}{}\t{}",
+                marker,
+                number,
+                source::render_line(&lines[number - 1])
+            );
+        }
+        self.last_listing = Some((file.to_string(), last));
+        Ok(())
+    }
+
+    /// Points out, the first time a source file can't be found, how to say where to look.
+    fn hint_source_directory(&mut self) {
+        if !self.source_hint_shown {
+            self.source_hint_shown = true;
+            outputln!(
+                self.output,
+                "Some source files can't be found; use \"directory <path>\" to add a directory to search."
+            );
+        }
+    }
+
+    /// Looks up the variable `name` in the scope of the function containing `addr`, falling back
+    /// to global variables.
+    fn lookup_variable(&self, addr: usize, name: &str) -> Result<&Variable, String> {
+        self.dwarf_data
+            .get_local_variable(addr, name)
+            .or_else(|| self.dwarf_data.get_global_variable(name))
+            .ok_or_else(|| format!("No symbol \"{}\" in current context.", name))
+    }
+
+    /// Reads the raw bytes of `var` in the frame with frame base `frame_base`.
+    fn read_variable(
+        &self,
+        process: &dyn Target,
+        var: &Variable,
+        frame_base: usize,
+    ) -> Result<Vec<u8>, String> {
+        let addr = match var.location {
+            Location::OptimizedOut => return Err("<optimized out>".to_string()),
+            Location::Unsupported => return Err("<unsupported location>".to_string()),
+            _ => var
+                .location
+                .address(frame_base, process.load_base())
+                .unwrap(),
+        };
+        process
+            .read_memory(addr, var.entity_type.size)
+            .map_err(|err| format!("<cannot access memory at {:#x}: {}>", addr, err))
+    }
+
+    /// Reads the value of an integer-like variable in the frame with frame base `frame_base`,
+    /// sign-extending it if its type is signed.
+    fn read_integer_variable(
+        &self,
+        process: &dyn Target,
+        var: &Variable,
+        frame_base: usize,
+    ) -> Result<i64, String> {
+        let size = var.entity_type.size;
+        if size == 0
+            || size > 8
+            || matches!(
+                var.entity_type.kind,
+                TypeKind::Struct(_) | TypeKind::Array(..)
+            )
+        {
+            return Err(format!(
+                "Cannot use {} of type {} as an integer",
+                var.name, var.entity_type.name
+            ));
+        }
+        let bytes = self.read_variable(process, var, frame_base)?;
+        Ok(value::to_integer(
+            &bytes,
+            value::is_signed(&var.entity_type),
+        ))
+    }
+
+    /// Reads the value of `expr` along with its type, if it is a variable, a dereferenced pointer
+    /// with a known (non-void) type, an element of an array or pointer, or an artificial array
+    /// (`p@n`). Returns None for any other expression.
+    fn read_typed_value(
+        &self,
+        process: &dyn Target,
+        frame: Frame,
+        expr: &Expr,
+    ) -> Result<Option<TypedValue>, String> {
+        let context = FrameContext {
+            debugger: self,
+            process,
+            frame,
+        };
+        let read = |addr: usize, entity_type: Type| {
+            let bytes = process
+                .read_memory(addr, entity_type.size)
+                .map_err(|_| format!("Cannot access memory at address {:#x}", addr))?;
+            Ok(Some(TypedValue {
+                addr: Some(addr),
+                bytes,
+                entity_type,
+            }))
+        };
+        match expr {
+            Expr::Variable(name) => {
+                let var = self.lookup_variable(frame.instruction_ptr, name)?;
+                let bytes = self.read_variable(process, var, frame.frame_base)?;
+                Ok(Some(TypedValue {
+                    addr: var.location.address(frame.frame_base, process.load_base()),
+                    bytes,
+                    entity_type: var.entity_type.clone(),
+                }))
+            }
+            Expr::Deref(inner) => match self.read_typed_value(process, frame, inner)? {
+                Some(value) if matches!(value.entity_type.kind, TypeKind::Struct(_)) => {
+                    Err(format!(
+                        "Cannot dereference a value of type {}",
+                        value.entity_type.name
+                    ))
+                }
+                Some(value) => match self.pointee(&value) {
+                    Some((addr, pointee)) => read(addr, pointee.clone()),
+                    None => Ok(None),
+                },
+                None => Ok(None),
+            },
+            Expr::Index(base, index) => {
+                let base = match self.read_typed_value(process, frame, base)? {
+                    Some(base) => base,
+                    None => return Ok(None),
+                };
+                let (addr, element) = self.element_address(&base, index.evaluate(&context)?)?;
+                read(addr, element)
+            }
+            Expr::Repeat(base, count) => {
+                let count = count.evaluate(&context)?;
+                if count <= 0 {
+                    return Err("Only a positive number of elements can be shown with '@'.".into());
+                }
+                let base = self
+                    .read_typed_value(process, frame, base)?
+                    .ok_or("Only values in memory can be extended with '@'.")?;
+                // Unlike in gdb, `p@n` shows what p points to rather than n pointers
+                let (addr, element) = match self.pointee(&base) {
+                    Some((addr, pointee)) => (addr, pointee.clone()),
+                    None => (
+                        base.addr
+                            .ok_or("Only values in memory can be extended with '@'.")?,
+                        base.entity_type,
+                    ),
+                };
+                let size = element.size * count as usize;
+                if size > MAX_VALUE_SIZE {
+                    return Err(format!(
+                        "Value requires {} bytes, which is more than the maximum of {}.",
+                        size, MAX_VALUE_SIZE
+                    ));
+                }
+                let array = Type::new(
+                    format!("{} [{}]", element.name, count),
+                    size,
+                    TypeKind::Array(Box::new(element), Some(count as usize)),
+                );
+                read(addr, array)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Returns the address a pointer value points to and the type there, unless it is a void
+    /// pointer (or its target type is unknown).
+    fn pointee(&self, value: &TypedValue) -> Option<(usize, &Type)> {
+        match value.entity_type.kind {
+            TypeKind::Pointer(Some(target)) => self
+                .dwarf_data
+                .get_type(target)
+                .filter(|pointee| pointee.size > 0)
+                .map(|pointee| (value::to_integer(&value.bytes, false) as usize, pointee)),
+            _ => None,
+        }
+    }
+
+    /// Returns the address and type of element `index` of an array or pointer. Indexing past the
+    /// declared bounds of an array is allowed, with a warning.
+    fn element_address(&self, base: &TypedValue, index: i64) -> Result<(usize, Type), String> {
+        let (start, element) = match &base.entity_type.kind {
+            TypeKind::Array(element, count) => {
+                if count.is_some_and(|count| index < 0 || index as usize >= count) {
+                    outputln!(
+                        self.output,
+                        "Warning: index {} is out of bounds for type {}",
+                        index,
+                        base.entity_type.name
+                    );
+                }
+                let start = base
+                    .addr
+                    .ok_or("Cannot subscript an array that is not in memory.")?;
+                (start, element.as_ref())
+            }
+            _ => self.pointee(base).ok_or_else(|| {
+                format!("Cannot subscript a value of type {}", base.entity_type.name)
+            })?,
+        };
+        let addr = (start as i64).wrapping_add(index.wrapping_mul(element.size as i64));
+        Ok((addr as usize, element.clone()))
+    }
+
+    /// Prints the value of the variable `name` in the current function (or a global), of a
+    /// dereferenced pointer (e.g. `*p`), of an element (`buf[2]`), or of an artificial array
+    /// (`p@4`), formatted according to its type or in the explicit `format` (see
+    /// `value::FORMAT_LETTERS`). Anything more complex is evaluated as an integer expression.
+    /// Registers print in hex unless given another format.
+    fn print_variable(&self, name: &str, format: Option<char>) -> Result<(), String> {
+        let process = self.stopped_process()?;
+        let frame = self.current_frame(process)?;
+        let expr = Expr::parse(name)?;
+        if let Expr::Deref(_) | Expr::Index(..) | Expr::Repeat(..) = &expr {
+            if let Some(value) = self.read_typed_value(process, frame, &expr)? {
+                outputln!(
+                    self.output,
+                    "{} = {}",
+                    name,
+                    self.format_bytes_as(process, &value.bytes, &value.entity_type, format)
+                );
+                return Ok(());
+            }
+        }
+        if !matches!(expr, Expr::Variable(_)) {
+            let value = expr.evaluate(&FrameContext {
+                debugger: self,
+                process,
+                frame,
+            })?;
+            let format = match format {
+                None if matches!(expr, Expr::Register(_)) => Some('x'),
+                format => format,
+            };
+            let formatted = match format {
+                Some(format) => value::format_value_as(
+                    &value.to_le_bytes(),
+                    &Type::new("long".to_string(), 8, TypeKind::Base(Encoding::Signed)),
+                    format,
+                    None,
+                    &|addr| self.address_symbol(process, addr as usize),
+                ),
+                None => value.to_string(),
+            };
+            outputln!(self.output, "{} = {}", name, formatted);
+            return Ok(());
+        }
+        let var = self.lookup_variable(frame.instruction_ptr, name)?;
+        if matches!(var.location, Location::FramePointerOffset(_))
+            && self.dwarf_data.get_global_variable(name).is_some()
+        {
+            outputln!(
+                self.output,
+                "(local {} shadows a global variable of the same name)",
+                name
+            );
+        }
+        let formatted = match self.read_variable(process, var, frame.frame_base) {
+            Ok(bytes) => self.format_bytes_as(process, &bytes, &var.entity_type, format),
+            Err(unavailable) => unavailable,
+        };
+        outputln!(self.output, "{} = {}", name, formatted);
+        Ok(())
+    }
+
+    /// Implements `info locals`: prints every local variable of the function the selected frame
+    /// is in. Variables declared below the current line hold garbage, so are shown as unavailable.
+    fn print_locals(&self) -> Result<(), String> {
+        let process = self.stopped_process()?;
+        let frame = self.current_frame(process)?;
+        let function = self
+            .dwarf_data
+            .get_function_containing(frame.instruction_ptr)
+            .ok_or("No symbol table info available.")?;
+        let current_line = self
+            .dwarf_data
+            .get_line_from_addr(frame.instruction_ptr)
+            .map(|line| line.number);
+        let locals: Vec<&Variable> = function
+            .variables
+            .iter()
+            .filter(|var| !var.is_parameter)
+            .collect();
+        if locals.is_empty() {
+            outputln!(self.output, "No locals.");
+        }
+        for var in locals {
+            let declared_later = current_line.is_some_and(|line| var.line_number > line);
+            let value = if declared_later || matches!(var.location, Location::Unsupported) {
+                "<unavailable>".to_string()
+            } else {
+                self.format_variable(process, var, frame.frame_base)
+            };
+            outputln!(self.output, "{} = {}", var.name, value);
+        }
+        Ok(())
+    }
+
+    /// Formats the value of `var` in the frame with frame base `frame_base`, followed by the
+    /// string it points to if it is a `char *`. Values that can't be read are described instead.
+    fn format_variable(&self, process: &dyn Target, var: &Variable, frame_base: usize) -> String {
+        match self.read_variable(process, var, frame_base) {
+            Ok(bytes) => self.format_bytes(process, &bytes, &var.entity_type),
+            Err(unavailable) => unavailable,
+        }
+    }
+
+    /// Formats a value of type `entity_type`, followed by the string it points to if it is a
+    /// `char *`.
+    fn format_bytes(&self, process: &dyn Target, bytes: &[u8], entity_type: &Type) -> String {
+        let mut formatted = value::format_value_limited(bytes, entity_type, self.max_elements);
+        let pointer = value::to_integer(bytes, false) as usize;
+        if self.is_char_pointer(entity_type) && pointer != 0 {
+            formatted.push(' ');
+            formatted.push_str(&format_cstring(process, pointer));
+        }
+        formatted
+    }
+
+    /// Formats a value of type `entity_type` in the output format `format`, or as `format_bytes`
+    /// does if there is none.
+    fn format_bytes_as(
+        &self,
+        process: &dyn Target,
+        bytes: &[u8],
+        entity_type: &Type,
+        format: Option<char>,
+    ) -> String {
+        match format {
+            Some(format) => {
+                value::format_value_as(bytes, entity_type, format, self.max_elements, &|addr| {
+                    self.address_symbol(process, addr as usize)
+                })
+            }
+            None => self.format_bytes(process, bytes, entity_type),
+        }
+    }
+
+    /// Names the code that run-time address `addr` points into, as `<function+offset>`, for
+    /// the `a` output format.
+    fn address_symbol(&self, process: &dyn Target, addr: usize) -> Option<String> {
+        let link_time_addr = process.to_link_time(addr);
+        self.symbolize(link_time_addr).or_else(|| {
+            self.library_symbol(link_time_addr)
+                .map(|(function, _)| format!("<{}>", function))
+        })
+    }
+
+    /// Evaluates a display expression: a variable, a `$register`, or `*<address>` (which is read
+    /// as a 4-byte int, like `set` writes it).
+    fn evaluate_display(&self, expression: &str) -> Result<String, String> {
+        let process = self.stopped_process()?;
+        if expression.starts_with('$') {
+            let value = self.resolve_address(process, expression)?;
+            Ok(format!("{:#x}", value))
+        } else if let Some(addr) = expression.strip_prefix('*') {
+            let addr = self.resolve_address(process, addr)?;
+            let bytes = process
+                .read_memory(addr, 4)
+                .map_err(|err| err.to_string())?;
+            Ok(value::to_integer(&bytes, true).to_string())
+        } else {
+            let frame = self.current_frame(process)?;
+            let var = self.lookup_variable(frame.instruction_ptr, expression)?;
+            Ok(self.format_variable(process, var, frame.frame_base))
+        }
+    }
+
+    /// Prints the display with number `num`, or `<unavailable>` if its expression can't be
+    /// evaluated here (e.g. a local of another function).
+    fn print_display(&self, num: usize) {
+        let expression = &self.displays[&num];
+        let value = self
+            .evaluate_display(expression)
+            .unwrap_or_else(|_| "<unavailable>".to_string());
+        outputln!(self.output, "{}: {} = {}", num, expression, value);
+    }
+
+    /// Prints every display; called whenever the inferior stops.
+    fn print_displays(&self) {
+        for &num in self.displays.keys() {
+            self.print_display(num);
+        }
+    }
+
+    /// Implements `info display`.
+    fn print_display_list(&self) {
+        if self.displays.is_empty() {
+            outputln!(self.output, "There are no auto-display expressions now.");
+            return;
+        }
+        outputln!(self.output, "Auto-display expressions now in effect:");
+        outputln!(self.output, "Num\tExpression");
+        for (num, expression) in &self.displays {
+            outputln!(self.output, "{}\t{}", num, expression);
+        }
+    }
+
+    /// Returns true if `entity_type` is a pointer to a (possibly qualified) character type.
+    fn is_char_pointer(&self, entity_type: &Type) -> bool {
+        let pointee = match entity_type.kind {
+            TypeKind::Pointer(Some(target)) => self.dwarf_data.get_type(target),
+            _ => None,
+        };
+        matches!(
+            pointee.map(|pointee| &pointee.kind),
+            Some(TypeKind::Base(
+                Encoding::SignedChar | Encoding::UnsignedChar
+            ))
+        )
+    }
+
+    /// Implements `print/s`: prints the NUL-terminated string at a hex address, at the address in
+    /// a register, or at the address held by a pointer variable.
+    fn print_string(&self, target: &str) -> Result<(), String> {
+        let process = self.stopped_process()?;
+        let addr = if target.starts_with('$') || target.to_lowercase().starts_with("0x") {
+            self.resolve_address(process, target)?
+        } else {
+            let frame = self.current_frame(process)?;
+            let var = self.lookup_variable(frame.instruction_ptr, target)?;
+            if !matches!(var.entity_type.kind, TypeKind::Pointer(_)) {
+                return Err(format!(
+                    "{} has type {}, which is not a pointer",
+                    target, var.entity_type.name
+                ));
+            }
+            self.read_integer_variable(process, var, frame.frame_base)? as usize
+        };
+        outputln!(self.output, "{:#x} {}", addr, format_cstring(process, addr));
+        Ok(())
+    }
+
+    /// Resolves an address argument (see `address`) to an address in `process`'s memory. Failing
+    /// that, evaluates it as an expression (see `expression`) in the selected frame.
+    fn resolve_address(&self, process: &dyn Target, expr: &str) -> Result<usize, String> {
+        let load_base = process.load_base();
+        let resolved = AddressExpr::parse(expr).and_then(|address| {
+            address.evaluate(
+                |symbol| {
+                    self.symbol_address(symbol)
+                        .map(|addr| addr.wrapping_add(load_base))
+                },
+                |register| read_register(process, register),
+            )
+        });
+        match (resolved, Expr::parse(expr)) {
+            (Ok(addr), _) => Ok(addr),
+            (Err(_), Ok(parsed)) => {
+                let context = FrameContext {
+                    debugger: self,
+                    process,
+                    frame: self.current_frame(process)?,
+                };
+                Ok(parsed.evaluate(&context)? as usize)
+            }
+            (Err(err), Err(_)) => Err(err),
+        }
+    }
+
+    /// Returns the link-time address of a function or, failing that, a global variable.
+    fn symbol_address(&self, name: &str) -> Option<usize> {
+        self.dwarf_data
+            .get_addr_for_function(None, name)
+            .or_else(|| {
+                self.dwarf_data
+                    .get_global_variable(name)?
+                    .location
+                    .address(0, 0)
+            })
+    }
+
+    /// Implements `x/NFU addr`: dumps inferior memory in the requested format.
+    fn examine_memory(&self, memory_format: MemoryFormat, addr: &str) -> Result<(), String> {
+        let process = self.stopped_process()?;
+        let mut addr = self.resolve_address(process, addr)?;
+        let cannot_access = |addr: usize| format!("Cannot access memory at address {:#x}", addr);
+
+        if memory_format.format == 's' {
+            for _ in 0..memory_format.count {
+                match process.read_cstring(addr, MAX_STRING_LEN) {
+                    Ok(bytes) => {
+                        let formatted = value::format_cstring(&bytes, MAX_STRING_LEN);
+                        outputln!(self.output, "{:#x}:\t{}", addr, formatted);
+                        addr += bytes.len() + 1;
+                    }
+                    Err(_) => {
+                        // Without a terminator there is no telling where the next string starts
+                        outputln!(self.output, "{:#x}:\t<unreadable at {:#x}>", addr, addr);
+                        break;
+                    }
+                }
+            }
+            return Ok(());
+        }
+
+        let unit_size = match memory_format.format {
+            'c' => 1,
+            'a' => 8,
+            _ => memory_format.unit_size,
+        };
+        let units_per_line = match unit_size {
+            8 => 2,
+            4 => 4,
+            _ => 8,
+        };
+        let bytes = process
+            .read_memory_bulk(addr, memory_format.count * unit_size)
+            .map_err(|_| cannot_access(addr))?;
+        for line in bytes.chunks(unit_size * units_per_line) {
+            let units: Vec<String> = line
+                .chunks(unit_size)
+                .map(|unit| {
+                    value::format_memory_unit(unit, memory_format.format, &|addr| {
+                        self.address_symbol(process, addr as usize)
+                    })
+                })
+                .collect();
+            outputln!(self.output, "{:#x}:\t{}", addr, units.join("\t"));
+            addr += line.len();
+        }
+        Ok(())
+    }
+
+    /// Implements `find[/SN] <start>, <end or +length>, <values...>`: prints the address of every
+    /// place in the range where the values occur, one after the other. Parts of the range that
+    /// aren't mapped (or can't be read) are skipped.
+    fn find_memory(&self, find_format: FindFormat, args: &str) -> Result<(), String> {
+        let process = self.stopped_process()?;
+        let (start, end, values) = debugger_command::split_find(args)?;
+        let start = self.resolve_address(process, &start)?;
+        let end = match end.strip_prefix('+') {
+            Some(length) => match condition::parse_literal(length.trim()) {
+                Some(length) if length > 0 => start.saturating_add(length as usize),
+                _ => return Err(format!("Invalid search length \"{}\".", length)),
+            },
+            // As in gdb, the end address is part of the range
+            None => self.resolve_address(process, &end)?.saturating_add(1),
+        };
+        if end <= start {
+            return Err("Invalid search range: the end comes before the start.".to_string());
+        }
+        let pattern = memory_search::parse_pattern(&values, find_format.value_size)?;
+        let regions = process.memory_regions();
+        let ranges = memory_search::readable_ranges(regions.as_deref(), start, end);
+        let (matches, more) =
+            memory_search::search(&ranges, &pattern, find_format.max_matches, |addr, len| {
+                process.read_memory_bulk(addr, len).ok()
+            });
+        for &addr in &matches {
+            match self.address_symbol(process, addr) {
+                Some(symbol) => outputln!(self.output, "{:#x} {}", addr, symbol),
+                None => outputln!(self.output, "{:#x}", addr),
+            }
+        }
+        match (matches.len(), more) {
+            (0, _) => outputln!(self.output, "Pattern not found."),
+            (found, false) => outputln!(self.output, "{} pattern(s) found.", found),
+            (found, true) => outputln!(
+                self.output,
+                "Stopped after {} matches; use find/<n> to report more.",
+                found
+            ),
+        }
+        Ok(())
+    }
+
+    /// Sets register `name` (without the `$`) of the current thread to `value`, an integer or an
+    /// address expression like `main` or `$rsp+8`.
+    fn set_register(&mut self, name: &str, value: &str) -> Result<(), String> {
+        let inferior = self.running_inferior()?;
+        let mut registers =
+            RegisterFile::new(inferior.get_registers().map_err(|err| err.to_string())?);
+        let old_value = registers.get(name)?;
+        let new_value = match condition::parse_literal(value) {
+            Some(integer) => integer as u64,
+            None => self.resolve_address(inferior, value)? as u64,
+        };
+        registers.set(name, new_value)?;
+        self.running_inferior_mut()?
+            .set_registers(registers.regs())
+            .map_err(|err| format!("Cannot set ${}: {}", name, err))?;
+        outputln!(
+            self.output,
+            "${} = {:#x} (was {:#x})",
+            name,
+            registers.get(name)?,
+            old_value
+        );
+        Ok(())
+    }
+
+    /// Implements `set <target> = <value>`, where target is a variable name, a `$<register>` or
+    /// `*<address>` (which is written as a 4-byte int).
+    fn set_variable(&mut self, target: &str, value: &str) -> Result<(), String> {
+        if let Some(register) = target.strip_prefix('$') {
+            return self.set_register(register, value);
+        }
+        let inferior = self.running_inferior()?;
+        let (addr, size, is_float) = if let Some(addr) = target.strip_prefix('*') {
+            (self.resolve_address(inferior, addr)?, 4, false)
+        } else {
+            let frame = self.current_frame(inferior)?;
+            let var = self.lookup_variable(frame.instruction_ptr, target)?;
+            let addr = var
+                .location
+                .address(frame.frame_base, inferior.load_base())
+                .ok_or_else(|| format!("Cannot assign to {}: it is not in memory", target))?;
+            match var.entity_type.kind {
+                TypeKind::Base(_) | TypeKind::Pointer(_) if var.entity_type.size <= 8 => {}
+                _ => {
+                    return Err(format!(
+                        "Cannot assign to {} of type {}",
+                        target, var.entity_type.name
+                    ))
+                }
+            }
+            let is_float = matches!(var.entity_type.kind, TypeKind::Base(Encoding::Float));
+            (addr, var.entity_type.size, is_float)
+        };
+
+        let bytes = if is_float {
+            let float = value
+                .parse::<f64>()
+                .map_err(|_| format!("Invalid number \"{}\".", value))?;
+            match size {
+                4 => (float as f32).to_le_bytes().to_vec(),
+                8 => float.to_le_bytes().to_vec(),
+                _ => return Err(format!("Unsupported floating-point size {}", size)),
+            }
+        } else {
+            let integer = condition::parse_literal(value)
+                .ok_or_else(|| format!("Invalid number \"{}\".", value))?;
+            // Only write `size` bytes so that neighbouring values aren't clobbered
+            integer.to_le_bytes()[..size].to_vec()
+        };
+        self.inferior
+            .as_mut()
+            .unwrap()
+            .write_memory(addr, &bytes)
+            .map_err(|err| format!("Cannot access memory at address {:#x}: {}", addr, err))
+    }
+
+    /// Kills any existing inferiors, or detaches from one that deet attached to
+    fn clean(&mut self) {
+        match &self.inferior {
+            Some(inferior) if inferior.is_attached() => self.detach_inferior(),
+            Some(_) => self.kill_inferior(),
+            None => {}
+        }
+    }
+
+    /// Detaches from the inferior, leaving it running without its breakpoints
+    fn detach_inferior(&mut self) {
+        let running = std::mem::take(&mut self.running_in_background);
+        if let Some(mut inferior) = self.inferior.take() {
+            // Threads can only be detached while they are stopped
+            if running {
+                let _ = inferior.interrupt().and_then(|()| inferior.poll(true));
+            }
+            outputln!(self.output, "Detaching from process {}", inferior.pid());
+            if let Err(err) = inferior.detach() {
+                outputln!(self.output, "Failed to detach: {}", err);
+            }
+        }
+    }
+
+    /// Kills the inferior and reaps it
+    fn kill_inferior(&mut self) {
+        self.running_in_background = false;
+        if let Some(mut inferior) = self.inferior.take() {
+            outputln!(
+                self.output,
+                "Killing running inferior (pid {})",
+                inferior.pid()
+            );
+            match inferior.kill() {
+                Ok(_) => {
+                    // Reap the child so it doesn't linger as a zombie
+                    let _ = inferior.wait(None);
+                    outputln!(self.output, "Killed");
+                }
+                Err(e) => outputln!(self.output, "Failed to kill: {}", e),
+            }
+        }
+    }
+
+    /// Before quitting from the prompt with a live inferior, asks whether to kill it, detach from
+    /// it, or not quit after all, and does the first two. Returns false if the user cancelled.
+    /// Without a terminal to ask at, `quit` kills or detaches as it would in a script.
+    fn confirm_quit(&mut self) -> bool {
+        let pid = match &self.inferior {
+            Some(inferior) => inferior.pid(),
+            None => return true,
+        };
+        let readline = match &mut self.input {
+            CommandInput::Terminal { readline, .. } => readline,
+            CommandInput::Piped => return true,
+        };
+        let prompt = format!(
+            "A program is being debugged (pid {}). Kill it, detach, or cancel? [k/d/c] ",
+            pid
+        );
+        loop {
+            match readline.readline(&prompt) {
+                Ok(answer) => match answer.trim() {
+                    "k" | "kill" => break self.kill_inferior(),
+                    "d" | "detach" => break self.detach_inferior(),
+                    "c" | "cancel" => return false,
+                    _ => outputln!(self.output, "Please answer k, d or c."),
+                },
+                Err(ReadlineError::Interrupted) => return false,
+                // Ctrl+d again: quit the way we would have without asking
+                Err(_) => return true,
+            }
+        }
+        true
+    }
+
+    /// This function prompts the user to enter a command, and continues re-prompting until the user
+    /// enters a valid command. It uses DebuggerCommand::from_tokens to do the command parsing.
+    ///
+    /// You don't need to read, understand, or modify this function.
+    fn get_next_command(&mut self) -> DebuggerCommand {
+        loop {
+            // Whatever happened in the background is reported before the prompt
+            self.poll_background();
+            if !self.run_breakpoint_commands() {
+                return DebuggerCommand::Quit(self.quit_code);
+            }
+            let line = match self.read_line() {
+                Some(line) => line,
+                None if self.recording_commands.is_some() => {
+                    self.finish_recording_commands();
+                    continue;
+                }
+                // Ctrl+d, or the end of piped input, is the equivalent of "quit" for our purposes
+                None if self.confirm_quit() => return DebuggerCommand::Quit(None),
+                None => continue,
+            };
+            self.output.command(line.trim());
+            if self.record_command_line(line.trim()) {
+                continue;
+            }
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            match DebuggerCommand::from_tokens(&tokens) {
+                Ok(DebuggerCommand::Quit(code)) => {
+                    if self.confirm_quit() {
+                        return DebuggerCommand::Quit(code);
+                    }
+                }
+                Ok(cmd) => return cmd,
+                Err(err) => outputln!(self.output, "{}", err),
+            }
+        }
+    }
+
+    /// Reads the next non-blank line of input, or returns None at the end of it. From a terminal,
+    /// this prompts and records the line in the history file; other input is read silently,
+    /// skipping `#` comments as in scripts, and each line is echoed if `set echo` is on.
+    fn read_line(&mut self) -> Option<String> {
+        // Lines of a breakpoint's command list get a prompt of their own
+        let prompt = if self.recording_commands.is_some() {
+            ">"
+        } else {
+            "(deet) "
+        };
+        match &mut self.input {
+            CommandInput::Terminal {
+                readline,
+                history_path,
+            } => loop {
+                // Print prompt and get next line of user input
+                match readline.readline(prompt) {
+                    Err(ReadlineError::Interrupted) => {
+                        // User pressed ctrl+c. We're going to ignore it
+                        outputln!(self.output, "Type \"quit\" to exit");
+                    }
+                    Err(ReadlineError::Eof) => return None,
+                    Err(err) => {
+                        panic!("Unexpected I/O error: {:?}", err);
+                    }
+                    Ok(line) => {
+                        if line.trim().is_empty() {
+                            continue;
+                        }
+                        let _ = readline.add_history_entry(line.as_str());
+                        if let Err(err) = readline.save_history(history_path) {
+                            outputln!(
+                                self.output,
+                                "Warning: failed to save history file at {}: {}",
+                                history_path,
+                                err
+                            );
+                        }
+                        return Some(line);
+                    }
+                }
+            },
+            CommandInput::Piped => loop {
+                let line = match std::io::stdin().lines().next()? {
+                    Ok(line) => line,
+                    Err(err) => {
+                        outputln!(self.output, "Failed to read command: {}", err);
+                        return None;
+                    }
+                };
+                let line = line.trim();
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                if self.echo_commands {
+                    outputln!(self.output, "+ {}", line);
+                }
+                return Some(line.to_string());
+            },
+        }
+    }
+}
+
+/// Evaluates expressions in a stack frame of a stopped process.
+struct FrameContext<'a> {
+    debugger: &'a Debugger,
+    process: &'a dyn Target,
+    frame: Frame,
+}
+
+impl expression::Context for FrameContext<'_> {
+    fn variable(&self, name: &str) -> Result<i64, String> {
+        let var = self
+            .debugger
+            .lookup_variable(self.frame.instruction_ptr, name)?;
+        self.debugger
+            .read_integer_variable(self.process, var, self.frame.frame_base)
+    }
+
+    fn address_of(&self, name: &str) -> Result<i64, String> {
+        let var = self
+            .debugger
+            .lookup_variable(self.frame.instruction_ptr, name)?;
+        var.location
+            .address(self.frame.frame_base, self.process.load_base())
+            .map(|addr| addr as i64)
+            .ok_or_else(|| {
+                format!(
+                    "Can't take the address of {}, which is not in memory.",
+                    name
+                )
+            })
+    }
+
+    fn register(&self, name: &str) -> Result<i64, String> {
+        read_register(self.process, name).map(|value| value as i64)
+    }
+
+    fn read_word(&self, addr: i64) -> Result<i64, String> {
+        let bytes = self
+            .process
+            .read_memory(addr as usize, 8)
+            .map_err(|_| format!("Cannot access memory at address {:#x}", addr))?;
+        Ok(value::to_integer(&bytes, true))
+    }
+
+    fn element(&self, base: &Expr, index: i64) -> Result<i64, String> {
+        let base = match self
+            .debugger
+            .read_typed_value(self.process, self.frame, base)?
+        {
+            Some(base) => base,
+            None => {
+                let addr = base.evaluate(self)?.wrapping_add(index.wrapping_mul(8));
+                return self.read_word(addr);
+            }
+        };
+        let (addr, element) = self.debugger.element_address(&base, index)?;
+        if element.size == 0
+            || element.size > 8
+            || matches!(element.kind, TypeKind::Struct(_) | TypeKind::Array(..))
+        {
+            return Err(format!(
+                "Cannot use a value of type {} as an integer",
+                element.name
+            ));
+        }
+        let bytes = self
+            .process
+            .read_memory(addr, element.size)
+            .map_err(|_| format!("Cannot access memory at address {:#x}", addr))?;
+        Ok(value::to_integer(&bytes, value::is_signed(&element)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_inferior_cleared_after_exit() {
+        let mut debugger = Debugger::new(&DebuggerConfig::new("samples/exit"));
+        let outcome = debugger.execute(DebuggerCommand::Run(Vec::new(), false));
+        assert_eq!(outcome.stop, Some(Status::Exited(0)));
+        assert!(debugger.inferior.is_none());
+        assert_eq!(
+            debugger.not_running_message(),
+            "The program is not being run (it exited with status 0)."
+        );
+        // None of these may touch the dead process (or panic)
+        assert!(
+            !debugger
+                .execute(DebuggerCommand::Continue(true, false))
+                .quit
+        );
+        assert!(!debugger.execute(DebuggerCommand::Backtrace(None)).quit);
+        assert!(!debugger.execute(DebuggerCommand::Frame(None)).quit);
+        assert!(debugger.inferior.is_none());
+        assert!(debugger.execute(DebuggerCommand::Quit(None)).quit);
+    }
+
+    #[test]
+    fn test_exit_code() {
+        let mut debugger = Debugger::new(&DebuggerConfig::new("samples/segfault"));
+        assert_eq!(debugger.exit_code(true), 0);
+        debugger.execute(DebuggerCommand::Run(Vec::new(), false));
+        // Stopped at the SIGSEGV; delivering it kills the inferior
+        debugger.execute(DebuggerCommand::Continue(true, false));
+        assert_eq!(debugger.exit_code(true), 128 + 11);
+        assert_eq!(debugger.exit_code(false), 0);
+        assert!(debugger.execute(DebuggerCommand::Quit(Some(3))).quit);
+        assert_eq!(debugger.exit_code(false), 3);
+
+        let mut debugger = Debugger::new(&DebuggerConfig::new("samples/exit"));
+        debugger.execute(DebuggerCommand::Run(Vec::new(), false));
+        assert_eq!(debugger.exit_code(true), 0);
+    }
+
+    #[test]
+    fn test_start() {
+        let mut debugger = Debugger::new(&DebuggerConfig::new("samples/function_calls"));
+        debugger.execute(DebuggerCommand::Start(Vec::new()));
+        let inferior = debugger.inferior.as_ref().unwrap();
+        let ip = inferior
+            .thread_instruction_ptr(inferior.current_thread())
+            .unwrap();
+        let main = debugger
+            .dwarf_data
+            .get_addr_for_function(None, "main")
+            .unwrap();
+        assert_eq!(ip, debugger.dwarf_data.skip_prologue(main));
+        // The temporary breakpoint is gone once it has been hit
+        assert!(debugger.breakpoints.is_empty());
+        debugger.execute(DebuggerCommand::Quit(None));
+    }
+
+    #[test]
+    fn test_shared_library_breakpoint() {
+        let mut debugger = Debugger::new(&DebuggerConfig::new("samples/hello"));
+        let puts = || DebuggerCommand::Break("libc:puts".to_string(), None);
+        // Libraries are only known once the program has loaded them
+        assert!(debugger.execute(puts()).failed);
+        assert!(debugger.breakpoints.is_empty());
+        debugger.execute(DebuggerCommand::Start(Vec::new()));
+        assert!(!debugger.execute(puts()).failed);
+        assert!(
+            debugger
+                .execute(DebuggerCommand::Break(
+                    "libc:nosuchfunction".to_string(),
+                    None
+                ))
+                .failed
+        );
+
+        let outcome = debugger.execute(DebuggerCommand::Continue(true, false));
+        let addr = debugger.breakpoints[&1].addr.unwrap();
+        assert_eq!(outcome.stop, Some(Status::Breakpoint(addr)));
+        assert!(debugger.describe_stop(addr).starts_with("in puts from /"));
+        let frames = debugger
+            .execute(DebuggerCommand::Backtrace(None))
+            .frames
+            .unwrap();
+        // Unwinding out of puts takes libc's own call frame information
+        assert_eq!(frames.len(), 2);
+        let inferior = debugger.inferior.as_ref().unwrap();
+        let top = inferior.format_frame(&frames[0], 0, &debugger.dwarf_data);
+        assert!(top.contains(" in puts from /") && top.contains("libc"));
+        assert_eq!(
+            debugger
+                .dwarf_data
+                .get_function_from_addr(frames[1].instruction_ptr)
+                .as_deref(),
+            Some("main")
+        );
+        debugger.execute(DebuggerCommand::Quit(None));
+    }
+
+    #[test]
+    fn test_describe_stop() {
+        let debugger = Debugger::new(&DebuggerConfig::new("samples/function_calls"));
+        let func2 = debugger
+            .dwarf_data
+            .get_addr_for_function(None, "func2")
+            .unwrap();
+        let description = debugger.describe_stop(func2);
+        assert!(description.starts_with("in func2 at "));
+        assert!(description.ends_with("function_calls.c:9"));
+        assert_eq!(debugger.describe_stop(0x10), "at 0x10");
+    }
+
+    #[test]
+    fn test_describe_breakpoint() {
+        let debugger = Debugger::new(&DebuggerConfig::new("samples/function_calls"));
+        let func2 = debugger.resolve_location("func2").unwrap();
+        let description = debugger.describe_breakpoint(func2);
+        assert!(description.starts_with(&format!("{:#x}: file ", func2)));
+        assert!(description.ends_with("function_calls.c, line 10 (in func2)"));
+        // `*<address>` stays exact, prologue and all
+        let entry = debugger
+            .dwarf_data
+            .get_addr_for_function(None, "func2")
+            .unwrap();
+        assert!(entry < func2);
+        assert_eq!(
+            debugger.resolve_location(&format!("*{:#x}", entry)),
+            Ok(entry)
+        );
+    }
+
+    #[test]
+    fn test_function_breakpoint_skips_prologue() {
+        let mut debugger = Debugger::new(&DebuggerConfig::new("samples/locals"));
+        debugger.add_breakpoint("weigh", None, false);
+        debugger.execute(DebuggerCommand::Run(Vec::new(), false));
+        let process = debugger.stopped_process().unwrap();
+        let frame = debugger.current_frame(process).unwrap();
+        assert_eq!(
+            debugger
+                .dwarf_data
+                .get_line_from_addr(frame.instruction_ptr)
+                .unwrap()
+                .number,
+            4
+        );
+        let print = |expression: &str| {
+            let expr = Expr::parse(expression).unwrap();
+            let value = debugger
+                .read_typed_value(process, frame, &expr)
+                .unwrap()
+                .unwrap();
+            debugger.format_bytes(process, &value.bytes, &value.entity_type)
+        };
+        assert_eq!(print("count"), "21");
+        assert_eq!(print("scale"), "1000");
+        assert_eq!(print("tag"), "87 'W'");
+        debugger.execute(DebuggerCommand::Quit(None));
+    }
+
+    #[test]
+    fn test_list_function() {
+        let mut debugger = Debugger::new(&DebuggerConfig::new("samples/locals"));
+        debugger.list_function("weigh").unwrap();
+        let (file, last) = debugger.last_listing.clone().unwrap();
+        assert!(file.ends_with("locals.c"));
+        assert_eq!(last, 10);
+        debugger.list_range(Some("locals.c"), 12, 40).unwrap();
+        assert_eq!(debugger.last_listing.as_ref().unwrap().1, 15);
+        assert!(debugger.list_range(None, 5, 3).is_err());
+        assert!(debugger.list_function("nonexistent").is_err());
+    }
+
+    #[test]
+    fn test_breakpoint_line_snapping() {
+        let debugger = Debugger::new(&DebuggerConfig::new("samples/comments"));
+        let line_of = |location: &str| {
+            let addr = debugger.resolve_location(location).unwrap();
+            debugger.dwarf_data.get_line_from_addr(addr).unwrap().number
+        };
+        // Blank lines, comments and closing braces snap to the next line with code
+        assert_eq!(line_of("7"), 9);
+        assert_eq!(line_of("8"), 9);
+        assert_eq!(line_of("11"), 16);
+        assert_eq!(line_of("comments.c:13"), 16);
+        assert_eq!(line_of("18"), 19);
+        assert_eq!(line_of("10"), 10);
+        // Nothing past the end of the file to snap to
+        assert!(debugger.resolve_location("23").is_err());
+        assert!(debugger.resolve_location("comments.c:23").is_err());
+    }
+
+    #[test]
+    fn test_info_locals() {
+        let mut debugger = Debugger::new(&DebuggerConfig::new("samples/function_calls"));
+        debugger.execute(DebuggerCommand::InfoLocals);
+        assert!(debugger.outcome.failed);
+        debugger.add_breakpoint("12", None, false);
+        debugger.execute(DebuggerCommand::Run(Vec::new(), false));
+        debugger.execute(DebuggerCommand::InfoLocals);
+        assert!(!debugger.outcome.failed);
+        let process = debugger.stopped_process().unwrap();
+        let frame = debugger.current_frame(process).unwrap();
+        let sum = debugger
+            .lookup_variable(frame.instruction_ptr, "sum")
+            .unwrap();
+        assert_eq!(
+            debugger.format_variable(process, sum, frame.frame_base),
+            "47"
+        );
+        debugger.execute(DebuggerCommand::Quit(None));
+    }
+
+    #[test]
+    fn test_print_structs() {
+        let mut debugger = Debugger::new(&DebuggerConfig::new("samples/structs"));
+        debugger.add_breakpoint("43", None, false);
+        debugger.execute(DebuggerCommand::Run(Vec::new(), false));
+        let process = debugger.stopped_process().unwrap();
+        let frame = debugger.current_frame(process).unwrap();
+        let print = |expression: &str| {
+            let expr = Expr::parse(expression).unwrap();
+            debugger
+                .read_typed_value(process, frame, &expr)
+                .map(|value| {
+                    let value = value.unwrap();
+                    debugger.format_bytes(process, &value.bytes, &value.entity_type)
+                })
+        };
+        assert_eq!(print("point"), Ok("{x = 3, y = 7}".to_string()));
+        assert_eq!(print("*ptr"), Ok("{x = 3, y = 7}".to_string()));
+        assert_eq!(
+            print("global_rect"),
+            Ok("{origin = {x = 1, y = 2}, size = {x = 30, y = 40}, label = 114 'r'}".to_string())
+        );
+        assert!(print("head")
+            .unwrap()
+            .starts_with("{value = 1, next = (struct Node *) 0x"));
+        assert_eq!(
+            print("*no_node"),
+            Err("Cannot access memory at address 0x0".to_string())
+        );
+        assert!(print("**ptr").is_err());
+        debugger.execute(DebuggerCommand::Quit(None));
+    }
+
+    #[test]
+    fn test_print_arrays() {
+        let mut debugger = Debugger::new(&DebuggerConfig::new("samples/arrays"));
+        debugger.add_breakpoint("19", None, false);
+        debugger.execute(DebuggerCommand::Run(Vec::new(), false));
+        let process = debugger.stopped_process().unwrap();
+        let frame = debugger.current_frame(process).unwrap();
+        let print = |expression: &str| {
+            let expr = Expr::parse(expression).unwrap();
+            debugger
+                .read_typed_value(process, frame, &expr)
+                .map(|value| {
+                    let value = value.unwrap();
+                    debugger.format_bytes(process, &value.bytes, &value.entity_type)
+                })
+        };
+        assert_eq!(print("numbers"), Ok("{1, 2, 3, 0, 0, 0, 0, 0}".to_string()));
+        assert_eq!(print("greeting"), Ok("\"hi \\\"there\\\"\\n\"".to_string()));
+        assert_eq!(print("grid[1]"), Ok("{4, 5, 6}".to_string()));
+        assert_eq!(print("grid[1][2]"), Ok("6".to_string()));
+        assert_eq!(print("buffer"), Ok("{len = 3, data = \"abc\"}".to_string()));
+        assert_eq!(print("p@4"), Ok("{4, 9, 16, 25}".to_string()));
+        assert_eq!(print("numbers[1]@2"), Ok("{2, 3}".to_string()));
+        // Out of bounds, but still read
+        assert!(print("numbers[8]").is_ok());
+        assert!(print("buffer[0]").is_err());
+        assert!(print("p@0").is_err());
+        assert!(print("squares").unwrap().ends_with(", 39601...}"));
+
+        // Elements also work in integer expressions
+        let context = FrameContext {
+            debugger: &debugger,
+            process,
+            frame,
+        };
+        let eval = |expression: &str| Expr::parse(expression).unwrap().evaluate(&context);
+        assert_eq!(eval("p[1] + grid[0][2]"), Ok(12));
+        assert_eq!(eval("*p + 1"), Ok(5));
+        assert!(eval("grid[1]").is_err());
+        debugger.execute(DebuggerCommand::Quit(None));
+    }
+
+    #[test]
+    fn test_address_breakpoint_in_pie() {
+        let mut debugger = Debugger::new(&DebuggerConfig::new("samples/pie"));
+        debugger.execute(DebuggerCommand::Break("main".to_string(), None));
+        debugger.execute(DebuggerCommand::Run(Vec::new(), false));
+        // Rebasing an address at the top of the address space wraps instead of overflowing
+        assert!(debugger.resolve_location("*0xffffffffffffffff").is_err());
+        assert!(debugger.resolve_location("*main").is_ok());
+        debugger.execute(DebuggerCommand::Quit(None));
+    }
+
+    #[test]
+    fn test_software_watchpoint() {
+        let mut debugger = Debugger::new(&DebuggerConfig::new("samples/pie"));
+        debugger.execute(DebuggerCommand::Break("11".to_string(), None));
+        debugger.execute(DebuggerCommand::Run(Vec::new(), false));
+        debugger.execute(DebuggerCommand::SoftwareWatch("counter".to_string()));
+        debugger.execute(DebuggerCommand::Continue(true, false));
+        // Stopped right after the write, with the change still visible
+        assert_eq!(debugger.current_line().unwrap().number, 7);
+        assert_eq!(debugger.changed_software_watchpoint(), Some(1));
+        let (old_value, new_value) = debugger.watched_value(1).unwrap();
+        assert_eq!(old_value, 0i32.to_le_bytes());
+        assert_eq!(new_value, 3i32.to_le_bytes());
+        assert!(debugger.execute(DebuggerCommand::Quit(None)).quit);
+    }
+
+    #[test]
+    fn test_source_script() {
+        let dir = std::env::temp_dir().join(format!("deet-source-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let script = dir.join("outer.deet");
+        let nested = dir.join("nested.deet");
+        let looping = dir.join("loop.deet");
+        fs::write(
+            &script,
+            format!(
+                "# set up\n\nbreak func1\nsource {}\nbreak func3\n",
+                nested.display()
+            ),
+        )
+        .unwrap();
+        fs::write(&nested, "br func2\nbreak *0x1\nbreak main\n").unwrap();
+        fs::write(&looping, format!("source {}\n", looping.display())).unwrap();
+
+        let mut debugger = Debugger::new(&DebuggerConfig::new("samples/function_calls"));
+        assert!(debugger.source_script(script.to_str().unwrap(), true));
+        // The failed breakpoint stops both the nested script and the one sourcing it
+        assert!(debugger.outcome.failed);
+        assert_eq!(debugger.breakpoints.len(), 2);
+
+        // Recursion is cut off rather than overflowing the stack
+        assert!(debugger.source_script(looping.to_str().unwrap(), true));
+        assert!(debugger.outcome.failed);
+        assert_eq!(debugger.source_depth, 0);
+
+        fs::write(&script, "break func1\nquit\nbreak func2\n").unwrap();
+        assert!(!debugger.source_script(script.to_str().unwrap(), true));
+        assert_eq!(debugger.breakpoints.len(), 3);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_pending_breakpoints() {
+        let mut debugger = Debugger::new(&DebuggerConfig::new("samples/function_calls"));
+        debugger.add_breakpoint("nosuchfunction", None, false);
+        debugger.add_breakpoint("nosuchfile.c:3", None, false);
+        assert!(!debugger.outcome.failed);
+        assert_eq!(debugger.breakpoints[&0].addr, None);
+        assert_eq!(debugger.breakpoints[&1].addr, None);
+        // Addresses and line numbers can't turn up later
+        debugger.add_breakpoint("*0x1", None, false);
+        debugger.add_breakpoint("9999", None, false);
+        assert!(debugger.outcome.failed);
+        assert_eq!(debugger.breakpoints.len(), 2);
+
+        // As if the function had been found in a library loaded since
+        debugger.breakpoints.get_mut(&0).unwrap().location = "func2".to_string();
+        debugger.resolve_pending_breakpoints();
+        assert_eq!(
+            debugger.breakpoints[&0].addr,
+            debugger.resolve_location("func2").ok()
+        );
+        assert_eq!(debugger.breakpoints[&1].addr, None);
+        assert!(debugger.delete_breakpoint(1));
+        assert_eq!(debugger.breakpoints.len(), 1);
+    }
+
+    #[test]
+    fn test_command_outcomes() {
+        let mut debugger = Debugger::new(&DebuggerConfig::new("samples/function_calls"));
+        let outcome = debugger.execute(DebuggerCommand::Break("func3".to_string(), None));
+        assert_eq!(outcome, CommandOutcome::default());
+        let func3 = debugger.breakpoints[&0].addr.unwrap();
+
+        let outcome = debugger.execute(DebuggerCommand::Run(Vec::new(), false));
+        assert_eq!(outcome.stop, Some(Status::Breakpoint(func3)));
+        assert_eq!(outcome.breakpoint, Some(0));
+        let frames = debugger
+            .execute(DebuggerCommand::Backtrace(None))
+            .frames
+            .unwrap();
+        let names: Vec<String> = frames
+            .iter()
+            .map(|frame| {
+                debugger
+                    .dwarf_data
+                    .get_function_from_addr(frame.instruction_ptr)
+                    .unwrap()
+            })
+            .collect();
+        assert_eq!(names, ["func3", "func2", "func1", "main"]);
+        assert_eq!(frames[0].instruction_ptr, func3);
+        let limited = debugger.execute(DebuggerCommand::Backtrace(Some(2)));
+        assert_eq!(limited.frames.unwrap(), frames[..2]);
+
+        // func3 is called again from func1
+        let outcome = debugger.execute(DebuggerCommand::Continue(true, false));
+        assert_eq!(outcome.stop, Some(Status::Breakpoint(func3)));
+        let outcome = debugger.execute(DebuggerCommand::Continue(true, false));
+        assert_eq!(outcome.stop, Some(Status::Exited(0)));
+        assert_eq!(outcome.breakpoint, None);
+        let outcome = debugger.execute(DebuggerCommand::Backtrace(None));
+        assert!(outcome.failed && outcome.frames.is_none());
+        assert!(debugger.execute(DebuggerCommand::Quit(None)).quit);
+    }
+
+    #[test]
+    fn test_breakpoint_commands() {
+        let mut debugger = Debugger::new(&DebuggerConfig::new("samples/function_calls"));
+        debugger.execute(DebuggerCommand::Break("func3".to_string(), None));
+        let func3 = debugger.breakpoints[&0].addr.unwrap();
+        assert!(debugger.execute(DebuggerCommand::Commands(Some(1))).failed);
+        assert!(debugger.recording_commands.is_none());
+        debugger.execute(DebuggerCommand::Commands(None));
+        for line in ["print a", "continue", "end"] {
+            assert!(debugger.record_command_line(line));
+        }
+        assert!(!debugger.record_command_line("run"));
+        assert_eq!(debugger.breakpoints[&0].commands, ["print a", "continue"]);
+
+        // Both calls to func3 run the list, and its final continue lets the program finish
+        let outcome = debugger.execute(DebuggerCommand::Run(Vec::new(), false));
+        assert_eq!(outcome.stop, Some(Status::Exited(0)));
+        assert!(!outcome.failed);
+
+        // The list stops at a failing command, leaving the inferior at the breakpoint
+        debugger.execute(DebuggerCommand::Commands(Some(0)));
+        for line in ["print nosuchvariable", "continue", "end"] {
+            debugger.record_command_line(line);
+        }
+        let outcome = debugger.execute(DebuggerCommand::Run(Vec::new(), false));
+        assert_eq!(outcome.stop, Some(Status::Breakpoint(func3)));
+        assert!(outcome.failed);
+
+        // An empty list removes the commands
+        debugger.execute(DebuggerCommand::Commands(Some(0)));
+        debugger.record_command_line("end");
+        assert!(debugger.breakpoints[&0].commands.is_empty());
+        assert!(debugger.execute(DebuggerCommand::Quit(None)).quit);
+    }
+
+    #[test]
+    fn test_condition_command() {
+        let mut debugger = Debugger::new(&DebuggerConfig::new("samples/function_calls"));
+        debugger.execute(DebuggerCommand::Break("func2".to_string(), None));
+        let func2 = debugger.breakpoints[&0].addr.unwrap();
+        let condition = |text: &str| Some(text.to_string());
+        assert!(debugger.execute(DebuggerCommand::Condition(1, None)).failed);
+        assert!(
+            !debugger
+                .execute(DebuggerCommand::Condition(0, condition("$rdi == 1")))
+                .failed
+        );
+        let outcome = debugger.execute(DebuggerCommand::Run(Vec::new(), false));
+        assert_eq!(outcome.stop, Some(Status::Exited(0)));
+
+        // A condition that doesn't parse is rejected, keeping the previous one
+        assert!(
+            debugger
+                .execute(DebuggerCommand::Condition(0, condition("$rdi = 42")))
+                .failed
+        );
+        assert_eq!(
+            debugger.breakpoints[&0].condition,
+            Some(Condition::parse("$rdi == 1").unwrap())
+        );
+        debugger.execute(DebuggerCommand::Condition(0, condition("$rdi == 42")));
+        let outcome = debugger.execute(DebuggerCommand::Run(Vec::new(), false));
+        assert_eq!(outcome.stop, Some(Status::Breakpoint(func2)));
+
+        debugger.execute(DebuggerCommand::Condition(0, None));
+        assert!(debugger.breakpoints[&0].condition.is_none());
+        assert!(debugger.execute(DebuggerCommand::Quit(None)).quit);
+    }
+
+    #[test]
+    fn test_breakpoint_on_one_byte_instruction() {
+        // recurse starts with `push %rbp`, which is a single byte
+        let mut debugger = Debugger::new(&DebuggerConfig::new("samples/recursion"));
+        debugger.execute(DebuggerCommand::Break("*recurse".to_string(), None));
+        let addr = debugger.breakpoints[&0].addr;
+        let outcome = debugger.execute(DebuggerCommand::Run(Vec::new(), false));
+        assert_eq!(outcome.stop, Some(Status::Breakpoint(addr.unwrap())));
+        // Stepping over the breakpoint must not rewind onto it again
+        for _ in 0..3 {
+            let outcome = debugger.execute(DebuggerCommand::Continue(true, false));
+            assert_eq!(outcome.stop, Some(Status::Breakpoint(addr.unwrap())));
+        }
+        let outcome = debugger.execute(DebuggerCommand::Continue(true, false));
+        assert_eq!(outcome.stop, Some(Status::Exited(0)));
+    }
+
+    #[test]
+    fn test_run_banner_with_redirections() {
+        let dir = std::env::temp_dir().join(format!("deet-banner-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let log = dir.join("log");
+        let out = dir.join("out").to_str().unwrap().to_string();
+        let mut debugger = Debugger::new(&DebuggerConfig::new("samples/hello"));
+        debugger.output.start_log(log.to_str().unwrap()).unwrap();
+        let run = |args: &[&str]| {
+            DebuggerCommand::Run(args.iter().map(|arg| arg.to_string()).collect(), false)
+        };
+        // Nothing is started (or announced) if a redirection can't be opened
+        assert!(debugger.execute(run(&["a", "<", "/nonexistent"])).failed);
+        let outcome = debugger.execute(run(&["a", ">", &out, "b"]));
+        assert_eq!(outcome.stop, Some(Status::Exited(0)));
+        debugger.output.stop_log();
+
+        let banners: Vec<String> = std::fs::read_to_string(&log)
+            .unwrap()
+            .lines()
+            .filter(|line| line.starts_with("Starting"))
+            .map(String::from)
+            .collect();
+        assert_eq!(
+            banners,
+            ["Starting samples/hello with args: a b (address space randomization disabled)"]
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_arithmetic_condition() {
+        let mut debugger = Debugger::new(&DebuggerConfig::new("samples/recursion"));
+        debugger.execute(DebuggerCommand::Break(
+            "recurse".to_string(),
+            Some("n*2 == 2".to_string()),
+        ));
+        let outcome = debugger.execute(DebuggerCommand::Run(Vec::new(), false));
+        assert!(matches!(outcome.stop, Some(Status::Breakpoint(_))));
+        let holds = |text: &str| debugger.evaluate_condition(&Condition::parse(text).unwrap());
+        assert_eq!(holds("n == 1 & depth == 2"), Ok(true));
+        debugger.execute(DebuggerCommand::Quit(None));
+    }
+
+    #[test]
+    fn test_dereference_condition() {
+        let mut debugger = Debugger::new(&DebuggerConfig::new("samples/arrays"));
+        let condition = |text: &str| Some(text.to_string());
+        debugger.execute(DebuggerCommand::Break(
+            "19".to_string(),
+            condition("*p == 3"),
+        ));
+        let outcome = debugger.execute(DebuggerCommand::Run(Vec::new(), false));
+        assert_eq!(outcome.stop, Some(Status::Exited(0)));
+
+        // p points to an int, so only squares[2] is read
+        debugger.execute(DebuggerCommand::Condition(0, condition("*p == 4")));
+        let outcome = debugger.execute(DebuggerCommand::Run(Vec::new(), false));
+        assert!(matches!(outcome.stop, Some(Status::Breakpoint(_))));
+        debugger.execute(DebuggerCommand::Quit(None));
+    }
+
+    #[test]
+    fn test_catchpoints() {
+        let mut debugger = Debugger::new(&DebuggerConfig::new("samples/exec"));
+        debugger.execute(DebuggerCommand::CatchExec);
+        debugger.execute(DebuggerCommand::CatchExit);
+        debugger.execute(DebuggerCommand::Break("func2".to_string(), None));
+        assert!(debugger.breakpoints[&2].addr.is_none());
+
+        let args = vec!["samples/function_calls".to_string()];
+        let outcome = debugger.execute(DebuggerCommand::Run(args, false));
+        assert!(matches!(outcome.stop, Some(Status::Exec(_))));
+        assert_eq!(outcome.breakpoint, Some(0));
+        // The breakpoint resolved in the new program's debug info
+        let func2 = debugger.breakpoints[&2].addr.unwrap();
+        let outcome = debugger.execute(DebuggerCommand::Continue(true, false));
+        assert_eq!(outcome.stop, Some(Status::Breakpoint(func2)));
+        let outcome = debugger.execute(DebuggerCommand::Continue(true, false));
+        assert!(matches!(outcome.stop, Some(Status::Exiting(0, _))));
+        assert_eq!(outcome.breakpoint, Some(1));
+
+        // Without the exit catchpoint, the inferior runs to its end
+        debugger.execute(DebuggerCommand::Delete(Some(1)));
+        let outcome = debugger.execute(DebuggerCommand::Continue(true, false));
+        assert_eq!(outcome.stop, Some(Status::Exited(0)));
+        assert!(debugger.execute(DebuggerCommand::Quit(None)).quit);
+    }
+
+    #[test]
+    fn test_call_function() {
+        let mut debugger = Debugger::new(&DebuggerConfig::new("samples/call"));
+        let call = |text: &str| DebuggerCommand::Call(text.to_string());
+        assert!(debugger.execute(call("add(1, 2)")).failed);
+        debugger.execute(DebuggerCommand::Break("24".to_string(), None));
+        let line24 = debugger.breakpoints[&0].addr.unwrap();
+        debugger.execute(DebuggerCommand::Run(Vec::new(), false));
+
+        let outcome = debugger.execute(call("add(20, 22)"));
+        assert_eq!(outcome.stop, Some(Status::Returned(42)));
+        let outcome = debugger.execute(call("length(greeting)"));
+        assert_eq!(outcome.stop, Some(Status::Returned(5)));
+        assert!(debugger.execute(call("add(1)")).failed);
+        assert!(debugger.execute(call("nosuch()")).failed);
+
+        // A crash abandons the call, leaving the program where it was
+        let outcome = debugger.execute(call("length(0)"));
+        assert!(matches!(
+            outcome.stop,
+            Some(Status::Stopped(Signal::SIGSEGV, _))
+        ));
+        let inferior = debugger.inferior.as_ref().unwrap();
+        assert!(!inferior.in_call());
+        assert_eq!(
+            inferior
+                .thread_instruction_ptr(inferior.current_thread())
+                .unwrap(),
+            line24
+        );
+        assert_eq!(inferior.pending_signal(), None);
+
+        // A breakpoint in the called function stops it, and calls can't nest
+        debugger.execute(DebuggerCommand::Break("add".to_string(), None));
+        let outcome = debugger.execute(call("add(2, 3)"));
+        assert_eq!(outcome.breakpoint, Some(1));
+        assert!(debugger.execute(call("add(4, 5)")).failed);
+        let outcome = debugger.execute(DebuggerCommand::Continue(true, false));
+        assert_eq!(outcome.stop, Some(Status::Returned(5)));
+
+        // The program goes on as if nothing happened, but for the calls' side effects
+        debugger.execute(DebuggerCommand::Delete(Some(1)));
+        debugger.execute(call("reset()"));
+        let outcome = debugger.execute(DebuggerCommand::Continue(true, false));
+        assert_eq!(outcome.stop, Some(Status::Exited(0)));
+    }
+
+    #[test]
+    fn test_rerun_stops_at_breakpoints() {
+        let mut debugger = Debugger::new(&DebuggerConfig::new("samples/function_calls"));
+        debugger.add_breakpoint("func2", None, false);
+        debugger.add_breakpoint("func3", None, false);
+        let func2 = debugger.breakpoints[&0].addr.unwrap();
+        let func3 = debugger.breakpoints[&1].addr.unwrap();
+        let stopped_at = |debugger: &Debugger| {
+            let inferior = debugger.inferior.as_ref().unwrap();
+            inferior
+                .thread_instruction_ptr(inferior.current_thread())
+                .unwrap()
+        };
+
+        debugger.execute(DebuggerCommand::Run(Vec::new(), false));
+        assert_eq!(stopped_at(&debugger), func2);
+        debugger.execute(DebuggerCommand::Run(Vec::new(), false));
+        assert_eq!(stopped_at(&debugger), func2);
+        // Breakpoints changed in between runs are planted from the list, not the old process
+        debugger.delete_breakpoint(0);
+        debugger.execute(DebuggerCommand::Run(Vec::new(), false));
+        assert_eq!(stopped_at(&debugger), func3);
+        debugger.add_breakpoint("func2", None, false);
+        debugger.execute(DebuggerCommand::Run(Vec::new(), false));
+        assert_eq!(stopped_at(&debugger), func2);
+        debugger.execute(DebuggerCommand::Continue(true, false));
+        assert_eq!(stopped_at(&debugger), func3);
+        assert!(!debugger.outcome.failed);
+        debugger.execute(DebuggerCommand::Quit(None));
+    }
+
+    #[test]
+    fn test_restart() {
+        let mut debugger = Debugger::new(&DebuggerConfig::new("samples/function_calls"));
+        debugger.add_breakpoint("func2", None, false);
+        debugger.execute(DebuggerCommand::Run(vec!["a".to_string()], false));
+        let first_pid = debugger.inferior.as_ref().unwrap().pid();
+        debugger.execute(DebuggerCommand::Restart);
+        let inferior = debugger.inferior.as_ref().unwrap();
+        assert_ne!(inferior.pid(), first_pid);
+        assert_eq!(
+            inferior.thread_instruction_ptr(inferior.current_thread()),
+            Ok(debugger.breakpoints[&0].addr.unwrap())
+        );
+        assert_eq!(debugger.args, ["a"]);
+        assert!(!debugger.outcome.failed);
+        debugger.execute(DebuggerCommand::Quit(None));
+    }
+
+    #[test]
+    fn test_set_register() {
+        let mut debugger = Debugger::new(&DebuggerConfig::new("samples/function_calls"));
+        debugger.add_breakpoint("func2", None, false);
+        debugger.add_breakpoint("func3", None, false);
+        debugger.execute(DebuggerCommand::Run(Vec::new(), false));
+        let func3 = debugger.breakpoints[&1].addr.unwrap();
+        let load_base = debugger.inferior.as_ref().unwrap().load_base();
+        debugger.execute(DebuggerCommand::SetVariable(
+            "$rip".to_string(),
+            format!("{:#x}", func3 + load_base),
+        ));
+        debugger.execute(DebuggerCommand::SetVariable(
+            "$eax".to_string(),
+            "42".to_string(),
+        ));
+        assert!(!debugger.outcome.failed);
+        let inferior = debugger.inferior.as_ref().unwrap();
+        assert_eq!(read_register(inferior, "eax"), Ok(42));
+        // Jumping onto a breakpoint reports it when continuing rather than stepping over it
+        debugger.execute(DebuggerCommand::Continue(true, false));
+        let inferior = debugger.inferior.as_ref().unwrap();
+        assert_eq!(
+            inferior.thread_instruction_ptr(inferior.current_thread()),
+            Ok(func3)
+        );
+
+        debugger.execute(DebuggerCommand::SetVariable(
+            "$nosuch".to_string(),
+            "1".to_string(),
+        ));
+        assert!(debugger.outcome.failed);
+        // Writing past the end of the address space fails rather than overflowing
+        debugger.execute(DebuggerCommand::SetVariable(
+            "*-1".to_string(),
+            "1".to_string(),
+        ));
+        assert!(debugger.outcome.failed);
+        debugger.execute(DebuggerCommand::Quit(None));
+    }
+
+    #[test]
+    fn test_checkpoint_restore() {
+        let mut debugger = Debugger::new(&DebuggerConfig::new("samples/function_calls"));
+        debugger.add_breakpoint("func3", None, false);
+        let func3 = debugger.breakpoints[&0].addr.unwrap();
+        let global = debugger.symbol_address("global").unwrap();
+        let read_global = |debugger: &Debugger| {
+            let bytes = debugger
+                .inferior
+                .as_ref()
+                .unwrap()
+                .read_memory(global, 4)
+                .unwrap();
+            i32::from_le_bytes(bytes.try_into().unwrap())
+        };
+        assert!(debugger.execute(DebuggerCommand::Checkpoint).failed);
+        debugger.execute(DebuggerCommand::Run(Vec::new(), false));
+        assert!(!debugger.execute(DebuggerCommand::Checkpoint).failed);
+        debugger.execute(DebuggerCommand::SetVariable(
+            "global".to_string(),
+            "99".to_string(),
+        ));
+        let outcome = debugger.execute(DebuggerCommand::Continue(true, false));
+        assert_eq!(outcome.stop, Some(Status::Breakpoint(func3)));
+
+        // Back to the first call, with global as it was then
+        for _ in 0..2 {
+            let outcome = debugger.execute(DebuggerCommand::Restore(0));
+            assert!(!outcome.failed);
+            let inferior = debugger.inferior.as_ref().unwrap();
+            assert_ne!(inferior.pid(), debugger.checkpoints[&0].inferior.pid());
+            assert_eq!(
+                inferior.thread_instruction_ptr(inferior.current_thread()),
+                Ok(func3)
+            );
+            assert_eq!(read_global(&debugger), 5);
+            // The restored process carries on like the original one
+            let outcome = debugger.execute(DebuggerCommand::Continue(true, false));
+            assert_eq!(outcome.stop, Some(Status::Breakpoint(func3)));
+            let outcome = debugger.execute(DebuggerCommand::Continue(true, false));
+            assert_eq!(outcome.stop, Some(Status::Exited(0)));
+        }
+        assert!(debugger.execute(DebuggerCommand::Restore(1)).failed);
+        debugger.execute(DebuggerCommand::Quit(None));
+        assert!(debugger.checkpoints.is_empty());
+    }
+
+    #[test]
+    fn test_background_run() {
+        let mut debugger = Debugger::new(&DebuggerConfig::new("samples/sleepy_print"));
+        debugger.execute(DebuggerCommand::Run(vec!["2".to_string()], true));
+        assert!(debugger.running_in_background);
+        // Commands that need a stopped program are refused rather than failing in ptrace
+        debugger.execute(DebuggerCommand::Backtrace(None));
+        assert!(debugger.outcome.failed);
+
+        debugger.execute(DebuggerCommand::Interrupt);
+        assert!(!debugger.outcome.failed);
+        assert!(!debugger.running_in_background);
+        assert!(debugger.inferior.is_some());
+        // The SIGSTOP used to interrupt isn't delivered to the program
+        assert_eq!(debugger.inferior.as_ref().unwrap().pending_signal(), None);
+
+        debugger.execute(DebuggerCommand::Continue(true, true));
+        assert!(debugger.running_in_background);
+        let start = std::time::Instant::now();
+        while debugger.running_in_background && start.elapsed().as_secs() < 10 {
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            debugger.poll_background();
+        }
+        assert!(debugger.inferior.is_none());
+        assert_eq!(debugger.last_exit.as_deref(), Some("exited with status 0"));
+    }
+
+    #[test]
+    fn test_matches_pattern() {
+        assert!(matches_pattern("func", "func1"));
+        assert!(matches_pattern("nc", "func1"));
+        assert!(!matches_pattern("^nc", "func1"));
+        assert!(matches_pattern("^func.$", "func1"));
+        assert!(!matches_pattern("^func$", "func1"));
+        assert!(matches_pattern("f.*1", "func1"));
+        assert!(matches_pattern("^m.*n$", "main"));
+        assert!(!matches_pattern("main", "func1"));
+    }
+
+    #[test]
+    fn test_split_location() {
+        assert_eq!(split_location("main.c:12"), Some(("main.c", "12")));
+        assert_eq!(split_location("libc:puts"), Some(("libc", "puts")));
+        assert_eq!(split_location("geometry::scale"), None);
+        assert_eq!(
+            split_location("libgeo.so:geometry::scale"),
+            Some(("libgeo.so", "geometry::scale"))
+        );
+    }
+
+    #[test]
+    fn test_thread_selection() {
+        let mut debugger = Debugger::new(&DebuggerConfig::new("samples/threads"));
+        debugger.execute(DebuggerCommand::Break("8".to_string(), None));
+        debugger.execute(DebuggerCommand::Run(Vec::new(), false));
+        // The worker that hit the breakpoint is selected on stop
+        let inferior = debugger.inferior.as_ref().unwrap();
+        let (main_thread, worker) = (inferior.pid(), inferior.current_thread());
+        assert_ne!(worker, main_thread);
+        assert_eq!(debugger.current_line().unwrap().number, 8);
+        assert!(debugger.select_thread(Some(main_thread.as_raw())).is_ok());
+        let inferior = debugger.inferior.as_ref().unwrap();
+        assert_eq!(inferior.current_thread(), main_thread);
+        // Continuing resumes every thread, so the other worker hits the breakpoint next
+        debugger.execute(DebuggerCommand::Continue(true, false));
+        let inferior = debugger.inferior.as_ref().unwrap();
+        assert!(![main_thread, worker].contains(&inferior.current_thread()));
+        // Threads of the previous run are gone after re-running
+        debugger.execute(DebuggerCommand::Run(Vec::new(), false));
+        assert!(debugger.select_thread(Some(worker.as_raw())).is_err());
+        let inferior = debugger.inferior.as_ref().unwrap();
+        assert!(inferior.threads().contains(&inferior.current_thread()));
+        assert!(debugger.execute(DebuggerCommand::Quit(None)).quit);
     }
 }
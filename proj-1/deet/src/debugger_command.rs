@@ -1,26 +1,1042 @@
+use crate::memory_search;
+use crate::value;
+
+/// The `/NFU` suffix of the `x` command: how many units to show, in what format, and how big
+/// each unit is (in bytes).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MemoryFormat {
+    pub count: usize,
+    pub format: char,
+    pub unit_size: usize,
+}
+
+impl MemoryFormat {
+    /// Parses the part of `x/NFU` after the slash. Any of the three parts may be omitted.
+    pub fn parse(spec: &str) -> Option<MemoryFormat> {
+        let digits_end = spec
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(spec.len());
+        let mut memory_format = MemoryFormat {
+            count: if digits_end == 0 {
+                1
+            } else {
+                spec[..digits_end].parse().ok()?
+            },
+            format: 'x',
+            unit_size: 4,
+        };
+        for c in spec[digits_end..].chars() {
+            match c {
+                's' => memory_format.format = c,
+                c if value::FORMAT_LETTERS.contains(c) => memory_format.format = c,
+                'b' => memory_format.unit_size = 1,
+                'h' => memory_format.unit_size = 2,
+                'w' => memory_format.unit_size = 4,
+                'g' => memory_format.unit_size = 8,
+                _ => return None,
+            }
+        }
+        Some(memory_format)
+    }
+}
+
+/// The `/SN` suffix of the `find` command: the size in bytes of each integer in the pattern (b,
+/// h, w or g; None if not given), and how many matches to report at most.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FindFormat {
+    pub value_size: Option<usize>,
+    pub max_matches: usize,
+}
+
+impl FindFormat {
+    /// Parses the part of `find/SN` after the slash. Either part may be omitted, and they may
+    /// come in either order.
+    pub fn parse(spec: &str) -> Option<FindFormat> {
+        let mut find_format = FindFormat {
+            value_size: None,
+            max_matches: memory_search::DEFAULT_MAX_MATCHES,
+        };
+        let digits: String = spec.chars().filter(char::is_ascii_digit).collect();
+        if !digits.is_empty() {
+            find_format.max_matches = digits.parse().ok().filter(|&max| max > 0)?;
+        }
+        for c in spec.chars().filter(|c| !c.is_ascii_digit()) {
+            find_format.value_size = Some(match c {
+                'b' => 1,
+                'h' => 2,
+                'w' => 4,
+                'g' => 8,
+                _ => return None,
+            });
+        }
+        Some(find_format)
+    }
+}
+
 pub enum DebuggerCommand {
-    Quit,
-    Run(Vec<String>),
-    Continue,
-    Backtrace,
-    Break(String),
+    /// `quit [code]`: exit deet, with the given exit code if there is one
+    Quit(Option<i32>),
+    /// `run [args...] [&]`: with no arguments, reuses the previous ones. The flag is true if the
+    /// program should run in the background.
+    Run(Vec<String>, bool),
+    /// `restart`: `run` again with the arguments and redirections of the last run
+    Restart,
+    /// `start [args...]`: like `run`, but stop at the top of main
+    Start(Vec<String>),
+    /// `continue [nosignal] [&]`: the first flag is false if the pending signal should be
+    /// discarded, the second true if the program should run in the background
+    Continue(bool, bool),
+    /// `interrupt`: stop a program running in the background
+    Interrupt,
+    /// `status`: whether the program is running, stopped or gone
+    Status,
+    /// `backtrace [n]`: print at most n frames
+    Backtrace(Option<usize>),
+    Break(String, Option<String>),
+    TemporaryBreak(String, Option<String>),
+    /// `watch <variable or *address>`
+    Watch(String),
+    /// `swatch <variable or *address>`
+    SoftwareWatch(String),
+    /// `jump <location>`: continue from somewhere else
+    Jump(String),
+    /// `call <function>(<args>)`: run a function of the program and print what it returns
+    Call(String),
+    /// `catch syscall [name]`: stop at every system call, or only at the named one
+    CatchSyscall(Option<String>),
+    /// `catch syscall off`
+    CatchSyscallOff,
+    /// `catch exec`: stop when the inferior execs a new program
+    CatchExec,
+    /// `catch exit`: stop right before the inferior exits
+    CatchExit,
+    Delete(Option<usize>),
+    Ignore(usize, usize),
+    /// `condition <n> [condition]`: stop at breakpoint n only when the condition holds, or
+    /// always if there is none
+    Condition(usize, Option<String>),
+    /// `commands [n]`: start typing the command list run whenever breakpoint n (or the last one
+    /// set) is hit, ended by a line saying just `end`
+    Commands(Option<usize>),
+    /// `print[/F] <variable or expression>`: the format, if given, is one of
+    /// `value::FORMAT_LETTERS`
+    Print(String, Option<char>),
+    /// `print/s <address, $register or pointer variable>`
+    PrintString(String),
+    Examine(MemoryFormat, String),
+    /// `find[/SN] <start>, <end or +length>, <values...>`: the values are still to be split with
+    /// `split_find`
+    Find(FindFormat, String),
+    /// `display [variable, $register or *address]`: with no argument, shows every display now
+    Display(Option<String>),
+    /// `undisplay [n]`: with no argument, removes every display
+    Undisplay(Option<usize>),
+    /// `info display`
+    InfoDisplay,
+    /// `set <variable or *address> = <value>`
+    SetVariable(String, String),
+    Frame(Option<usize>),
+    Up,
+    Down,
+    /// `set args [args...]`
+    SetArgs(Vec<String>),
+    /// `show args`
+    ShowArgs,
+    /// `set disable-aslr on|off`
+    SetDisableAslr(bool),
+    /// `show disable-aslr`
+    ShowDisableAslr,
+    /// `set timing on|off`: whether to report how long the program ran when it exits
+    SetTiming(bool),
+    /// `show timing`
+    ShowTiming,
+    /// `set print elements <n>|unlimited`: None for no limit (also `set print elements 0`)
+    SetPrintElements(Option<usize>),
+    /// `show print elements`
+    ShowPrintElements,
+    /// `set print demangle on|off`: whether to show C++ and Rust names demangled
+    SetPrintDemangle(bool),
+    /// `show print demangle`
+    ShowPrintDemangle,
+    /// `set echo on|off`: whether to print each command read from non-terminal input
+    SetEcho(bool),
+    /// `show echo`
+    ShowEcho,
+    /// `set logging on [file]|off`: whether to copy commands and output to a file
+    SetLogging(bool, Option<String>),
+    /// `show logging`
+    ShowLogging,
+    /// `set detach-on-exit on|off`: true to leave a started program running if deet dies
+    SetDetachOnExit(bool),
+    /// `show detach-on-exit`
+    ShowDetachOnExit,
+    /// `set follow-fork-mode parent|child`: true to follow the child
+    SetFollowForkMode(bool),
+    /// `show follow-fork-mode`
+    ShowFollowForkMode,
+    /// `info breakpoints`
+    InfoBreakpoints,
+    /// `info threads`
+    InfoThreads,
+    /// `info functions [-raw] [pattern]`: the flag is true to show mangled names as they are
+    InfoFunctions(Option<String>, bool),
+    /// `info variables [pattern]`: global variables, with their addresses and types
+    InfoVariables(Option<String>),
+    /// `info sources`: the compilation units with debug info
+    InfoSources,
+    /// `info line <line|file:line|function>`
+    InfoLine(String),
+    /// `info locals`: the local variables of the selected frame
+    InfoLocals,
+    /// `thread [tid]`: select a thread, or show which one is selected
+    Thread(Option<i32>),
+    /// `attach <pid>`
+    Attach(i32),
+    Detach,
+    /// `list [line]`
+    List(Option<usize>),
+    /// `list <function>`: the whole function, from its first line to its closing brace
+    ListFunction(String),
+    /// `list [file:]<first>,<last>`: an explicit range of lines
+    ListRange(Option<String>, usize, usize),
+    /// `directory <path>`: also look for source files in `path`
+    Directory(String),
+    ShowDirectories,
+    /// `source <file>`: run the commands in a script
+    Source(String),
+    /// `disassemble [function]`
+    Disassemble(Option<String>),
+    /// `disassemble more`: continue where the last `disassemble` stopped
+    DisassembleMore,
+    /// `checkpoint`: save a snapshot of the stopped program
+    Checkpoint,
+    /// `restore <n>`: go back to checkpoint n
+    Restore(usize),
+    /// `info checkpoints`
+    InfoCheckpoints,
+    /// `info proc`: pid, executable, state and working directory of the program
+    InfoProc,
+    /// `info proc cmdline`
+    InfoProcCmdline,
+    /// `info proc environ`
+    InfoProcEnviron,
+    /// `handle <signal> [stop|nostop] [print|noprint] [pass|nopass]`: with no actions, shows
+    /// what is done with the signal
+    Handle(String, Vec<String>),
+    /// `info signals [signal]`: what is done with every signal, or with the given one
+    InfoSignals(Option<String>),
+    /// `gcore [file]`: write a core file of the stopped program, to `core.<pid>` by default
+    Gcore(Option<String>),
+}
+
+/// An I/O redirection for the inferior given on the `run` command line.
+#[derive(Debug, PartialEq)]
+pub enum Redirection {
+    /// `< file`
+    Stdin(String),
+    /// `> file`, or `>> file` to append
+    Stdout(String, bool),
+    /// `2> file`
+    Stderr(String),
+}
+
+/// Separates `<`, `>`, `>>` and `2>` redirections from the program arguments. The file name may
+/// be attached to the operator (`<in.txt`) or be the following argument (`< in.txt`).
+pub fn split_redirections(args: &[String]) -> Result<(Vec<String>, Vec<Redirection>), String> {
+    // Longer operators must be checked before their prefixes
+    const OPERATORS: &[&str] = &["2>", ">>", ">", "<"];
+    let mut program_args = Vec::new();
+    let mut redirections = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let operator = match OPERATORS.iter().find(|op| arg.starts_with(*op)) {
+            Some(operator) => *operator,
+            None => {
+                program_args.push(arg.clone());
+                continue;
+            }
+        };
+        let path = match &arg[operator.len()..] {
+            "" => iter
+                .next()
+                .ok_or_else(|| format!("Missing file name after {}", operator))?
+                .clone(),
+            path => path.to_string(),
+        };
+        redirections.push(match operator {
+            "<" => Redirection::Stdin(path),
+            ">" => Redirection::Stdout(path, false),
+            ">>" => Redirection::Stdout(path, true),
+            _ => Redirection::Stderr(path),
+        });
+    }
+    Ok((program_args, redirections))
+}
+
+/// Splits the arguments of `find` at the commas that aren't inside string literals, into the
+/// start of the range, its end (or `+length`), and the values to look for.
+pub fn split_find(args: &str) -> Result<(String, String, Vec<String>), String> {
+    let mut parts = Vec::new();
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut start = 0;
+    for (i, c) in args.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            ',' if !in_string => {
+                parts.push(args[start..i].trim().to_string());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(args[start..].trim().to_string());
+    if parts.len() < 3 || parts.iter().any(String::is_empty) {
+        return Err("Expected find <start>, <end or +length>, <value> [, <value>...].".to_string());
+    }
+    let values = parts.split_off(2);
+    let end = parts.pop().unwrap();
+    let start = parts.pop().unwrap();
+    Ok((start, end, values))
+}
+
+/// Splits a function call like `add(x, *p + 1)` into the function's name and its argument
+/// expressions. Commas inside parentheses or brackets belong to an argument.
+pub fn split_call(call: &str) -> Result<(String, Vec<String>), String> {
+    let malformed = || format!("Expected a function call like foo(1, 2), not \"{}\".", call);
+    let (name, rest) = call.split_once('(').ok_or_else(malformed)?;
+    let inner = rest.trim_end().strip_suffix(')').ok_or_else(malformed)?;
+    let name = name.trim();
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        return Err(malformed());
+    }
+    if inner.trim().is_empty() {
+        return Ok((name.to_string(), Vec::new()));
+    }
+    let mut args = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in inner.char_indices() {
+        match c {
+            '(' | '[' => depth += 1,
+            ')' | ']' if depth == 0 => return Err(malformed()),
+            ')' | ']' => depth -= 1,
+            ',' if depth == 0 => {
+                args.push(inner[start..i].trim().to_string());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    args.push(inner[start..].trim().to_string());
+    if depth != 0 || args.iter().any(String::is_empty) {
+        return Err(malformed());
+    }
+    Ok((name.to_string(), args))
+}
+
+/// Names of all commands. Any unambiguous prefix of a name may be used in its place.
+const COMMANDS: &[&str] = &[
+    "attach",
+    "backtrace",
+    "break",
+    "call",
+    "catch",
+    "checkpoint",
+    "commands",
+    "condition",
+    "continue",
+    "delete",
+    "detach",
+    "directory",
+    "disassemble",
+    "display",
+    "down",
+    "find",
+    "frame",
+    "gcore",
+    "handle",
+    "ignore",
+    "info",
+    "interrupt",
+    "jump",
+    "list",
+    "print",
+    "quit",
+    "restart",
+    "restore",
+    "run",
+    "set",
+    "show",
+    "source",
+    "start",
+    "status",
+    "swatch",
+    "tbreak",
+    "thread",
+    "undisplay",
+    "up",
+    "watch",
+    "x",
+];
+
+/// gdb's short forms, which take precedence over prefix matching (so `c` is `continue` even
+/// though `catch` starts with it too).
+const ALIASES: &[(&str, &str)] = &[
+    ("b", "break"),
+    ("br", "break"),
+    ("back", "backtrace"),
+    ("bt", "backtrace"),
+    ("where", "backtrace"),
+    ("c", "continue"),
+    ("cont", "continue"),
+    ("d", "delete"),
+    ("dis", "disassemble"),
+    ("disas", "disassemble"),
+    ("f", "frame"),
+    ("l", "list"),
+    ("p", "print"),
+    ("q", "quit"),
+    ("r", "run"),
+    ("rerun", "restart"),
+    ("tb", "tbreak"),
+];
+
+/// Resolves a command name, alias or unambiguous prefix to the full command name. Names that
+/// don't match any command are returned unchanged.
+fn resolve_command_name(name: &str) -> Result<&str, String> {
+    if let Some(&command) = COMMANDS.iter().find(|&&command| command == name) {
+        return Ok(command);
+    }
+    if let Some(&(_, command)) = ALIASES.iter().find(|(alias, _)| *alias == name) {
+        return Ok(command);
+    }
+    let matches: Vec<&str> = COMMANDS
+        .iter()
+        .copied()
+        .filter(|command| command.starts_with(name))
+        .collect();
+    match matches.as_slice() {
+        [] => Ok(name),
+        [command] => Ok(command),
+        _ => Err(format!(
+            "Ambiguous command \"{}\": {}.",
+            name,
+            matches.join(", ")
+        )),
+    }
 }
 
 impl DebuggerCommand {
-    pub fn from_tokens(tokens: &Vec<&str>) -> Option<DebuggerCommand> {
+    /// Parses a line of input that has been split into tokens. Commands may be abbreviated (see
+    /// `resolve_command_name`); a `/` suffix, as in `x/4xg`, is kept.
+    pub fn from_tokens(tokens: &[&str]) -> Result<DebuggerCommand, String> {
+        let (name, suffix) = match tokens[0].find('/') {
+            Some(slash) => tokens[0].split_at(slash),
+            None => (tokens[0], ""),
+        };
+        let command = format!("{}{}", resolve_command_name(name)?, suffix);
+        let mut tokens = tokens.to_vec();
+        tokens[0] = &command;
+        DebuggerCommand::parse(&tokens).ok_or_else(|| "Unrecognized command.".to_string())
+    }
+
+    /// Parses tokens whose first token is a full command name.
+    fn parse(tokens: &[&str]) -> Option<DebuggerCommand> {
+        // A trailing `&` runs the program in the background
+        let (tokens, background) = match tokens.split_last() {
+            Some((&"&", rest)) if matches!(tokens[0], "run" | "continue") => (rest, true),
+            _ => (tokens, false),
+        };
         match tokens[0] {
-            "q" | "quit" => Some(DebuggerCommand::Quit),
-            "r" | "run" => {
+            "quit" => match tokens.get(1) {
+                Some(code) => Some(DebuggerCommand::Quit(Some(code.parse().ok()?))),
+                None => Some(DebuggerCommand::Quit(None)),
+            },
+            "run" => {
                 let args = tokens[1..].to_vec();
                 Some(DebuggerCommand::Run(
                     args.iter().map(|s| s.to_string()).collect(),
+                    background,
                 ))
             }
-            "c" | "cont" | "continue" => Some(DebuggerCommand::Continue),
-            "bt" | "back" | "backtrace" => Some(DebuggerCommand::Backtrace),
-            "b" | "break" => Some(DebuggerCommand::Break(String::from(tokens[1]))),
+            "continue" => match tokens.get(1) {
+                Some(&"nosignal") => Some(DebuggerCommand::Continue(false, background)),
+                Some(_) => None,
+                None => Some(DebuggerCommand::Continue(true, background)),
+            },
+            "start" => Some(DebuggerCommand::Start(
+                tokens[1..].iter().map(|s| s.to_string()).collect(),
+            )),
+            "restart" => Some(DebuggerCommand::Restart),
+            "interrupt" => Some(DebuggerCommand::Interrupt),
+            "status" => Some(DebuggerCommand::Status),
+            "backtrace" => match tokens.get(1) {
+                Some(count) => Some(DebuggerCommand::Backtrace(Some(count.parse().ok()?))),
+                None => Some(DebuggerCommand::Backtrace(None)),
+            },
+            "break" | "tbreak" => {
+                let location = String::from(*tokens.get(1)?);
+                let condition = match tokens.get(2) {
+                    Some(&"if") => Some(tokens[3..].join(" ")),
+                    Some(_) => return None,
+                    None => None,
+                };
+                if tokens[0].starts_with('t') {
+                    Some(DebuggerCommand::TemporaryBreak(location, condition))
+                } else {
+                    Some(DebuggerCommand::Break(location, condition))
+                }
+            }
+            "print" => Some(DebuggerCommand::Print(tokens[1..].join(" "), None)),
+            "print/s" => Some(DebuggerCommand::PrintString(tokens.get(1)?.to_string())),
+            cmd if cmd.starts_with("print/") => {
+                let format = match cmd
+                    .strip_prefix("print/")
+                    .unwrap()
+                    .chars()
+                    .collect::<Vec<_>>()[..]
+                {
+                    [format] if value::FORMAT_LETTERS.contains(format) => format,
+                    _ => return None,
+                };
+                Some(DebuggerCommand::Print(tokens[1..].join(" "), Some(format)))
+            }
+            cmd if cmd == "x" || cmd.starts_with("x/") => {
+                let memory_format = MemoryFormat::parse(cmd.strip_prefix("x/").unwrap_or(""))?;
+                tokens.get(1)?;
+                Some(DebuggerCommand::Examine(
+                    memory_format,
+                    tokens[1..].join(" "),
+                ))
+            }
+            cmd if cmd == "find" || cmd.starts_with("find/") => {
+                // The suffix may also be a separate word, as in `find /10 ...`
+                let (spec, args) = match (cmd.strip_prefix("find/"), tokens.get(1)) {
+                    (Some(spec), _) => (spec, &tokens[1..]),
+                    (None, Some(word)) if word.starts_with('/') => (&word[1..], &tokens[2..]),
+                    (None, _) => ("", &tokens[1..]),
+                };
+                if args.is_empty() {
+                    return None;
+                }
+                Some(DebuggerCommand::Find(
+                    FindFormat::parse(spec)?,
+                    args.join(" "),
+                ))
+            }
+            "set" if tokens.get(1) == Some(&"args") => Some(DebuggerCommand::SetArgs(
+                tokens[2..].iter().map(|s| s.to_string()).collect(),
+            )),
+            "show" if tokens.get(1) == Some(&"args") => Some(DebuggerCommand::ShowArgs),
+            "set" if tokens.get(1) == Some(&"disable-aslr") => {
+                match tokens.get(2).copied().unwrap_or("on") {
+                    "on" => Some(DebuggerCommand::SetDisableAslr(true)),
+                    "off" => Some(DebuggerCommand::SetDisableAslr(false)),
+                    _ => None,
+                }
+            }
+            "show" if tokens.get(1) == Some(&"disable-aslr") => {
+                Some(DebuggerCommand::ShowDisableAslr)
+            }
+            "set" if tokens.get(1) == Some(&"timing") => {
+                match tokens.get(2).copied().unwrap_or("on") {
+                    "on" => Some(DebuggerCommand::SetTiming(true)),
+                    "off" => Some(DebuggerCommand::SetTiming(false)),
+                    _ => None,
+                }
+            }
+            "show" if tokens.get(1) == Some(&"timing") => Some(DebuggerCommand::ShowTiming),
+            "set" if tokens[1..].starts_with(&["print", "elements"]) => match *tokens.get(3)? {
+                "unlimited" | "0" => Some(DebuggerCommand::SetPrintElements(None)),
+                max => Some(DebuggerCommand::SetPrintElements(Some(max.parse().ok()?))),
+            },
+            "show" if tokens[1..] == ["print", "elements"] => {
+                Some(DebuggerCommand::ShowPrintElements)
+            }
+            "set" if tokens[1..].starts_with(&["print", "demangle"]) => {
+                match tokens.get(3).copied().unwrap_or("on") {
+                    "on" => Some(DebuggerCommand::SetPrintDemangle(true)),
+                    "off" => Some(DebuggerCommand::SetPrintDemangle(false)),
+                    _ => None,
+                }
+            }
+            "show" if tokens[1..] == ["print", "demangle"] => {
+                Some(DebuggerCommand::ShowPrintDemangle)
+            }
+            "set" if tokens.get(1) == Some(&"echo") => match tokens.get(2).copied().unwrap_or("on")
+            {
+                "on" => Some(DebuggerCommand::SetEcho(true)),
+                "off" => Some(DebuggerCommand::SetEcho(false)),
+                _ => None,
+            },
+            "show" if tokens.get(1) == Some(&"echo") => Some(DebuggerCommand::ShowEcho),
+            "set" if tokens.get(1) == Some(&"logging") => {
+                match (tokens.get(2).copied().unwrap_or("on"), tokens.get(3)) {
+                    ("on", path) if tokens.len() <= 4 => Some(DebuggerCommand::SetLogging(
+                        true,
+                        path.map(|path| path.to_string()),
+                    )),
+                    ("off", None) => Some(DebuggerCommand::SetLogging(false, None)),
+                    _ => None,
+                }
+            }
+            "show" if tokens.get(1) == Some(&"logging") => Some(DebuggerCommand::ShowLogging),
+            "set" if tokens.get(1) == Some(&"detach-on-exit") => {
+                match tokens.get(2).copied().unwrap_or("on") {
+                    "on" => Some(DebuggerCommand::SetDetachOnExit(true)),
+                    "off" => Some(DebuggerCommand::SetDetachOnExit(false)),
+                    _ => None,
+                }
+            }
+            "show" if tokens.get(1) == Some(&"detach-on-exit") => {
+                Some(DebuggerCommand::ShowDetachOnExit)
+            }
+            "set" if tokens.get(1) == Some(&"follow-fork-mode") => match *tokens.get(2)? {
+                "parent" => Some(DebuggerCommand::SetFollowForkMode(false)),
+                "child" => Some(DebuggerCommand::SetFollowForkMode(true)),
+                _ => None,
+            },
+            "show" if tokens.get(1) == Some(&"follow-fork-mode") => {
+                Some(DebuggerCommand::ShowFollowForkMode)
+            }
+            "set" => {
+                let assignment = tokens[1..].join(" ");
+                let (lhs, rhs) = assignment.split_once('=')?;
+                Some(DebuggerCommand::SetVariable(
+                    lhs.trim().to_string(),
+                    rhs.trim().to_string(),
+                ))
+            }
+            "frame" => match tokens.get(1) {
+                Some(num) => Some(DebuggerCommand::Frame(Some(num.parse().ok()?))),
+                None => Some(DebuggerCommand::Frame(None)),
+            },
+            "info" if matches!(tokens.get(1), Some(&"breakpoints" | &"break" | &"b")) => {
+                Some(DebuggerCommand::InfoBreakpoints)
+            }
+            "info" if tokens.get(1) == Some(&"threads") => Some(DebuggerCommand::InfoThreads),
+            "info" if tokens.get(1) == Some(&"checkpoints") => {
+                Some(DebuggerCommand::InfoCheckpoints)
+            }
+            "info" if tokens.get(1) == Some(&"functions") => {
+                let (raw, pattern) = match tokens.get(2) {
+                    Some(&"-raw") => (true, tokens.get(3)),
+                    pattern => (false, pattern),
+                };
+                Some(DebuggerCommand::InfoFunctions(
+                    pattern.map(|pattern| pattern.to_string()),
+                    raw,
+                ))
+            }
+            "info" if tokens.get(1) == Some(&"variables") => Some(DebuggerCommand::InfoVariables(
+                tokens.get(2).map(|pattern| pattern.to_string()),
+            )),
+            "info" if tokens.get(1) == Some(&"sources") => Some(DebuggerCommand::InfoSources),
+            "info" if tokens.get(1) == Some(&"signals") && tokens.len() <= 3 => Some(
+                DebuggerCommand::InfoSignals(tokens.get(2).map(|name| name.to_string())),
+            ),
+            "info" if tokens.get(1) == Some(&"display") => Some(DebuggerCommand::InfoDisplay),
+            "info" if tokens.get(1) == Some(&"proc") => match tokens.get(2) {
+                None => Some(DebuggerCommand::InfoProc),
+                Some(&"cmdline") => Some(DebuggerCommand::InfoProcCmdline),
+                Some(&"environ") => Some(DebuggerCommand::InfoProcEnviron),
+                Some(_) => None,
+            },
+            "info" if tokens.get(1) == Some(&"locals") => Some(DebuggerCommand::InfoLocals),
+            "info" if tokens.get(1) == Some(&"line") => {
+                Some(DebuggerCommand::InfoLine(tokens.get(2)?.to_string()))
+            }
+            "thread" => match tokens.get(1) {
+                Some(tid) => Some(DebuggerCommand::Thread(Some(tid.parse().ok()?))),
+                None => Some(DebuggerCommand::Thread(None)),
+            },
+            "attach" => Some(DebuggerCommand::Attach(tokens.get(1)?.parse().ok()?)),
+            "detach" => Some(DebuggerCommand::Detach),
+            "checkpoint" => Some(DebuggerCommand::Checkpoint),
+            "restore" => Some(DebuggerCommand::Restore(tokens.get(1)?.parse().ok()?)),
+            "up" => Some(DebuggerCommand::Up),
+            "list" if tokens.len() == 1 => Some(DebuggerCommand::List(None)),
+            "list" => {
+                let spec = tokens[1..].concat();
+                if let Ok(line) = spec.parse() {
+                    return Some(DebuggerCommand::List(Some(line)));
+                }
+                match spec.split_once(',') {
+                    Some((start, last)) => {
+                        let (file, first) = match start.rsplit_once(':') {
+                            Some((file, first)) => (Some(file.to_string()), first),
+                            None => (None, start),
+                        };
+                        Some(DebuggerCommand::ListRange(
+                            file,
+                            first.parse().ok()?,
+                            last.parse().ok()?,
+                        ))
+                    }
+                    None => Some(DebuggerCommand::ListFunction(spec)),
+                }
+            }
+            "directory" if tokens.len() > 1 => {
+                Some(DebuggerCommand::Directory(tokens[1..].join(" ")))
+            }
+            "show" if tokens.get(1) == Some(&"directories") => {
+                Some(DebuggerCommand::ShowDirectories)
+            }
+            "down" => Some(DebuggerCommand::Down),
+            "source" if tokens.len() > 1 => Some(DebuggerCommand::Source(tokens[1..].join(" "))),
+            "disassemble" => match tokens.get(1) {
+                Some(&"more") => Some(DebuggerCommand::DisassembleMore),
+                Some(function) => Some(DebuggerCommand::Disassemble(Some(function.to_string()))),
+                None => Some(DebuggerCommand::Disassemble(None)),
+            },
+            "ignore" => Some(DebuggerCommand::Ignore(
+                tokens.get(1)?.parse().ok()?,
+                tokens.get(2)?.parse().ok()?,
+            )),
+            "condition" => Some(DebuggerCommand::Condition(
+                tokens.get(1)?.parse().ok()?,
+                Some(tokens[2..].join(" ")).filter(|condition| !condition.is_empty()),
+            )),
+            "commands" => match tokens.get(1) {
+                Some(num) => Some(DebuggerCommand::Commands(Some(num.parse().ok()?))),
+                None => Some(DebuggerCommand::Commands(None)),
+            },
+            "jump" => Some(DebuggerCommand::Jump(tokens.get(1)?.to_string())),
+            "call" if tokens.len() > 1 => Some(DebuggerCommand::Call(tokens[1..].join(" "))),
+            "watch" if tokens.len() > 1 => Some(DebuggerCommand::Watch(tokens[1..].join(" "))),
+            "swatch" if tokens.len() > 1 => {
+                Some(DebuggerCommand::SoftwareWatch(tokens[1..].join(" ")))
+            }
+            "catch" if tokens.get(1) == Some(&"syscall") => match tokens.get(2) {
+                Some(&"off") => Some(DebuggerCommand::CatchSyscallOff),
+                Some(name) => Some(DebuggerCommand::CatchSyscall(Some(name.to_string()))),
+                None => Some(DebuggerCommand::CatchSyscall(None)),
+            },
+            "gcore" if tokens.len() <= 2 => Some(DebuggerCommand::Gcore(
+                tokens.get(1).map(|path| path.to_string()),
+            )),
+            "handle" => Some(DebuggerCommand::Handle(
+                tokens.get(1)?.to_string(),
+                tokens[2..]
+                    .iter()
+                    .map(|action| action.to_string())
+                    .collect(),
+            )),
+            "catch" if tokens.get(1) == Some(&"exec") => Some(DebuggerCommand::CatchExec),
+            "catch" if tokens.get(1) == Some(&"exit") => Some(DebuggerCommand::CatchExit),
+            "display" if tokens.len() > 1 => {
+                Some(DebuggerCommand::Display(Some(tokens[1..].join(" "))))
+            }
+            "display" => Some(DebuggerCommand::Display(None)),
+            "undisplay" => match tokens.get(1) {
+                Some(num) => Some(DebuggerCommand::Undisplay(Some(num.parse().ok()?))),
+                None => Some(DebuggerCommand::Undisplay(None)),
+            },
+            "delete" => match tokens.get(1) {
+                Some(num) => Some(DebuggerCommand::Delete(Some(num.parse().ok()?))),
+                None => Some(DebuggerCommand::Delete(None)),
+            },
             // Default case:
             _ => None,
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_command_abbreviations() {
+        let parse = |line: &str| {
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            DebuggerCommand::from_tokens(&tokens)
+        };
+        assert!(matches!(
+            parse("c"),
+            Ok(DebuggerCommand::Continue(true, false))
+        ));
+        assert!(matches!(
+            parse("cont"),
+            Ok(DebuggerCommand::Continue(true, false))
+        ));
+        assert!(matches!(
+            parse("where 3"),
+            Ok(DebuggerCommand::Backtrace(Some(3)))
+        ));
+        assert!(matches!(
+            parse("del 2"),
+            Ok(DebuggerCommand::Delete(Some(2)))
+        ));
+        assert!(matches!(
+            parse("dis"),
+            Ok(DebuggerCommand::Disassemble(None))
+        ));
+        assert!(matches!(
+            parse("tbr main"),
+            Ok(DebuggerCommand::TemporaryBreak(..))
+        ));
+        assert!(matches!(
+            parse("pr/s 0x10"),
+            Ok(DebuggerCommand::PrintString(_))
+        ));
+        assert!(matches!(
+            parse("p/d $rax"),
+            Ok(DebuggerCommand::Print(expr, Some('d'))) if expr == "$rax"
+        ));
+        assert!(matches!(
+            parse("p/t n"),
+            Ok(DebuggerCommand::Print(expr, Some('t'))) if expr == "n"
+        ));
+        assert!(matches!(parse("l 12"), Ok(DebuggerCommand::List(Some(12)))));
+        assert!(matches!(
+            parse("list compute"),
+            Ok(DebuggerCommand::ListFunction(name)) if name == "compute"
+        ));
+        assert!(matches!(
+            parse("list locals.c:30, 60"),
+            Ok(DebuggerCommand::ListRange(Some(file), 30, 60)) if file == "locals.c"
+        ));
+        assert!(matches!(
+            parse("list 5,9"),
+            Ok(DebuggerCommand::ListRange(None, 5, 9))
+        ));
+        assert!(parse("list 5,x").is_err());
+        assert!(matches!(
+            parse("dir ../src"),
+            Ok(DebuggerCommand::Directory(path)) if path == "../src"
+        ));
+        assert!(parse("print/q n").is_err());
+        assert!(parse("print/xd n").is_err());
+        assert!(matches!(
+            parse("x/4xg $rsp"),
+            Ok(DebuggerCommand::Examine(..))
+        ));
+        assert!(parse("s")
+            .err()
+            .unwrap()
+            .starts_with("Ambiguous command \"s\": set, show, "));
+        assert_eq!(
+            parse("de 1").err().unwrap(),
+            "Ambiguous command \"de\": delete, detach."
+        );
+        assert!(matches!(
+            parse("comm 2"),
+            Ok(DebuggerCommand::Commands(Some(2)))
+        ));
+        assert!(matches!(
+            parse("cond 2 n == 50"),
+            Ok(DebuggerCommand::Condition(2, Some(condition))) if condition == "n == 50"
+        ));
+        assert!(matches!(
+            parse("condition 2"),
+            Ok(DebuggerCommand::Condition(2, None))
+        ));
+        assert!(matches!(
+            parse("info functions -raw scale"),
+            Ok(DebuggerCommand::InfoFunctions(Some(pattern), true)) if pattern == "scale"
+        ));
+        assert!(matches!(
+            parse("info proc cmdline"),
+            Ok(DebuggerCommand::InfoProcCmdline)
+        ));
+        assert!(parse("info proc maps").is_err());
+        assert!(matches!(
+            parse("handle SIGALRM stop nopass"),
+            Ok(DebuggerCommand::Handle(signal, actions))
+                if signal == "SIGALRM" && actions == ["stop", "nopass"]
+        ));
+        assert!(parse("handle").is_err());
+        assert!(matches!(
+            parse("gcore /tmp/snapshot"),
+            Ok(DebuggerCommand::Gcore(Some(path))) if path == "/tmp/snapshot"
+        ));
+        assert!(matches!(parse("gcore"), Ok(DebuggerCommand::Gcore(None))));
+        assert!(matches!(
+            parse("info signals"),
+            Ok(DebuggerCommand::InfoSignals(None))
+        ));
+        // Prefixes of aliases aren't expanded, and unknown names stay unknown
+        assert_eq!(parse("wh").err().unwrap(), "Unrecognized command.");
+        assert_eq!(parse("frobnicate").err().unwrap(), "Unrecognized command.");
+    }
+
+    #[test]
+    fn test_parse_background() {
+        let parse = |line: &str| {
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            DebuggerCommand::from_tokens(&tokens)
+        };
+        assert!(matches!(
+            parse("run a b &"),
+            Ok(DebuggerCommand::Run(args, true)) if args == ["a", "b"]
+        ));
+        assert!(matches!(parse("r"), Ok(DebuggerCommand::Run(args, false)) if args.is_empty()));
+        assert!(matches!(parse("rerun"), Ok(DebuggerCommand::Restart)));
+        assert!(matches!(
+            parse("start x < in"),
+            Ok(DebuggerCommand::Start(args)) if args == ["x", "<", "in"]
+        ));
+        assert!(matches!(
+            parse("c nosignal &"),
+            Ok(DebuggerCommand::Continue(false, true))
+        ));
+        assert!(matches!(
+            parse("c &"),
+            Ok(DebuggerCommand::Continue(true, true))
+        ));
+        assert!(parse("continue foo &").is_err());
+    }
+
+    #[test]
+    fn test_parse_quit() {
+        let parse = |line: &str| {
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            DebuggerCommand::from_tokens(&tokens)
+        };
+        assert!(matches!(parse("q"), Ok(DebuggerCommand::Quit(None))));
+        assert!(matches!(
+            parse("quit 2"),
+            Ok(DebuggerCommand::Quit(Some(2)))
+        ));
+        assert!(parse("quit now").is_err());
+    }
+
+    #[test]
+    fn test_parse_display() {
+        let parse = |line: &str| {
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            DebuggerCommand::from_tokens(&tokens)
+        };
+        assert!(matches!(
+            parse("display *0x7ffc1000"),
+            Ok(DebuggerCommand::Display(Some(expression))) if expression == "*0x7ffc1000"
+        ));
+        assert!(matches!(parse("disp"), Ok(DebuggerCommand::Display(None))));
+        assert!(matches!(
+            parse("undisplay 3"),
+            Ok(DebuggerCommand::Undisplay(Some(3)))
+        ));
+        assert!(matches!(
+            parse("undisplay"),
+            Ok(DebuggerCommand::Undisplay(None))
+        ));
+        assert!(matches!(
+            parse("info display"),
+            Ok(DebuggerCommand::InfoDisplay)
+        ));
+        assert!(parse("undisplay x").is_err());
+    }
+
+    #[test]
+    fn test_parse_memory_format() {
+        let expected = |count, format, unit_size| {
+            Some(MemoryFormat {
+                count,
+                format,
+                unit_size,
+            })
+        };
+        assert_eq!(MemoryFormat::parse(""), expected(1, 'x', 4));
+        assert_eq!(MemoryFormat::parse("8xw"), expected(8, 'x', 4));
+        assert_eq!(MemoryFormat::parse("16xb"), expected(16, 'x', 1));
+        assert_eq!(MemoryFormat::parse("gd"), expected(1, 'd', 8));
+        assert_eq!(MemoryFormat::parse("3s"), expected(3, 's', 4));
+        assert_eq!(MemoryFormat::parse("2tb"), expected(2, 't', 1));
+        assert_eq!(MemoryFormat::parse("a"), expected(1, 'a', 4));
+        assert_eq!(MemoryFormat::parse("4q"), None);
+    }
+
+    #[test]
+    fn test_parse_find() {
+        let parse = |line: &str| {
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            DebuggerCommand::from_tokens(&tokens)
+        };
+        assert!(matches!(
+            parse("find &buf, +4096, 0xdeadbeef"),
+            Ok(DebuggerCommand::Find(
+                FindFormat {
+                    value_size: None,
+                    max_matches: memory_search::DEFAULT_MAX_MATCHES
+                },
+                args
+            )) if args == "&buf, +4096, 0xdeadbeef"
+        ));
+        assert!(matches!(
+            parse("find/b5 $rsp, +64, 1, 2"),
+            Ok(DebuggerCommand::Find(
+                FindFormat {
+                    value_size: Some(1),
+                    max_matches: 5
+                },
+                _
+            ))
+        ));
+        assert!(matches!(
+            parse("find /1000 $rsp, +64, 1"),
+            Ok(DebuggerCommand::Find(FindFormat { max_matches: 1000, .. }, args))
+                if args == "$rsp, +64, 1"
+        ));
+        assert!(parse("find").is_err());
+        assert!(parse("find/q $rsp, +64, 1").is_err());
+        assert!(parse("find/0 $rsp, +64, 1").is_err());
+
+        assert_eq!(
+            split_find("0x1000, 0x2000, \"a, \\\"b\", 7"),
+            Ok((
+                "0x1000".to_string(),
+                "0x2000".to_string(),
+                vec!["\"a, \\\"b\"".to_string(), "7".to_string()]
+            ))
+        );
+        assert!(split_find("0x1000, 0x2000").is_err());
+        assert!(split_find("0x1000, , 7").is_err());
+    }
+
+    #[test]
+    fn test_split_redirections() {
+        let args: Vec<String> = ["a", "<", "in.txt", ">>out.txt", "b", "2>", "err.txt"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let (program_args, redirections) = split_redirections(&args).unwrap();
+        assert_eq!(program_args, vec!["a", "b"]);
+        assert_eq!(
+            redirections,
+            vec![
+                Redirection::Stdin("in.txt".to_string()),
+                Redirection::Stdout("out.txt".to_string(), true),
+                Redirection::Stderr("err.txt".to_string()),
+            ]
+        );
+        assert!(split_redirections(&[">".to_string()]).is_err());
+        let (program_args, redirections) = split_redirections(&["x".to_string()]).unwrap();
+        assert_eq!(program_args, vec!["x"]);
+        assert!(redirections.is_empty());
+    }
+
+    #[test]
+    fn test_split_call() {
+        assert_eq!(
+            split_call("add(1, 2)").unwrap(),
+            ("add".to_string(), vec!["1".to_string(), "2".to_string()])
+        );
+        assert_eq!(
+            split_call("dump_tree (root)").unwrap(),
+            ("dump_tree".to_string(), vec!["root".to_string()])
+        );
+        assert_eq!(
+            split_call("f(a[i], (x + 1) * 2)").unwrap().1,
+            vec!["a[i]".to_string(), "(x + 1) * 2".to_string()]
+        );
+        assert_eq!(split_call("reset()").unwrap().1, Vec::<String>::new());
+        assert!(split_call("add").is_err());
+        assert!(split_call("add(1, )").is_err());
+        assert!(split_call("add(1))").is_err());
+        assert!(split_call("(1)").is_err());
+    }
+}
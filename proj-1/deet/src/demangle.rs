@@ -0,0 +1,70 @@
+//! Demangling of Rust (legacy `_ZN...17h<hash>E` and v0 `_R...`) and C++ (Itanium `_Z...`)
+//! symbol names, which are what the debug info's linkage names and symbol tables hold.
+
+/// Returns the source-level name behind the mangled `name`, e.g. `core::fmt::Formatter::write_str`
+/// or `geometry::scale(int, int)`, or None if `name` isn't mangled.
+pub fn demangle(name: &str) -> Option<String> {
+    if let Ok(symbol) = rustc_demangle::try_demangle(name) {
+        // The alternate form leaves out the hash that legacy mangling appends
+        return Some(format!("{:#}", symbol));
+    }
+    if !name.starts_with("_Z") {
+        return None;
+    }
+    cpp_demangle::Symbol::new(name)
+        .ok()?
+        .demangle(&Default::default())
+        .ok()
+}
+
+/// Returns true if `query` names the symbol `name`: either exactly, or by its demangled name,
+/// with or without a C++ parameter list.
+pub fn matches(name: &str, query: &str) -> bool {
+    if name == query {
+        return true;
+    }
+    match demangle(name) {
+        Some(demangled) => {
+            demangled == query
+                || demangled
+                    .split_once('(')
+                    .is_some_and(|(without_params, _)| without_params == query)
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_demangle() {
+        assert_eq!(
+            demangle("_ZN4core3fmt9Formatter9write_str17h6f4b9c9e5c1bc7a8E").as_deref(),
+            Some("core::fmt::Formatter::write_str")
+        );
+        assert_eq!(
+            demangle("_RNvCs1234_7mycrate3foo").as_deref(),
+            Some("mycrate::foo")
+        );
+        assert_eq!(
+            demangle("_ZN8geometry5scaleEii").as_deref(),
+            Some("geometry::scale(int, int)")
+        );
+        assert_eq!(demangle("main"), None);
+        assert_eq!(demangle("_Zgarbage"), None);
+    }
+
+    #[test]
+    fn test_matches() {
+        assert!(matches("_ZN8geometry5scaleEii", "geometry::scale"));
+        assert!(matches(
+            "_ZN8geometry5scaleEii",
+            "geometry::scale(int, int)"
+        ));
+        assert!(matches("_ZN8geometry5scaleEii", "_ZN8geometry5scaleEii"));
+        assert!(!matches("_ZN8geometry5scaleEii", "scale"));
+        assert!(matches("main", "main"));
+    }
+}
@@ -0,0 +1,1024 @@
+//! A small x86-64 instruction decoder producing AT&T syntax, like gdb's default output. It knows
+//! the general-purpose instructions and the common SSE ones that compilers emit; other
+//! instructions are decoded for their length only (so the instructions after them still decode
+//! correctly) or shown as `(bad)`.
+
+/// A decoded instruction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Instruction {
+    /// Length of the instruction in bytes
+    pub len: usize,
+    pub mnemonic: String,
+    /// Operands in AT&T order (source first), e.g. `%rsp,%rbp`
+    pub operands: String,
+    /// Address a relative jump or call goes to
+    pub branch_target: Option<usize>,
+    /// Address a %rip-relative memory operand refers to
+    pub memory_target: Option<usize>,
+}
+
+impl Instruction {
+    fn bad() -> Instruction {
+        Instruction {
+            len: 1,
+            mnemonic: "(bad)".to_string(),
+            operands: String::new(),
+            branch_target: None,
+            memory_target: None,
+        }
+    }
+
+    /// Formats the instruction the way gdb does, e.g. `mov    %rsp,%rbp`.
+    pub fn text(&self) -> String {
+        if self.operands.is_empty() {
+            self.mnemonic.clone()
+        } else {
+            format!("{:<6} {}", self.mnemonic, self.operands)
+        }
+    }
+}
+
+/// Longest possible x86 instruction, in bytes
+pub const MAX_INSTRUCTION_LEN: usize = 15;
+
+/// Decodes the instruction at the start of `bytes`, which were read from address `addr`.
+/// Undecodable bytes come out as a one-byte `(bad)` instruction.
+pub fn decode(bytes: &[u8], addr: usize) -> Instruction {
+    let bytes = &bytes[..bytes.len().min(MAX_INSTRUCTION_LEN)];
+    Decoder::new(bytes, addr)
+        .decode()
+        .unwrap_or_else(Instruction::bad)
+}
+
+const REGS_64: [&str; 16] = [
+    "rax", "rcx", "rdx", "rbx", "rsp", "rbp", "rsi", "rdi", "r8", "r9", "r10", "r11", "r12", "r13",
+    "r14", "r15",
+];
+const REGS_32: [&str; 16] = [
+    "eax", "ecx", "edx", "ebx", "esp", "ebp", "esi", "edi", "r8d", "r9d", "r10d", "r11d", "r12d",
+    "r13d", "r14d", "r15d",
+];
+const REGS_16: [&str; 16] = [
+    "ax", "cx", "dx", "bx", "sp", "bp", "si", "di", "r8w", "r9w", "r10w", "r11w", "r12w", "r13w",
+    "r14w", "r15w",
+];
+/// Byte registers when a REX prefix is present
+const REGS_8: [&str; 16] = [
+    "al", "cl", "dl", "bl", "spl", "bpl", "sil", "dil", "r8b", "r9b", "r10b", "r11b", "r12b",
+    "r13b", "r14b", "r15b",
+];
+/// Byte registers 4-7 without a REX prefix
+const REGS_8_LEGACY_HIGH: [&str; 4] = ["ah", "ch", "dh", "bh"];
+
+/// Condition code suffixes, in the order of the condition field of jcc, setcc and cmovcc
+const CONDITIONS: [&str; 16] = [
+    "o", "no", "b", "ae", "e", "ne", "be", "a", "s", "ns", "p", "np", "l", "ge", "le", "g",
+];
+
+const ALU_OPS: [&str; 8] = ["add", "or", "adc", "sbb", "and", "sub", "xor", "cmp"];
+const SHIFT_OPS: [&str; 8] = ["rol", "ror", "rcl", "rcr", "shl", "shr", "sal", "sar"];
+
+fn suffix(size: usize) -> char {
+    match size {
+        1 => 'b',
+        2 => 'w',
+        4 => 'l',
+        _ => 'q',
+    }
+}
+
+fn hex(value: i64) -> String {
+    if value < 0 {
+        format!("-{:#x}", value.unsigned_abs())
+    } else {
+        format!("{:#x}", value)
+    }
+}
+
+/// An instruction operand, in AT&T syntax
+#[derive(Clone)]
+enum Operand {
+    Reg(String),
+    Mem(String),
+    Imm(String),
+}
+
+impl Operand {
+    fn text(&self) -> &str {
+        match self {
+            Operand::Reg(text) | Operand::Mem(text) | Operand::Imm(text) => text,
+        }
+    }
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    addr: usize,
+    rex: u8,
+    /// 0x66 operand-size prefix
+    operand_size_prefix: bool,
+    /// 0xf2 or 0xf3 prefix
+    rep: Option<u8>,
+    segment: Option<&'static str>,
+    lock: bool,
+    /// Displacement of a %rip-relative memory operand
+    rip_displacement: Option<i64>,
+    /// Displacement of a relative jump or call
+    branch_displacement: Option<i64>,
+}
+
+impl<'a> Decoder<'a> {
+    fn new(bytes: &'a [u8], addr: usize) -> Decoder<'a> {
+        Decoder {
+            bytes,
+            pos: 0,
+            addr,
+            rex: 0,
+            operand_size_prefix: false,
+            rep: None,
+            segment: None,
+            lock: false,
+            rip_displacement: None,
+            branch_displacement: None,
+        }
+    }
+
+    fn byte(&mut self) -> Option<u8> {
+        let byte = *self.bytes.get(self.pos)?;
+        self.pos += 1;
+        Some(byte)
+    }
+
+    /// Reads a little-endian immediate of `size` bytes, sign-extended.
+    fn signed(&mut self, size: usize) -> Option<i64> {
+        let bytes = self.bytes.get(self.pos..self.pos + size)?;
+        self.pos += size;
+        let mut buf = [0u8; 8];
+        buf[..size].copy_from_slice(bytes);
+        let value = u64::from_le_bytes(buf);
+        let shift = 64 - 8 * size as u32;
+        Some(((value << shift) as i64) >> shift)
+    }
+
+    fn rex_w(&self) -> bool {
+        self.rex & 0b1000 != 0
+    }
+
+    fn rex_r(&self) -> u8 {
+        (self.rex >> 2) & 1
+    }
+
+    fn rex_x(&self) -> u8 {
+        (self.rex >> 1) & 1
+    }
+
+    fn rex_b(&self) -> u8 {
+        self.rex & 1
+    }
+
+    /// Operand size of instructions that work on words, doublewords or quadwords
+    fn operand_size(&self) -> usize {
+        if self.rex_w() {
+            8
+        } else if self.operand_size_prefix {
+            2
+        } else {
+            4
+        }
+    }
+
+    /// Operand size of push, pop and indirect branches, which default to 64 bits
+    fn stack_operand_size(&self) -> usize {
+        if self.operand_size_prefix {
+            2
+        } else {
+            8
+        }
+    }
+
+    fn reg(&self, num: u8, size: usize) -> Operand {
+        let num = num as usize;
+        let name = match size {
+            1 if self.rex == 0 && (4..8).contains(&num) => REGS_8_LEGACY_HIGH[num - 4],
+            1 => REGS_8[num],
+            2 => REGS_16[num],
+            4 => REGS_32[num],
+            _ => REGS_64[num],
+        };
+        Operand::Reg(format!("%{}", name))
+    }
+
+    fn xmm(&self, num: u8) -> Operand {
+        Operand::Reg(format!("%xmm{}", num))
+    }
+
+    fn imm(&mut self, imm_size: usize, operand_size: usize) -> Option<Operand> {
+        let value = self.signed(imm_size)? as u64;
+        let masked = if operand_size >= 8 {
+            value
+        } else {
+            value & ((1u64 << (8 * operand_size)) - 1)
+        };
+        Some(Operand::Imm(format!("${:#x}", masked)))
+    }
+
+    /// Size of the immediate of instructions taking `Iz` operands: 16 bits with the
+    /// operand-size prefix, 32 bits (sign-extended for 64-bit operations) otherwise
+    fn iz_size(&self, operand_size: usize) -> usize {
+        operand_size.min(4)
+    }
+
+    /// Reads a ModRM byte, returning its mod, reg (extended by REX.R) and r/m fields.
+    fn modrm(&mut self) -> Option<(u8, u8, u8)> {
+        let byte = self.byte()?;
+        Some((byte >> 6, ((byte >> 3) & 7) | (self.rex_r() << 3), byte & 7))
+    }
+
+    /// Decodes the r/m operand of a ModRM byte: a register of `size` bytes (or an xmm register
+    /// if `xmm` is set), or a memory reference.
+    fn rm(&mut self, md: u8, rm: u8, size: usize, xmm: bool) -> Option<Operand> {
+        if md == 3 {
+            let num = rm | (self.rex_b() << 3);
+            return Some(if xmm {
+                self.xmm(num)
+            } else {
+                self.reg(num, size)
+            });
+        }
+
+        let mut base = None;
+        let mut index = None;
+        let mut scale = 1;
+        let mut rip_relative = false;
+        let mut absolute = false;
+        if rm == 4 {
+            let sib = self.byte()?;
+            scale = 1 << (sib >> 6);
+            let index_num = ((sib >> 3) & 7) | (self.rex_x() << 3);
+            if index_num != 4 {
+                index = Some(REGS_64[index_num as usize]);
+            }
+            if sib & 7 == 5 && md == 0 {
+                absolute = true;
+            } else {
+                base = Some(REGS_64[((sib & 7) | (self.rex_b() << 3)) as usize]);
+            }
+        } else if rm == 5 && md == 0 {
+            rip_relative = true;
+        } else {
+            base = Some(REGS_64[(rm | (self.rex_b() << 3)) as usize]);
+        }
+        let displacement = match md {
+            1 => Some(self.signed(1)?),
+            2 => Some(self.signed(4)?),
+            _ if rip_relative || absolute => Some(self.signed(4)?),
+            _ => None,
+        };
+
+        let mut text = String::new();
+        if let Some(segment) = self.segment {
+            text.push_str(&format!("%{}:", segment));
+        }
+        if rip_relative {
+            let displacement = displacement.unwrap();
+            self.rip_displacement = Some(displacement);
+            text.push_str(&format!("{}(%rip)", hex(displacement)));
+            return Some(Operand::Mem(text));
+        }
+        match displacement {
+            Some(displacement) if base.is_none() && index.is_none() => {
+                text.push_str(&format!("{:#x}", displacement as u32));
+                return Some(Operand::Mem(text));
+            }
+            Some(displacement) => text.push_str(&hex(displacement)),
+            None => {}
+        }
+        text.push('(');
+        if let Some(base) = base {
+            text.push_str(&format!("%{}", base));
+        }
+        if let Some(index) = index {
+            text.push_str(&format!(",%{},{}", index, scale));
+        }
+        text.push(')');
+        Some(Operand::Mem(text))
+    }
+
+    /// Builds the instruction. `operands` are in Intel order (destination first). If
+    /// `sized_as` is given and no operand is a register, the mnemonic gets a size suffix, as
+    /// in `movl $0x0,-0x4(%rbp)`.
+    fn finish(
+        self,
+        mnemonic: &str,
+        operands: Vec<Operand>,
+        sized_as: Option<usize>,
+    ) -> Option<Instruction> {
+        let mut mnemonic = mnemonic.to_string();
+        if let Some(size) = sized_as {
+            let has_reg = operands.iter().any(|op| matches!(op, Operand::Reg(_)));
+            let has_mem = operands.iter().any(|op| matches!(op, Operand::Mem(_)));
+            if has_mem && !has_reg {
+                mnemonic.push(suffix(size));
+            }
+        }
+        if self.lock {
+            mnemonic = format!("lock {}", mnemonic);
+        }
+        let operands = operands
+            .iter()
+            .rev()
+            .map(|op| op.text().to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        let end = self.addr.wrapping_add(self.pos);
+        Some(Instruction {
+            len: self.pos,
+            mnemonic,
+            operands,
+            branch_target: self
+                .branch_displacement
+                .map(|displacement| end.wrapping_add(displacement as usize)),
+            memory_target: self
+                .rip_displacement
+                .map(|displacement| end.wrapping_add(displacement as usize)),
+        })
+    }
+
+    /// Builds a relative jump or call with a displacement of `size` bytes.
+    fn branch(mut self, mnemonic: &str, size: usize) -> Option<Instruction> {
+        let displacement = self.signed(size)?;
+        self.branch_displacement = Some(displacement);
+        let target = self
+            .addr
+            .wrapping_add(self.pos)
+            .wrapping_add(displacement as usize);
+        // bnd-prefixed branches show up in PLTs
+        let mnemonic = if self.rep == Some(0xf2) {
+            format!("bnd {}", mnemonic)
+        } else {
+            mnemonic.to_string()
+        };
+        self.finish(
+            &mnemonic,
+            vec![Operand::Imm(format!("{:#x}", target))],
+            None,
+        )
+    }
+
+    fn decode(mut self) -> Option<Instruction> {
+        let mut opcode = self.byte()?;
+        loop {
+            match opcode {
+                0x66 => self.operand_size_prefix = true,
+                0xf2 | 0xf3 => self.rep = Some(opcode),
+                0xf0 => self.lock = true,
+                0x26 => self.segment = Some("es"),
+                0x2e => self.segment = Some("cs"),
+                0x36 => self.segment = Some("ss"),
+                0x3e => self.segment = Some("ds"),
+                0x64 => self.segment = Some("fs"),
+                0x65 => self.segment = Some("gs"),
+                _ => break,
+            }
+            opcode = self.byte()?;
+        }
+        // A REX prefix must come right before the opcode
+        if opcode & 0xf0 == 0x40 {
+            self.rex = opcode;
+            opcode = self.byte()?;
+        }
+
+        let size = self.operand_size();
+        match opcode {
+            // add, or, adc, sbb, and, sub, xor, cmp in their six forms
+            0x00..=0x3f if opcode & 7 < 6 => {
+                let op = ALU_OPS[(opcode >> 3) as usize];
+                match opcode & 7 {
+                    0..=3 => {
+                        let op_size = if opcode & 1 == 0 { 1 } else { size };
+                        let (md, reg, rm) = self.modrm()?;
+                        let rm = self.rm(md, rm, op_size, false)?;
+                        let reg = self.reg(reg, op_size);
+                        let operands = if opcode & 2 == 0 {
+                            vec![rm, reg]
+                        } else {
+                            vec![reg, rm]
+                        };
+                        self.finish(op, operands, None)
+                    }
+                    4 => {
+                        let imm = self.imm(1, 1)?;
+                        let al = self.reg(0, 1);
+                        self.finish(op, vec![al, imm], None)
+                    }
+                    _ => {
+                        let imm = self.imm(self.iz_size(size), size)?;
+                        let ax = self.reg(0, size);
+                        self.finish(op, vec![ax, imm], None)
+                    }
+                }
+            }
+            0x50..=0x5f => {
+                let reg = self.reg((opcode & 7) | (self.rex_b() << 3), 8);
+                let op = if opcode < 0x58 { "push" } else { "pop" };
+                self.finish(op, vec![reg], None)
+            }
+            0x63 => {
+                let (md, reg, rm) = self.modrm()?;
+                let src = self.rm(md, rm, 4, false)?;
+                let dst = self.reg(reg, size);
+                let op = if size == 8 { "movslq" } else { "movsxd" };
+                self.finish(op, vec![dst, src], None)
+            }
+            0x68 | 0x6a => {
+                let imm_size = if opcode == 0x68 { 4 } else { 1 };
+                let imm = self.imm(imm_size, 8)?;
+                self.finish("push", vec![imm], None)
+            }
+            0x69 | 0x6b => {
+                let (md, reg, rm) = self.modrm()?;
+                let src = self.rm(md, rm, size, false)?;
+                let imm_size = if opcode == 0x69 {
+                    self.iz_size(size)
+                } else {
+                    1
+                };
+                let imm = self.imm(imm_size, size)?;
+                let dst = self.reg(reg, size);
+                self.finish("imul", vec![dst, src, imm], None)
+            }
+            0x70..=0x7f => self.branch(&format!("j{}", CONDITIONS[(opcode & 0xf) as usize]), 1),
+            0x80 | 0x81 | 0x83 => {
+                let op_size = if opcode == 0x80 { 1 } else { size };
+                let (md, reg, rm) = self.modrm()?;
+                let dst = self.rm(md, rm, op_size, false)?;
+                let imm_size = if opcode == 0x81 {
+                    self.iz_size(size)
+                } else {
+                    1
+                };
+                let imm = self.imm(imm_size, op_size)?;
+                self.finish(ALU_OPS[(reg & 7) as usize], vec![dst, imm], Some(op_size))
+            }
+            0x84..=0x8b => {
+                let op_size = if opcode & 1 == 0 { 1 } else { size };
+                let (md, reg, rm) = self.modrm()?;
+                let rm = self.rm(md, rm, op_size, false)?;
+                let reg = self.reg(reg, op_size);
+                let (op, operands) = match opcode {
+                    0x84 | 0x85 => ("test", vec![rm, reg]),
+                    0x86 | 0x87 => ("xchg", vec![rm, reg]),
+                    0x88 | 0x89 => ("mov", vec![rm, reg]),
+                    _ => ("mov", vec![reg, rm]),
+                };
+                self.finish(op, operands, None)
+            }
+            0x8d => {
+                let (md, reg, rm) = self.modrm()?;
+                if md == 3 {
+                    return None;
+                }
+                let src = self.rm(md, rm, size, false)?;
+                let dst = self.reg(reg, size);
+                self.finish("lea", vec![dst, src], None)
+            }
+            0x8f => {
+                let (md, _reg, rm) = self.modrm()?;
+                let op_size = self.stack_operand_size();
+                let dst = self.rm(md, rm, op_size, false)?;
+                self.finish("pop", vec![dst], Some(op_size))
+            }
+            0x90 if self.rex_b() == 0 => {
+                let op = if self.rep == Some(0xf3) {
+                    "pause"
+                } else {
+                    "nop"
+                };
+                self.finish(op, vec![], None)
+            }
+            0x90..=0x97 => {
+                let reg = self.reg((opcode & 7) | (self.rex_b() << 3), size);
+                let ax = self.reg(0, size);
+                self.finish("xchg", vec![reg, ax], None)
+            }
+            0x98 => {
+                let op = match size {
+                    8 => "cltq",
+                    2 => "cbtw",
+                    _ => "cwtl",
+                };
+                self.finish(op, vec![], None)
+            }
+            0x99 => {
+                let op = match size {
+                    8 => "cqto",
+                    2 => "cwtd",
+                    _ => "cltd",
+                };
+                self.finish(op, vec![], None)
+            }
+            0xa4 | 0xa5 | 0xaa | 0xab => {
+                let op_size = if opcode & 1 == 0 { 1 } else { size };
+                let prefix = if self.rep.is_some() { "rep " } else { "" };
+                let (op, operands) = if opcode <= 0xa5 {
+                    (
+                        "movs",
+                        vec![
+                            Operand::Mem("%es:(%rdi)".to_string()),
+                            Operand::Mem("%ds:(%rsi)".to_string()),
+                        ],
+                    )
+                } else {
+                    (
+                        "stos",
+                        vec![Operand::Mem("%es:(%rdi)".to_string()), self.reg(0, op_size)],
+                    )
+                };
+                self.finish(&format!("{}{}", prefix, op), operands, Some(op_size))
+            }
+            0xa8 => {
+                let imm = self.imm(1, 1)?;
+                let al = self.reg(0, 1);
+                self.finish("test", vec![al, imm], None)
+            }
+            0xa9 => {
+                let imm = self.imm(self.iz_size(size), size)?;
+                let ax = self.reg(0, size);
+                self.finish("test", vec![ax, imm], None)
+            }
+            0xb0..=0xb7 => {
+                let imm = self.imm(1, 1)?;
+                let reg = self.reg((opcode & 7) | (self.rex_b() << 3), 1);
+                self.finish("mov", vec![reg, imm], None)
+            }
+            0xb8..=0xbf => {
+                let reg = self.reg((opcode & 7) | (self.rex_b() << 3), size);
+                if size == 8 {
+                    let imm = self.imm(8, 8)?;
+                    self.finish("movabs", vec![reg, imm], None)
+                } else {
+                    let imm = self.imm(size, size)?;
+                    self.finish("mov", vec![reg, imm], None)
+                }
+            }
+            0xc0 | 0xc1 | 0xd0..=0xd3 => {
+                let op_size = if opcode & 1 == 0 { 1 } else { size };
+                let (md, reg, rm) = self.modrm()?;
+                let dst = self.rm(md, rm, op_size, false)?;
+                let op = SHIFT_OPS[(reg & 7) as usize];
+                match opcode {
+                    0xc0 | 0xc1 => {
+                        let imm = self.imm(1, 1)?;
+                        self.finish(op, vec![dst, imm], Some(op_size))
+                    }
+                    0xd0 | 0xd1 => self.finish(op, vec![dst], Some(op_size)),
+                    _ => {
+                        let cl = self.reg(1, 1);
+                        self.finish(op, vec![dst, cl], Some(op_size))
+                    }
+                }
+            }
+            0xc2 => {
+                let imm = self.imm(2, 2)?;
+                self.finish("ret", vec![imm], None)
+            }
+            0xc3 => {
+                let op = match self.rep {
+                    Some(0xf3) => "repz ret",
+                    Some(_) => "bnd ret",
+                    None => "ret",
+                };
+                self.finish(op, vec![], None)
+            }
+            0xc6 | 0xc7 => {
+                let op_size = if opcode == 0xc6 { 1 } else { size };
+                let (md, _reg, rm) = self.modrm()?;
+                let dst = self.rm(md, rm, op_size, false)?;
+                let imm = self.imm(self.iz_size(op_size), op_size)?;
+                self.finish("mov", vec![dst, imm], Some(op_size))
+            }
+            0xc8 => {
+                let frame_size = self.imm(2, 2)?;
+                let nesting = self.imm(1, 1)?;
+                self.finish("enter", vec![frame_size, nesting], None)
+            }
+            0xc9 => self.finish("leave", vec![], None),
+            0xcc => self.finish("int3", vec![], None),
+            0xcd => {
+                let imm = self.imm(1, 1)?;
+                self.finish("int", vec![imm], None)
+            }
+            // x87 instructions: decoded for their length only
+            0xd8..=0xdf => {
+                let (md, _reg, rm) = self.modrm()?;
+                self.rm(md, rm, 8, false)?;
+                self.finish("(x87)", vec![], None)
+            }
+            0xe3 => self.branch("jrcxz", 1),
+            0xe8 => self.branch("call", 4),
+            0xe9 => self.branch("jmp", 4),
+            0xeb => self.branch("jmp", 1),
+            0xf4 => self.finish("hlt", vec![], None),
+            0xf5 => self.finish("cmc", vec![], None),
+            0xf8 => self.finish("clc", vec![], None),
+            0xf9 => self.finish("stc", vec![], None),
+            0xfc => self.finish("cld", vec![], None),
+            0xfd => self.finish("std", vec![], None),
+            0xf6 | 0xf7 => {
+                let op_size = if opcode == 0xf6 { 1 } else { size };
+                let (md, reg, rm) = self.modrm()?;
+                let operand = self.rm(md, rm, op_size, false)?;
+                let op = ["test", "test", "not", "neg", "mul", "imul", "div", "idiv"]
+                    [(reg & 7) as usize];
+                if reg & 7 < 2 {
+                    let imm = self.imm(self.iz_size(op_size), op_size)?;
+                    self.finish(op, vec![operand, imm], Some(op_size))
+                } else {
+                    self.finish(op, vec![operand], Some(op_size))
+                }
+            }
+            0xfe => {
+                let (md, reg, rm) = self.modrm()?;
+                let operand = self.rm(md, rm, 1, false)?;
+                match reg & 7 {
+                    0 => self.finish("inc", vec![operand], Some(1)),
+                    1 => self.finish("dec", vec![operand], Some(1)),
+                    _ => None,
+                }
+            }
+            0xff => {
+                let (md, reg, rm) = self.modrm()?;
+                match reg & 7 {
+                    0 | 1 => {
+                        let operand = self.rm(md, rm, size, false)?;
+                        let op = if reg & 7 == 0 { "inc" } else { "dec" };
+                        self.finish(op, vec![operand], Some(size))
+                    }
+                    2 | 4 => {
+                        let operand = self.rm(md, rm, 8, false)?;
+                        let op = match (reg & 7, self.rep) {
+                            (2, Some(0xf2)) => "bnd call",
+                            (2, _) => "call",
+                            (_, Some(0xf2)) => "bnd jmp",
+                            _ => "jmp",
+                        };
+                        let target = Operand::Imm(format!("*{}", operand.text()));
+                        self.finish(op, vec![target], None)
+                    }
+                    6 => {
+                        let op_size = self.stack_operand_size();
+                        let operand = self.rm(md, rm, op_size, false)?;
+                        self.finish("push", vec![operand], Some(op_size))
+                    }
+                    _ => None,
+                }
+            }
+            0x0f => self.decode_0f(),
+            // VEX-encoded (AVX) instructions: decoded for their length only
+            0xc4 | 0xc5 => {
+                let map = if opcode == 0xc5 {
+                    self.byte()?;
+                    1
+                } else {
+                    let map = self.byte()? & 0x1f;
+                    self.byte()?;
+                    map
+                };
+                self.byte()?;
+                let (md, _reg, rm) = self.modrm()?;
+                self.rm(md, rm, 8, true)?;
+                if map == 3 {
+                    self.byte()?;
+                }
+                self.finish("(avx)", vec![], None)
+            }
+            _ => None,
+        }
+    }
+
+    /// Decodes the two-byte opcodes, which start with 0x0f.
+    fn decode_0f(mut self) -> Option<Instruction> {
+        let opcode = self.byte()?;
+        let size = self.operand_size();
+        match opcode {
+            0x05 => self.finish("syscall", vec![], None),
+            0x0b => self.finish("ud2", vec![], None),
+            0x1e if self.rep == Some(0xf3) && self.bytes.get(self.pos) == Some(&0xfa) => {
+                self.pos += 1;
+                self.finish("endbr64", vec![], None)
+            }
+            0x1f => {
+                let (md, _reg, rm) = self.modrm()?;
+                let operand = self.rm(md, rm, size, false)?;
+                self.finish("nop", vec![operand], Some(size))
+            }
+            0x31 => self.finish("rdtsc", vec![], None),
+            0xa2 => self.finish("cpuid", vec![], None),
+            0x40..=0x4f => {
+                let (md, reg, rm) = self.modrm()?;
+                let src = self.rm(md, rm, size, false)?;
+                let dst = self.reg(reg, size);
+                let op = format!("cmov{}", CONDITIONS[(opcode & 0xf) as usize]);
+                self.finish(&op, vec![dst, src], None)
+            }
+            0x80..=0x8f => self.branch(&format!("j{}", CONDITIONS[(opcode & 0xf) as usize]), 4),
+            0x90..=0x9f => {
+                let (md, _reg, rm) = self.modrm()?;
+                let dst = self.rm(md, rm, 1, false)?;
+                let op = format!("set{}", CONDITIONS[(opcode & 0xf) as usize]);
+                self.finish(&op, vec![dst], None)
+            }
+            0xa3 | 0xab | 0xb3 | 0xbb => {
+                let (md, reg, rm) = self.modrm()?;
+                let dst = self.rm(md, rm, size, false)?;
+                let src = self.reg(reg, size);
+                let op = match opcode {
+                    0xa3 => "bt",
+                    0xab => "bts",
+                    0xb3 => "btr",
+                    _ => "btc",
+                };
+                self.finish(op, vec![dst, src], None)
+            }
+            0xa4 | 0xa5 | 0xac | 0xad => {
+                let (md, reg, rm) = self.modrm()?;
+                let dst = self.rm(md, rm, size, false)?;
+                let src = self.reg(reg, size);
+                let count = if opcode & 1 == 0 {
+                    self.imm(1, 1)?
+                } else {
+                    self.reg(1, 1)
+                };
+                let op = if opcode < 0xac { "shld" } else { "shrd" };
+                self.finish(op, vec![dst, src, count], None)
+            }
+            0xaf => {
+                let (md, reg, rm) = self.modrm()?;
+                let src = self.rm(md, rm, size, false)?;
+                let dst = self.reg(reg, size);
+                self.finish("imul", vec![dst, src], None)
+            }
+            0xb0 | 0xb1 | 0xc0 | 0xc1 => {
+                let op_size = if opcode & 1 == 0 { 1 } else { size };
+                let (md, reg, rm) = self.modrm()?;
+                let dst = self.rm(md, rm, op_size, false)?;
+                let src = self.reg(reg, op_size);
+                let op = if opcode < 0xc0 { "cmpxchg" } else { "xadd" };
+                self.finish(op, vec![dst, src], None)
+            }
+            0xb6 | 0xb7 | 0xbe | 0xbf => {
+                let src_size = if opcode & 1 == 0 { 1 } else { 2 };
+                let (md, reg, rm) = self.modrm()?;
+                let src = self.rm(md, rm, src_size, false)?;
+                let dst = self.reg(reg, size);
+                let op = format!(
+                    "mov{}{}{}",
+                    if opcode < 0xbe { 'z' } else { 's' },
+                    suffix(src_size),
+                    suffix(size)
+                );
+                self.finish(&op, vec![dst, src], None)
+            }
+            0xb8 | 0xbc | 0xbd => {
+                let (md, reg, rm) = self.modrm()?;
+                let src = self.rm(md, rm, size, false)?;
+                let dst = self.reg(reg, size);
+                let op = match (opcode, self.rep == Some(0xf3)) {
+                    (0xb8, true) => "popcnt",
+                    (0xbc, true) => "tzcnt",
+                    (0xbd, true) => "lzcnt",
+                    (0xbc, false) => "bsf",
+                    (0xbd, false) => "bsr",
+                    _ => return None,
+                };
+                self.finish(op, vec![dst, src], None)
+            }
+            0xba => {
+                let (md, reg, rm) = self.modrm()?;
+                let dst = self.rm(md, rm, size, false)?;
+                let imm = self.imm(1, 1)?;
+                let op = match reg & 7 {
+                    4 => "bt",
+                    5 => "bts",
+                    6 => "btr",
+                    7 => "btc",
+                    _ => return None,
+                };
+                self.finish(op, vec![dst, imm], Some(size))
+            }
+            0xc8..=0xcf => {
+                let reg = self.reg((opcode & 7) | (self.rex_b() << 3), size);
+                self.finish("bswap", vec![reg], None)
+            }
+            // Three-byte opcodes (SSSE3, SSE4 and friends): decoded for their length only
+            0x38 | 0x3a => {
+                self.byte()?;
+                let (md, _reg, rm) = self.modrm()?;
+                self.rm(md, rm, 8, true)?;
+                if opcode == 0x3a {
+                    self.byte()?;
+                }
+                self.finish("(sse)", vec![], None)
+            }
+            _ => self.decode_sse(opcode),
+        }
+    }
+
+    /// Decodes the SSE instructions compilers use for floating-point code. Their operand types
+    /// depend on the 0x66, 0xf2 and 0xf3 prefixes.
+    fn decode_sse(mut self, opcode: u8) -> Option<Instruction> {
+        // ps, pd, ss or sd variant, selected by the prefix
+        let variant = match (self.rep, self.operand_size_prefix) {
+            (Some(0xf3), _) => "ss",
+            (Some(_), _) => "sd",
+            (None, true) => "pd",
+            (None, false) => "ps",
+        };
+        let packed_or_double = if variant == "ss" || variant == "ps" {
+            "ps"
+        } else {
+            "pd"
+        };
+        let (md, reg, rm) = self.modrm()?;
+        let xmm_reg = self.xmm(reg);
+        match opcode {
+            0x10 | 0x11 => {
+                let op = match variant {
+                    "ps" => "movups".to_string(),
+                    "pd" => "movupd".to_string(),
+                    scalar => format!("mov{}", scalar),
+                };
+                let rm = self.rm(md, rm, 16, true)?;
+                let operands = if opcode == 0x10 {
+                    vec![xmm_reg, rm]
+                } else {
+                    vec![rm, xmm_reg]
+                };
+                self.finish(&op, operands, None)
+            }
+            0x28 | 0x29 => {
+                let rm = self.rm(md, rm, 16, true)?;
+                let operands = if opcode == 0x28 {
+                    vec![xmm_reg, rm]
+                } else {
+                    vec![rm, xmm_reg]
+                };
+                self.finish(&format!("mova{}", packed_or_double), operands, None)
+            }
+            0x2a if self.rep.is_some() => {
+                let int_size = if self.rex_w() { 8 } else { 4 };
+                let src = self.rm(md, rm, int_size, false)?;
+                let op = format!("cvtsi2{}", variant);
+                self.finish(&op, vec![xmm_reg, src], Some(int_size))
+            }
+            0x2c | 0x2d if self.rep.is_some() => {
+                let src = self.rm(md, rm, 16, true)?;
+                let dst = self.reg(reg, if self.rex_w() { 8 } else { 4 });
+                let truncate = if opcode == 0x2c { "t" } else { "" };
+                let op = format!("cvt{}{}2si", truncate, variant);
+                self.finish(&op, vec![dst, src], None)
+            }
+            0x2e | 0x2f if self.rep.is_none() => {
+                let src = self.rm(md, rm, 16, true)?;
+                let scalar = if self.operand_size_prefix { "sd" } else { "ss" };
+                let op = format!("{}comi{}", if opcode == 0x2e { "u" } else { "" }, scalar);
+                self.finish(&op, vec![xmm_reg, src], None)
+            }
+            0x51 | 0x58 | 0x59 | 0x5c..=0x5f => {
+                let src = self.rm(md, rm, 16, true)?;
+                let op = match opcode {
+                    0x51 => "sqrt",
+                    0x58 => "add",
+                    0x59 => "mul",
+                    0x5c => "sub",
+                    0x5d => "min",
+                    0x5e => "div",
+                    _ => "max",
+                };
+                self.finish(&format!("{}{}", op, variant), vec![xmm_reg, src], None)
+            }
+            0x54 | 0x55 | 0x57 if self.rep.is_none() => {
+                let src = self.rm(md, rm, 16, true)?;
+                let op = match opcode {
+                    0x54 => "and",
+                    0x55 => "andn",
+                    _ => "xor",
+                };
+                self.finish(
+                    &format!("{}{}", op, packed_or_double),
+                    vec![xmm_reg, src],
+                    None,
+                )
+            }
+            0x5a => {
+                let src = self.rm(md, rm, 16, true)?;
+                let op = match variant {
+                    "ss" => "cvtss2sd",
+                    "sd" => "cvtsd2ss",
+                    "ps" => "cvtps2pd",
+                    _ => "cvtpd2ps",
+                };
+                self.finish(op, vec![xmm_reg, src], None)
+            }
+            0x6e if self.operand_size_prefix => {
+                let src = self.rm(md, rm, if self.rex_w() { 8 } else { 4 }, false)?;
+                let op = if self.rex_w() { "movq" } else { "movd" };
+                self.finish(op, vec![xmm_reg, src], None)
+            }
+            0x7e if self.operand_size_prefix => {
+                let dst = self.rm(md, rm, if self.rex_w() { 8 } else { 4 }, false)?;
+                let op = if self.rex_w() { "movq" } else { "movd" };
+                self.finish(op, vec![dst, xmm_reg], None)
+            }
+            0x7e if self.rep == Some(0xf3) => {
+                let src = self.rm(md, rm, 16, true)?;
+                self.finish("movq", vec![xmm_reg, src], None)
+            }
+            0xd6 if self.operand_size_prefix => {
+                let dst = self.rm(md, rm, 16, true)?;
+                self.finish("movq", vec![dst, xmm_reg], None)
+            }
+            0x6f | 0x7f if self.operand_size_prefix || self.rep == Some(0xf3) => {
+                let rm = self.rm(md, rm, 16, true)?;
+                let op = if self.operand_size_prefix {
+                    "movdqa"
+                } else {
+                    "movdqu"
+                };
+                let operands = if opcode == 0x6f {
+                    vec![xmm_reg, rm]
+                } else {
+                    vec![rm, xmm_reg]
+                };
+                self.finish(op, operands, None)
+            }
+            0xef if self.operand_size_prefix => {
+                let src = self.rm(md, rm, 16, true)?;
+                self.finish("pxor", vec![xmm_reg, src], None)
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn text(bytes: &[u8]) -> (usize, String) {
+        let instruction = decode(bytes, 0x401000);
+        (instruction.len, instruction.text())
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(text(&[0x55]), (1, "push   %rbp".to_string()));
+        assert_eq!(
+            text(&[0x48, 0x89, 0xe5]),
+            (3, "mov    %rsp,%rbp".to_string())
+        );
+        assert_eq!(
+            text(&[0x48, 0x83, 0xec, 0x10]),
+            (4, "sub    $0x10,%rsp".to_string())
+        );
+        assert_eq!(
+            text(&[0x89, 0x7d, 0xec]),
+            (3, "mov    %edi,-0x14(%rbp)".to_string())
+        );
+        assert_eq!(
+            text(&[0xc7, 0x45, 0xfc, 0x00, 0x00, 0x00, 0x00]),
+            (7, "movl   $0x0,-0x4(%rbp)".to_string())
+        );
+        assert_eq!(
+            text(&[0x8b, 0x05, 0xd6, 0x2e, 0x00, 0x00]),
+            (6, "mov    0x2ed6(%rip),%eax".to_string())
+        );
+        assert_eq!(
+            decode(&[0x8b, 0x05, 0xd6, 0x2e, 0x00, 0x00], 0x401000).memory_target,
+            Some(0x401006 + 0x2ed6)
+        );
+        let call = decode(&[0xe8, 0xfb, 0xff, 0xff, 0xff], 0x401000);
+        assert_eq!(call.text(), "call   0x401000");
+        assert_eq!(call.branch_target, Some(0x401000));
+        assert_eq!(text(&[0x74, 0x02]), (2, "je     0x401004".to_string()));
+        assert_eq!(
+            text(&[0x0f, 0xb6, 0x45, 0xff]),
+            (4, "movzbl -0x1(%rbp),%eax".to_string())
+        );
+        assert_eq!(
+            text(&[0x48, 0x8d, 0x04, 0x85, 0x00, 0x00, 0x00, 0x00]),
+            (8, "lea    0x0(,%rax,4),%rax".to_string())
+        );
+        assert_eq!(text(&[0xf3, 0x0f, 0x1e, 0xfa]), (4, "endbr64".to_string()));
+        assert_eq!(
+            text(&[0xf2, 0x0f, 0x10, 0x45, 0xf8]),
+            (5, "movsd  -0x8(%rbp),%xmm0".to_string())
+        );
+        assert_eq!(text(&[0x48, 0x98]), (2, "cltq".to_string()));
+        assert_eq!(text(&[0xc3]), (1, "ret".to_string()));
+        assert_eq!(text(&[0x06]), (1, "(bad)".to_string()));
+        // Truncated instructions can't be decoded either
+        assert_eq!(text(&[0x48, 0x89]), (1, "(bad)".to_string()));
+    }
+}
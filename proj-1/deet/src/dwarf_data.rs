@@ -1,6 +1,10 @@
+use crate::demangle;
 use crate::gimli_wrapper;
+use crate::unwind::{CallFrameInfo, FrameRules};
 use addr2line::Context;
-use object::Object;
+use object::elf::PF_X;
+use object::{Object, ObjectSegment, SegmentFlags};
+use std::collections::HashMap;
 use std::convert::TryInto;
 use std::{fmt, fs};
 
@@ -10,8 +14,33 @@ pub enum Error {
     DwarfFormatError(gimli_wrapper::Error),
 }
 
+/// How many lines past a line without code (a comment, a blank line, a lone brace...)
+/// `DwarfData::lookup_line` looks for one with code
+const MAX_LINE_SNAP: usize = 20;
+
+/// Why `DwarfData::lookup_line` found no address for a line
+#[derive(Debug, Clone, PartialEq)]
+pub enum LineLookupError {
+    /// No compilation unit matches the file name
+    NoSuchFile,
+    /// Several compilation units match the file name; holds their names
+    AmbiguousFile(Vec<String>),
+    /// Neither the line nor any of the `MAX_LINE_SNAP` lines after it has code
+    NoCode,
+}
+
 pub struct DwarfData {
     files: Vec<File>,
+    /// All types in the program, keyed by their .debug_info offset
+    types: HashMap<usize, Type>,
+    /// Address ranges `[start, end)` of the executable segments in the ELF program headers
+    executable_ranges: Vec<(usize, usize)>,
+    /// The ELF entry point (`_start`)
+    entry_point: usize,
+    /// Unwinding rules from `.eh_frame` and `.debug_frame`
+    call_frame_info: CallFrameInfo,
+    /// Whether `display_name` demangles names
+    demangle: bool,
     addr2line: Context<addr2line::gimli::EndianRcSlice<addr2line::gimli::RunTimeEndian>>,
 }
 
@@ -32,53 +61,102 @@ impl DwarfData {
         let file = fs::File::open(path).or(Err(Error::ErrorOpeningFile))?;
         let mmap = unsafe { memmap2::Mmap::map(&file).or(Err(Error::ErrorOpeningFile))? };
         let object = object::File::parse(&*mmap)
-            .or_else(|e| Err(gimli_wrapper::Error::ObjectError(e.to_string())))?;
+            .map_err(|e| gimli_wrapper::Error::ObjectError(e.to_string()))?;
         let endian = if object.is_little_endian() {
             gimli::RunTimeEndian::Little
         } else {
             gimli::RunTimeEndian::Big
         };
+        let (files, types) = gimli_wrapper::load_file(&object, endian)?;
+        let executable_ranges = object
+            .segments()
+            .filter(|segment| {
+                matches!(segment.flags(), SegmentFlags::Elf { p_flags } if p_flags & PF_X != 0)
+            })
+            .map(|segment| {
+                let start = segment.address() as usize;
+                (start, start + segment.size() as usize)
+            })
+            .collect();
         Ok(DwarfData {
-            files: gimli_wrapper::load_file(&object, endian)?,
-            addr2line: Context::new(&object).or_else(|e| Err(gimli_wrapper::Error::from(e)))?,
+            files,
+            types,
+            executable_ranges,
+            entry_point: object.entry() as usize,
+            call_frame_info: CallFrameInfo::load(&object, endian),
+            demangle: true,
+            addr2line: Context::new(&object).map_err(gimli_wrapper::Error::from)?,
         })
     }
 
-    #[allow(dead_code)]
+    fn file_matches(name: &str, file: &str) -> bool {
+        name == file || (!file.contains('/') && name.ends_with(&format!("/{}", file)))
+    }
+
     fn get_target_file(&self, file: &str) -> Option<&File> {
-        self.files.iter().find(|f| {
-            f.name == file || (!file.contains("/") && f.name.ends_with(&format!("/{}", file)))
-        })
+        self.files
+            .iter()
+            .find(|f| DwarfData::file_matches(&f.name, file))
     }
 
-    #[allow(dead_code)]
-    pub fn get_addr_for_line(&self, file: Option<&str>, line_number: usize) -> Option<usize> {
+    /// Returns the names of all compilation units matching `file`, either by full path or (if
+    /// `file` has no slashes) by basename. More than one result means the name is ambiguous.
+    pub fn get_matching_files(&self, file: &str) -> Vec<&str> {
+        self.files
+            .iter()
+            .filter(|f| DwarfData::file_matches(&f.name, file))
+            .map(|f| f.name.as_str())
+            .collect()
+    }
+
+    /// Returns the address of the first code for `line_number` in `file` (matched like
+    /// `get_matching_files`), or in the default file if None. A line without code snaps to the
+    /// closest line after it that has some.
+    pub fn lookup_line(
+        &self,
+        file: Option<&str>,
+        line_number: usize,
+    ) -> Result<usize, LineLookupError> {
         let target_file = match file {
-            Some(filename) => self.get_target_file(filename)?,
-            None => self.files.get(0)?,
+            Some(filename) => match self.get_matching_files(filename).as_slice() {
+                [] => return Err(LineLookupError::NoSuchFile),
+                [_] => self.get_target_file(filename).unwrap(),
+                names => {
+                    return Err(LineLookupError::AmbiguousFile(
+                        names.iter().map(|name| name.to_string()).collect(),
+                    ))
+                }
+            },
+            None => self.files.first().ok_or(LineLookupError::NoSuchFile)?,
         };
-        Some(
-            target_file
-                .lines
-                .iter()
-                .find(|line| line.number >= line_number)?
-                .address,
-        )
+        target_file
+            .lines
+            .iter()
+            .filter(|line| (line_number..=line_number + MAX_LINE_SNAP).contains(&line.number))
+            .min_by_key(|line| (line.number, line.address))
+            .map(|line| line.address)
+            .ok_or(LineLookupError::NoCode)
+    }
+
+    /// Returns the number of compilation units with line information.
+    pub fn num_source_files(&self) -> usize {
+        self.files.iter().filter(|f| !f.lines.is_empty()).count()
     }
 
-    #[allow(dead_code)]
     pub fn get_addr_for_function(&self, file: Option<&str>, func_name: &str) -> Option<usize> {
+        // Prototypes of functions defined elsewhere (or in libraries) have no code
+        let is_definition = |func: &&Function| func.matches(func_name) && func.text_length > 0;
         match file {
             Some(filename) => Some(
                 self.get_target_file(filename)?
                     .functions
                     .iter()
-                    .find(|func| func.name == func_name)?
+                    .find(is_definition)?
                     .address,
             ),
             None => {
                 for file in &self.files {
-                    if let Some(func) = file.functions.iter().find(|func| func.name == func_name) {
+                    if let Some(func) = file.functions.iter().find(is_definition) {
                         return Some(func.address);
                     }
                 }
@@ -87,7 +165,206 @@ impl DwarfData {
         }
     }
 
-    #[allow(dead_code)]
+    /// Returns the address of the first statement of the function starting at `func_addr`, where
+    /// its arguments and locals have been set up: the row flagged `prologue_end` if the compiler
+    /// emitted one (clang does), or else the first row for a line after the function's opening
+    /// line (as gcc lays it out). Falls back to `func_addr` if the line table says nothing.
+    pub fn skip_prologue(&self, func_addr: usize) -> usize {
+        let end = match self.get_function_containing(func_addr) {
+            Some(func) => func.address + func.text_length,
+            None => return func_addr,
+        };
+        let mut rows: Vec<&Line> = self
+            .files
+            .iter()
+            .flat_map(|file| file.lines.iter())
+            .filter(|line| (func_addr..end).contains(&line.address))
+            .collect();
+        rows.sort_by_key(|line| line.address);
+        if let Some(row) = rows.iter().find(|line| line.prologue_end) {
+            return row.address;
+        }
+        let opening_line = match rows.first() {
+            Some(first) if first.address == func_addr => first.number,
+            _ => return func_addr,
+        };
+        rows.iter()
+            .find(|line| line.address > func_addr && line.number != opening_line)
+            .map_or(func_addr, |line| line.address)
+    }
+
+    /// Returns the last source line of the function starting at `func_addr` (normally the line of
+    /// its closing brace): the highest line number in the line table rows for its code.
+    pub fn get_function_last_line(&self, func_addr: usize) -> Option<usize> {
+        let func = self.get_function_containing(func_addr)?;
+        self.files
+            .iter()
+            .flat_map(|file| file.lines.iter())
+            .filter(|line| (func.address..func.address + func.text_length).contains(&line.address))
+            .map(|line| line.number)
+            .max()
+    }
+
+    /// Returns the name of the compilation unit that line numbers without a file refer to.
+    pub fn get_default_file(&self) -> Option<&str> {
+        Some(self.files.first()?.name.as_str())
+    }
+
+    /// Returns the address ranges `[start, end)` of the code generated for line `line_number` of
+    /// `file`, in address order. A line may have several ranges (e.g. the parts of a `for`
+    /// header), or none at all if no code was generated for it.
+    pub fn get_line_ranges(&self, file: &str, line_number: usize) -> Vec<(usize, usize)> {
+        let lines = match self.get_target_file(file) {
+            Some(target_file) => &target_file.lines,
+            None => return Vec::new(),
+        };
+        let mut ranges: Vec<(usize, usize)> = Vec::new();
+        for (i, line) in lines.iter().enumerate() {
+            if line.number != line_number {
+                continue;
+            }
+            // The row's code runs up to the next row, or to the end of the function for the
+            // last row of a sequence
+            let end = match lines.get(i + 1) {
+                Some(next) if next.address > line.address => next.address,
+                _ => match self.get_function_containing(line.address) {
+                    Some(func) => func.address + func.text_length,
+                    None => continue,
+                },
+            };
+            if end <= line.address {
+                continue;
+            }
+            match ranges.last_mut() {
+                Some(last) if last.1 == line.address => last.1 = end,
+                _ => ranges.push((line.address, end)),
+            }
+        }
+        ranges
+    }
+
+    /// Returns the first line after `line_number` in `file` that has code, along with its
+    /// lowest address.
+    pub fn get_next_line_with_code(
+        &self,
+        file: &str,
+        line_number: usize,
+    ) -> Option<(usize, usize)> {
+        self.get_target_file(file)?
+            .lines
+            .iter()
+            .filter(|line| line.number > line_number)
+            .map(|line| (line.number, line.address))
+            .min()
+    }
+
+    /// Returns every function defined with debug info, sorted by address. (Declarations of
+    /// functions defined elsewhere, such as library functions, are left out.)
+    pub fn get_functions(&self) -> Vec<FunctionRecord> {
+        let mut functions: Vec<FunctionRecord> = self
+            .files
+            .iter()
+            .flat_map(|file| {
+                file.functions
+                    .iter()
+                    .filter(|func| func.text_length > 0)
+                    .map(|func| FunctionRecord {
+                        name: self.function_name(func),
+                        raw_name: func.linkage_name.clone().unwrap_or(func.name.clone()),
+                        address: func.address,
+                        file: file.name.clone(),
+                        line_number: func.line_number,
+                    })
+            })
+            .collect();
+        functions.sort_by_key(|func| func.address);
+        functions
+    }
+
+    /// Returns the names of the compilation units (source files) with debug info, in the order
+    /// they appear in it.
+    pub fn get_source_files(&self) -> Vec<&str> {
+        self.files.iter().map(|file| file.name.as_str()).collect()
+    }
+
+    /// Returns every global variable with debug info, sorted by address. Variables without an
+    /// address (e.g. optimized out) come last.
+    pub fn get_global_variables(&self) -> Vec<GlobalRecord> {
+        let mut globals: Vec<GlobalRecord> = self
+            .files
+            .iter()
+            .flat_map(|file| {
+                file.global_variables.iter().map(|var| GlobalRecord {
+                    name: var.name.clone(),
+                    type_name: var.entity_type.name.clone(),
+                    address: var.location.address(0, 0),
+                    file: file.name.clone(),
+                    line_number: var.line_number,
+                })
+            })
+            .collect();
+        globals.sort_by_key(|var| var.address.unwrap_or(usize::MAX));
+        globals
+    }
+
+    /// Returns true if `addr` lies in one of the target's executable segments.
+    pub fn is_executable_address(&self, addr: usize) -> bool {
+        self.executable_ranges
+            .iter()
+            .any(|&(start, end)| start <= addr && addr < end)
+    }
+
+    /// Returns the address execution starts at, from the ELF header.
+    pub fn get_entry_point(&self) -> usize {
+        self.entry_point
+    }
+
+    /// Returns the rules for unwinding out of a frame stopped at `addr`, or None if the call frame
+    /// information doesn't cover it.
+    pub fn get_frame_rules(&self, addr: usize) -> Option<FrameRules> {
+        self.call_frame_info.rules_for_address(addr)
+    }
+
+    /// Returns the type at the given .debug_info offset (as referenced by `TypeKind::Pointer`).
+    pub fn get_type(&self, offset: usize) -> Option<&Type> {
+        self.types.get(&offset)
+    }
+
+    /// Returns the function whose text contains `addr`, if any.
+    pub fn get_function_containing(&self, addr: usize) -> Option<&Function> {
+        self.files
+            .iter()
+            .flat_map(|file| file.functions.iter())
+            .find(|func| func.address <= addr && addr < func.address + func.text_length)
+    }
+
+    /// Returns the local variable or parameter called `name` in the function containing `addr`.
+    pub fn get_local_variable(&self, addr: usize, name: &str) -> Option<&Variable> {
+        self.get_function_containing(addr)?
+            .variables
+            .iter()
+            .find(|var| var.name == name)
+    }
+
+    /// Returns the formal parameters of the function containing `addr`, in declaration order.
+    pub fn get_function_parameters(&self, addr: usize) -> Option<Vec<&Variable>> {
+        Some(
+            self.get_function_containing(addr)?
+                .variables
+                .iter()
+                .filter(|var| var.is_parameter)
+                .collect(),
+        )
+    }
+
+    /// Returns the global (file-scope) variable called `name`, if any.
+    pub fn get_global_variable(&self, name: &str) -> Option<&Variable> {
+        self.files
+            .iter()
+            .flat_map(|file| file.global_variables.iter())
+            .find(|var| var.name == name)
+    }
+
     pub fn get_line_from_addr(&self, curr_addr: usize) -> Option<Line> {
         let location = self
             .addr2line
@@ -97,10 +374,10 @@ impl DwarfData {
             file: location.file?.to_string(),
             number: location.line?.try_into().unwrap(),
             address: curr_addr,
+            prologue_end: false,
         })
     }
 
-    #[allow(dead_code)]
     pub fn get_function_from_addr(&self, curr_addr: usize) -> Option<String> {
         let frame = self
             .addr2line
@@ -108,65 +385,104 @@ impl DwarfData {
             .ok()?
             .next()
             .ok()??;
-        Some(frame.function?.raw_name().ok()?.to_string())
-    }
-
-    #[allow(dead_code)]
-    pub fn print(&self) {
-        for file in &self.files {
-            println!("------");
-            println!("{}", file.name);
-            println!("------");
-
-            println!("Global variables:");
-            for var in &file.global_variables {
-                println!(
-                    "  * {} ({}, located at {}, declared at line {})",
-                    var.name, var.entity_type.name, var.location, var.line_number
-                );
-            }
-
-            println!("Functions:");
-            for func in &file.functions {
-                println!(
-                    "  * {} (declared on line {}, located at {:#x}, {} bytes long)",
-                    func.name, func.line_number, func.address, func.text_length
-                );
-                for var in &func.variables {
-                    println!(
-                        "    * Variable: {} ({}, located at {}, declared at line {})",
-                        var.name, var.entity_type.name, var.location, var.line_number
-                    );
-                }
-            }
+        Some(self.display_name(&frame.function?.raw_name().ok()?))
+    }
 
-            println!("Line numbers:");
-            for line in &file.lines {
-                println!("  * {} (at {:#x})", line.number, line.address);
+    /// Returns `name`, demangled unless demangling was turned off.
+    pub fn display_name(&self, name: &str) -> String {
+        if self.demangle {
+            if let Some(demangled) = demangle::demangle(name) {
+                return demangled;
             }
         }
+        name.to_string()
+    }
+
+    /// Returns the name of `func` to show the user: the demangled linkage name (which, unlike
+    /// DW_AT_name, includes namespaces), or the raw one if demangling is off.
+    pub fn function_name(&self, func: &Function) -> String {
+        self.display_name(func.linkage_name.as_deref().unwrap_or(&func.name))
+    }
+
+    /// Sets whether names are demangled (`set print demangle`).
+    pub fn set_demangle(&mut self, demangle: bool) {
+        self.demangle = demangle;
+    }
+
+    pub fn demangles(&self) -> bool {
+        self.demangle
     }
 }
 
+/// How the bits of a base type are to be interpreted (DW_AT_encoding)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Signed,
+    Unsigned,
+    SignedChar,
+    UnsignedChar,
+    Float,
+    Boolean,
+    Other,
+}
+
+#[derive(Debug, Clone, Default)]
+pub enum TypeKind {
+    Base(Encoding),
+    /// Pointer to the type at the given .debug_info offset, or None for `void *`
+    Pointer(Option<usize>),
+    /// Struct with the given members, in declaration order
+    Struct(Vec<Member>),
+    /// Array of elements of the given type, and their number if it is known
+    Array(Box<Type>, Option<usize>),
+    #[default]
+    Unknown,
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct Type {
     pub name: String,
     pub size: usize,
+    pub kind: TypeKind,
 }
 
 impl Type {
-    pub fn new(name: String, size: usize) -> Self {
-        Type {
-            name: name,
-            size: size,
-        }
+    pub fn new(name: String, size: usize, kind: TypeKind) -> Self {
+        Type { name, size, kind }
     }
 }
 
+/// A member of a struct: its name, its byte offset within the struct, and its type. Bitfields
+/// have a type of unknown kind covering the bytes they are stored in.
+#[derive(Debug, Clone)]
+pub struct Member {
+    pub name: String,
+    pub offset: usize,
+    pub entity_type: Type,
+}
+
 #[derive(Clone)]
 pub enum Location {
     Address(usize),
     FramePointerOffset(isize),
+    /// The variable has no location (it was optimized away)
+    OptimizedOut,
+    /// The variable's location expression is one we don't know how to evaluate
+    Unsupported,
+}
+
+impl Location {
+    /// Computes the address this location refers to in a frame whose DWARF frame base (the
+    /// canonical frame address, see `Frame`) is `frame_base`, in a process whose executable was
+    /// loaded `load_base` bytes above its link-time addresses. Returns None if the location isn't
+    /// in memory.
+    pub fn address(&self, frame_base: usize, load_base: usize) -> Option<usize> {
+        match *self {
+            Location::Address(addr) => Some(addr + load_base),
+            Location::FramePointerOffset(offset) => Some((frame_base as isize + offset) as usize),
+            Location::OptimizedOut | Location::Unsupported => None,
+        }
+    }
 }
 
 impl fmt::Display for Location {
@@ -174,6 +490,8 @@ impl fmt::Display for Location {
         match *self {
             Location::Address(addr) => write!(f, "Address({:#x})", addr),
             Location::FramePointerOffset(offset) => write!(f, "FramePointerOffset({})", offset),
+            Location::OptimizedOut => write!(f, "OptimizedOut"),
+            Location::Unsupported => write!(f, "Unsupported"),
         }
     }
 }
@@ -191,15 +509,58 @@ pub struct Variable {
     pub entity_type: Type,
     pub location: Location,
     pub line_number: usize, // Line number in source file
+    /// True for a function's formal parameters, false for local and global variables
+    pub is_parameter: bool,
 }
 
 #[derive(Debug, Default, Clone)]
 pub struct Function {
     pub name: String,
+    /// Mangled name (C++ and Rust), which unlike `name` includes namespaces
+    pub linkage_name: Option<String>,
     pub address: usize,
     pub text_length: usize,
     pub line_number: usize, // Line number in source file
     pub variables: Vec<Variable>,
+    /// Type of the return value, or None for void functions
+    pub return_type: Option<Type>,
+}
+
+impl Function {
+    /// Returns true if `query` names this function, by DW_AT_name or by its linkage name,
+    /// mangled or demangled.
+    pub fn matches(&self, query: &str) -> bool {
+        self.name == query
+            || self
+                .linkage_name
+                .as_deref()
+                .is_some_and(|linkage_name| demangle::matches(linkage_name, query))
+    }
+}
+
+/// Where a function is, as returned by `DwarfData::get_functions`
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionRecord {
+    /// Name as shown to the user (see `DwarfData::function_name`)
+    pub name: String,
+    /// Name in the symbol table, mangled for C++ and Rust
+    pub raw_name: String,
+    pub address: usize,
+    /// Compilation unit declaring the function
+    pub file: String,
+    pub line_number: usize,
+}
+
+/// Where a global variable is, as returned by `DwarfData::get_global_variables`
+#[derive(Debug, Clone, PartialEq)]
+pub struct GlobalRecord {
+    pub name: String,
+    pub type_name: String,
+    /// Link-time address, or None if the variable isn't in memory
+    pub address: Option<usize>,
+    /// Compilation unit defining the variable
+    pub file: String,
+    pub line_number: usize,
 }
 
 #[derive(Debug, Default, Clone)]
@@ -215,6 +576,8 @@ pub struct Line {
     pub file: String,
     pub number: usize,
     pub address: usize,
+    /// Whether the line table marks this row as the first one past the function's prologue
+    pub prologue_end: bool,
 }
 
 impl fmt::Display for Line {
@@ -222,3 +585,146 @@ impl fmt::Display for Line {
         write!(f, "{}:{}", self.file, self.number)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_get_functions() {
+        let dwarf_data = DwarfData::from_file("samples/function_calls")
+            .expect("Could not load samples/function_calls. Have you run make?");
+        let functions = dwarf_data.get_functions();
+        let names: Vec<&str> = functions.iter().map(|func| func.name.as_str()).collect();
+        // In order of address, which is the order of definition here
+        assert_eq!(names, vec!["func3", "func2", "func1", "main"]);
+        assert_eq!(
+            Some(functions[3].address),
+            dwarf_data.get_addr_for_function(None, "main")
+        );
+        assert!(functions[3].file.ends_with("samples/function_calls.c"));
+        assert_eq!(functions[3].line_number, 23);
+    }
+
+    #[test]
+    fn test_get_global_variables() {
+        let dwarf_data = DwarfData::from_file("samples/structs")
+            .expect("Could not load samples/structs. Have you run make?");
+        let globals = dwarf_data.get_global_variables();
+        let names: Vec<&str> = globals.iter().map(|var| var.name.as_str()).collect();
+        assert_eq!(names, vec!["global_rect", "no_node"]);
+        assert_eq!(globals[1].type_name, "struct Node *");
+        assert!(globals[0].address < globals[1].address);
+        assert_eq!(globals[0].line_number, 29);
+
+        let dwarf_data = DwarfData::from_file("samples/multifile")
+            .expect("Could not load samples/multifile. Have you run make?");
+        assert_eq!(
+            dwarf_data.get_source_files(),
+            vec!["samples/multifile.c", "samples/lib/worker.c"]
+        );
+    }
+
+    #[test]
+    fn test_demangled_functions() {
+        let mut dwarf_data = DwarfData::from_file("samples/namespaces")
+            .expect("Could not load samples/namespaces. Have you run make?");
+        let functions = dwarf_data.get_functions();
+        let names: Vec<&str> = functions.iter().map(|func| func.name.as_str()).collect();
+        assert_eq!(
+            names,
+            ["geometry::scale(int, int)", "geometry::scale(int)", "main"]
+        );
+        assert_eq!(functions[1].raw_name, "_ZN8geometry5scaleEi");
+        assert_eq!(functions[1].line_number, 9);
+
+        // Functions can be looked up by either name
+        let scale = Some(functions[1].address);
+        assert_eq!(
+            dwarf_data.get_addr_for_function(None, "geometry::scale(int)"),
+            scale
+        );
+        assert_eq!(
+            dwarf_data.get_addr_for_function(None, "_ZN8geometry5scaleEi"),
+            scale
+        );
+        assert_eq!(
+            dwarf_data
+                .get_function_from_addr(functions[1].address)
+                .as_deref(),
+            Some("geometry::scale(int)")
+        );
+        dwarf_data.set_demangle(false);
+        assert_eq!(
+            dwarf_data
+                .get_function_from_addr(functions[1].address)
+                .as_deref(),
+            Some("_ZN8geometry5scaleEi")
+        );
+        assert_eq!(dwarf_data.get_functions()[1].name, "_ZN8geometry5scaleEi");
+    }
+
+    #[test]
+    fn test_lookup_line() {
+        let dwarf_data = DwarfData::from_file("samples/multifile")
+            .expect("Could not load samples/multifile. Have you run make?");
+        assert_eq!(dwarf_data.num_source_files(), 2);
+        let work = dwarf_data.get_addr_for_function(None, "work").unwrap();
+        let addr = dwarf_data.lookup_line(Some("worker.c"), 1).unwrap();
+        assert_eq!(addr, work);
+        assert_eq!(
+            dwarf_data.lookup_line(Some("samples/lib/worker.c"), 1),
+            Ok(work)
+        );
+        assert_eq!(
+            dwarf_data.lookup_line(Some("lib/worker.c"), 1),
+            Err(LineLookupError::NoSuchFile)
+        );
+        assert_eq!(
+            dwarf_data.lookup_line(Some("worker.c"), 30),
+            Err(LineLookupError::NoCode)
+        );
+        // Without a file, lines are looked up in the first compilation unit
+        let main = dwarf_data.get_addr_for_function(None, "main").unwrap();
+        assert_eq!(dwarf_data.lookup_line(None, 1), Ok(main));
+    }
+
+    #[test]
+    fn test_get_line_ranges() {
+        let dwarf_data = DwarfData::from_file("samples/function_calls")
+            .expect("Could not load samples/function_calls. Have you run make?");
+        let file = dwarf_data.get_default_file().unwrap().to_string();
+        let ranges = dwarf_data.get_line_ranges(&file, 11);
+        assert_eq!(ranges.len(), 1);
+        let (start, end) = ranges[0];
+        assert_eq!(Ok(start), dwarf_data.lookup_line(None, 11));
+        assert_eq!(dwarf_data.get_line_from_addr(end).unwrap().number, 12);
+        // The last line of a function runs to the end of the function
+        let func2 = dwarf_data.get_addr_for_function(None, "func2").unwrap();
+        let func1 = dwarf_data.get_addr_for_function(None, "func1").unwrap();
+        assert_eq!(
+            dwarf_data.get_line_ranges(&file, 14).last().unwrap().1,
+            func1
+        );
+        assert!(func2 < func1);
+        // Blank lines have no code
+        assert!(dwarf_data.get_line_ranges(&file, 15).is_empty());
+        assert_eq!(
+            dwarf_data.get_next_line_with_code(&file, 15),
+            Some((16, func1))
+        );
+    }
+
+    #[test]
+    fn test_skip_prologue() {
+        let dwarf_data = DwarfData::from_file("samples/locals")
+            .expect("Could not load samples/locals. Have you run make?");
+        let weigh = dwarf_data.get_addr_for_function(None, "weigh").unwrap();
+        let body = dwarf_data.skip_prologue(weigh);
+        assert!(body > weigh);
+        assert_eq!(dwarf_data.get_line_from_addr(weigh).unwrap().number, 3);
+        assert_eq!(dwarf_data.get_line_from_addr(body).unwrap().number, 4);
+        // Addresses outside any function are left alone
+        assert_eq!(dwarf_data.skip_prologue(0x10), 0x10);
+    }
+}
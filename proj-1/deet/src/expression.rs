@@ -0,0 +1,443 @@
+//! Integer expressions for `print`, `x` and breakpoint conditions, e.g. `n * 2 + 1`, `*p`,
+//! `buf[i]` or `$rsp + 0x10`. Operands are decimal or `0x`-prefixed hexadecimal literals,
+//! variables and registers; the operators are subscripts, unary `*`, `&` and `-`, and binary
+//! `* / + - << >> < > <= >= == != & |` with C's precedence. Comparisons are 1 when true and 0
+//! when false. Every value is a 64-bit integer; dereferencing reads the pointee's type, or a
+//! 64-bit word through a pointer without a type. gdb's `p@n` (n elements starting at p) binds
+//! loosest of all, but only `print` can show its value.
+
+use crate::condition;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Literal(i64),
+    Variable(String),
+    Register(String),
+    Deref(Box<Expr>),
+    /// `base[index]`, where base is an array or a pointer
+    Index(Box<Expr>, Box<Expr>),
+    /// `base@count`: an artificial array of `count` elements
+    Repeat(Box<Expr>, Box<Expr>),
+    AddressOf(String),
+    Negate(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOp {
+    Mul,
+    Div,
+    Add,
+    Sub,
+    Shl,
+    Shr,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+    Eq,
+    Ne,
+    And,
+    Or,
+}
+
+/// Where an expression gets the values of its operands from.
+pub trait Context {
+    /// Returns the value of the variable `name`.
+    fn variable(&self, name: &str) -> Result<i64, String>;
+    /// Returns the address of the variable `name`.
+    fn address_of(&self, name: &str) -> Result<i64, String>;
+    /// Returns the value of register `name` (without the `$`).
+    fn register(&self, name: &str) -> Result<i64, String>;
+    /// Reads the 64-bit word at `addr`.
+    fn read_word(&self, addr: i64) -> Result<i64, String>;
+    /// Returns the value of element `index` of the array or pointer `base` (`*base` is element
+    /// 0). A base without a type, such as a register, points to 64-bit words.
+    fn element(&self, base: &Expr, index: i64) -> Result<i64, String>;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(i64),
+    Identifier(String),
+    Register(String),
+    Operator(&'static str),
+}
+
+fn tokenize(expression: &str) -> Result<Vec<Token>, String> {
+    // Two-character operators must be checked before their one-character prefixes
+    const OPERATORS: &[&str] = &[
+        "<<", ">>", "<=", ">=", "==", "!=", "<", ">", "*", "/", "+", "-", "&", "|", "(", ")", "[",
+        "]", "@",
+    ];
+    let mut tokens = Vec::new();
+    let mut rest = expression.trim_start();
+    while !rest.is_empty() {
+        let word_len = rest
+            .find(|c: char| !c.is_alphanumeric() && c != '_')
+            .unwrap_or(rest.len());
+        if let Some(&op) = OPERATORS.iter().find(|&&op| rest.starts_with(op)) {
+            tokens.push(Token::Operator(op));
+            rest = &rest[op.len()..];
+        } else if let Some(register) = rest.strip_prefix('$') {
+            let len = register
+                .find(|c: char| !c.is_alphanumeric())
+                .unwrap_or(register.len());
+            if len == 0 {
+                return Err("Missing register name after '$'.".to_string());
+            }
+            tokens.push(Token::Register(register[..len].to_string()));
+            rest = &register[len..];
+        } else if rest.starts_with(|c: char| c.is_ascii_digit()) {
+            let word = &rest[..word_len];
+            let value = condition::parse_literal(word)
+                .ok_or_else(|| format!("Invalid number \"{}\".", word))?;
+            tokens.push(Token::Number(value));
+            rest = &rest[word_len..];
+        } else if word_len > 0 {
+            tokens.push(Token::Identifier(rest[..word_len].to_string()));
+            rest = &rest[word_len..];
+        } else {
+            return Err(format!(
+                "Invalid character '{}' in expression.",
+                rest.chars().next().unwrap()
+            ));
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+/// A recursive-descent parser with one function per precedence level, lowest first.
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek_operator(&self) -> Option<&'static str> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Operator(op)) => Some(op),
+            _ => None,
+        }
+    }
+
+    /// Parses a left-associative chain of the given operators, with operands parsed by `operand`.
+    fn binary(
+        &mut self,
+        operators: &[(&str, BinaryOp)],
+        operand: fn(&mut Parser) -> Result<Expr, String>,
+    ) -> Result<Expr, String> {
+        let mut lhs = operand(self)?;
+        while let Some(&(_, op)) = self
+            .peek_operator()
+            .and_then(|token| operators.iter().find(|(name, _)| *name == token))
+        {
+            self.pos += 1;
+            let rhs = operand(self)?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn repeat(&mut self) -> Result<Expr, String> {
+        let base = self.or()?;
+        if self.peek_operator() != Some("@") {
+            return Ok(base);
+        }
+        self.pos += 1;
+        Ok(Expr::Repeat(Box::new(base), Box::new(self.or()?)))
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        self.binary(&[("|", BinaryOp::Or)], Parser::and)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        self.binary(&[("&", BinaryOp::And)], Parser::equality)
+    }
+
+    fn equality(&mut self) -> Result<Expr, String> {
+        self.binary(
+            &[("==", BinaryOp::Eq), ("!=", BinaryOp::Ne)],
+            Parser::relational,
+        )
+    }
+
+    fn relational(&mut self) -> Result<Expr, String> {
+        self.binary(
+            &[
+                ("<", BinaryOp::Lt),
+                (">", BinaryOp::Gt),
+                ("<=", BinaryOp::Le),
+                (">=", BinaryOp::Ge),
+            ],
+            Parser::shift,
+        )
+    }
+
+    fn shift(&mut self) -> Result<Expr, String> {
+        self.binary(
+            &[("<<", BinaryOp::Shl), (">>", BinaryOp::Shr)],
+            Parser::additive,
+        )
+    }
+
+    fn additive(&mut self) -> Result<Expr, String> {
+        self.binary(
+            &[("+", BinaryOp::Add), ("-", BinaryOp::Sub)],
+            Parser::multiplicative,
+        )
+    }
+
+    fn multiplicative(&mut self) -> Result<Expr, String> {
+        self.binary(&[("*", BinaryOp::Mul), ("/", BinaryOp::Div)], Parser::unary)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        match self.peek_operator() {
+            Some("*") => {
+                self.pos += 1;
+                Ok(Expr::Deref(Box::new(self.unary()?)))
+            }
+            Some("-") => {
+                self.pos += 1;
+                Ok(Expr::Negate(Box::new(self.unary()?)))
+            }
+            Some("&") => {
+                self.pos += 1;
+                match self.tokens.get(self.pos) {
+                    Some(Token::Identifier(name)) => {
+                        self.pos += 1;
+                        Ok(Expr::AddressOf(name.clone()))
+                    }
+                    _ => Err("Can only take the address of a variable.".to_string()),
+                }
+            }
+            _ => self.postfix(),
+        }
+    }
+
+    fn postfix(&mut self) -> Result<Expr, String> {
+        let mut expr = self.primary()?;
+        while self.peek_operator() == Some("[") {
+            self.pos += 1;
+            let index = self.or()?;
+            if self.peek_operator() != Some("]") {
+                return Err("Missing ']'.".to_string());
+            }
+            self.pos += 1;
+            expr = Expr::Index(Box::new(expr), Box::new(index));
+        }
+        Ok(expr)
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .cloned()
+            .ok_or("Unexpected end of expression.")?;
+        self.pos += 1;
+        match token {
+            Token::Number(value) => Ok(Expr::Literal(value)),
+            Token::Identifier(name) => Ok(Expr::Variable(name)),
+            Token::Register(name) => Ok(Expr::Register(name)),
+            Token::Operator("(") => {
+                let expr = self.or()?;
+                match self.peek_operator() {
+                    Some(")") => {
+                        self.pos += 1;
+                        Ok(expr)
+                    }
+                    _ => Err("Missing ')'.".to_string()),
+                }
+            }
+            Token::Operator(op) => Err(format!("Unexpected '{}' in expression.", op)),
+        }
+    }
+}
+
+impl Expr {
+    pub fn parse(expression: &str) -> Result<Expr, String> {
+        let mut parser = Parser {
+            tokens: tokenize(expression)?,
+            pos: 0,
+        };
+        let expr = parser.repeat()?;
+        match parser.tokens.get(parser.pos) {
+            None => Ok(expr),
+            Some(Token::Operator(op)) => Err(format!("Unexpected '{}' in expression.", op)),
+            Some(_) => Err("Missing operator in expression.".to_string()),
+        }
+    }
+
+    /// Computes the value of the expression, reading operands through `context`.
+    pub fn evaluate(&self, context: &dyn Context) -> Result<i64, String> {
+        match self {
+            Expr::Literal(value) => Ok(*value),
+            Expr::Variable(name) => context.variable(name),
+            Expr::Register(name) => context.register(name),
+            Expr::Deref(expr) => context.element(expr, 0),
+            Expr::Index(base, index) => context.element(base, index.evaluate(context)?),
+            Expr::Repeat(..) => Err("Cannot use an artificial array as an integer.".to_string()),
+            Expr::AddressOf(name) => context.address_of(name),
+            Expr::Negate(expr) => Ok(expr.evaluate(context)?.wrapping_neg()),
+            Expr::Binary(op, lhs, rhs) => {
+                let (lhs, rhs) = (lhs.evaluate(context)?, rhs.evaluate(context)?);
+                let shift = || {
+                    u32::try_from(rhs)
+                        .ok()
+                        .filter(|&amount| amount < 64)
+                        .ok_or_else(|| format!("Shift amount {} is out of range.", rhs))
+                };
+                Ok(match op {
+                    BinaryOp::Mul => lhs.wrapping_mul(rhs),
+                    BinaryOp::Div if rhs == 0 => return Err("Division by zero.".to_string()),
+                    BinaryOp::Div => lhs.wrapping_div(rhs),
+                    BinaryOp::Add => lhs.wrapping_add(rhs),
+                    BinaryOp::Sub => lhs.wrapping_sub(rhs),
+                    BinaryOp::Shl => lhs << shift()?,
+                    BinaryOp::Shr => lhs >> shift()?,
+                    BinaryOp::Lt => (lhs < rhs) as i64,
+                    BinaryOp::Gt => (lhs > rhs) as i64,
+                    BinaryOp::Le => (lhs <= rhs) as i64,
+                    BinaryOp::Ge => (lhs >= rhs) as i64,
+                    BinaryOp::Eq => (lhs == rhs) as i64,
+                    BinaryOp::Ne => (lhs != rhs) as i64,
+                    BinaryOp::And => lhs & rhs,
+                    BinaryOp::Or => lhs | rhs,
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A process with `n` = 20 stored at 0x1000, `p` = 0x1000 at 0x1008, and %rsp = 0x2000. Its
+    /// only type is the 64-bit word.
+    struct FakeProcess;
+
+    impl Context for FakeProcess {
+        fn variable(&self, name: &str) -> Result<i64, String> {
+            self.read_word(self.address_of(name)?)
+        }
+
+        fn address_of(&self, name: &str) -> Result<i64, String> {
+            match name {
+                "n" => Ok(0x1000),
+                "p" => Ok(0x1008),
+                _ => Err(format!("No symbol \"{}\" in current context.", name)),
+            }
+        }
+
+        fn register(&self, name: &str) -> Result<i64, String> {
+            match name {
+                "rsp" => Ok(0x2000),
+                _ => Err(format!("Unknown register ${}", name)),
+            }
+        }
+
+        fn read_word(&self, addr: i64) -> Result<i64, String> {
+            match addr {
+                0x1000 => Ok(20),
+                0x1008 => Ok(0x1000),
+                _ => Err(format!("Cannot access memory at address {:#x}", addr)),
+            }
+        }
+
+        fn element(&self, base: &Expr, index: i64) -> Result<i64, String> {
+            self.read_word(base.evaluate(self)? + index * 8)
+        }
+    }
+
+    fn eval(expression: &str) -> Result<i64, String> {
+        Expr::parse(expression)?.evaluate(&FakeProcess)
+    }
+
+    #[test]
+    fn test_parse_precedence() {
+        use Expr::*;
+        assert_eq!(
+            Expr::parse("n * 2 + 1").unwrap(),
+            Binary(
+                BinaryOp::Add,
+                Box::new(Binary(
+                    BinaryOp::Mul,
+                    Box::new(Variable("n".to_string())),
+                    Box::new(Literal(2))
+                )),
+                Box::new(Literal(1))
+            )
+        );
+        assert_eq!(
+            Expr::parse("*p").unwrap(),
+            Deref(Box::new(Variable("p".to_string())))
+        );
+        assert_eq!(
+            Expr::parse("*p[1]@n + 1").unwrap(),
+            Repeat(
+                Box::new(Deref(Box::new(Index(
+                    Box::new(Variable("p".to_string())),
+                    Box::new(Literal(1))
+                )))),
+                Box::new(Binary(
+                    BinaryOp::Add,
+                    Box::new(Variable("n".to_string())),
+                    Box::new(Literal(1))
+                ))
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(Expr::parse("").is_err());
+        assert!(Expr::parse("n +").is_err());
+        assert!(Expr::parse("(n + 1").is_err());
+        assert!(Expr::parse("n 1").is_err());
+        assert!(Expr::parse("&1").is_err());
+        assert!(Expr::parse("n % 2").is_err());
+        assert!(Expr::parse("0xzz").is_err());
+        assert!(Expr::parse("p[1").is_err());
+        assert!(Expr::parse("p[]").is_err());
+        assert!(Expr::parse("p@").is_err());
+        assert!(Expr::parse("n = 1").is_err());
+        assert!(Expr::parse("n == ").is_err());
+    }
+
+    #[test]
+    fn test_evaluate() {
+        assert_eq!(eval("n * 2 + 1"), Ok(41));
+        assert_eq!(eval("(n + 1) * 2"), Ok(42));
+        assert_eq!(eval("n - 30"), Ok(-10));
+        assert_eq!(eval("-n / 3"), Ok(-6));
+        assert_eq!(eval("*p"), Ok(20));
+        assert_eq!(eval("**&p"), Ok(20));
+        assert_eq!(eval("&n"), Ok(0x1000));
+        assert_eq!(eval("$rsp + 0x10"), Ok(0x2010));
+        assert_eq!(eval("1 << 4 | 0xf0 & 0x3c"), Ok(0x30));
+        assert_eq!(eval("n >> 2"), Ok(5));
+        assert_eq!(eval("p[0] + 1"), Ok(21));
+        assert_eq!(eval("(&n)[1]"), Ok(0x1000));
+        assert_eq!(eval("n * 2 == 40"), Ok(1));
+        assert_eq!(eval("*p != 20"), Ok(0));
+        assert_eq!(eval("n < 21 == n >= 20"), Ok(1));
+        assert_eq!(eval("n > 20 | n <= -1"), Ok(0));
+        assert_eq!(eval("1 << 2 < 5"), Ok(1));
+    }
+
+    #[test]
+    fn test_evaluate_errors() {
+        assert_eq!(eval("n / (n - 20)"), Err("Division by zero.".to_string()));
+        assert!(eval("*0").unwrap_err().contains("Cannot access memory"));
+        assert!(eval("1 << 64").is_err());
+        assert!(eval("1 << -1").is_err());
+        assert!(eval("m + 1").unwrap_err().contains("\"m\""));
+        assert!(eval("$foo").is_err());
+        assert!(eval("p@2").is_err());
+    }
+}
@@ -5,18 +5,22 @@
 //!
 //! This code is a huge mess. Please don't read it unless you're trying to do an extension :)
 
-use gimli;
 use gimli::{UnitOffset, UnitSectionOffset};
 use object::{Object, ObjectSection};
 use std::borrow;
 //use std::io::{BufWriter, Write};
-use crate::dwarf_data::{File, Function, Line, Location, Type, Variable};
+use crate::dwarf_data::{
+    Encoding, File, Function, Line, Location, Member, Type, TypeKind, Variable,
+};
 use std::collections::HashMap;
 use std::convert::TryInto;
 use std::fmt::Write;
 use std::{io, path};
 
-pub fn load_file(object: &object::File, endian: gimli::RunTimeEndian) -> Result<Vec<File>, Error> {
+pub fn load_file(
+    object: &object::File,
+    endian: gimli::RunTimeEndian,
+) -> Result<(Vec<File>, HashMap<usize, Type>), Error> {
     // Load a section and return as `Cow<[u8]>`.
     let load_section = |id: gimli::SectionId| -> Result<borrow::Cow<[u8]>, gimli::Error> {
         match object.section_by_name(id.name()) {
@@ -37,13 +41,14 @@ pub fn load_file(object: &object::File, endian: gimli::RunTimeEndian) -> Result<
     let borrow_section: &dyn for<'a> Fn(
         &'a borrow::Cow<[u8]>,
     ) -> gimli::EndianSlice<'a, gimli::RunTimeEndian> =
-        &|section| gimli::EndianSlice::new(&*section, endian);
+        &|section| gimli::EndianSlice::new(section, endian);
 
     // Create `EndianSlice`s for all of the sections.
     let dwarf = dwarf_cow.borrow(&borrow_section);
 
-    // Define a mapping from type offsets to type structs
-    let mut offset_to_type: HashMap<usize, Type> = HashMap::new();
+    // Define a mapping from type offsets to type structs. Types are collected in a separate pass
+    // because variables may refer to types defined later in the unit.
+    let offset_to_type = load_types(&dwarf)?;
 
     let mut compilation_units: Vec<File> = Vec::new();
 
@@ -77,33 +82,6 @@ pub fn load_file(object: &object::File, endian: gimli::RunTimeEndian) -> Result<
                         lines: Vec::new(),
                     });
                 }
-                gimli::DW_TAG_base_type => {
-                    let name = if let Ok(Some(attr)) = entry.attr(gimli::DW_AT_name) {
-                        if let Ok(DebugValue::Str(name)) = get_attr_value(&attr, &unit, &dwarf) {
-                            name
-                        } else {
-                            "<unknown>".to_string()
-                        }
-                    } else {
-                        "<unknown>".to_string()
-                    };
-                    let byte_size = if let Ok(Some(attr)) = entry.attr(gimli::DW_AT_byte_size) {
-                        if let Ok(DebugValue::Uint(byte_size)) =
-                            get_attr_value(&attr, &unit, &dwarf)
-                        {
-                            byte_size
-                        } else {
-                            // TODO: report error?
-                            0
-                        }
-                    } else {
-                        // TODO: report error?
-                        0
-                    };
-                    let type_offset = entry.offset().0;
-                    offset_to_type
-                        .insert(type_offset, Type::new(name, byte_size.try_into().unwrap()));
-                }
                 gimli::DW_TAG_subprogram => {
                     let mut func: Function = Default::default();
                     let mut attrs = entry.attrs();
@@ -116,6 +94,11 @@ pub fn load_file(object: &object::File, endian: gimli::RunTimeEndian) -> Result<
                                     func.name = name;
                                 }
                             }
+                            gimli::DW_AT_linkage_name | gimli::DW_AT_MIPS_linkage_name => {
+                                if let Ok(DebugValue::Str(name)) = val {
+                                    func.linkage_name = Some(name);
+                                }
+                            }
                             gimli::DW_AT_high_pc => {
                                 if let Ok(DebugValue::Uint(high_pc)) = val {
                                     func.text_length = high_pc.try_into().unwrap();
@@ -132,15 +115,50 @@ pub fn load_file(object: &object::File, endian: gimli::RunTimeEndian) -> Result<
                                     func.line_number = line_number.try_into().unwrap();
                                 }
                             }
+                            gimli::DW_AT_type => {
+                                if let Ok(DebugValue::Size(offset)) = val {
+                                    func.return_type = offset_to_type.get(&offset).cloned();
+                                }
+                            }
                             _ => {}
                         }
                     }
+                    // The definition of a function declared in a C++ namespace or class refers to
+                    // the declaration, which has the names
+                    if let Ok(Some(gimli::AttributeValue::UnitRef(offset))) =
+                        entry.attr_value(gimli::DW_AT_specification)
+                    {
+                        if let Ok(declaration) = unit.entry(offset) {
+                            if func.name.is_empty() {
+                                func.name =
+                                    get_name(&declaration, &unit, &dwarf).unwrap_or_default();
+                            }
+                            if func.linkage_name.is_none() {
+                                func.linkage_name = get_linkage_name(&declaration, &unit, &dwarf);
+                            }
+                            if func.return_type.is_none() {
+                                func.return_type = get_type_offset(&declaration, &unit)
+                                    .and_then(|offset| offset_to_type.get(&offset).cloned());
+                            }
+                            if func.line_number == 0 {
+                                if let Some(Ok(DebugValue::Uint(line_number))) = declaration
+                                    .attr(gimli::DW_AT_decl_line)
+                                    .ok()
+                                    .flatten()
+                                    .map(|attr| get_attr_value(&attr, &unit, &dwarf))
+                                {
+                                    func.line_number = line_number as usize;
+                                }
+                            }
+                        }
+                    }
                     compilation_units.last_mut().unwrap().functions.push(func);
                 }
                 gimli::DW_TAG_formal_parameter | gimli::DW_TAG_variable => {
                     let mut name = String::new();
                     let mut entity_type: Option<Type> = None;
                     let mut location: Option<Location> = None;
+                    let mut is_declaration = false;
                     let mut line_number = 0;
                     let mut attrs = entry.attrs();
                     while let Some(attr) = attrs.next()? {
@@ -154,15 +172,18 @@ pub fn load_file(object: &object::File, endian: gimli::RunTimeEndian) -> Result<
                             }
                             gimli::DW_AT_type => {
                                 if let Ok(DebugValue::Size(offset)) = val {
-                                    if let Some(dtype) = offset_to_type.get(&offset).clone() {
+                                    if let Some(dtype) = offset_to_type.get(&offset) {
                                         entity_type = Some(dtype.clone());
                                     }
                                 }
                             }
                             gimli::DW_AT_location => {
-                                if let Some(loc) = get_location(&attr, &unit) {
-                                    location = Some(loc);
-                                }
+                                location = Some(
+                                    get_location(&attr, &unit).unwrap_or(Location::Unsupported),
+                                );
+                            }
+                            gimli::DW_AT_declaration => {
+                                is_declaration = true;
                             }
                             gimli::DW_AT_decl_line => {
                                 if let Ok(DebugValue::Uint(num)) = val {
@@ -172,12 +193,18 @@ pub fn load_file(object: &object::File, endian: gimli::RunTimeEndian) -> Result<
                             _ => {}
                         }
                     }
-                    if entity_type.is_some() && location.is_some() {
+                    // Named variables without a location were optimized out. (Declarations, e.g.
+                    // of extern globals, have no location either but aren't real variables.)
+                    if location.is_none() && !name.is_empty() && !is_declaration {
+                        location = Some(Location::OptimizedOut);
+                    }
+                    if let (Some(entity_type), Some(location)) = (entity_type, location) {
                         let var = Variable {
                             name,
-                            entity_type: entity_type.unwrap(),
-                            location: location.unwrap(),
+                            entity_type,
+                            location,
                             line_number: line_number.try_into().unwrap(),
+                            is_parameter: entry.tag() == gimli::DW_TAG_formal_parameter,
                         };
                         if depth == 1 {
                             compilation_units
@@ -241,24 +268,373 @@ pub fn load_file(object: &object::File, endian: gimli::RunTimeEndian) -> Result<
                             file: file.name.clone(),
                             number: line.try_into().unwrap(),
                             address: row.address().try_into().unwrap(),
+                            prologue_end: row.prologue_end(),
                         });
                     }
                 }
             }
         }
     }
-    Ok(compilation_units)
+    Ok((compilation_units, offset_to_type))
+}
+
+/// Type information as it appears in the DWARF, before references to other types are resolved
+enum RawType {
+    Base {
+        name: String,
+        size: usize,
+        encoding: Encoding,
+    },
+    Pointer {
+        size: usize,
+        target: Option<usize>,
+    },
+    Struct {
+        name: String,
+        size: usize,
+        members: Vec<RawMember>,
+    },
+    /// Arrays of elements of type `target`, with the number of elements in each dimension
+    /// (outermost first) where it is known
+    Array {
+        target: Option<usize>,
+        dimensions: Vec<Option<usize>>,
+    },
+    /// Unions and enums, which are only known by name for now
+    Named {
+        name: String,
+        size: usize,
+        kind: TypeKind,
+    },
+    /// Typedefs and cv-qualified types: a renamed view of another type
+    Alias {
+        name: Option<String>,
+        qualifier: Option<&'static str>,
+        target: Option<usize>,
+    },
+}
+
+/// A struct member as it appears in the DWARF, referring to its type by offset
+struct RawMember {
+    name: String,
+    offset: usize,
+    target: Option<usize>,
+    /// Bitfields are only located to the byte for now
+    bit_field: bool,
+}
+
+// Guards against cyclic type references in malformed DWARF
+const MAX_TYPE_DEPTH: usize = 32;
+
+fn load_types<R: Reader>(dwarf: &gimli::Dwarf<R>) -> Result<HashMap<usize, Type>, Error> {
+    let mut raw_types: HashMap<usize, RawType> = HashMap::new();
+    let mut iter = dwarf.units();
+    while let Some(header) = iter.next()? {
+        let unit = dwarf.unit(header)?;
+        let mut entries = unit.entries();
+        // Structs and arrays enclosing the current entry, with their depth in the tree
+        let mut depth = 0;
+        let mut parents: Vec<(isize, usize)> = Vec::new();
+        while let Some((delta_depth, entry)) = entries.next_dfs()? {
+            depth += delta_depth;
+            while parents
+                .last()
+                .is_some_and(|&(parent_depth, _)| parent_depth >= depth)
+            {
+                parents.pop();
+            }
+            let name = get_name(entry, &unit, dwarf);
+            let size = get_byte_size(entry, &unit, dwarf);
+            let target = get_type_offset(entry, &unit);
+            if let gimli::DW_TAG_member | gimli::DW_TAG_subrange_type = entry.tag() {
+                let parent = parents
+                    .last()
+                    .filter(|&&(parent_depth, _)| parent_depth == depth - 1)
+                    .and_then(|&(_, parent_offset)| raw_types.get_mut(&parent_offset));
+                match (entry.tag(), parent) {
+                    (gimli::DW_TAG_member, Some(RawType::Struct { members, .. })) => {
+                        members.push(get_member(entry, name, target));
+                    }
+                    (gimli::DW_TAG_subrange_type, Some(RawType::Array { dimensions, .. })) => {
+                        dimensions.push(get_element_count(entry));
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+            let raw_type = match entry.tag() {
+                gimli::DW_TAG_base_type => RawType::Base {
+                    name: name.unwrap_or_else(|| "<unknown>".to_string()),
+                    size: size.unwrap_or(0),
+                    encoding: get_encoding(entry),
+                },
+                gimli::DW_TAG_pointer_type => RawType::Pointer {
+                    size: size.unwrap_or(std::mem::size_of::<usize>()),
+                    target,
+                },
+                gimli::DW_TAG_array_type => {
+                    parents.push((depth, section_offset(entry.offset(), &unit)));
+                    RawType::Array {
+                        target,
+                        dimensions: Vec::new(),
+                    }
+                }
+                gimli::DW_TAG_structure_type => {
+                    parents.push((depth, section_offset(entry.offset(), &unit)));
+                    RawType::Struct {
+                        name: format!(
+                            "struct {}",
+                            name.unwrap_or_else(|| "<anonymous>".to_string())
+                        ),
+                        size: size.unwrap_or(0),
+                        members: Vec::new(),
+                    }
+                }
+                gimli::DW_TAG_union_type | gimli::DW_TAG_enumeration_type => {
+                    let (keyword, kind) = match entry.tag() {
+                        gimli::DW_TAG_union_type => ("union", TypeKind::Unknown),
+                        _ => ("enum", TypeKind::Base(Encoding::Signed)),
+                    };
+                    RawType::Named {
+                        name: format!(
+                            "{} {}",
+                            keyword,
+                            name.unwrap_or_else(|| "<anonymous>".to_string())
+                        ),
+                        size: size.unwrap_or(0),
+                        kind,
+                    }
+                }
+                gimli::DW_TAG_typedef => RawType::Alias {
+                    name,
+                    qualifier: None,
+                    target,
+                },
+                gimli::DW_TAG_const_type => RawType::Alias {
+                    name: None,
+                    qualifier: Some("const"),
+                    target,
+                },
+                gimli::DW_TAG_volatile_type => RawType::Alias {
+                    name: None,
+                    qualifier: Some("volatile"),
+                    target,
+                },
+                _ => continue,
+            };
+            raw_types.insert(section_offset(entry.offset(), &unit), raw_type);
+        }
+    }
+
+    Ok(raw_types
+        .keys()
+        .filter_map(|&offset| Some((offset, resolve_type(offset, &raw_types, 0)?)))
+        .collect())
+}
+
+fn resolve_type(offset: usize, raw_types: &HashMap<usize, RawType>, depth: usize) -> Option<Type> {
+    if depth > MAX_TYPE_DEPTH {
+        return None;
+    }
+    Some(match raw_types.get(&offset)? {
+        RawType::Base {
+            name,
+            size,
+            encoding,
+        } => Type::new(name.clone(), *size, TypeKind::Base(*encoding)),
+        RawType::Pointer { size, target } => {
+            let pointee_name = match target {
+                Some(target) => match resolve_type(*target, raw_types, depth + 1) {
+                    Some(pointee) => pointee.name,
+                    None => "<unknown>".to_string(),
+                },
+                None => "void".to_string(),
+            };
+            Type::new(
+                format!("{} *", pointee_name),
+                *size,
+                TypeKind::Pointer(*target),
+            )
+        }
+        RawType::Struct {
+            name,
+            size,
+            members,
+        } => {
+            let members = members
+                .iter()
+                .map(|member| {
+                    let mut entity_type = member
+                        .target
+                        .and_then(|target| resolve_type(target, raw_types, depth + 1))
+                        .unwrap_or_else(|| {
+                            Type::new("<unknown>".to_string(), 0, TypeKind::Unknown)
+                        });
+                    if member.bit_field {
+                        entity_type.kind = TypeKind::Unknown;
+                    }
+                    Member {
+                        name: member.name.clone(),
+                        offset: member.offset,
+                        entity_type,
+                    }
+                })
+                .collect();
+            Type::new(name.clone(), *size, TypeKind::Struct(members))
+        }
+        RawType::Array { target, dimensions } => {
+            let element = resolve_type((*target)?, raw_types, depth + 1)?;
+            // An array of arrays for every dimension but the last
+            let mut array = element.clone();
+            for (i, &count) in dimensions.iter().enumerate().rev() {
+                let bounds: String = dimensions[i..]
+                    .iter()
+                    .map(|count| match count {
+                        Some(count) => format!("[{}]", count),
+                        None => "[]".to_string(),
+                    })
+                    .collect();
+                array = Type::new(
+                    format!("{} {}", element.name, bounds),
+                    array.size * count.unwrap_or(0),
+                    TypeKind::Array(Box::new(array), count),
+                );
+            }
+            array
+        }
+        RawType::Named { name, size, kind } => Type::new(name.clone(), *size, kind.clone()),
+        RawType::Alias {
+            name,
+            qualifier,
+            target,
+        } => {
+            let mut aliased = match target {
+                Some(target) => resolve_type(*target, raw_types, depth + 1)?,
+                None => Type::new("void".to_string(), 0, TypeKind::Unknown),
+            };
+            if let Some(name) = name {
+                aliased.name = name.clone();
+            } else if let Some(qualifier) = qualifier {
+                aliased.name = format!("{} {}", qualifier, aliased.name);
+            }
+            aliased
+        }
+    })
+}
+
+fn section_offset<R: Reader>(offset: UnitOffset, unit: &gimli::Unit<R>) -> usize {
+    match offset.to_unit_section_offset(unit) {
+        UnitSectionOffset::DebugInfoOffset(goff) => goff.0,
+        UnitSectionOffset::DebugTypesOffset(goff) => goff.0,
+    }
+}
+
+fn get_name<R: Reader>(
+    entry: &gimli::DebuggingInformationEntry<R>,
+    unit: &gimli::Unit<R>,
+    dwarf: &gimli::Dwarf<R>,
+) -> Option<String> {
+    match get_attr_value(&entry.attr(gimli::DW_AT_name).ok()??, unit, dwarf) {
+        Ok(DebugValue::Str(name)) => Some(name),
+        _ => None,
+    }
+}
+
+fn get_linkage_name<R: Reader>(
+    entry: &gimli::DebuggingInformationEntry<R>,
+    unit: &gimli::Unit<R>,
+    dwarf: &gimli::Dwarf<R>,
+) -> Option<String> {
+    let attr = entry
+        .attr(gimli::DW_AT_linkage_name)
+        .ok()?
+        .or(entry.attr(gimli::DW_AT_MIPS_linkage_name).ok()?)?;
+    match get_attr_value(&attr, unit, dwarf) {
+        Ok(DebugValue::Str(name)) => Some(name),
+        _ => None,
+    }
+}
+
+fn get_byte_size<R: Reader>(
+    entry: &gimli::DebuggingInformationEntry<R>,
+    unit: &gimli::Unit<R>,
+    dwarf: &gimli::Dwarf<R>,
+) -> Option<usize> {
+    match get_attr_value(&entry.attr(gimli::DW_AT_byte_size).ok()??, unit, dwarf) {
+        Ok(DebugValue::Uint(size)) => size.try_into().ok(),
+        _ => None,
+    }
+}
+
+/// Reads a struct member's location. A bitfield is placed at the byte holding its first bit.
+fn get_member<R: Reader>(
+    entry: &gimli::DebuggingInformationEntry<R>,
+    name: Option<String>,
+    target: Option<usize>,
+) -> RawMember {
+    let attr = |name| entry.attr_value(name).ok().flatten();
+    let bit_offset = attr(gimli::DW_AT_data_bit_offset).and_then(|value| value.udata_value());
+    let offset = attr(gimli::DW_AT_data_member_location)
+        .and_then(|value| value.udata_value())
+        .or(bit_offset.map(|bits| bits / 8))
+        .unwrap_or(0);
+    RawMember {
+        name: name.unwrap_or_else(|| "<anonymous>".to_string()),
+        offset: offset as usize,
+        target,
+        bit_field: bit_offset.is_some() || attr(gimli::DW_AT_bit_size).is_some(),
+    }
+}
+
+/// Reads the number of elements in an array dimension, if it is a constant.
+fn get_element_count<R: Reader>(entry: &gimli::DebuggingInformationEntry<R>) -> Option<usize> {
+    let attr = |name| entry.attr_value(name).ok().flatten();
+    if let Some(count) = attr(gimli::DW_AT_count).and_then(|value| value.udata_value()) {
+        return Some(count as usize);
+    }
+    // Flexible array members have no upper bound (or, from some compilers, one of -1)
+    match attr(gimli::DW_AT_upper_bound)? {
+        gimli::AttributeValue::Sdata(bound) if bound < 0 => None,
+        bound => bound.udata_value().map(|bound| bound as usize + 1),
+    }
+}
+
+fn get_type_offset<R: Reader>(
+    entry: &gimli::DebuggingInformationEntry<R>,
+    unit: &gimli::Unit<R>,
+) -> Option<usize> {
+    match entry.attr_value(gimli::DW_AT_type).ok()?? {
+        gimli::AttributeValue::UnitRef(offset) => Some(section_offset(offset, unit)),
+        _ => None,
+    }
+}
+
+fn get_encoding<R: Reader>(entry: &gimli::DebuggingInformationEntry<R>) -> Encoding {
+    match entry.attr_value(gimli::DW_AT_encoding) {
+        Ok(Some(gimli::AttributeValue::Encoding(encoding))) => match encoding {
+            gimli::DW_ATE_signed => Encoding::Signed,
+            gimli::DW_ATE_unsigned => Encoding::Unsigned,
+            gimli::DW_ATE_signed_char => Encoding::SignedChar,
+            gimli::DW_ATE_unsigned_char | gimli::DW_ATE_UTF => Encoding::UnsignedChar,
+            gimli::DW_ATE_float => Encoding::Float,
+            gimli::DW_ATE_boolean => Encoding::Boolean,
+            _ => Encoding::Other,
+        },
+        _ => Encoding::Other,
+    }
 }
 
 #[derive(Debug, Clone)]
 pub enum DebugValue {
     Str(String),
     Uint(u64),
+    #[allow(dead_code)]
     Int(i64),
     Size(usize),
     NoVal,
 }
 
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Error {
     GimliError(gimli::Error),
@@ -356,14 +732,17 @@ fn get_attr_value<R: Reader>(
             write!(w, "0x{:08x}", value)?;
             dump_file_index(w, value, unit, dwarf)?;
             Ok(DebugValue::Str(w.to_string()))
-        },
+        }
         gimli::AttributeValue::DebugLineStrRef(offset) => {
             if let Ok(s) = dwarf.debug_line_str.get_str(offset) {
                 Ok(DebugValue::Str(format!("{}", s.to_string_lossy()?)))
             } else {
-                Ok(DebugValue::Str(format!("<.debug_line_str+0x{:08x}>", offset.0)))
+                Ok(DebugValue::Str(format!(
+                    "<.debug_line_str+0x{:08x}>",
+                    offset.0
+                )))
             }
-        },
+        }
         _ => Ok(DebugValue::NoVal),
     }
 }
@@ -614,10 +993,10 @@ fn dump_op<R: Reader, W: Write>(
         | gimli::Operation::PushObjectAddress
         | gimli::Operation::TLS
         | gimli::Operation::CallFrameCFA
-        | gimli::Operation::StackValue => {},
+        | gimli::Operation::StackValue => {}
         gimli::Operation::WasmGlobal { index: _ }
         | gimli::Operation::WasmLocal { index: _ }
         | gimli::Operation::WasmStack { index: _ } => {}
     };
     Ok(())
-}
\ No newline at end of file
+}
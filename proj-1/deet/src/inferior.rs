@@ -1,22 +1,185 @@
+use crate::debugger::Breakpoint;
 use crate::dwarf_data::DwarfData;
-use ::std::collections::HashMap;
+use crate::output::{outputln, Output};
+use crate::shared_library::SharedLibrary;
+use crate::unwind::{CfaRegister, FrameRules, RegisterRule, UnwindRegisters};
+use crate::value;
+use ::std::collections::{HashMap, HashSet};
+use nix::libc::{siginfo_t, user_regs_struct};
+use nix::sys::personality::{self, Persona};
 use nix::sys::ptrace;
 use nix::sys::signal;
+use nix::sys::uio::{self, RemoteIoVec};
 use nix::sys::wait::{waitpid, WaitPidFlag, WaitStatus};
 use nix::unistd::Pid;
+use std::fs::File;
+use std::io::{IoSliceMut, Read};
 use std::mem::size_of;
 use std::os::unix::process::CommandExt;
-use std::process::{Child, Command};
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
 
 fn align_addr_to_word(addr: usize) -> usize {
     addr & (-(size_of::<usize>() as isize) as usize)
 }
 
+/// Mappings start and end on page boundaries, so a read that stays within one page either
+/// succeeds or fails as a whole.
+const PAGE_SIZE: usize = 4096;
+
+/// Returns the number of bytes from `addr` to the start of the next page.
+fn bytes_to_page_end(addr: usize) -> usize {
+    PAGE_SIZE - addr % PAGE_SIZE
+}
+
+/// Writes the byte `val` at `addr` in the memory of traced process `pid`, returning the byte
+/// that was there before.
+fn poke_byte(pid: Pid, addr: usize, val: u8) -> Result<u8, nix::Error> {
+    let aligned_addr = align_addr_to_word(addr);
+    let byte_offset = addr - aligned_addr;
+    let word = ptrace::read(pid, aligned_addr as ptrace::AddressType)? as u64;
+    let orig_byte = (word >> (8 * byte_offset)) & 0xff;
+    let masked_word = word & !(0xff << (8 * byte_offset));
+    let updated_word = masked_word | ((val as u64) << (8 * byte_offset));
+    unsafe {
+        ptrace::write(
+            pid,
+            aligned_addr as ptrace::AddressType,
+            updated_word as *mut std::ffi::c_void,
+        )?;
+    }
+    Ok(orig_byte as u8)
+}
+
+/// Options for every traced inferior: tell system call stops apart from SIGTRAPs, stop at
+/// forks and clones so that new processes and threads don't escape unnoticed, and at execs so
+/// that the new program's breakpoints can be planted.
+fn ptrace_options() -> ptrace::Options {
+    ptrace::Options::PTRACE_O_TRACESYSGOOD
+        | ptrace::Options::PTRACE_O_TRACEFORK
+        | ptrace::Options::PTRACE_O_TRACEVFORK
+        | ptrace::Options::PTRACE_O_TRACECLONE
+        | ptrace::Options::PTRACE_O_TRACEEXEC
+}
+
+/// Returns a name for a `PTRACE_EVENT_*` constant, as reported by `Status::PtraceEvent`.
+pub fn ptrace_event_name(event: i32) -> String {
+    match event {
+        nix::libc::PTRACE_EVENT_FORK => "fork".to_string(),
+        nix::libc::PTRACE_EVENT_VFORK => "vfork".to_string(),
+        nix::libc::PTRACE_EVENT_CLONE => "clone".to_string(),
+        nix::libc::PTRACE_EVENT_EXEC => "exec".to_string(),
+        nix::libc::PTRACE_EVENT_VFORK_DONE => "vfork-done".to_string(),
+        nix::libc::PTRACE_EVENT_EXIT => "exit".to_string(),
+        nix::libc::PTRACE_EVENT_SECCOMP => "seccomp".to_string(),
+        nix::libc::PTRACE_EVENT_STOP => "stop".to_string(),
+        other => format!("event {}", other),
+    }
+}
+
+/// Like waitpid, but also returns the resource usage of the process if it terminated.
+fn wait4(pid: Pid, options: WaitPidFlag) -> Result<(WaitStatus, nix::libc::rusage), nix::Error> {
+    let mut status = 0;
+    let mut usage: nix::libc::rusage = unsafe { std::mem::zeroed() };
+    let res = unsafe { nix::libc::wait4(pid.as_raw(), &mut status, options.bits(), &mut usage) };
+    let status = match nix::errno::Errno::result(res)? {
+        0 => WaitStatus::StillAlive,
+        res => WaitStatus::from_raw(Pid::from_raw(res), status)?,
+    };
+    Ok((status, usage))
+}
+
+/// Returns the user and system CPU time recorded in a struct rusage.
+fn cpu_time(usage: &nix::libc::rusage) -> (Duration, Duration) {
+    let to_duration =
+        |time: nix::libc::timeval| Duration::new(time.tv_sec as u64, time.tv_usec as u32 * 1000);
+    (to_duration(usage.ru_utime), to_duration(usage.ru_stime))
+}
+
+/// Sends SIGSTOP to thread `tid` of process `pid` alone, rather than to the whole process.
+fn stop_thread(pid: Pid, tid: Pid) -> Result<(), nix::Error> {
+    let result = unsafe {
+        nix::libc::tgkill(
+            pid.as_raw(),
+            tid.as_raw(),
+            signal::Signal::SIGSTOP as nix::libc::c_int,
+        )
+    };
+    nix::errno::Errno::result(result).map(drop)
+}
+
+/// Returns true if `tid` is a thread of some other process rather than a process of its own.
+fn is_thread(tid: Pid) -> bool {
+    std::fs::read_to_string(format!("/proc/{}/status", tid))
+        .ok()
+        .and_then(|status| {
+            status
+                .lines()
+                .find_map(|line| line.strip_prefix("Tgid:"))
+                .and_then(|tgid| tgid.trim().parse::<i32>().ok())
+        })
+        .is_some_and(|tgid| tgid != tid.as_raw())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Status {
     /// Indicates inferior stopped. Contains the signal that stopped the process, as well as the
     /// current instruction pointer that it is stopped at.
     Stopped(signal::Signal, usize),
 
+    /// Indicates inferior stopped at one of its planted breakpoints. Contains the breakpoint's
+    /// address, which is where the instruction pointer has been rewound to.
+    Breakpoint(usize),
+
+    /// Indicates inferior wrote to a location watched by a hardware watchpoint. Contains the
+    /// watchpoint's debug register slot and the instruction pointer after the writing
+    /// instruction.
+    Watchpoint(usize, usize),
+
+    /// Indicates inferior forked. Contains the new process's pid, and whether deet is now
+    /// following the child (in which case the parent was detached) rather than the parent (in
+    /// which case the child was detached).
+    Forked(Pid, bool),
+
+    /// Indicates the current thread exited while the rest of the inferior was stopped (e.g. while
+    /// it was being single-stepped). Contains the thread's id; the main thread is now current.
+    ThreadExited(Pid),
+
+    /// Indicates inferior is about to execute a system call (only reported while catching
+    /// system calls). Contains the system call number.
+    SyscallEntry(usize),
+
+    /// Indicates inferior has just returned from a system call (only reported while catching
+    /// system calls). Contains the system call number.
+    SyscallExit(usize),
+
+    /// Indicates inferior stopped because deet interrupted it. Contains the instruction pointer.
+    Interrupted(usize),
+
+    /// Indicates inferior replaced its program with a new one through execve. Its breakpoints
+    /// and watchpoints are gone. Contains the instruction pointer, where the new program starts.
+    Exec(usize),
+
+    /// Indicates a function called with `start_call` returned. Contains its return value (%rax);
+    /// the calling thread has been put back the way it was before the call.
+    Returned(u64),
+
+    /// Indicates inferior is about to exit (only reported while catching exits). Contains the
+    /// exit status code and the instruction pointer.
+    Exiting(i32, usize),
+
+    /// Indicates inferior is about to be killed by a signal (only reported while catching exits).
+    /// Contains the signal and the instruction pointer.
+    Dying(signal::Signal, usize),
+
+    /// Indicates inferior stopped at a ptrace event deet doesn't handle itself (e.g. a seccomp
+    /// stop). Contains the event (one of the `PTRACE_EVENT_*` constants) and the instruction
+    /// pointer.
+    PtraceEvent(i32, usize),
+
+    /// Indicates inferior is still running (only returned by a non-blocking wait).
+    Running,
+
     /// Indicates inferior exited normally. Contains the exit status code.
     Exited(i32),
 
@@ -25,146 +188,1640 @@ pub enum Status {
     Signaled(signal::Signal),
 }
 
+/// A stack frame of the inferior: the instruction pointer within the frame's function, and the
+/// frame's DWARF frame base, which the locations of its locals and parameters are relative to.
+/// That is the canonical frame address: the value %rsp had in the caller right before the call
+/// into this frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frame {
+    pub instruction_ptr: usize,
+    pub frame_base: usize,
+}
+
+/// How to unwind a frame without call frame information, assuming the function starts with
+/// `push %rbp; mov %rsp,%rbp` as when compiled with frame pointers
+const FRAME_POINTER_RULES: FrameRules = FrameRules {
+    cfa_register: CfaRegister::Rbp,
+    cfa_offset: 16,
+    rbp: RegisterRule::SavedAt(-16),
+    return_address: RegisterRule::SavedAt(-8),
+};
+
+/// Default cap on the number of frames walked, so that a corrupted rbp chain can't loop forever
+pub const MAX_BACKTRACE_FRAMES: usize = 200;
+
+/// Number of x86 debug address registers (DR0-DR3), and so of hardware watchpoints
+pub const MAX_WATCHPOINTS: usize = 4;
+
+/// Most arguments `start_call` passes, one in each System V argument register
+pub const MAX_CALL_ARGS: usize = 6;
+
+/// Bytes below %rsp that a function may use without moving %rsp, which a call made by deet
+/// must leave alone
+const RED_ZONE_SIZE: usize = 128;
+
+/// Offset of debug register `num` in the user area, for PTRACE_PEEKUSER/PTRACE_POKEUSER
+fn debug_register_offset(num: usize) -> ptrace::AddressType {
+    (std::mem::offset_of!(nix::libc::user, u_debugreg) + num * size_of::<u64>())
+        as ptrace::AddressType
+}
+
+/// Returns the DR7 length field encoding for a watchpoint covering `len` bytes.
+fn watch_length_bits(len: usize) -> Option<u64> {
+    match len {
+        1 => Some(0b00),
+        2 => Some(0b01),
+        4 => Some(0b11),
+        8 => Some(0b10),
+        _ => None,
+    }
+}
+
+fn read_debug_register(tid: Pid, num: usize) -> Result<u64, nix::Error> {
+    ptrace::read_user(tid, debug_register_offset(num)).map(|value| value as u64)
+}
+
+fn write_debug_register(tid: Pid, num: usize, value: u64) -> Result<(), nix::Error> {
+    unsafe {
+        ptrace::write_user(
+            tid,
+            debug_register_offset(num),
+            value as *mut std::ffi::c_void,
+        )
+    }
+}
+
+/// Programs thread `tid`'s debug register `slot` to watch for writes at `addr`, with the DR7
+/// length field set to `len_bits`.
+fn arm_watchpoint(tid: Pid, slot: usize, addr: usize, len_bits: u64) -> Result<(), nix::Error> {
+    write_debug_register(tid, slot, addr as u64)?;
+    let mut dr7 = read_debug_register(tid, 7)?;
+    // Break on data writes only (RW = 01)
+    let control_shift = 16 + 4 * slot;
+    dr7 &= !(0b1111 << control_shift);
+    dr7 |= (0b01 | (len_bits << 2)) << control_shift;
+    // Local enable bit
+    dr7 |= 1 << (2 * slot);
+    write_debug_register(tid, 7, dr7)
+}
+
+/// Returns true if the ELF file at `path` is a position-independent executable (ELF type ET_DYN),
+/// which the kernel loads at an address of its choosing.
+pub fn is_position_independent(path: &str) -> bool {
+    const ET_DYN: u16 = 3;
+    let mut header = [0u8; 18];
+    File::open(path)
+        .and_then(|mut file| file.read_exact(&mut header))
+        .map(|_| u16::from_le_bytes([header[16], header[17]]) == ET_DYN)
+        .unwrap_or(false)
+}
+
+/// A line of /proc/<pid>/maps: a mapped range of the inferior's address space.
+#[derive(Debug, Clone)]
+pub struct MemoryRegion {
+    pub start: usize,
+    pub end: usize,
+    /// Permissions such as `r-xp`
+    pub perms: String,
+    /// Where in the mapped file the range starts
+    pub offset: usize,
+    /// Mapped file, a pseudo-path like `[stack]`, or empty for anonymous mappings
+    pub path: String,
+}
+
+impl MemoryRegion {
+    /// Parses a line such as `00400000-00401000 r-xp 00000000 08:01 1234   /bin/prog`.
+    fn parse(line: &str) -> Option<MemoryRegion> {
+        let mut fields = line.split_whitespace();
+        let (start, end) = fields.next()?.split_once('-')?;
+        let perms = fields.next()?.to_string();
+        let offset = usize::from_str_radix(fields.next()?, 16).ok()?;
+        // Skip device and inode; the path (which may contain spaces) is the rest
+        let path = fields.skip(2).collect::<Vec<_>>().join(" ");
+        Some(MemoryRegion {
+            start: usize::from_str_radix(start, 16).ok()?,
+            end: usize::from_str_radix(end, 16).ok()?,
+            perms,
+            offset,
+            path,
+        })
+    }
+
+    pub fn contains(&self, addr: usize) -> bool {
+        self.start <= addr && addr < self.end
+    }
+
+    pub fn is_executable(&self) -> bool {
+        self.perms.contains('x')
+    }
+}
+
+/// A stopped process whose registers and memory deet can inspect: a live `Inferior`, or a
+/// `CoreFile` left behind by a crash. Everything that only reads the process (backtraces,
+/// printing variables, examining memory) is written against this trait.
+pub trait Target {
+    /// Returns the offset between the executable's run-time and link-time addresses.
+    fn load_base(&self) -> usize;
+
+    /// Returns the process's memory mappings, or None if they can't be determined.
+    fn memory_regions(&self) -> Option<Vec<MemoryRegion>>;
+
+    /// Returns the current thread's register values.
+    fn get_registers(&self) -> Result<user_regs_struct, nix::Error>;
+
+    /// Reads `len` bytes of memory starting at `addr`.
+    fn read_memory(&self, addr: usize, len: usize) -> Result<Vec<u8>, nix::Error>;
+
+    /// Returns information about the signal the current thread stopped (or died) with.
+    fn get_siginfo(&self) -> Result<siginfo_t, nix::Error>;
+
+    /// Like `read_memory`, for targets that have a faster way to read large ranges.
+    fn read_memory_bulk(&self, addr: usize, len: usize) -> Result<Vec<u8>, nix::Error> {
+        self.read_memory(addr, len)
+    }
+
+    /// Converts a link-time code address into the address it has in the running process.
+    fn to_runtime(&self, addr: usize) -> usize {
+        addr.wrapping_add(self.load_base())
+    }
+
+    /// Converts a run-time code address into the link-time address used by the debug info.
+    fn to_link_time(&self, addr: usize) -> usize {
+        addr.wrapping_sub(self.load_base())
+    }
+
+    /// Returns the bounds `[start, end)` of the stack that `stack_ptr` points into: `[stack]`
+    /// for the main thread, or the mapping the thread library allocated for any other thread.
+    fn stack_bounds(&self, stack_ptr: usize) -> Option<(usize, usize)> {
+        let region = self
+            .memory_regions()?
+            .into_iter()
+            .find(|region| region.contains(stack_ptr))?;
+        Some((region.start, region.end))
+    }
+
+    /// Returns true if `addr` lies in an executable mapping of the process, or None if the
+    /// mappings can't be read.
+    fn is_executable_address(&self, addr: usize) -> Option<bool> {
+        Some(
+            self.memory_regions()?
+                .iter()
+                .any(|region| region.is_executable() && region.contains(addr)),
+        )
+    }
+
+    /// Describes the fault behind a SIGSEGV, SIGBUS or SIGFPE stop, e.g. "Segmentation fault:
+    /// invalid access at 0x0000000000000008". Returns None for other signals.
+    fn describe_fault(&self, signal: signal::Signal) -> Result<Option<String>, nix::Error> {
+        let siginfo = self.get_siginfo()?;
+        let addr = unsafe { siginfo.si_addr() } as usize;
+        Ok(match signal {
+            signal::Signal::SIGSEGV => Some(format!(
+                "Segmentation fault: invalid access at {:#018x}",
+                addr
+            )),
+            signal::Signal::SIGBUS => Some(format!("Bus error: invalid access at {:#018x}", addr)),
+            signal::Signal::SIGFPE => {
+                // si_code values for SIGFPE, from <asm-generic/siginfo.h>
+                let reason = match siginfo.si_code {
+                    1 => "integer divide by zero",
+                    2 => "integer overflow",
+                    3 => "floating-point divide by zero",
+                    4 => "floating-point overflow",
+                    5 => "floating-point underflow",
+                    6 => "floating-point inexact result",
+                    7 => "invalid floating-point operation",
+                    8 => "subscript out of range",
+                    _ => "unknown error",
+                };
+                Some(format!(
+                    "Arithmetic exception: {} at {:#018x}",
+                    reason, addr
+                ))
+            }
+            _ => None,
+        })
+    }
+
+    /// Walks the current thread's stack and returns the stack frames from innermost (frame 0,
+    /// where the thread is stopped) outwards, ending at `main`. Frames are unwound with the call
+    /// frame information where the executable has some, and by following the chain of saved frame
+    /// pointers elsewhere. If the stack looks corrupted, the walk stops early and the reason is
+    /// returned alongside the frames found so far.
+    fn unwind_stack(
+        &self,
+        dwarf_data: &DwarfData,
+        max_frames: usize,
+    ) -> Result<(Vec<Frame>, Option<String>), nix::Error> {
+        let regs = self.get_registers()?;
+        let stack_bounds = self.stack_bounds(regs.rsp as usize);
+        let mut regs = UnwindRegisters {
+            rip: regs.rip as usize,
+            rsp: regs.rsp as usize,
+            rbp: regs.rbp as usize,
+        };
+        let mut frames = Vec::new();
+        let regions = self.memory_regions().unwrap_or_default();
+        // The shared library the last frame was in, kept since consecutive frames often are
+        let mut library: Option<SharedLibrary> = None;
+
+        while frames.len() < max_frames {
+            let instruction_ptr = self.to_link_time(regs.rip);
+            // A caller's rip is a return address, which is past the end of the function if it
+            // ends with a call that doesn't return
+            let lookup_addr = if frames.is_empty() {
+                instruction_ptr
+            } else {
+                instruction_ptr.wrapping_sub(1)
+            };
+            let mut rules = dwarf_data.get_frame_rules(lookup_addr);
+            if rules.is_none() {
+                // Frames in shared libraries are unwound with the library's own rules
+                let runtime_addr = self.to_runtime(lookup_addr);
+                if !library
+                    .as_ref()
+                    .is_some_and(|library| library.contains(&regions, runtime_addr))
+                {
+                    library = SharedLibrary::containing(&regions, runtime_addr);
+                }
+                rules = library
+                    .as_ref()
+                    .and_then(|library| library.frame_rules(runtime_addr));
+            }
+            // Without call frame information, assume the function saved %rbp right below its
+            // return address and pointed %rbp at it
+            let frame_base = match &rules {
+                Some(rules) => rules.cfa(&regs),
+                None => regs.rbp.wrapping_add(16),
+            };
+            // Every caller's frame is above the frame of the function it called
+            if frames
+                .last()
+                .is_some_and(|callee: &Frame| frame_base <= callee.frame_base)
+            {
+                let reason = "previous frame inner to this frame (corrupt stack?)".to_string();
+                return Ok((frames, Some(reason)));
+            }
+            frames.push(Frame {
+                instruction_ptr,
+                frame_base,
+            });
+            // Frames without debug info (e.g. a callback trampoline in a library built without
+            // -g) don't stop the walk; we keep unwinding until main.
+            if dwarf_data
+                .get_function_from_addr(instruction_ptr)
+                .as_deref()
+                == Some("main")
+                || rules.map_or(regs.rbp == 0, |rules| rules.is_outermost())
+            {
+                break;
+            }
+            if align_addr_to_word(frame_base) != frame_base {
+                return Ok((
+                    frames,
+                    Some("frame address is not word-aligned".to_string()),
+                ));
+            }
+            if let Some((start, end)) = stack_bounds {
+                if frame_base <= start || frame_base > end {
+                    return Ok((
+                        frames,
+                        Some("frame address outside stack bounds".to_string()),
+                    ));
+                }
+            }
+
+            let read_word = |addr| {
+                let bytes = self.read_memory(addr, size_of::<usize>()).ok()?;
+                Some(usize::from_le_bytes(bytes.try_into().unwrap()))
+            };
+            let caller = match rules {
+                Some(rules) => rules.unwind(&regs, read_word),
+                // The saved rbp is at rbp, and the return address just above it
+                None => FRAME_POINTER_RULES.unwind(&regs, read_word),
+            };
+            match caller {
+                Ok(caller) if caller.rip == 0 => break,
+                Ok(caller) => regs = caller,
+                Err(reason) => return Ok((frames, Some(reason))),
+            }
+        }
+
+        Ok((frames, None))
+    }
+
+    /// Like `unwind_stack`, but silently drops the reason the walk stopped.
+    fn stack_frames(
+        &self,
+        dwarf_data: &DwarfData,
+        max_frames: usize,
+    ) -> Result<Vec<Frame>, nix::Error> {
+        Ok(self.unwind_stack(dwarf_data, max_frames)?.0)
+    }
+
+    /// Formats the formal parameters of `frame`'s function with their current values, e.g.
+    /// `a=42, b=5`. Returns None if the function has no debug info.
+    fn frame_arguments(&self, frame: &Frame, dwarf_data: &DwarfData) -> Option<String> {
+        let params = dwarf_data.get_function_parameters(frame.instruction_ptr)?;
+        let formatted: Vec<String> = params
+            .iter()
+            .map(|param| {
+                let value = match param.location.address(frame.frame_base, self.load_base()) {
+                    Some(addr) => match self.read_memory(addr, param.entity_type.size) {
+                        Ok(bytes) => value::format_value(&bytes, &param.entity_type),
+                        Err(_) => format!("<cannot access memory at {:#x}>", addr),
+                    },
+                    None => "<optimized out>".to_string(),
+                };
+                format!("{}={}", param.name, value)
+            })
+            .collect();
+        Some(formatted.join(", "))
+    }
+
+    /// Formats `frame` gdb-style, e.g. `#0  0x0000000000401136 in func (a=1) at main.c:12`.
+    /// Frames without debug info are shown as `in ?? ()`.
+    fn format_frame(&self, frame: &Frame, num: usize, dwarf_data: &DwarfData) -> String {
+        let function_name = match dwarf_data.get_function_from_addr(frame.instruction_ptr) {
+            Some(name) => name,
+            None => {
+                if let Some(described) = self.format_library_frame(frame, num, dwarf_data) {
+                    return described;
+                }
+                "??".to_string()
+            }
+        };
+        let args = self.frame_arguments(frame, dwarf_data).unwrap_or_default();
+        match dwarf_data.get_line_from_addr(frame.instruction_ptr) {
+            Some(line) => format!(
+                "#{:<2} {:#018x} in {} ({}) at {}",
+                num,
+                self.to_runtime(frame.instruction_ptr),
+                function_name,
+                args,
+                line
+            ),
+            None => format!(
+                "#{:<2} {:#018x} in {} ({})",
+                num,
+                self.to_runtime(frame.instruction_ptr),
+                function_name,
+                args
+            ),
+        }
+    }
+
+    /// Formats a frame in a shared library as e.g. `#3  0x00007ffff7e0a1ba in printf+0x1a from
+    /// /lib/x86_64-linux-gnu/libc.so.6`, or returns None if the frame isn't in one.
+    fn format_library_frame(
+        &self,
+        frame: &Frame,
+        num: usize,
+        dwarf_data: &DwarfData,
+    ) -> Option<String> {
+        let runtime_addr = self.to_runtime(frame.instruction_ptr);
+        let library = SharedLibrary::containing(&self.memory_regions()?, runtime_addr)?;
+        let function = match library.symbolize(runtime_addr) {
+            Some((name, 0)) => dwarf_data.display_name(name),
+            Some((name, offset)) => format!("{}+{:#x}", dwarf_data.display_name(name), offset),
+            None => "??".to_string(),
+        };
+        Some(format!(
+            "#{:<2} {:#018x} in {} from {}",
+            num, runtime_addr, function, library.path
+        ))
+    }
+
+    /// Prints at most `limit` frames (or `MAX_BACKTRACE_FRAMES` if no limit is given), noting
+    /// if there were more or if the stack looked corrupted. Returns the frames printed.
+    fn print_backtrace(
+        &self,
+        dwarf_data: &DwarfData,
+        limit: Option<usize>,
+        output: &Output,
+    ) -> Result<Vec<Frame>, nix::Error> {
+        let limit = limit.unwrap_or(MAX_BACKTRACE_FRAMES);
+        let (mut frames, stop_reason) = self.unwind_stack(dwarf_data, limit.saturating_add(1))?;
+        for (num, frame) in frames.iter().take(limit).enumerate() {
+            outputln!(output, "{}", self.format_frame(frame, num, dwarf_data));
+        }
+        if frames.len() > limit {
+            outputln!(output, "(more frames follow)");
+            frames.truncate(limit);
+        } else if let Some(reason) = stop_reason {
+            outputln!(output, "Backtrace stopped: {}", reason);
+        }
+        Ok(frames)
+    }
+
+    /// Reads a NUL-terminated string starting at `addr`, up to `max_len` bytes. The returned
+    /// bytes don't include the terminator.
+    fn read_cstring(&self, addr: usize, max_len: usize) -> Result<Vec<u8>, nix::Error> {
+        let mut bytes = Vec::new();
+        let mut curr_addr = addr;
+        while bytes.len() < max_len {
+            // read up to the next page boundary at a time
+            let chunk_len = bytes_to_page_end(curr_addr).min(max_len - bytes.len());
+            for byte in self.read_memory_bulk(curr_addr, chunk_len)? {
+                if byte == 0 || bytes.len() == max_len {
+                    return Ok(bytes);
+                }
+                bytes.push(byte);
+            }
+            curr_addr += chunk_len;
+        }
+        Ok(bytes)
+    }
+}
+
+#[derive(Debug)]
+pub enum InferiorError {
+    /// The target could not be started (not found, not executable, ...)
+    Spawn(std::io::Error),
+    /// Waiting for the new process to stop at its first instruction failed
+    Wait(nix::Error),
+    /// The new process didn't stop with the SIGTRAP expected from PTRACE_TRACEME
+    UnexpectedWaitStatus(WaitStatus),
+}
+
+/// Files to connect to the inferior's standard streams. Streams left as None are shared with
+/// deet.
+#[derive(Default)]
+pub struct InferiorStdio {
+    pub stdin: Option<File>,
+    pub stdout: Option<File>,
+    pub stderr: Option<File>,
+}
+
 /// This function calls ptrace with PTRACE_TRACEME to enable debugging on a process. You should use
 /// pre_exec with Command to call this in the child process.
 fn child_traceme() -> Result<(), std::io::Error> {
-    ptrace::traceme().or(Err(std::io::Error::new(
-        std::io::ErrorKind::Other,
-        "ptrace TRACEME failed",
-    )))
+    ptrace::traceme().or(Err(std::io::Error::other("ptrace TRACEME failed")))
+}
+
+/// Turns off address space randomization for the calling process and whatever it execs, so that
+/// stack, heap and PIE addresses are the same on every run.
+fn child_disable_aslr() -> Result<(), std::io::Error> {
+    let persona = personality::get().or(Err(std::io::Error::other("personality failed")))?;
+    personality::set(persona | Persona::ADDR_NO_RANDOMIZE)
+        .or(Err(std::io::Error::other("personality failed")))?;
+    Ok(())
+}
+
+/// A function call into the inferior that deet set up with `start_call`, and what it changed to
+/// do so
+struct InferiorCall {
+    /// Thread making the call
+    tid: Pid,
+    /// Its registers from before the call
+    saved_regs: user_regs_struct,
+    /// Signal the thread was to be resumed with before the call
+    pending_signal: Option<signal::Signal>,
+    /// Whether the thread was rewound to hit a breakpoint again before the call
+    rewound: bool,
+    /// Run-time address of the int3 the called function returns to, and the byte it replaced
+    return_addr: usize,
+    orig_byte: u8,
+    /// Address of the stack slot holding the return address, and what the slot held before
+    return_slot: usize,
+    orig_word: i64,
+}
+
+/// How the inferior process came to be traced by deet.
+enum Process {
+    /// Started by deet, which kills it when done with it
+    Spawned(Child),
+    /// Already running when deet attached to it; it is detached (not killed) when deet is done
+    Attached(Pid),
+    /// A child of the original inferior that deet followed after a fork, or made it fork as a
+    /// checkpoint; it is killed when deet is done with it
+    Followed(Pid),
 }
 
 pub struct Inferior {
-    child: Child,
+    process: Process,
+    /// Original bytes under the int3s planted in this process, by run-time address. Kept in line
+    /// with the debugger's breakpoint list by sync_breakpoints.
     bps: HashMap<usize, Option<u8>>,
+    /// Signals that stopped threads, to be delivered to each thread when it is next resumed
+    pending_signals: HashMap<Pid, signal::Signal>,
+    /// Where a position-independent executable was loaded (0 for non-PIE executables). Code
+    /// addresses in deet's interface (breakpoints, stop locations, frames) are link-time
+    /// addresses as found in the debug info; this is added to them to get run-time addresses.
+    load_base: usize,
+    /// Run-time addresses and lengths watched by debug registers DR0-DR3, indexed by register
+    watchpoints: [Option<(usize, usize)>; MAX_WATCHPOINTS],
+    /// If set, deet follows the child rather than the parent when the inferior forks
+    follow_fork_child: bool,
+    /// If set, the inferior is resumed with PTRACE_SYSCALL so that it stops at every system call
+    catch_syscalls: bool,
+    /// If set, the kernel kills the inferior should deet die without cleaning up
+    kill_on_exit: bool,
+    /// If set, the inferior stops right before it exits
+    catch_exit: bool,
+    /// Function call made by `start_call` that hasn't returned or been abandoned yet
+    call: Option<InferiorCall>,
+    /// Threads between a syscall-entry stop and the matching syscall-exit stop
+    in_syscall: HashSet<Pid>,
+    /// Ids of the inferior's threads, main thread first. All of them are traced.
+    threads: Vec<Pid>,
+    /// The thread that caused the last stop. Registers, stack and single steps are its.
+    current_thread: Pid,
+    /// Threads that were resumed and haven't been seen to stop since
+    running: HashSet<Pid>,
+    /// Threads deet sent a SIGSTOP to that hasn't been seen yet
+    stop_requested: HashSet<Pid>,
+    /// New threads and processes whose first stop was seen before the event announcing them
+    early_stops: HashSet<Pid>,
+    /// Threads that hit a breakpoint while being stopped, and were rewound to hit it again later
+    /// rather than reporting it
+    rewound_threads: HashSet<Pid>,
+    /// True while only the current thread is being single-stepped
+    stepping: bool,
+    /// True if interrupt() was called and no stop has been reported since
+    interrupted: bool,
+    /// When the inferior was last woken up, if it hasn't been seen to stop since
+    woken_at: Option<Instant>,
+    /// Total time the inferior has spent running (rather than stopped)
+    run_time: Duration,
+    /// User and system CPU time used by the process, once it has terminated
+    cpu_time: Option<(Duration, Duration)>,
 }
 
 impl Inferior {
-    /// Attempts to start a new inferior process. Returns Some(Inferior) if successful, or None if
-    /// an error is encountered.
-    pub fn new(target: &str, args: &Vec<String>, breakpoints: &Vec<usize>) -> Option<Inferior> {
+    /// Attempts to start a new inferior process, leaving it stopped before its first instruction
+    /// (with no breakpoints planted yet). If `disable_aslr` is set, the process runs without
+    /// address space randomization.
+    pub fn new(
+        target: &str,
+        args: &[String],
+        stdio: InferiorStdio,
+        disable_aslr: bool,
+    ) -> Result<Inferior, InferiorError> {
         let mut cmd = Command::new(target);
         cmd.args(args);
+        if let Some(file) = stdio.stdin {
+            cmd.stdin(Stdio::from(file));
+        }
+        if let Some(file) = stdio.stdout {
+            cmd.stdout(Stdio::from(file));
+        }
+        if let Some(file) = stdio.stderr {
+            cmd.stderr(Stdio::from(file));
+        }
         unsafe {
-            cmd.pre_exec(child_traceme);
-        }
-        match cmd.spawn() {
-            Ok(child) => {
-                let child_pid = nix::unistd::Pid::from_raw(child.id() as i32);
-                match waitpid(child_pid, None).ok()? {
-                    WaitStatus::Stopped(_pid, _signal) => {
-                        let mut inferior = Inferior {
-                            child,
-                            bps: HashMap::new(),
-                        };
-                        for breakpoint in breakpoints.iter() {
-                            let orig_byte = inferior
-                                .write_byte(*breakpoint, 0xcc)
-                                .expect(&format!("Failed to set breakpoint at {}", breakpoint));
-                            inferior.bps.insert(*breakpoint, Some(orig_byte));
-                        }
-                        Some(inferior)
+            cmd.pre_exec(move || {
+                if disable_aslr {
+                    child_disable_aslr()?;
+                }
+                child_traceme()
+            });
+        }
+        let child = cmd.spawn().map_err(InferiorError::Spawn)?;
+        let child_pid = nix::unistd::Pid::from_raw(child.id() as i32);
+        let mut inferior = Inferior::with_process(Process::Spawned(child), child_pid);
+        match waitpid(child_pid, None) {
+            // The executable is mapped by now, so we can find out where it was loaded
+            Ok(WaitStatus::Stopped(_pid, signal::Signal::SIGTRAP)) => {
+                inferior.load_base = inferior.find_load_base();
+                if let Err(err) = ptrace::setoptions(child_pid, ptrace_options()) {
+                    inferior.kill_and_reap();
+                    return Err(InferiorError::Wait(err));
+                }
+            }
+            Ok(status) => {
+                inferior.kill_and_reap();
+                return Err(InferiorError::UnexpectedWaitStatus(status));
+            }
+            Err(err) => {
+                inferior.kill_and_reap();
+                return Err(InferiorError::Wait(err));
+            }
+        }
+        Ok(inferior)
+    }
+
+    /// Kills this inferior and reaps it, so it doesn't linger as a zombie. Unlike `wait`, this
+    /// doesn't pick up events of other processes deet traces.
+    pub fn kill_and_reap(&mut self) {
+        if self.kill().is_ok() {
+            let _ = waitpid(self.pid(), Some(WaitPidFlag::__WALL));
+        }
+    }
+
+    /// Attaches to the running process `pid`, leaving it stopped.
+    pub fn attach(pid: Pid) -> Result<Inferior, nix::Error> {
+        ptrace::attach(pid)?;
+        waitpid(pid, None)?;
+        ptrace::setoptions(pid, ptrace_options())?;
+        let mut inferior = Inferior::with_process(Process::Attached(pid), pid);
+        if let Err(err) = inferior.attach_threads() {
+            let _ = inferior.detach();
+            return Err(err);
+        }
+        inferior.load_base = inferior.find_load_base();
+        Ok(inferior)
+    }
+
+    /// Returns an inferior for the traced process `pid`, whose only thread is its main thread.
+    fn with_process(process: Process, pid: Pid) -> Inferior {
+        Inferior {
+            process,
+            bps: HashMap::new(),
+            pending_signals: HashMap::new(),
+            load_base: 0,
+            watchpoints: [None; MAX_WATCHPOINTS],
+            follow_fork_child: false,
+            catch_syscalls: false,
+            kill_on_exit: false,
+            catch_exit: false,
+            call: None,
+            in_syscall: HashSet::new(),
+            threads: vec![pid],
+            current_thread: pid,
+            running: HashSet::new(),
+            stop_requested: HashSet::new(),
+            early_stops: HashSet::new(),
+            rewound_threads: HashSet::new(),
+            stepping: false,
+            interrupted: false,
+            woken_at: None,
+            run_time: Duration::ZERO,
+            cpu_time: None,
+        }
+    }
+
+    /// Attaches to the threads of an attached process other than its main thread. Threads
+    /// started before the attach aren't reported through PTRACE_O_TRACECLONE.
+    fn attach_threads(&mut self) -> Result<(), nix::Error> {
+        // Threads may start while we attach, so look again until no new ones turn up
+        let mut seen: HashSet<Pid> = self.threads.iter().copied().collect();
+        loop {
+            let tids: Vec<Pid> = std::fs::read_dir(format!("/proc/{}/task", self.pid()))
+                .map_err(|_| nix::Error::ESRCH)?
+                .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse().ok())
+                .map(Pid::from_raw)
+                .filter(|tid| seen.insert(*tid))
+                .collect();
+            if tids.is_empty() {
+                return Ok(());
+            }
+            for tid in tids {
+                match ptrace::attach(tid) {
+                    Ok(()) => {}
+                    // The thread exited already, or was traced through a clone of one we attached
+                    // to (its clone event shows up once the inferior is resumed)
+                    Err(nix::Error::ESRCH) | Err(nix::Error::EPERM) => continue,
+                    Err(err) => return Err(err),
+                }
+                waitpid(tid, Some(WaitPidFlag::__WALL))?;
+                ptrace::setoptions(tid, ptrace_options())?;
+                self.threads.push(tid);
+            }
+        }
+    }
+
+    /// Works out where the executable was loaded: position-independent executables (ELF type
+    /// ET_DYN) are loaded at the start of their first mapping; anything else is loaded at the
+    /// addresses it was linked at.
+    fn find_load_base(&self) -> usize {
+        let exe_path = format!("/proc/{}/exe", self.pid());
+        if !is_position_independent(&exe_path) {
+            return 0;
+        }
+        let exe = match std::fs::read_link(&exe_path) {
+            Ok(exe) => exe.to_string_lossy().into_owned(),
+            Err(_) => return 0,
+        };
+        self.memory_regions()
+            .and_then(|regions| {
+                regions
+                    .iter()
+                    .filter(|region| region.path == exe)
+                    .map(|region| region.start)
+                    .min()
+            })
+            .unwrap_or(0)
+    }
+
+    /// Returns true if deet attached to this inferior rather than starting it.
+    pub fn is_attached(&self) -> bool {
+        matches!(self.process, Process::Attached(_))
+    }
+
+    /// Removes all breakpoints and watchpoints and lets the inferior continue running untraced.
+    pub fn detach(&mut self) -> Result<(), nix::Error> {
+        let addrs: Vec<usize> = self.bps.keys().copied().collect();
+        for addr in addrs {
+            self.remove_breakpoint(self.to_link_time(addr))?;
+        }
+        // A watchpoint left armed would kill the process with SIGTRAP once untraced
+        for slot in 0..MAX_WATCHPOINTS {
+            self.remove_watchpoint(slot)?;
+        }
+        self.flush_stop_requests()?;
+        for tid in self.threads.clone() {
+            ptrace::detach(tid, None)?;
+        }
+        Ok(())
+    }
+
+    /// Lets the SIGSTOPs we sent that are still pending arrive, since they would stop threads
+    /// that are no longer traced.
+    fn flush_stop_requests(&mut self) -> Result<(), nix::Error> {
+        for tid in self.stop_requested.clone() {
+            ptrace::cont(tid, None)?;
+            self.wait_for_stop(tid)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the signal that will be delivered to the current thread when the inferior is next
+    /// woken up.
+    pub fn pending_signal(&self) -> Option<signal::Signal> {
+        self.pending_signals.get(&self.current_thread).copied()
+    }
+
+    /// Makes the next wake-up continue the current thread without delivering the signal it
+    /// stopped with.
+    pub fn discard_pending_signal(&mut self) {
+        self.pending_signals.remove(&self.current_thread);
+    }
+
+    /// Sets whether deet follows the child (true) or the parent (false) when the inferior forks.
+    pub fn set_follow_fork_child(&mut self, follow_fork_child: bool) {
+        self.follow_fork_child = follow_fork_child;
+    }
+
+    /// Sets whether the kernel kills the inferior (and the children it forked later) if deet dies
+    /// without cleaning up, e.g. because it panicked or was sent SIGKILL. Otherwise the inferior
+    /// is left to run on untraced.
+    pub fn set_kill_on_exit(&mut self, kill_on_exit: bool) -> Result<(), nix::Error> {
+        self.kill_on_exit = kill_on_exit;
+        self.apply_options()
+    }
+
+    /// Sets whether the inferior stops right before it exits (or is killed by a signal), which is
+    /// reported as `Status::Exiting` or `Status::Dying`.
+    pub fn set_catch_exit(&mut self, catch_exit: bool) -> Result<(), nix::Error> {
+        self.catch_exit = catch_exit;
+        self.apply_options()
+    }
+
+    /// Sets the ptrace options of all threads according to `kill_on_exit` and `catch_exit`.
+    fn apply_options(&self) -> Result<(), nix::Error> {
+        let mut options = ptrace_options();
+        if self.kill_on_exit {
+            options |= ptrace::Options::PTRACE_O_EXITKILL;
+        }
+        // Only asked for when wanted, since every thread would stop on its way out
+        if self.catch_exit {
+            options |= ptrace::Options::PTRACE_O_TRACEEXIT;
+        }
+        for &tid in &self.threads {
+            ptrace::setoptions(tid, options)?;
+        }
+        Ok(())
+    }
+
+    /// Sets whether the inferior stops at the entry and exit of every system call when it is next
+    /// woken up.
+    pub fn set_catch_syscalls(&mut self, catch_syscalls: bool) {
+        self.catch_syscalls = catch_syscalls;
+    }
+
+    /// Wakes up all threads of the inferior and waits until one of them stops (at which point the
+    /// others are stopped too) or the inferior terminates. Threads that last stopped because of a
+    /// signal (other than our own SIGTRAPs) have that signal delivered to them now.
+    pub fn wake_and_wait(&mut self) -> Result<Status, nix::Error> {
+        if let Some(status) = self.wake()? {
+            return Ok(status);
+        }
+        let status = self.wait(None)?;
+        self.examine_stop(status)
+    }
+
+    /// Wakes up all threads of the inferior without waiting for them. Returns the status if the
+    /// inferior already stopped again while stepping threads over their breakpoints.
+    pub fn wake(&mut self) -> Result<Option<Status>, nix::Error> {
+        // threads sitting on a breakpoint they stopped at (the current thread, and any other
+        // thread selected since) step over it before continuing
+        let selected_thread = self.current_thread;
+        for tid in self.threads.clone() {
+            let instruction_ptr = ptrace::getregs(tid)?.rip as usize;
+            if self.bps.contains_key(&instruction_ptr) && !self.rewound_threads.contains(&tid) {
+                self.current_thread = tid;
+                match self.step()? {
+                    Status::Stopped(signal::Signal::SIGTRAP, _) => {}
+                    status => return Ok(Some(status)),
+                }
+            }
+        }
+        self.current_thread = selected_thread;
+        self.rewound_threads.clear();
+
+        self.stepping = false;
+        if !self.catch_syscalls {
+            // Without PTRACE_SYSCALL, a system call we are stopped in doesn't report its exit
+            self.in_syscall.clear();
+        }
+        for tid in self.threads.clone() {
+            let pending_signal = self.pending_signals.remove(&tid);
+            match self.resume(tid, pending_signal) {
+                // The thread was killed while stopped; wait reports its exit
+                Err(nix::Error::ESRCH) if tid != self.current_thread => {}
+                result => result?,
+            }
+        }
+        Ok(None)
+    }
+
+    /// Checks whether the woken inferior has stopped or terminated, and returns how; returns None
+    /// if it is still running. With `block` set, waits for that to happen.
+    pub fn poll(&mut self, block: bool) -> Result<Option<Status>, nix::Error> {
+        let options = if block {
+            None
+        } else {
+            Some(WaitPidFlag::WNOHANG)
+        };
+        match self.wait(options)? {
+            Status::Running => Ok(None),
+            status => self.examine_stop(status).map(Some),
+        }
+    }
+
+    /// Asks the woken inferior to stop. The stop is reported by a later wait as
+    /// Status::Interrupted, unless the inferior stops for some other reason first.
+    pub fn interrupt(&mut self) -> Result<(), nix::Error> {
+        let tid = self.current_thread;
+        if self.stop_requested.insert(tid) {
+            stop_thread(self.pid(), tid)?;
+        }
+        self.interrupted = true;
+        Ok(())
+    }
+
+    /// Finishes handling a status returned by a wait after all threads were woken: SIGTRAPs are
+    /// matched to watchpoints and breakpoints, and other signals are saved for delivery.
+    fn examine_stop(&mut self, status: Status) -> Result<Status, nix::Error> {
+        // Whatever stopped the inferior answers a pending interrupt; a SIGSTOP it sent that
+        // arrives later is swallowed
+        self.interrupted = false;
+        match status {
+            Status::Stopped(signal::Signal::SIGTRAP, instruction_ptr) => {
+                // a function called by deet returning to the int3 set up for it
+                if let Some(call) = &self.call {
+                    if call.tid == self.current_thread
+                        && self.to_runtime(instruction_ptr).wrapping_sub(1) == call.return_addr
+                    {
+                        let value = ptrace::getregs(call.tid)?.rax;
+                        self.end_call()?;
+                        return Ok(Status::Returned(value));
                     }
-                    _ => None,
                 }
+                // watchpoints trap right after the instruction that wrote the watched location
+                if let Some(slot) = self.triggered_watchpoint()? {
+                    return Ok(Status::Watchpoint(slot, instruction_ptr));
+                }
+                // if inferior stopped at a breakpoint, rewind %rip to the start of the
+                // breakpointed instruction so that it looks like we stopped right before
+                // executing it
+                if self.hit_breakpoint(self.current_thread, self.to_runtime(instruction_ptr))? {
+                    let mut regs = ptrace::getregs(self.current_thread)?;
+                    regs.rip -= 1;
+                    ptrace::setregs(self.current_thread, regs)?;
+                    return Ok(Status::Breakpoint(instruction_ptr - 1));
+                }
+                Ok(Status::Stopped(signal::Signal::SIGTRAP, instruction_ptr))
+            }
+            Status::Stopped(signal, instruction_ptr) => {
+                self.pending_signals.insert(self.current_thread, signal);
+                Ok(Status::Stopped(signal, instruction_ptr))
             }
-            Err(_) => None,
+            status => Ok(status),
         }
     }
 
-    /// Wakes up the inferior and waits until it stops or terminates.
-    pub fn wake_and_wait(&mut self, breakpoints: &Vec<usize>) -> Result<Status, nix::Error> {
-        // where i am
-        let mut regs = ptrace::getregs(self.pid())?;
-        let instruction_ptr = regs.rip as usize;
+    /// Resumes thread `tid` the way the inferior is being run: single-stepping, stopping at
+    /// system calls, or just continuing.
+    fn resume(&mut self, tid: Pid, sig: Option<signal::Signal>) -> Result<(), nix::Error> {
+        self.woken_at.get_or_insert_with(Instant::now);
+        if self.stepping {
+            ptrace::step(tid, sig)?;
+        } else if self.catch_syscalls {
+            ptrace::syscall(tid, sig)?;
+        } else {
+            ptrace::cont(tid, sig)?;
+        }
+        self.running.insert(tid);
+        Ok(())
+    }
 
-        // if inferior stopped at a breakpoint
-        if let Some((&addr, &Some(orig_byte))) = self.bps.get_key_value(&(instruction_ptr - 1)) {
+    /// Executes a single instruction in the current thread (delivering its pending signal), with
+    /// the other threads kept stopped, and waits for it to stop again. A breakpoint planted on the
+    /// instruction is stepped over. If the thread ends up at the start of a breakpointed
+    /// instruction, Status::Breakpoint is returned; that instruction has not been executed yet.
+    pub fn step(&mut self) -> Result<Status, nix::Error> {
+        let tid = self.current_thread;
+        let pending_signal = self.pending_signals.remove(&tid);
+        let instruction_ptr = ptrace::getregs(tid)?.rip as usize;
+        let orig_byte = self.bps.get(&instruction_ptr).copied().flatten();
+        if let Some(orig_byte) = orig_byte {
             // restore the first byte of the instruction
-            let _ = self.write_byte(addr, orig_byte);
-            // set %rip = %rip - 1 to rewind the instruction pointer
-            regs.rip = (instruction_ptr - 1) as u64; // `usize`?
-            ptrace::setregs(self.pid(), regs)?;
-            // ptrace::step to go to next instruction
-            ptrace::step(self.pid(), None)?;
-            // wait for inferior to stop due to SIGTRAP
-            match self.wait(None).unwrap() {
-                Status::Exited(exit_code) => return Ok(Status::Exited(exit_code)),
-                Status::Signaled(signal) => return Ok(Status::Signaled(signal)),
-                Status::Stopped(_, _) => {
-                    self.write_byte(instruction_ptr - 1, 0xcc)?;
+            self.write_byte(instruction_ptr, orig_byte)?;
+        }
+        self.in_syscall.remove(&tid);
+        self.stepping = true;
+        self.resume(tid, pending_signal)?;
+        let status = self.wait(None)?;
+        if orig_byte.is_some() && !matches!(status, Status::Exited(_) | Status::Signaled(_)) {
+            self.write_byte(instruction_ptr, 0xcc)?;
+        }
+        match status {
+            Status::Stopped(signal::Signal::SIGTRAP, instruction_ptr) => {
+                // the stepped instruction may have written a watched location
+                if let Some(slot) = self.triggered_watchpoint()? {
+                    return Ok(Status::Watchpoint(slot, instruction_ptr));
+                }
+                if self.bps.contains_key(&self.to_runtime(instruction_ptr)) {
+                    return Ok(Status::Breakpoint(instruction_ptr));
                 }
+                Ok(Status::Stopped(signal::Signal::SIGTRAP, instruction_ptr))
             }
+            Status::Stopped(signal, instruction_ptr) => {
+                self.pending_signals.insert(self.current_thread, signal);
+                Ok(Status::Stopped(signal, instruction_ptr))
+            }
+            status => Ok(status),
         }
-
-        ptrace::cont(self.pid(), None)?;
-        self.wait(None)
     }
 
-    /// Returns the pid of this inferior.
+    /// Returns the pid of this inferior (the id of its main thread).
     pub fn pid(&self) -> Pid {
-        nix::unistd::Pid::from_raw(self.child.id() as i32)
+        match &self.process {
+            Process::Spawned(child) => nix::unistd::Pid::from_raw(child.id() as i32),
+            Process::Attached(pid) | Process::Followed(pid) => *pid,
+        }
     }
 
-    /// Calls waitpid on this inferior and returns a Status to indicate the state of the process
-    /// after the waitpid call.
-    pub fn wait(&self, options: Option<WaitPidFlag>) -> Result<Status, nix::Error> {
-        Ok(match waitpid(self.pid(), options)? {
-            WaitStatus::Exited(_pid, exit_code) => Status::Exited(exit_code),
-            WaitStatus::Signaled(_pid, signal, _core_dumped) => Status::Signaled(signal),
-            WaitStatus::Stopped(_pid, signal) => {
-                let regs = ptrace::getregs(self.pid())?;
-                Status::Stopped(signal, regs.rip as usize)
+    /// Returns the ids of the inferior's threads, main thread first.
+    pub fn threads(&self) -> &[Pid] {
+        &self.threads
+    }
+
+    /// Returns the id of the current thread: the one that caused the last stop, unless another
+    /// was selected since.
+    pub fn current_thread(&self) -> Pid {
+        self.current_thread
+    }
+
+    /// Makes `tid` the current thread, whose registers and stack are looked at and which single
+    /// steps apply to. Fails with ESRCH if the inferior has no such thread (e.g. it exited).
+    pub fn select_thread(&mut self, tid: Pid) -> Result<(), nix::Error> {
+        if !self.threads.contains(&tid) {
+            return Err(nix::Error::ESRCH);
+        }
+        // Stopping at a breakpoint counts as reported once the thread has been looked at
+        self.rewound_threads.remove(&tid);
+        self.current_thread = tid;
+        Ok(())
+    }
+
+    /// Returns the (link-time) address of the instruction thread `tid` is stopped at.
+    pub fn thread_instruction_ptr(&self, tid: Pid) -> Result<usize, nix::Error> {
+        Ok(self.to_link_time(self.thread_registers(tid)?.rip as usize))
+    }
+
+    /// Returns the register values of thread `tid`.
+    pub fn thread_registers(&self, tid: Pid) -> Result<user_regs_struct, nix::Error> {
+        ptrace::getregs(tid)
+    }
+
+    /// Waits for any thread of this inferior to stop and returns a Status describing the stop,
+    /// after stopping the other threads as well; or waits for the inferior to terminate. New
+    /// threads are picked up along the way, and the exits of threads other than the main thread
+    /// are only reported if nothing else is left running.
+    pub fn wait(&mut self, options: Option<WaitPidFlag>) -> Result<Status, nix::Error> {
+        let status = self.wait_for_event(options)?;
+        if !matches!(status, Status::Running) {
+            if let Some(woken_at) = self.woken_at.take() {
+                self.run_time += woken_at.elapsed();
             }
-            other => panic!("waitpid returned unexpected status: {:?}", other),
-        })
+        }
+        Ok(status)
     }
 
-    /// Kills this inferior.
-    pub fn kill(&mut self) -> Result<(), std::io::Error> {
-        self.child.kill()
+    /// Returns the total time the inferior has spent running, not counting the time it was
+    /// stopped (e.g. at breakpoints).
+    pub fn run_time(&self) -> Duration {
+        self.run_time
     }
 
-    pub fn print_backtrace(&self, dwarf_data: &DwarfData) -> Result<(), nix::Error> {
-        let regs = ptrace::getregs(self.pid())?;
-        let mut instruction_ptr = regs.rip as usize;
-        let mut base_ptr = regs.rbp as usize;
+    /// Returns the user and system CPU time used by the process, once it has terminated.
+    pub fn cpu_time(&self) -> Option<(Duration, Duration)> {
+        self.cpu_time
+    }
 
+    /// Waits for the next event that is reported as a status; the rest of wait().
+    fn wait_for_event(&mut self, options: Option<WaitPidFlag>) -> Result<Status, nix::Error> {
+        // __WNOTHREAD limits this to processes traced by the calling (deet) thread
+        let flags = options.unwrap_or(WaitPidFlag::empty())
+            | WaitPidFlag::__WALL
+            | WaitPidFlag::__WNOTHREAD;
         loop {
-            let line_number = dwarf_data.get_line_from_addr(instruction_ptr).unwrap();
-            let function_name = dwarf_data.get_function_from_addr(instruction_ptr).unwrap();
-            println!("{} ({})", function_name, line_number);
+            let (status, usage) = wait4(Pid::from_raw(-1), flags)?;
+            let tid = match status.pid() {
+                Some(tid) => tid,
+                // Only StillAlive comes without a pid
+                None => return Ok(Status::Running),
+            };
+            if !self.threads.contains(&tid) {
+                // A new thread or process may stop before the event announcing it. Anything else
+                // (e.g. a parent detached after a fork exiting) is none of our business.
+                if let WaitStatus::Stopped(_, signal::Signal::SIGSTOP) = status {
+                    self.early_stops.insert(tid);
+                }
+                continue;
+            }
+            if let WaitStatus::Continued(_) = status {
+                // Resumed by a SIGCONT (only reported with WCONTINUED): still running
+                continue;
+            }
+            self.running.remove(&tid);
+            match status {
+                WaitStatus::PtraceEvent(_pid, _signal, event)
+                    if event == nix::libc::PTRACE_EVENT_FORK
+                        || event == nix::libc::PTRACE_EVENT_VFORK
+                        || event == nix::libc::PTRACE_EVENT_CLONE =>
+                {
+                    let new_pid = Pid::from_raw(ptrace::getevent(tid)? as i32);
+                    if is_thread(new_pid) {
+                        // New threads are traced along with the rest, without stopping
+                        self.add_thread(new_pid)?;
+                        if !self.stepping {
+                            self.resume(new_pid, None)?;
+                        }
+                        self.resume(tid, None)?;
+                        continue;
+                    }
+                    self.stop_all_threads(tid)?;
+                    return self.handle_fork(new_pid, event == nix::libc::PTRACE_EVENT_VFORK);
+                }
+                WaitStatus::PtraceEvent(_pid, _signal, nix::libc::PTRACE_EVENT_EXEC) => {
+                    return self.handle_exec();
+                }
+                WaitStatus::PtraceEvent(_pid, _signal, nix::libc::PTRACE_EVENT_EXIT) => {
+                    if tid != self.pid() {
+                        // Only the process as a whole exiting is reported
+                        self.resume(tid, None)?;
+                        continue;
+                    }
+                    // The event message is the wait status the process is about to exit with
+                    let wait_status = ptrace::getevent(tid)? as i32;
+                    self.stop_all_threads(tid)?;
+                    let instruction_ptr = self.to_link_time(ptrace::getregs(tid)?.rip as usize);
+                    return Ok(match wait_status & 0x7f {
+                        0 => Status::Exiting((wait_status >> 8) & 0xff, instruction_ptr),
+                        signal => Status::Dying(signal::Signal::try_from(signal)?, instruction_ptr),
+                    });
+                }
+                WaitStatus::PtraceSyscall(_pid) => {
+                    self.stop_all_threads(tid)?;
+                    let syscall = ptrace::getregs(tid)?.orig_rax as usize;
+                    return Ok(if self.in_syscall.insert(tid) {
+                        Status::SyscallEntry(syscall)
+                    } else {
+                        self.in_syscall.remove(&tid);
+                        Status::SyscallExit(syscall)
+                    });
+                }
+                WaitStatus::Exited(_pid, exit_code) if tid == self.pid() => {
+                    self.cpu_time = Some(cpu_time(&usage));
+                    return Ok(Status::Exited(exit_code));
+                }
+                WaitStatus::Signaled(_pid, signal, _core_dumped) if tid == self.pid() => {
+                    self.cpu_time = Some(cpu_time(&usage));
+                    return Ok(Status::Signaled(signal));
+                }
+                WaitStatus::Exited(..) | WaitStatus::Signaled(..) => {
+                    self.remove_thread(tid);
+                    if tid == self.current_thread && self.running.is_empty() {
+                        self.current_thread = self.pid();
+                        return Ok(Status::ThreadExited(tid));
+                    }
+                }
+                WaitStatus::Stopped(_pid, signal::Signal::SIGSTOP)
+                    if self.stop_requested.remove(&tid) =>
+                {
+                    if self.interrupted {
+                        self.stop_all_threads(tid)?;
+                        let regs = ptrace::getregs(tid)?;
+                        return Ok(Status::Interrupted(self.to_link_time(regs.rip as usize)));
+                    }
+                    // A stop we asked for, arriving after the thread had stopped for another
+                    // reason
+                    self.resume(tid, None)?;
+                }
+                WaitStatus::Stopped(_pid, signal) => {
+                    self.stop_all_threads(tid)?;
+                    let regs = ptrace::getregs(tid)?;
+                    return Ok(Status::Stopped(
+                        signal,
+                        self.to_link_time(regs.rip as usize),
+                    ));
+                }
+                WaitStatus::PtraceEvent(_pid, _signal, event) => {
+                    self.stop_all_threads(tid)?;
+                    let regs = ptrace::getregs(tid)?;
+                    return Ok(Status::PtraceEvent(
+                        event,
+                        self.to_link_time(regs.rip as usize),
+                    ));
+                }
+                // Handled above
+                WaitStatus::StillAlive | WaitStatus::Continued(_) => {}
+            }
+        }
+    }
 
-            if function_name == "main" {
-                break;
+    /// Makes `tid` the current thread and stops all other threads that are running, so that the
+    /// whole inferior is stopped while deet looks at it.
+    fn stop_all_threads(&mut self, tid: Pid) -> Result<(), nix::Error> {
+        self.current_thread = tid;
+        let running: Vec<Pid> = self.running.drain().collect();
+        for tid in running {
+            if self.stop_requested.insert(tid) {
+                stop_thread(self.pid(), tid)?;
             }
+            self.wait_for_stop(tid)?;
+        }
+        Ok(())
+    }
+
+    /// Returns whether thread `tid`, stopped by a SIGTRAP with %rip at `rip`, executed the int3 of
+    /// one of our breakpoints. Single-stepping a one-byte instruction that has a breakpoint also
+    /// leaves %rip just past the breakpoint, but must not be rewound onto it again.
+    fn hit_breakpoint(&self, tid: Pid, rip: usize) -> Result<bool, nix::Error> {
+        // si_code of a SIGTRAP raised by int3, from <asm-generic/siginfo.h>
+        const SI_KERNEL: i32 = 0x80;
+        Ok(self.bps.contains_key(&rip.wrapping_sub(1))
+            && ptrace::getsiginfo(tid)?.si_code == SI_KERNEL)
+    }
 
-            instruction_ptr =
-                ptrace::read(self.pid(), (base_ptr + 8) as ptrace::AddressType)? as usize;
-            base_ptr = ptrace::read(self.pid(), base_ptr as ptrace::AddressType)? as usize;
+    /// Waits for thread `tid`, which was sent a SIGSTOP, to stop. If it stops for some other
+    /// reason first, the SIGSTOP is swallowed when it arrives later on; a breakpoint it hit in the
+    /// meantime is rewound so that it is hit again once the thread is resumed, and other signals
+    /// are delivered on resumption.
+    fn wait_for_stop(&mut self, tid: Pid) -> Result<(), nix::Error> {
+        match waitpid(tid, Some(WaitPidFlag::__WALL))? {
+            WaitStatus::Stopped(_pid, signal::Signal::SIGSTOP)
+                if self.stop_requested.remove(&tid) => {}
+            WaitStatus::Stopped(_pid, signal::Signal::SIGTRAP) => {
+                let mut regs = ptrace::getregs(tid)?;
+                if self.hit_breakpoint(tid, regs.rip as usize)? {
+                    regs.rip -= 1;
+                    ptrace::setregs(tid, regs)?;
+                    self.rewound_threads.insert(tid);
+                }
+            }
+            WaitStatus::Stopped(_pid, signal) => {
+                self.pending_signals.insert(tid, signal);
+            }
+            WaitStatus::PtraceSyscall(_pid) => {
+                if !self.in_syscall.insert(tid) {
+                    self.in_syscall.remove(&tid);
+                }
+            }
+            // On its way out; the SIGSTOP is forgotten once the thread is reaped
+            WaitStatus::PtraceEvent(_pid, _signal, nix::libc::PTRACE_EVENT_EXIT) => {}
+            WaitStatus::PtraceEvent(_pid, _signal, event) => {
+                let new_pid = Pid::from_raw(ptrace::getevent(tid)? as i32);
+                if is_thread(new_pid) {
+                    self.add_thread(new_pid)?;
+                } else {
+                    // Only forks in the current thread can be followed
+                    self.release_process(new_pid, event == nix::libc::PTRACE_EVENT_VFORK)?;
+                }
+            }
+            _ => self.remove_thread(tid),
         }
+        Ok(())
+    }
 
+    /// Starts tracing the inferior's new thread `tid`, leaving it stopped.
+    fn add_thread(&mut self, tid: Pid) -> Result<(), nix::Error> {
+        // The new thread starts out stopped with SIGSTOP
+        if !self.early_stops.remove(&tid) {
+            waitpid(tid, Some(WaitPidFlag::__WALL))?;
+        }
+        self.threads.push(tid);
+        // Debug registers aren't inherited by new threads
+        for (slot, watchpoint) in self.watchpoints.iter().enumerate() {
+            if let Some((addr, len)) = *watchpoint {
+                arm_watchpoint(tid, slot, addr, watch_length_bits(len).unwrap())?;
+            }
+        }
         Ok(())
     }
 
-    fn write_byte(&mut self, addr: usize, val: u8) -> Result<u8, nix::Error> {
-        let aligned_addr = align_addr_to_word(addr);
-        let byte_offset = addr - aligned_addr;
-        let word = ptrace::read(self.pid(), aligned_addr as ptrace::AddressType)? as u64;
-        let orig_byte = (word >> 8 * byte_offset) & 0xff;
-        let masked_word = word & !(0xff << 8 * byte_offset);
-        let updated_word = masked_word | ((val as u64) << 8 * byte_offset);
+    /// Moves the current thread to the (link-time) address `addr`. A breakpoint planted there is
+    /// hit as soon as the inferior is resumed, rather than stepped over.
+    pub fn set_instruction_ptr(&mut self, addr: usize) -> Result<(), nix::Error> {
+        let mut regs = ptrace::getregs(self.current_thread)?;
+        regs.rip = self.to_runtime(addr) as u64;
+        self.set_registers(regs)
+    }
+
+    /// Overwrites the registers of the current thread. If this moves the thread, a breakpoint at
+    /// its new address is hit as soon as the inferior is resumed, as with `set_instruction_ptr`.
+    pub fn set_registers(&mut self, regs: user_regs_struct) -> Result<(), nix::Error> {
+        let tid = self.current_thread;
+        let old_rip = ptrace::getregs(tid)?.rip;
+        ptrace::setregs(tid, regs)?;
+        if regs.rip != old_rip {
+            // The int3 (or the original byte of one we were sitting on) takes care of the rest
+            if self.bps.contains_key(&(regs.rip as usize)) {
+                self.rewound_threads.insert(tid);
+            } else {
+                self.rewound_threads.remove(&tid);
+            }
+        }
+        Ok(())
+    }
+
+    /// Makes the stopped inferior fork by running a fork system call in its current thread, and
+    /// returns the new process: a snapshot of this one, breakpoints and all, stopped where this one
+    /// is. This one is left as it was. Fails with ENOTSUP for multithreaded inferiors, since only
+    /// the current thread would be copied.
+    pub fn checkpoint(&mut self) -> Result<Inferior, nix::Error> {
+        if self.threads.len() > 1 {
+            return Err(nix::Error::ENOTSUP);
+        }
+        let tid = self.current_thread;
+        let saved_regs = ptrace::getregs(tid)?;
+        let rip = saved_regs.rip as usize;
+        // Temporarily replace the instruction at rip with `syscall`
+        let orig_bytes = [poke_byte(tid, rip, 0x0f)?, poke_byte(tid, rip + 1, 0x05)?];
+        let mut regs = saved_regs;
+        regs.rax = nix::libc::SYS_fork as u64;
+        // Keep the kernel from restarting a system call the thread was stopped in
+        regs.orig_rax = u64::MAX;
+        let forked = ptrace::setregs(tid, regs).and_then(|()| self.step_over_fork(tid));
+        poke_byte(tid, rip, orig_bytes[0])?;
+        poke_byte(tid, rip + 1, orig_bytes[1])?;
+        ptrace::setregs(tid, saved_regs)?;
+        let new_pid = forked?;
+
+        // The child is a copy of the parent from the middle of the system call
+        poke_byte(new_pid, rip, orig_bytes[0])?;
+        poke_byte(new_pid, rip + 1, orig_bytes[1])?;
+        ptrace::setregs(new_pid, saved_regs)?;
+        ptrace::setoptions(new_pid, ptrace_options())?;
+        let mut checkpoint = Inferior::with_process(Process::Followed(new_pid), new_pid);
+        checkpoint.bps = self.bps.clone();
+        checkpoint.load_base = self.load_base;
+        checkpoint.follow_fork_child = self.follow_fork_child;
+        checkpoint.catch_syscalls = self.catch_syscalls;
+        Ok(checkpoint)
+    }
+
+    /// Sets the current thread up to call the function at (link-time) address `func` with the
+    /// integer or pointer arguments `args` when the inferior is next woken up. The function
+    /// returns to an int3 at the program's entry point, which is reported as `Status::Returned`.
+    /// Fails with EALREADY if a call is already in progress, E2BIG for more than MAX_CALL_ARGS
+    /// arguments, and EBUSY if the thread is stopped in a system call.
+    pub fn start_call(&mut self, func: usize, args: &[u64]) -> Result<(), nix::Error> {
+        if self.call.is_some() {
+            return Err(nix::Error::EALREADY);
+        }
+        if args.len() > MAX_CALL_ARGS {
+            return Err(nix::Error::E2BIG);
+        }
+        let tid = self.current_thread;
+        if self.in_syscall.contains(&tid) {
+            return Err(nix::Error::EBUSY);
+        }
+        let saved_regs = ptrace::getregs(tid)?;
+        let return_addr = self.entry_point()?;
+        // Past the red zone, aligned so that %rsp + 8 is a multiple of 16 on entry, as after a
+        // call instruction
+        let return_slot = ((saved_regs.rsp as usize - RED_ZONE_SIZE) & !0xf) - 8;
+        let orig_word = ptrace::read(tid, return_slot as ptrace::AddressType)?;
         unsafe {
             ptrace::write(
-                self.pid(),
-                aligned_addr as ptrace::AddressType,
-                updated_word as *mut std::ffi::c_void,
+                tid,
+                return_slot as ptrace::AddressType,
+                return_addr as *mut std::ffi::c_void,
             )?;
         }
-        Ok(orig_byte as u8)
+        let orig_byte = poke_byte(tid, return_addr, 0xcc)?;
+
+        let mut regs = saved_regs;
+        let arg_registers = [
+            &mut regs.rdi,
+            &mut regs.rsi,
+            &mut regs.rdx,
+            &mut regs.rcx,
+            &mut regs.r8,
+            &mut regs.r9,
+        ];
+        for (register, &arg) in arg_registers.into_iter().zip(args) {
+            *register = arg;
+        }
+        regs.rsp = return_slot as u64;
+        regs.rip = self.to_runtime(func) as u64;
+        // Variadic functions take the number of vector registers used for arguments in %al
+        regs.rax = 0;
+        // Keep the kernel from restarting a system call the thread was stopped in
+        regs.orig_rax = u64::MAX;
+        self.call = Some(InferiorCall {
+            tid,
+            saved_regs,
+            pending_signal: self.pending_signals.remove(&tid),
+            rewound: self.rewound_threads.contains(&tid),
+            return_addr,
+            orig_byte,
+            return_slot,
+            orig_word,
+        });
+        // A breakpoint at the start of the function is hit
+        self.set_registers(regs)
+    }
+
+    /// Returns true if a function call set up by `start_call` is in progress.
+    pub fn in_call(&self) -> bool {
+        self.call.is_some()
+    }
+
+    /// Puts the thread that made the call in progress (if any) back the way it was before
+    /// `start_call`, abandoning the call. Other changes the called function made, e.g. to global
+    /// variables, stay.
+    pub fn end_call(&mut self) -> Result<(), nix::Error> {
+        let call = match self.call.take() {
+            Some(call) => call,
+            None => return Ok(()),
+        };
+        poke_byte(call.tid, call.return_addr, call.orig_byte)?;
+        unsafe {
+            ptrace::write(
+                call.tid,
+                call.return_slot as ptrace::AddressType,
+                call.orig_word as *mut std::ffi::c_void,
+            )?;
+        }
+        ptrace::setregs(call.tid, call.saved_regs)?;
+        self.current_thread = call.tid;
+        self.in_syscall.remove(&call.tid);
+        // A signal that stopped the called function isn't delivered
+        self.pending_signals.remove(&call.tid);
+        if let Some(signal) = call.pending_signal {
+            self.pending_signals.insert(call.tid, signal);
+        }
+        if call.rewound {
+            self.rewound_threads.insert(call.tid);
+        } else {
+            self.rewound_threads.remove(&call.tid);
+        }
+        Ok(())
+    }
+
+    /// Returns the run-time address of the program's entry point, from its auxiliary vector.
+    fn entry_point(&self) -> Result<usize, nix::Error> {
+        let auxv =
+            std::fs::read(format!("/proc/{}/auxv", self.pid())).map_err(|_| nix::Error::ESRCH)?;
+        // Pairs of words: a type (AT_*), and a value
+        auxv.chunks_exact(2 * size_of::<u64>())
+            .find_map(|pair| {
+                let (key, value) = pair.split_at(size_of::<u64>());
+                (u64::from_ne_bytes(key.try_into().unwrap()) == nix::libc::AT_ENTRY)
+                    .then(|| u64::from_ne_bytes(value.try_into().unwrap()) as usize)
+            })
+            .ok_or(nix::Error::ENOENT)
+    }
+
+    /// Single-steps thread `tid` over the fork system call set up by `checkpoint`, and returns
+    /// the pid of the new process once it has stopped.
+    fn step_over_fork(&mut self, tid: Pid) -> Result<Pid, nix::Error> {
+        let new_pid = loop {
+            ptrace::step(tid, None)?;
+            match waitpid(tid, Some(WaitPidFlag::__WALL))? {
+                WaitStatus::PtraceEvent(_pid, _signal, nix::libc::PTRACE_EVENT_FORK) => {
+                    break Pid::from_raw(ptrace::getevent(tid)? as i32);
+                }
+                // A signal (e.g. SIGCHLD from an earlier checkpoint exiting) arrived before the
+                // system call ran; it is delivered when the inferior is next woken up
+                WaitStatus::Stopped(_pid, signal) if signal != signal::Signal::SIGTRAP => {
+                    self.pending_signals.insert(tid, signal);
+                }
+                _ => return Err(nix::Error::ESRCH),
+            }
+        };
+        // The new process starts out stopped with SIGSTOP
+        waitpid(new_pid, Some(WaitPidFlag::__WALL))?;
+        // Finish the step, which now stops right after the system call
+        ptrace::step(tid, None)?;
+        match waitpid(tid, Some(WaitPidFlag::__WALL))? {
+            WaitStatus::Stopped(_pid, signal::Signal::SIGTRAP) => {}
+            WaitStatus::Stopped(_pid, signal) => {
+                self.pending_signals.insert(tid, signal);
+            }
+            _ => return Err(nix::Error::ESRCH),
+        }
+        Ok(new_pid)
+    }
+
+    /// Forgets about thread `tid`, which has exited.
+    fn remove_thread(&mut self, tid: Pid) {
+        self.threads.retain(|&thread| thread != tid);
+        self.running.remove(&tid);
+        self.stop_requested.remove(&tid);
+        self.pending_signals.remove(&tid);
+        self.in_syscall.remove(&tid);
+        self.rewound_threads.remove(&tid);
+    }
+
+    /// Deals with the inferior having exec'd a new program. By the time the exec is reported, the
+    /// kernel has done away with all other threads, and with the old program's memory (and with it
+    /// the int3s planted in it) and debug registers.
+    fn handle_exec(&mut self) -> Result<Status, nix::Error> {
+        let pid = self.pid();
+        self.threads = vec![pid];
+        self.current_thread = pid;
+        self.running.clear();
+        self.stop_requested.clear();
+        self.early_stops.clear();
+        self.rewound_threads.clear();
+        self.pending_signals.clear();
+        self.in_syscall.clear();
+        self.bps.clear();
+        self.watchpoints = [None; MAX_WATCHPOINTS];
+        self.load_base = self.find_load_base();
+        let regs = ptrace::getregs(pid)?;
+        Ok(Status::Exec(self.to_link_time(regs.rip as usize)))
+    }
+
+    /// Returns the path of the program the inferior is running, which changes when it execs.
+    pub fn executable_path(&self) -> Option<String> {
+        std::fs::read_link(format!("/proc/{}/exe", self.pid()))
+            .ok()
+            .map(|path| path.to_string_lossy().into_owned())
+    }
+
+    /// Deals with the process `new_pid` that the current thread just forked, which starts out
+    /// traced by deet: whichever side of the fork isn't followed is detached. `vfork` is true if
+    /// the two processes share memory until the child execs or exits.
+    fn handle_fork(&mut self, new_pid: Pid, vfork: bool) -> Result<Status, nix::Error> {
+        if !self.follow_fork_child {
+            self.release_process(new_pid, vfork)?;
+            return Ok(Status::Forked(new_pid, false));
+        }
+
+        if !self.early_stops.remove(&new_pid) {
+            waitpid(new_pid, Some(WaitPidFlag::__WALL))?;
+        }
+        // The parent must not be left with our breakpoints (which would kill it with SIGTRAP),
+        // unless it shares its memory with the child
+        if !vfork {
+            for (&addr, orig_byte) in &self.bps {
+                if let Some(orig_byte) = *orig_byte {
+                    poke_byte(self.current_thread, addr, orig_byte)?;
+                }
+            }
+        }
+        self.flush_stop_requests()?;
+        // Debug registers aren't inherited by the child, so move the watchpoints over
+        let watching = self.watchpoints.iter().any(Option::is_some);
+        for &tid in &self.threads {
+            if watching {
+                write_debug_register(tid, 7, 0)?;
+            }
+            ptrace::detach(tid, None)?;
+        }
+        self.process = Process::Followed(new_pid);
+        self.threads = vec![new_pid];
+        self.current_thread = new_pid;
+        self.pending_signals.clear();
+        self.in_syscall.clear();
+        self.rewound_threads.clear();
+        for (slot, watchpoint) in self.watchpoints.iter().enumerate() {
+            if let Some((addr, len)) = *watchpoint {
+                arm_watchpoint(new_pid, slot, addr, watch_length_bits(len).unwrap())?;
+            }
+        }
+        Ok(Status::Forked(new_pid, true))
+    }
+
+    /// Detaches the process `new_pid` that the inferior just forked, once it has stopped, after
+    /// taking our breakpoints out of it (unless it shares the inferior's memory, as after vfork).
+    fn release_process(&mut self, new_pid: Pid, vfork: bool) -> Result<(), nix::Error> {
+        // The new process starts out stopped with SIGSTOP
+        if !self.early_stops.remove(&new_pid) {
+            waitpid(new_pid, Some(WaitPidFlag::__WALL))?;
+        }
+        if !vfork {
+            for (&addr, orig_byte) in &self.bps {
+                if let Some(orig_byte) = *orig_byte {
+                    poke_byte(new_pid, addr, orig_byte)?;
+                }
+            }
+        }
+        ptrace::detach(new_pid, None)
+    }
+
+    /// Kills this inferior.
+    pub fn kill(&mut self) -> Result<(), std::io::Error> {
+        let result = match &mut self.process {
+            Process::Spawned(child) => child.kill(),
+            Process::Attached(pid) | Process::Followed(pid) => {
+                signal::kill(*pid, signal::Signal::SIGKILL).map_err(std::io::Error::from)
+            }
+        };
+        // SIGKILL doesn't wake up threads stopped on their way out (the rest fail with ESRCH)
+        if self.catch_exit {
+            for &tid in &self.threads {
+                let _ = ptrace::cont(tid, None);
+            }
+        }
+        result
+    }
+
+    fn write_byte(&mut self, addr: usize, val: u8) -> Result<u8, nix::Error> {
+        poke_byte(self.current_thread, addr, val)
+    }
+
+    /// Writes `bytes` into the inferior's memory starting at `addr`, touching only those bytes
+    /// (the rest of each affected word is preserved). Bytes that land on a planted breakpoint
+    /// update the breakpoint's saved original byte instead, so the breakpoint stays in place.
+    /// The saved bytes are only updated once the word holding them has been written, so a failed
+    /// write leaves the breakpoints restoring what is really in memory.
+    pub fn write_memory(&mut self, addr: usize, bytes: &[u8]) -> Result<(), nix::Error> {
+        let end = addr.checked_add(bytes.len()).ok_or(nix::Error::EFAULT)?;
+        let mut word_addr = align_addr_to_word(addr);
+        while word_addr < end {
+            let mut word_bytes =
+                (ptrace::read(self.current_thread, word_addr as ptrace::AddressType)? as u64)
+                    .to_ne_bytes();
+            let written =
+                addr.max(word_addr)..end.min(word_addr.saturating_add(size_of::<usize>()));
+            for byte_addr in written.clone() {
+                if !self.bps.contains_key(&byte_addr) {
+                    word_bytes[byte_addr - word_addr] = bytes[byte_addr - addr];
+                }
+            }
+            unsafe {
+                ptrace::write(
+                    self.current_thread,
+                    word_addr as ptrace::AddressType,
+                    u64::from_ne_bytes(word_bytes) as *mut std::ffi::c_void,
+                )?;
+            }
+            for byte_addr in written {
+                if let Some(orig_byte) = self.bps.get_mut(&byte_addr) {
+                    *orig_byte = Some(bytes[byte_addr - addr]);
+                }
+            }
+            word_addr = word_addr.saturating_add(size_of::<usize>());
+        }
+        Ok(())
+    }
+
+    /// Plants an int3 at every resolved address in `breakpoints` and removes those planted at any
+    /// other address, so that this process matches the debugger's breakpoint list. Breakpoints
+    /// sharing an address share one int3. Returns the (link-time) addresses that couldn't be
+    /// updated, with the reason.
+    pub fn sync_breakpoints<'a>(
+        &mut self,
+        breakpoints: impl IntoIterator<Item = &'a Breakpoint>,
+    ) -> Vec<(usize, nix::Error)> {
+        let wanted: HashSet<usize> = breakpoints
+            .into_iter()
+            .filter_map(|bp| bp.addr)
+            .map(|addr| self.to_runtime(addr))
+            .collect();
+        let stale: Vec<usize> = self
+            .bps
+            .keys()
+            .filter(|addr| !wanted.contains(addr))
+            .copied()
+            .collect();
+        let mut failed = Vec::new();
+        for addr in stale {
+            if let Err(err) = self.remove_breakpoint(self.to_link_time(addr)) {
+                failed.push((self.to_link_time(addr), err));
+            }
+        }
+        for addr in wanted {
+            if let Err(err) = self.set_breakpoint(self.to_link_time(addr)) {
+                failed.push((self.to_link_time(addr), err));
+            }
+        }
+        failed
     }
 
-    pub fn set_breakpoint(&mut self, addr: usize) -> Result<(), nix::Error> {
+    /// Sets a breakpoint at the (link-time) address `addr`, unless there already is one.
+    fn set_breakpoint(&mut self, addr: usize) -> Result<(), nix::Error> {
+        let addr = self.to_runtime(addr);
+        // Saving the int3 itself as the original byte would corrupt the instruction for good
         if self.bps.contains_key(&addr) {
             return Ok(());
         }
@@ -173,4 +1830,702 @@ impl Inferior {
         self.bps.insert(addr, Some(orig_byte));
         Ok(())
     }
+
+    /// Removes the breakpoint at `addr`, restoring the original instruction byte.
+    fn remove_breakpoint(&mut self, addr: usize) -> Result<(), nix::Error> {
+        let addr = self.to_runtime(addr);
+        if let Some(Some(orig_byte)) = self.bps.remove(&addr) {
+            self.write_byte(addr, orig_byte)?;
+        }
+        Ok(())
+    }
+
+    /// Sets a hardware watchpoint that stops the inferior after it writes any of the `len` bytes
+    /// at (run-time) address `addr`, and returns the debug register slot it occupies. `len` must
+    /// be 1, 2, 4 or 8, and `addr` must be aligned to it. Fails with ENOSPC if all slots are in
+    /// use.
+    pub fn set_watchpoint(&mut self, addr: usize, len: usize) -> Result<usize, nix::Error> {
+        let len_bits = watch_length_bits(len).ok_or(nix::Error::EINVAL)?;
+        if !addr.is_multiple_of(len) {
+            return Err(nix::Error::EINVAL);
+        }
+        let slot = self
+            .watchpoints
+            .iter()
+            .position(Option::is_none)
+            .ok_or(nix::Error::ENOSPC)?;
+        // Debug registers are per thread
+        for &tid in &self.threads {
+            arm_watchpoint(tid, slot, addr, len_bits)?;
+        }
+        self.watchpoints[slot] = Some((addr, len));
+        Ok(slot)
+    }
+
+    /// Disables the hardware watchpoint in debug register slot `slot`, if it is in use.
+    pub fn remove_watchpoint(&mut self, slot: usize) -> Result<(), nix::Error> {
+        if self.watchpoints[slot].take().is_none() {
+            return Ok(());
+        }
+        for &tid in &self.threads {
+            let dr7 = read_debug_register(tid, 7)?;
+            write_debug_register(
+                tid,
+                7,
+                dr7 & !(1 << (2 * slot)) & !(0b1111 << (16 + 4 * slot)),
+            )?;
+            write_debug_register(tid, slot, 0)?;
+        }
+        Ok(())
+    }
+
+    /// Checks the current thread's DR6 to see whether its last SIGTRAP was caused by one of our
+    /// watchpoints, and returns its slot if so. DR6 is cleared afterwards, since the CPU never
+    /// clears it itself.
+    fn triggered_watchpoint(&self) -> Result<Option<usize>, nix::Error> {
+        if self.watchpoints.iter().all(Option::is_none) {
+            return Ok(None);
+        }
+        let dr6 = read_debug_register(self.current_thread, 6)?;
+        write_debug_register(self.current_thread, 6, 0)?;
+        Ok((0..MAX_WATCHPOINTS)
+            .find(|&slot| dr6 & (1 << slot) != 0 && self.watchpoints[slot].is_some()))
+    }
+
+    /// Reads up to `len` bytes of code starting at the (link-time) address `addr`, as the program
+    /// has them: planted breakpoints show the original byte rather than 0xcc. Fewer bytes are
+    /// returned if the end of the mapping is reached first.
+    pub fn read_code(&self, addr: usize, len: usize) -> Result<Vec<u8>, nix::Error> {
+        let addr = self.to_runtime(addr);
+        let mut bytes = Vec::with_capacity(len);
+        while bytes.len() < len {
+            // read up to the next page boundary at a time
+            let chunk_addr = addr + bytes.len();
+            let chunk_len = bytes_to_page_end(chunk_addr).min(len - bytes.len());
+            match self.read_memory_bulk(chunk_addr, chunk_len) {
+                Ok(chunk) => bytes.extend(chunk),
+                Err(err) if bytes.is_empty() => return Err(err),
+                Err(_) => break,
+            }
+        }
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            if let Some(Some(orig_byte)) = self.bps.get(&(addr + i)) {
+                *byte = *orig_byte;
+            }
+        }
+        Ok(bytes)
+    }
+}
+
+impl Target for Inferior {
+    /// Returns the offset between the executable's run-time and link-time addresses.
+    fn load_base(&self) -> usize {
+        self.load_base
+    }
+
+    /// Returns the inferior's memory mappings, as listed in /proc/<pid>/maps.
+    fn memory_regions(&self) -> Option<Vec<MemoryRegion>> {
+        let maps = std::fs::read_to_string(format!("/proc/{}/maps", self.pid())).ok()?;
+        maps.lines().map(MemoryRegion::parse).collect()
+    }
+
+    /// Returns the inferior's current register values.
+    fn get_registers(&self) -> Result<user_regs_struct, nix::Error> {
+        ptrace::getregs(self.current_thread)
+    }
+
+    /// Reads `len` bytes of the inferior's memory starting at `addr`.
+    fn read_memory(&self, addr: usize, len: usize) -> Result<Vec<u8>, nix::Error> {
+        let mut bytes = Vec::with_capacity(len);
+        let mut word_addr = align_addr_to_word(addr);
+        let mut skip = addr - word_addr;
+        while bytes.len() < len {
+            let word = ptrace::read(self.current_thread, word_addr as ptrace::AddressType)?;
+            let word_bytes = word.to_ne_bytes();
+            let take = (size_of::<usize>() - skip).min(len - bytes.len());
+            bytes.extend_from_slice(&word_bytes[skip..skip + take]);
+            word_addr += size_of::<usize>();
+            skip = 0;
+        }
+        Ok(bytes)
+    }
+
+    /// Reads `len` bytes of the inferior's memory starting at `addr` with a single
+    /// process_vm_readv call, which is much faster than `read_memory` for more than a few words.
+    /// Falls back to `read_memory` if the kernel doesn't allow process_vm_readv, or if only part
+    /// of the range could be read (so that the error comes from the first unreadable word).
+    fn read_memory_bulk(&self, addr: usize, len: usize) -> Result<Vec<u8>, nix::Error> {
+        let mut bytes = vec![0; len];
+        let remote_iov = [RemoteIoVec { base: addr, len }];
+        let result = uio::process_vm_readv(
+            self.current_thread,
+            &mut [IoSliceMut::new(&mut bytes)],
+            &remote_iov,
+        );
+        match result {
+            Ok(read) if read == len => Ok(bytes),
+            Ok(_) | Err(nix::Error::ENOSYS) | Err(nix::Error::EPERM) => self.read_memory(addr, len),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Returns information about the signal that last stopped the inferior.
+    fn get_siginfo(&self) -> Result<siginfo_t, nix::Error> {
+        ptrace::getsiginfo(self.current_thread)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn load_sample(program: &str) -> DwarfData {
+        DwarfData::from_file(program)
+            .unwrap_or_else(|_| panic!("Could not load {}. Have you run make?", program))
+    }
+
+    fn breakpoint(addr: usize, temporary: bool) -> Breakpoint {
+        Breakpoint {
+            location: format!("*{:#x}", addr),
+            addr: Some(addr),
+            condition: None,
+            temporary,
+            ignore_count: 0,
+            skipped_hits: 0,
+            commands: Vec::new(),
+        }
+    }
+
+    /// Starts `program` with a breakpoint at `addr` and runs it until the breakpoint is hit.
+    fn run_to(program: &str, addr: usize) -> Inferior {
+        let mut inferior = Inferior::new(program, &[], InferiorStdio::default(), true)
+            .expect("Failed to start inferior");
+        inferior.set_breakpoint(addr).unwrap();
+        match inferior.wake_and_wait() {
+            Ok(Status::Breakpoint(rip)) => assert_eq!(rip, addr),
+            _ => panic!("Inferior did not stop at the breakpoint"),
+        }
+        inferior
+    }
+
+    #[test]
+    fn test_duplicate_breakpoint_keeps_original_byte() {
+        let dwarf_data = load_sample("samples/function_calls");
+        let addr = dwarf_data.lookup_line(None, 11).unwrap();
+        let mut inferior = run_to("samples/function_calls", addr);
+        let orig_byte = inferior.bps[&addr];
+        let breakpoints = [breakpoint(addr, false), breakpoint(addr, true)];
+        assert!(inferior.sync_breakpoints(&breakpoints).is_empty());
+        assert_eq!(inferior.bps[&addr], orig_byte);
+        assert_ne!(orig_byte, Some(0xcc));
+        // Deleting one of them leaves the other planted; deleting both restores the byte
+        assert!(inferior.sync_breakpoints(&breakpoints[1..]).is_empty());
+        assert_eq!(inferior.bps[&addr], orig_byte);
+        assert!(inferior.sync_breakpoints(&[]).is_empty());
+        assert_eq!(
+            inferior.read_memory(addr, 1).unwrap(),
+            vec![orig_byte.unwrap()]
+        );
+        // A leftover int3 would trap instead of letting the program finish
+        assert!(matches!(inferior.wake_and_wait(), Ok(Status::Exited(0))));
+    }
+
+    #[test]
+    fn test_run_time_excludes_stops() {
+        let dwarf_data = load_sample("samples/function_calls");
+        let addr = dwarf_data.lookup_line(None, 11).unwrap();
+        let mut inferior = run_to("samples/function_calls", addr);
+        assert_eq!(inferior.cpu_time(), None);
+        std::thread::sleep(Duration::from_millis(300));
+        assert!(inferior.sync_breakpoints(&[]).is_empty());
+        assert!(matches!(inferior.wake_and_wait(), Ok(Status::Exited(0))));
+        assert!(inferior.run_time() > Duration::ZERO);
+        assert!(inferior.run_time() < Duration::from_millis(300));
+        let (user, sys) = inferior.cpu_time().unwrap();
+        assert!(user + sys < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_jump_cooperates_with_breakpoints() {
+        let dwarf_data = load_sample("samples/function_calls");
+        let line = |number| dwarf_data.lookup_line(None, number).unwrap();
+        let mut inferior = run_to("samples/function_calls", line(11));
+        inferior.set_breakpoint(line(13)).unwrap();
+        // Jumping from one breakpoint onto another stops there right away
+        inferior.set_instruction_ptr(line(13)).unwrap();
+        assert!(matches!(inferior.wake_and_wait(), Ok(Status::Breakpoint(rip)) if rip == line(13)));
+        inferior.set_instruction_ptr(line(11)).unwrap();
+        assert!(matches!(inferior.wake_and_wait(), Ok(Status::Breakpoint(rip)) if rip == line(11)));
+        // Jumping away from a breakpoint leaves it planted
+        inferior.set_instruction_ptr(line(12)).unwrap();
+        assert!(matches!(inferior.wake_and_wait(), Ok(Status::Breakpoint(rip)) if rip == line(13)));
+        inferior.set_instruction_ptr(line(10)).unwrap();
+        assert!(matches!(inferior.wake_and_wait(), Ok(Status::Breakpoint(rip)) if rip == line(11)));
+        let _ = inferior.kill();
+    }
+
+    #[test]
+    fn test_parse_memory_region() {
+        let region =
+            MemoryRegion::parse("00400000-00401000 r-xp 00001000 08:01 1234    /tmp/my prog")
+                .unwrap();
+        assert_eq!((region.start, region.end), (0x400000, 0x401000));
+        assert!(region.is_executable());
+        assert!(region.contains(0x400fff) && !region.contains(0x401000));
+        assert_eq!(region.path, "/tmp/my prog");
+        assert_eq!(region.offset, 0x1000);
+        let anonymous = MemoryRegion::parse("7ffd0000-7ffe0000 rw-p 00000000 00:00 0").unwrap();
+        assert!(!anonymous.is_executable());
+        assert_eq!(anonymous.path, "");
+    }
+
+    #[test]
+    fn test_backtrace_top_frame_format() {
+        let dwarf_data = load_sample("samples/function_calls");
+        let addr = dwarf_data.lookup_line(None, 11).unwrap();
+        let mut inferior = run_to("samples/function_calls", addr);
+        let frames = inferior
+            .stack_frames(&dwarf_data, MAX_BACKTRACE_FRAMES)
+            .unwrap();
+        assert_eq!(
+            inferior.format_frame(&frames[0], 0, &dwarf_data),
+            format!(
+                "#0  {:#018x} in func2 (a=42, b=5) at {}/samples/function_calls.c:11",
+                addr,
+                env!("CARGO_MANIFEST_DIR")
+            )
+        );
+        let _ = inferior.kill();
+    }
+
+    #[test]
+    fn test_backtrace_through_frame_without_debug_info() {
+        let dwarf_data = load_sample("samples/callback");
+        let addr = dwarf_data.lookup_line(Some("callback.c"), 6).unwrap();
+        let mut inferior = run_to("samples/callback", addr);
+        let frames = inferior
+            .stack_frames(&dwarf_data, MAX_BACKTRACE_FRAMES)
+            .unwrap();
+        let formatted: Vec<String> = frames
+            .iter()
+            .enumerate()
+            .map(|(num, frame)| inferior.format_frame(frame, num, &dwarf_data))
+            .collect();
+        assert_eq!(formatted.len(), 3, "unexpected backtrace: {:?}", formatted);
+        assert!(formatted[0].contains(" in callback (value=21) at "));
+        assert!(formatted[1].starts_with("#1  0x") && formatted[1].ends_with(" in ?? ()"));
+        assert!(formatted[2].contains(" in main () at "));
+        let _ = inferior.kill();
+    }
+
+    #[test]
+    fn test_backtrace_without_frame_pointers() {
+        let backtrace = |program: &str| {
+            let dwarf_data = load_sample(program);
+            let addr = dwarf_data.get_addr_for_function(None, "bottom").unwrap();
+            let mut inferior = run_to(program, addr);
+            let (frames, reason) = inferior
+                .unwind_stack(&dwarf_data, MAX_BACKTRACE_FRAMES)
+                .unwrap();
+            assert_eq!(reason, None);
+            let names: Vec<String> = frames
+                .iter()
+                .map(|frame| {
+                    dwarf_data
+                        .get_function_from_addr(frame.instruction_ptr)
+                        .unwrap_or_default()
+                })
+                .collect();
+            let args = inferior.frame_arguments(&frames[1], &dwarf_data);
+            let _ = inferior.kill();
+            (names, args)
+        };
+        let (names, args) = backtrace("samples/recursion");
+        assert_eq!(
+            names,
+            ["bottom", "recurse", "recurse", "recurse", "recurse", "main"]
+        );
+        assert_eq!(args.as_deref(), Some("n=0, depth=3"));
+        // Optimized code keeps rbp for other values, so this needs the call frame information
+        assert_eq!(backtrace("samples/recursion_opt").0, names);
+    }
+
+    #[test]
+    fn test_backtrace_stops_on_corrupted_frame_pointer() {
+        let dwarf_data = load_sample("samples/function_calls");
+        let addr = dwarf_data.lookup_line(None, 11).unwrap();
+        let mut inferior = run_to("samples/function_calls", addr);
+        let regs = ptrace::getregs(inferior.pid()).unwrap();
+        let check = |inferior: &Inferior, rbp: u64, expected: &str| {
+            let mut regs = regs;
+            regs.rbp = rbp;
+            ptrace::setregs(inferior.pid(), regs).unwrap();
+            let (frames, reason) = inferior
+                .unwind_stack(&dwarf_data, MAX_BACKTRACE_FRAMES)
+                .unwrap();
+            assert_eq!(frames.len(), 1);
+            assert_eq!(reason.as_deref(), Some(expected));
+        };
+        check(&inferior, regs.rbp + 1, "frame address is not word-aligned");
+        check(&inferior, 0x1000, "frame address outside stack bounds");
+        // A saved rbp pointing back down the stack would otherwise make the walk loop
+        inferior
+            .write_memory(regs.rbp as usize, &(regs.rbp - 16).to_le_bytes())
+            .unwrap();
+        check(
+            &inferior,
+            regs.rbp,
+            "previous frame inner to this frame (corrupt stack?)",
+        );
+        let _ = inferior.kill();
+    }
+
+    #[test]
+    fn test_backtrace_with_return_address_at_load_base() {
+        let dwarf_data = load_sample("samples/pie");
+        let addr = dwarf_data.get_addr_for_function(None, "bump").unwrap();
+        let mut inferior = run_to("samples/pie", addr);
+        let regs = ptrace::getregs(inferior.pid()).unwrap();
+        // Stopped on bump's first instruction, with the return address on top of the stack. A
+        // return address at the load base has link-time address 0, with nothing before it.
+        let load_base = inferior.load_base();
+        inferior
+            .write_memory(regs.rsp as usize, &load_base.to_le_bytes())
+            .unwrap();
+        let (frames, _) = inferior
+            .unwind_stack(&dwarf_data, MAX_BACKTRACE_FRAMES)
+            .unwrap();
+        assert_eq!(frames[0].instruction_ptr, addr);
+        assert!(frames.len() > 1);
+        let _ = inferior.kill();
+    }
+
+    #[test]
+    fn test_read_cstring() {
+        let dwarf_data = load_sample("samples/strings");
+        let addr = dwarf_data.get_addr_for_function(None, "greet").unwrap();
+        let mut inferior = run_to("samples/strings", addr);
+        let global_addr = |name: &str| {
+            let var = dwarf_data.get_global_variable(name).unwrap();
+            var.location.address(0, inferior.load_base()).unwrap()
+        };
+        let read_pointer = |name: &str| {
+            let bytes = inferior.read_memory(global_addr(name), 8).unwrap();
+            usize::from_le_bytes(bytes.try_into().unwrap())
+        };
+        let greeting = read_pointer("greeting");
+        assert_eq!(
+            inferior.read_cstring(greeting, 200).unwrap(),
+            b"Hello, world!"
+        );
+        // Strings may start and stop partway through a word
+        assert_eq!(inferior.read_cstring(greeting + 7, 200).unwrap(), b"world!");
+        assert_eq!(inferior.read_cstring(greeting + 3, 6).unwrap(), b"lo, wo");
+        assert!(inferior
+            .read_cstring(read_pointer("dangling"), 200)
+            .is_err());
+        let _ = inferior.kill();
+    }
+
+    #[test]
+    fn test_read_memory_bulk() {
+        const LEN: usize = 64 * 1024;
+        let dwarf_data = load_sample("samples/function_calls");
+        let addr = dwarf_data.get_addr_for_function(None, "main").unwrap();
+        let mut inferior = run_to("samples/function_calls", addr);
+        let region = inferior
+            .memory_regions()
+            .unwrap()
+            .into_iter()
+            .find(|region| region.path.contains("libc") && region.end - region.start >= LEN)
+            .expect("No mapping of libc large enough to read");
+        // Start partway through a word so that the word-at-a-time path trims both ends
+        let start = region.start + 3;
+        let len = LEN - 8;
+        let by_word = inferior.read_memory(start, len).unwrap();
+        let bulk = inferior.read_memory_bulk(start, len).unwrap();
+        assert!(by_word == bulk, "bulk read returned different bytes");
+        // Unmapped memory is still an error
+        assert!(inferior.read_memory_bulk(0x10, 16).is_err());
+        let _ = inferior.kill();
+    }
+
+    #[test]
+    fn test_signals_delivered_on_continue() {
+        for (deliver, expected_status) in [(true, 0), (false, 1)] {
+            let mut inferior = Inferior::new("samples/signal", &[], InferiorStdio::default(), true)
+                .expect("Failed to start samples/signal. Have you run make?");
+            match inferior.wake_and_wait() {
+                Ok(Status::Stopped(signal::Signal::SIGUSR1, _)) => {}
+                _ => panic!("Inferior did not stop with SIGUSR1"),
+            }
+            assert_eq!(inferior.pending_signal(), Some(signal::Signal::SIGUSR1));
+            if !deliver {
+                inferior.discard_pending_signal();
+            }
+            match inferior.wake_and_wait() {
+                Ok(Status::Exited(status)) => assert_eq!(status, expected_status),
+                _ => panic!("Inferior did not exit"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_describe_faults() {
+        for (program, expected_signal, expected) in [
+            (
+                "samples/segfault",
+                signal::Signal::SIGSEGV,
+                "Segmentation fault: invalid access at 0x0000000000000000",
+            ),
+            (
+                "samples/divide",
+                signal::Signal::SIGFPE,
+                "Arithmetic exception: integer divide by zero at 0x",
+            ),
+        ] {
+            let mut inferior = Inferior::new(program, &[], InferiorStdio::default(), true)
+                .unwrap_or_else(|_| panic!("Failed to start {}. Have you run make?", program));
+            match inferior.wake_and_wait() {
+                Ok(Status::Stopped(signal, _)) => {
+                    assert_eq!(signal, expected_signal);
+                    let description = inferior.describe_fault(signal).unwrap().unwrap();
+                    assert!(description.starts_with(expected), "got {}", description);
+                }
+                _ => panic!("{} did not stop with {}", program, expected_signal),
+            }
+            let _ = inferior.kill();
+        }
+    }
+
+    #[test]
+    fn test_position_independent_executable() {
+        let dwarf_data = load_sample("samples/pie");
+        let addr = dwarf_data.lookup_line(None, 7).unwrap();
+        let mut inferior = run_to("samples/pie", addr);
+        assert_ne!(inferior.load_base(), 0);
+        let frames = inferior
+            .stack_frames(&dwarf_data, MAX_BACKTRACE_FRAMES)
+            .unwrap();
+        assert_eq!(frames[0].instruction_ptr, addr);
+        let formatted = inferior.format_frame(&frames[0], 0, &dwarf_data);
+        assert!(
+            formatted.contains(" in bump (amount=3) at "),
+            "got {}",
+            formatted
+        );
+        // Globals are relocated along with the code
+        let counter = dwarf_data.get_global_variable("counter").unwrap();
+        let counter_addr = counter.location.address(0, inferior.load_base()).unwrap();
+        assert_eq!(
+            inferior.read_memory(counter_addr, 4).unwrap(),
+            3i32.to_le_bytes()
+        );
+        let _ = inferior.kill();
+    }
+
+    #[test]
+    fn test_watchpoints() {
+        let dwarf_data = load_sample("samples/pie");
+        let mut inferior = run_to("samples/pie", dwarf_data.lookup_line(None, 11).unwrap());
+        let counter = dwarf_data.get_global_variable("counter").unwrap();
+        let counter_addr = counter.location.address(0, inferior.load_base()).unwrap();
+        assert!(matches!(
+            inferior.set_watchpoint(counter_addr + 1, 4),
+            Err(nix::Error::EINVAL)
+        ));
+        let slot = inferior.set_watchpoint(counter_addr, 4).unwrap();
+        for expected in [3i32, 7] {
+            match inferior.wake_and_wait() {
+                Ok(Status::Watchpoint(hit_slot, instruction_ptr)) => {
+                    assert_eq!(hit_slot, slot);
+                    assert_eq!(
+                        dwarf_data
+                            .get_line_from_addr(instruction_ptr)
+                            .unwrap()
+                            .number,
+                        7
+                    );
+                }
+                _ => panic!("Inferior did not stop at the watchpoint"),
+            }
+            assert_eq!(
+                inferior.read_memory(counter_addr, 4).unwrap(),
+                expected.to_le_bytes()
+            );
+        }
+        // Only four debug registers to go around
+        for _ in 1..MAX_WATCHPOINTS {
+            inferior.set_watchpoint(counter_addr, 4).unwrap();
+        }
+        assert!(matches!(
+            inferior.set_watchpoint(counter_addr, 4),
+            Err(nix::Error::ENOSPC)
+        ));
+        for slot in 0..MAX_WATCHPOINTS {
+            inferior.remove_watchpoint(slot).unwrap();
+        }
+        assert!(matches!(inferior.wake_and_wait(), Ok(Status::Exited(0))));
+    }
+
+    #[test]
+    fn test_catch_syscalls() {
+        const SYS_WRITE: usize = 1;
+        let mut inferior = Inferior::new("samples/hello", &[], InferiorStdio::default(), true)
+            .expect("Failed to start samples/hello. Have you run make?");
+        inferior.set_catch_syscalls(true);
+        loop {
+            match inferior.wake_and_wait() {
+                Ok(Status::SyscallEntry(SYS_WRITE)) => break,
+                Ok(Status::SyscallEntry(_)) | Ok(Status::SyscallExit(_)) => {}
+                _ => panic!("Inferior did not stop at the write system call"),
+            }
+        }
+        assert!(matches!(
+            inferior.wake_and_wait(),
+            Ok(Status::SyscallExit(SYS_WRITE))
+        ));
+        // "Hello world!\n"
+        assert_eq!(inferior.get_registers().unwrap().rax, 13);
+        inferior.set_catch_syscalls(false);
+        assert!(matches!(inferior.wake_and_wait(), Ok(Status::Exited(0))));
+    }
+
+    #[test]
+    fn test_start_call() {
+        let dwarf_data = load_sample("samples/call");
+        let main = dwarf_data.get_addr_for_function(None, "main").unwrap();
+        let add = dwarf_data.get_addr_for_function(None, "add").unwrap();
+        let mut inferior = Inferior::new("samples/call", &[], InferiorStdio::default(), true)
+            .expect("Failed to start samples/call. Have you run make?");
+        assert!(inferior
+            .sync_breakpoints(&[breakpoint(main, false)])
+            .is_empty());
+        assert_eq!(inferior.wake_and_wait(), Ok(Status::Breakpoint(main)));
+        let regs = inferior.get_registers().unwrap();
+
+        inferior.start_call(add, &[40, 2]).unwrap();
+        assert_eq!(inferior.start_call(add, &[1, 2]), Err(nix::Error::EALREADY));
+        assert_eq!(inferior.wake_and_wait(), Ok(Status::Returned(42)));
+        assert!(!inferior.in_call());
+        let restored = inferior.get_registers().unwrap();
+        assert_eq!((restored.rip, restored.rsp), (regs.rip, regs.rsp));
+        assert_eq!(inferior.wake_and_wait(), Ok(Status::Exited(0)));
+    }
+
+    #[test]
+    fn test_catch_exit() {
+        let mut inferior = Inferior::new("samples/exec", &[], InferiorStdio::default(), true)
+            .expect("Failed to start samples/exec. Have you run make?");
+        inferior.set_catch_exit(true).unwrap();
+        match inferior.wake_and_wait() {
+            Ok(Status::Exiting(3, _)) => {}
+            other => panic!("Inferior did not stop at its exit: {:?}", other),
+        }
+        assert_eq!(inferior.wake_and_wait(), Ok(Status::Exited(3)));
+    }
+
+    #[test]
+    fn test_exec() {
+        let args = ["samples/function_calls".to_string()];
+        let mut inferior = Inferior::new("samples/exec", &args, InferiorStdio::default(), true)
+            .expect("Failed to start samples/exec. Have you run make?");
+        assert!(matches!(inferior.wake_and_wait(), Ok(Status::Exec(_))));
+        assert!(inferior
+            .executable_path()
+            .unwrap()
+            .ends_with("samples/function_calls"));
+        // Breakpoints planted in the new program work as usual
+        let dwarf_data = load_sample("samples/function_calls");
+        let func2 = dwarf_data.get_addr_for_function(None, "func2").unwrap();
+        assert!(inferior
+            .sync_breakpoints(&[breakpoint(func2, false)])
+            .is_empty());
+        assert_eq!(inferior.wake_and_wait(), Ok(Status::Breakpoint(func2)));
+    }
+
+    #[test]
+    fn test_follow_fork() {
+        let dwarf_data = load_sample("samples/fork");
+        let child_line = dwarf_data.lookup_line(None, 8).unwrap();
+        let parent_line = dwarf_data.lookup_line(None, 13).unwrap();
+        for follow_child in [false, true] {
+            let mut inferior = Inferior::new("samples/fork", &[], InferiorStdio::default(), true)
+                .expect("Failed to start samples/fork. Have you run make?");
+            let breakpoints = [
+                breakpoint(child_line, false),
+                breakpoint(parent_line, false),
+            ];
+            assert!(inferior.sync_breakpoints(&breakpoints).is_empty());
+            inferior.set_follow_fork_child(follow_child);
+            let new_pid = match inferior.wake_and_wait() {
+                Ok(Status::Forked(new_pid, following_child)) => {
+                    assert_eq!(following_child, follow_child);
+                    new_pid
+                }
+                _ => panic!("Inferior did not fork"),
+            };
+            if follow_child {
+                assert_eq!(inferior.pid(), new_pid);
+                assert!(
+                    matches!(inferior.wake_and_wait(), Ok(Status::Breakpoint(addr)) if addr == child_line)
+                );
+            } else {
+                // The detached child must run to completion without tripping over breakpoints
+                loop {
+                    match inferior.wake_and_wait() {
+                        Ok(Status::Stopped(signal::Signal::SIGCHLD, _)) => {}
+                        Ok(Status::Breakpoint(addr)) if addr == parent_line => break,
+                        _ => panic!("Parent did not stop at its breakpoint"),
+                    }
+                }
+            }
+            // Both processes exit with the child's status
+            assert!(matches!(inferior.wake_and_wait(), Ok(Status::Exited(3))));
+        }
+    }
+
+    #[test]
+    fn test_disable_aslr() {
+        let load_bases: Vec<usize> = (0..2)
+            .map(|_| {
+                let mut inferior =
+                    Inferior::new("samples/pie", &[], InferiorStdio::default(), true)
+                        .expect("Failed to start samples/pie. Have you run make?");
+                let load_base = inferior.load_base();
+                let _ = inferior.kill();
+                load_base
+            })
+            .collect();
+        assert_eq!(load_bases[0], load_bases[1]);
+    }
+
+    #[test]
+    fn test_threads() {
+        let dwarf_data = load_sample("samples/threads");
+        let addr = dwarf_data.lookup_line(None, 8).unwrap();
+        let mut inferior = run_to("samples/threads", addr);
+        let mut workers = HashSet::new();
+        loop {
+            // The worker thread that hit the breakpoint is current, and its stack is walked
+            let tid = inferior.current_thread();
+            assert_ne!(tid, inferior.pid());
+            assert!(inferior.threads().contains(&tid));
+            assert_eq!(inferior.threads()[0], inferior.pid());
+            let frames = inferior.stack_frames(&dwarf_data, 1).unwrap();
+            assert_eq!(
+                dwarf_data
+                    .get_function_from_addr(frames[0].instruction_ptr)
+                    .as_deref(),
+                Some("worker")
+            );
+            workers.insert(tid);
+            match inferior.wake_and_wait() {
+                Ok(Status::Breakpoint(rip)) => assert_eq!(rip, addr),
+                Ok(Status::Exited(3)) => break,
+                _ => panic!("Inferior did not stop at the breakpoint or exit"),
+            }
+        }
+        assert_eq!(workers.len(), 2);
+    }
 }
@@ -1,24 +1,71 @@
+mod address;
+mod condition;
+mod config;
+mod core_file;
 mod debugger;
 mod debugger_command;
+mod demangle;
+mod disassemble;
 mod dwarf_data;
+mod expression;
 mod gimli_wrapper;
 mod inferior;
+mod memory_search;
+mod output;
+mod proc_info;
+mod registers;
+mod shared_library;
+mod signal_policy;
+mod source;
+mod syscalls;
+mod unwind;
+mod value;
 
+use crate::config::{DebuggerConfig, Invocation};
 use crate::debugger::Debugger;
 use nix::sys::signal::{signal, SigHandler, Signal};
 use std::env;
+use std::io::IsTerminal;
 
 fn main() {
     let args: Vec<String> = env::args().collect();
-    if args.len() != 2 {
-        println!("Usage: {} <target program>", args[0]);
-        std::process::exit(1);
-    }
-    let target = &args[1];
+    let config = match DebuggerConfig::parse(&args[1..]) {
+        Ok(Invocation::Debug(config)) => config,
+        Ok(Invocation::Help) => {
+            println!("{}", config::usage(&args[0]));
+            return;
+        }
+        Ok(Invocation::Version) => {
+            println!("deet {}", env!("CARGO_PKG_VERSION"));
+            return;
+        }
+        Err(err) => {
+            eprintln!("{}\nTry \"{} --help\" for more information.", err, args[0]);
+            std::process::exit(1);
+        }
+    };
 
     // Disable handling of ctrl+c in this process (so that ctrl+c only gets delivered to child
     // processes)
     unsafe { signal(Signal::SIGINT, SigHandler::SigIgn) }.expect("Error disabling SIGINT handling");
 
-    Debugger::new(target).run();
+    let mut debugger = Debugger::new(&config);
+    // A script that quits (or input that isn't a terminal) means nobody is at the prompt, so
+    // report how the inferior ended through our own exit status
+    if config.load_init_files && !debugger.load_init_files() {
+        std::process::exit(debugger.exit_code(true));
+    }
+    if let Some(core) = &config.core {
+        debugger.load_core(core);
+    }
+    for script in &config.scripts {
+        if !debugger.source_script(script, true) {
+            std::process::exit(debugger.exit_code(true));
+        }
+    }
+    if config.batch {
+        std::process::exit(debugger.exit_code(true));
+    }
+    debugger.run();
+    std::process::exit(debugger.exit_code(!std::io::stdin().is_terminal()));
 }
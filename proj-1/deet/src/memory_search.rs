@@ -0,0 +1,221 @@
+//! Searching the inferior's memory for a byte pattern, for `find`.
+
+use crate::condition;
+use crate::inferior::MemoryRegion;
+
+/// How many bytes are read from the inferior at a time
+const CHUNK_SIZE: usize = 1 << 20;
+
+/// How many matches `find` reports unless told otherwise
+pub const DEFAULT_MAX_MATCHES: usize = 100;
+
+/// Turns the values given to `find` into the bytes to look for. A value is a string literal
+/// (without its NUL terminator) or an integer stored in `value_size` bytes, little-endian; an
+/// integer without a size takes 4 bytes (as an int or unsigned int would), or 8 if it doesn't
+/// fit in 4.
+pub fn parse_pattern(values: &[String], value_size: Option<usize>) -> Result<Vec<u8>, String> {
+    let mut pattern = Vec::new();
+    for value in values {
+        if let Some(literal) = value.strip_prefix('"') {
+            let literal = literal
+                .strip_suffix('"')
+                .ok_or_else(|| format!("Unterminated string {}", value))?;
+            pattern.extend(unescape(literal)?);
+            continue;
+        }
+        let integer = condition::parse_literal(value)
+            .ok_or_else(|| format!("Expected a string or an integer, not \"{}\".", value))?;
+        let fits_in_int = i32::try_from(integer).is_ok() || u32::try_from(integer).is_ok();
+        let size = value_size.unwrap_or(if fits_in_int { 4 } else { 8 });
+        if size < 8 && (integer >> (8 * size)) != 0 && (integer >> (8 * size - 1)) != -1 {
+            return Err(format!("{} doesn't fit in {} byte(s).", value, size));
+        }
+        pattern.extend_from_slice(&integer.to_le_bytes()[..size]);
+    }
+    if pattern.is_empty() {
+        return Err("Empty search pattern.".to_string());
+    }
+    Ok(pattern)
+}
+
+/// Decodes the escapes `\n`, `\t`, `\r`, `\0`, `\\`, `\"` and `\xHH` in a string literal.
+fn unescape(literal: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    let mut chars = literal.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buf = [0; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            continue;
+        }
+        bytes.push(match chars.next() {
+            Some('n') => b'\n',
+            Some('t') => b'\t',
+            Some('r') => b'\r',
+            Some('0') => 0,
+            Some('\\') => b'\\',
+            Some('"') => b'"',
+            Some('x') => {
+                let hex: String = chars.by_ref().take(2).collect();
+                u8::from_str_radix(&hex, 16)
+                    .map_err(|_| format!("Bad escape \\x{} in \"{}\"", hex, literal))?
+            }
+            other => {
+                return Err(format!(
+                    "Unknown escape \\{} in \"{}\"",
+                    other.map_or(String::new(), String::from),
+                    literal
+                ))
+            }
+        });
+    }
+    Ok(bytes)
+}
+
+/// Returns the parts of `[start, end)` that lie in readable mappings, merging mappings that are
+/// next to each other so that a match may straddle them. Without a list of mappings, the whole
+/// range is tried.
+pub fn readable_ranges(
+    regions: Option<&[MemoryRegion]>,
+    start: usize,
+    end: usize,
+) -> Vec<(usize, usize)> {
+    let regions = match regions {
+        Some(regions) => regions,
+        None => return vec![(start, end)],
+    };
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for region in regions
+        .iter()
+        .filter(|region| region.perms.starts_with('r'))
+    {
+        let (from, to) = (region.start.max(start), region.end.min(end));
+        if from >= to {
+            continue;
+        }
+        match ranges.last_mut() {
+            Some(last) if last.1 == from => last.1 = to,
+            _ => ranges.push((from, to)),
+        }
+    }
+    ranges
+}
+
+/// Looks for `pattern` in `ranges`, reading memory with `read(addr, len)`. Chunks that can't be
+/// read are skipped. Returns the addresses of at most `max_matches` matches, and whether there
+/// were more.
+pub fn search(
+    ranges: &[(usize, usize)],
+    pattern: &[u8],
+    max_matches: usize,
+    read: impl Fn(usize, usize) -> Option<Vec<u8>>,
+) -> (Vec<usize>, bool) {
+    let mut matches = Vec::new();
+    for &(start, end) in ranges {
+        let mut chunk_start = start;
+        while chunk_start < end {
+            // Chunks overlap by one byte less than the pattern, to catch matches across them
+            let chunk_end = chunk_start.saturating_add(CHUNK_SIZE).min(end);
+            let read_end = chunk_end.saturating_add(pattern.len() - 1).min(end);
+            if let Some(bytes) = read(chunk_start, read_end - chunk_start) {
+                for (offset, window) in bytes.windows(pattern.len()).enumerate() {
+                    if chunk_start + offset >= chunk_end {
+                        break;
+                    }
+                    if window == pattern {
+                        if matches.len() == max_matches {
+                            return (matches, true);
+                        }
+                        matches.push(chunk_start + offset);
+                    }
+                }
+            }
+            chunk_start = chunk_end;
+        }
+    }
+    (matches, false)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_pattern() {
+        assert_eq!(
+            parse_pattern(&strings(&["\"MAG\\x49C\""]), None),
+            Ok(b"MAGIC".to_vec())
+        );
+        assert_eq!(
+            parse_pattern(&strings(&["0xdeadbeef"]), None),
+            Ok(vec![0xef, 0xbe, 0xad, 0xde])
+        );
+        assert_eq!(
+            parse_pattern(&strings(&["0x100000000"]), None),
+            Ok(vec![0, 0, 0, 0, 1, 0, 0, 0])
+        );
+        assert_eq!(parse_pattern(&strings(&["-1"]), None), Ok(vec![0xff; 4]));
+        assert_eq!(
+            parse_pattern(&strings(&["0x12", "0x34"]), Some(1)),
+            Ok(vec![0x12, 0x34])
+        );
+        assert_eq!(
+            parse_pattern(&strings(&["7"]), Some(8)),
+            Ok(vec![7, 0, 0, 0, 0, 0, 0, 0])
+        );
+        assert!(parse_pattern(&strings(&["0x1234"]), Some(1)).is_err());
+        assert!(parse_pattern(&strings(&["\"open"]), None).is_err());
+        assert!(parse_pattern(&strings(&["buf"]), None).is_err());
+        assert!(parse_pattern(&strings(&["\"\""]), None).is_err());
+    }
+
+    #[test]
+    fn test_readable_ranges() {
+        let region = |start, end, perms: &str| MemoryRegion {
+            start,
+            end,
+            perms: perms.to_string(),
+            offset: 0,
+            path: String::new(),
+        };
+        let regions = [
+            region(0x1000, 0x2000, "r--p"),
+            region(0x2000, 0x3000, "rw-p"),
+            region(0x3000, 0x4000, "---p"),
+            region(0x5000, 0x6000, "r-xp"),
+        ];
+        assert_eq!(
+            readable_ranges(Some(&regions), 0x1800, 0x5800),
+            vec![(0x1800, 0x3000), (0x5000, 0x5800)]
+        );
+        assert_eq!(readable_ranges(None, 1, 2), vec![(1, 2)]);
+    }
+
+    #[test]
+    fn test_search() {
+        let mut memory = vec![0u8; CHUNK_SIZE + 16];
+        memory[10..13].copy_from_slice(b"abc");
+        // Across the boundary between two chunks
+        memory[CHUNK_SIZE - 1..CHUNK_SIZE + 2].copy_from_slice(b"abc");
+        let read = |addr: usize, len: usize| memory.get(addr..addr + len).map(<[u8]>::to_vec);
+        let ranges = [(0, memory.len())];
+        assert_eq!(
+            search(&ranges, b"abc", 10, read),
+            (vec![10, CHUNK_SIZE - 1], false)
+        );
+        assert_eq!(search(&ranges, b"abc", 1, read), (vec![10], true));
+        // A match must lie entirely inside the range
+        assert_eq!(search(&[(0, 12)], b"abc", 10, read), (vec![], false));
+        // Up against the top of the address space
+        let top = usize::MAX - 4;
+        let read = |addr: usize, len: usize| (addr == top).then(|| b"xabcx"[..len].to_vec());
+        assert_eq!(
+            search(&[(top, usize::MAX)], b"abc", 10, read),
+            (vec![top + 1], false)
+        );
+    }
+}
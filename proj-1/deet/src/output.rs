@@ -0,0 +1,116 @@
+//! Where deet's messages go: the terminal, and also the transcript file while `set logging` is on.
+
+use std::cell::RefCell;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+
+/// Transcript file used by `set logging on` when no path is given
+pub const DEFAULT_LOG_PATH: &str = "deet.log";
+
+/// Like `println!`, but through an `Output`.
+macro_rules! outputln {
+    ($output:expr) => {
+        $output.line(format_args!(""))
+    };
+    ($output:expr, $($arg:tt)*) => {
+        $output.line(format_args!($($arg)*))
+    };
+}
+pub(crate) use outputln;
+
+struct Log {
+    path: String,
+    file: File,
+}
+
+/// The sink for everything deet prints. Lines go to stdout, and are appended to the log file
+/// if there is one. The log sits behind a RefCell so that code holding only `&self` can print.
+#[derive(Default)]
+pub struct Output {
+    log: RefCell<Option<Log>>,
+}
+
+impl Output {
+    /// Prints one line.
+    pub fn line(&self, args: fmt::Arguments) {
+        println!("{}", args);
+        self.append(&args.to_string());
+    }
+
+    /// Records a command in the log, with the time it was entered. The terminal already shows it.
+    pub fn command(&self, line: &str) {
+        self.append(&format!("[{}] (deet) {}", timestamp(), line));
+    }
+
+    /// Starts appending to the file at `path`, replacing any log already open.
+    pub fn start_log(&self, path: &str) -> Result<(), String> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|err| format!("Cannot open {}: {}.", path, err))?;
+        *self.log.borrow_mut() = Some(Log {
+            path: path.to_string(),
+            file,
+        });
+        Ok(())
+    }
+
+    /// Stops logging, returning the path of the log that was open.
+    pub fn stop_log(&self) -> Option<String> {
+        self.log.borrow_mut().take().map(|log| log.path)
+    }
+
+    /// Returns the path of the open log, if any.
+    pub fn log_path(&self) -> Option<String> {
+        self.log.borrow().as_ref().map(|log| log.path.clone())
+    }
+
+    fn append(&self, line: &str) {
+        let mut log = self.log.borrow_mut();
+        if let Some(Log { path, file }) = log.as_mut() {
+            if let Err(err) = writeln!(file, "{}", line) {
+                // Reporting through `line` would try the log again
+                println!("Failed to write to {}: {}; logging stopped.", path, err);
+                *log = None;
+            }
+        }
+    }
+}
+
+/// Returns the local time of day as `HH:MM:SS`.
+fn timestamp() -> String {
+    let now = unsafe { nix::libc::time(std::ptr::null_mut()) };
+    let mut tm: nix::libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { nix::libc::localtime_r(&now, &mut tm) }.is_null() {
+        return "??:??:??".to_string();
+    }
+    format!("{:02}:{:02}:{:02}", tm.tm_hour, tm.tm_min, tm.tm_sec)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_log() {
+        let path = std::env::temp_dir().join(format!("deet-log-test-{}", std::process::id()));
+        let path = path.to_str().unwrap();
+        let output = Output::default();
+        outputln!(output, "not logged");
+        output.start_log(path).unwrap();
+        assert_eq!(output.log_path().as_deref(), Some(path));
+        output.command("print x");
+        outputln!(output, "x = {}", 3);
+        assert_eq!(output.stop_log().as_deref(), Some(path));
+        outputln!(output, "not logged either");
+
+        let log = std::fs::read_to_string(path).unwrap();
+        let lines: Vec<&str> = log.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with('[') && lines[0].ends_with("] (deet) print x"));
+        assert_eq!(lines[1], "x = 3");
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,203 @@
+//! What /proc says about a process: its command line, environment, executable, state and working
+//! directory, for `info proc`, and the rest of what `gcore` records. Each is read on its own, so
+//! that one failing (say, `environ`, which needs the same permissions as ptrace) doesn't keep the
+//! others from being shown.
+
+use nix::unistd::Pid;
+use std::fs;
+use std::io;
+
+/// Describes why a file under /proc/<pid> couldn't be read.
+fn describe_error(err: io::Error) -> String {
+    match err.kind() {
+        // The whole /proc/<pid> directory goes away with the process
+        io::ErrorKind::NotFound => "process is gone".to_string(),
+        io::ErrorKind::PermissionDenied => "permission denied".to_string(),
+        _ => err.to_string(),
+    }
+}
+
+fn read(pid: Pid, file: &str) -> Result<Vec<u8>, String> {
+    fs::read(format!("/proc/{}/{}", pid, file)).map_err(describe_error)
+}
+
+fn read_link(pid: Pid, file: &str) -> Result<String, String> {
+    fs::read_link(format!("/proc/{}/{}", pid, file))
+        .map(|path| path.to_string_lossy().into_owned())
+        .map_err(describe_error)
+}
+
+/// Splits the contents of `cmdline` or `environ`: strings that each end with a NUL byte.
+fn split_nul(bytes: &[u8]) -> Vec<String> {
+    if bytes.is_empty() {
+        return Vec::new();
+    }
+    bytes
+        .strip_suffix(b"\0")
+        .unwrap_or(bytes)
+        .split(|&byte| byte == 0)
+        .map(|string| String::from_utf8_lossy(string).into_owned())
+        .collect()
+}
+
+/// Quotes `arg` the way a shell would need it: as is if it has no spaces or special characters,
+/// otherwise in single quotes.
+pub fn quote(arg: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c);
+    if !arg.is_empty() && arg.chars().all(plain) {
+        return arg.to_string();
+    }
+    format!("'{}'", arg.replace('\'', r"'\''"))
+}
+
+/// Returns the arguments the process was started with, program name first.
+pub fn cmdline(pid: Pid) -> Result<Vec<String>, String> {
+    Ok(split_nul(&read(pid, "cmdline")?))
+}
+
+/// Returns the environment the process was started with, as `NAME=value` strings.
+pub fn environ(pid: Pid) -> Result<Vec<String>, String> {
+    Ok(split_nul(&read(pid, "environ")?))
+}
+
+/// Returns the path of the program the process is running.
+pub fn exe(pid: Pid) -> Result<String, String> {
+    read_link(pid, "exe")
+}
+
+/// Returns the process's current working directory.
+pub fn cwd(pid: Pid) -> Result<String, String> {
+    read_link(pid, "cwd")
+}
+
+/// Returns the process's scheduling state, e.g. `t (tracing stop)`.
+pub fn state(pid: Pid) -> Result<String, String> {
+    let status = String::from_utf8_lossy(&read(pid, "status")?).into_owned();
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("State:"))
+        .map(|state| state.trim().to_string())
+        .ok_or_else(|| "no State line in status".to_string())
+}
+
+/// The fields of /proc/<pid>/stat that a core file records.
+#[derive(Debug, PartialEq)]
+pub struct Stat {
+    /// The executable's name, truncated to 15 bytes
+    pub comm: String,
+    /// A letter such as `R` (running) or `t` (tracing stop)
+    pub state: char,
+    pub ppid: i32,
+    pub pgrp: i32,
+    pub session: i32,
+    /// The kernel's `PF_*` flags
+    pub flags: u64,
+    pub nice: i64,
+}
+
+/// Parses the contents of /proc/<pid>/stat. The name comes in parentheses and may contain spaces
+/// and parentheses itself, so the other fields are counted from the last `)`.
+fn parse_stat(stat: &str) -> Option<Stat> {
+    let (head, tail) = stat.rsplit_once(')')?;
+    let comm = head.split_once('(')?.1.to_string();
+    let fields: Vec<&str> = tail.split_whitespace().collect();
+    Some(Stat {
+        comm,
+        state: fields.first()?.chars().next()?,
+        ppid: fields.get(1)?.parse().ok()?,
+        pgrp: fields.get(2)?.parse().ok()?,
+        session: fields.get(3)?.parse().ok()?,
+        flags: fields.get(6)?.parse().ok()?,
+        nice: fields.get(16)?.parse().ok()?,
+    })
+}
+
+/// Returns the process's scheduling details from /proc/<pid>/stat.
+pub fn stat(pid: Pid) -> Result<Stat, String> {
+    parse_stat(&String::from_utf8_lossy(&read(pid, "stat")?))
+        .ok_or_else(|| "malformed stat".to_string())
+}
+
+/// Returns the real user and group IDs of the process.
+pub fn ids(pid: Pid) -> Result<(u32, u32), String> {
+    let status = String::from_utf8_lossy(&read(pid, "status")?).into_owned();
+    let id = |field: &str| {
+        status
+            .lines()
+            .find_map(|line| line.strip_prefix(field))
+            .and_then(|ids| ids.split_whitespace().next()?.parse().ok())
+            .ok_or_else(|| format!("no {} line in status", field))
+    };
+    Ok((id("Uid:")?, id("Gid:")?))
+}
+
+/// Returns the auxiliary vector the kernel passed to the program, as raw bytes.
+pub fn auxv(pid: Pid) -> Result<Vec<u8>, String> {
+    read(pid, "auxv")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_split_nul() {
+        assert_eq!(split_nul(b"ls\0-l\0a b\0"), vec!["ls", "-l", "a b"]);
+        assert_eq!(split_nul(b"x\0\0"), vec!["x", ""]);
+        assert!(split_nul(b"").is_empty());
+    }
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote("samples/hello"), "samples/hello");
+        assert_eq!(quote("--name=x"), "--name=x");
+        assert_eq!(quote("a b"), "'a b'");
+        assert_eq!(quote(""), "''");
+        assert_eq!(quote("it's"), r"'it'\''s'");
+    }
+
+    #[test]
+    fn test_parse_stat() {
+        let stat = "4242 (my (odd) prog) t 4200 4242 4100 34816 4242 1077936128 110 0 0 0 0 0 \
+                    0 0 20 -5 1 0 12345 2564096 200 18446744073709551615";
+        assert_eq!(
+            parse_stat(stat),
+            Some(Stat {
+                comm: "my (odd) prog".to_string(),
+                state: 't',
+                ppid: 4200,
+                pgrp: 4242,
+                session: 4100,
+                flags: 1077936128,
+                nice: -5,
+            })
+        );
+        assert_eq!(parse_stat("4242 (prog) t"), None);
+    }
+
+    #[test]
+    fn test_own_process() {
+        let pid = nix::unistd::getpid();
+        assert!(!cmdline(pid).unwrap().is_empty());
+        assert!(environ(pid).unwrap().iter().all(|var| var.contains('=')));
+        assert_eq!(
+            exe(pid).unwrap(),
+            std::env::current_exe().unwrap().to_string_lossy()
+        );
+        // The main thread's state, which waits for the test threads
+        assert!(state(pid).unwrap().contains('('));
+        assert_eq!(stat(pid).unwrap().ppid, nix::unistd::getppid().as_raw());
+        assert_eq!(
+            ids(pid).unwrap(),
+            (
+                nix::unistd::getuid().as_raw(),
+                nix::unistd::getgid().as_raw()
+            )
+        );
+        assert!(!auxv(pid).unwrap().is_empty());
+        assert_eq!(
+            cwd(Pid::from_raw(i32::MAX)),
+            Err("process is gone".to_string())
+        );
+    }
+}
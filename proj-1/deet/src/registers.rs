@@ -0,0 +1,144 @@
+use nix::libc::user_regs_struct;
+
+/// Picks one register out of the struct used by PTRACE_GETREGS and PTRACE_SETREGS
+type Field = fn(&mut user_regs_struct) -> &mut u64;
+
+/// The registers that can be referenced as `$name`, in the order gdb lists them.
+const REGISTERS: &[(&str, Field)] = &[
+    ("rax", |regs| &mut regs.rax),
+    ("rbx", |regs| &mut regs.rbx),
+    ("rcx", |regs| &mut regs.rcx),
+    ("rdx", |regs| &mut regs.rdx),
+    ("rsi", |regs| &mut regs.rsi),
+    ("rdi", |regs| &mut regs.rdi),
+    ("rbp", |regs| &mut regs.rbp),
+    ("rsp", |regs| &mut regs.rsp),
+    ("r8", |regs| &mut regs.r8),
+    ("r9", |regs| &mut regs.r9),
+    ("r10", |regs| &mut regs.r10),
+    ("r11", |regs| &mut regs.r11),
+    ("r12", |regs| &mut regs.r12),
+    ("r13", |regs| &mut regs.r13),
+    ("r14", |regs| &mut regs.r14),
+    ("r15", |regs| &mut regs.r15),
+    ("rip", |regs| &mut regs.rip),
+    ("eflags", |regs| &mut regs.eflags),
+    ("cs", |regs| &mut regs.cs),
+    ("ss", |regs| &mut regs.ss),
+    ("ds", |regs| &mut regs.ds),
+    ("es", |regs| &mut regs.es),
+    ("fs", |regs| &mut regs.fs),
+    ("gs", |regs| &mut regs.gs),
+    ("fs_base", |regs| &mut regs.fs_base),
+    ("gs_base", |regs| &mut regs.gs_base),
+];
+
+/// 32-bit names for the low halves of the general-purpose registers
+const HALF_REGISTERS: &[(&str, &str)] = &[
+    ("eax", "rax"),
+    ("ebx", "rbx"),
+    ("ecx", "rcx"),
+    ("edx", "rdx"),
+    ("esi", "rsi"),
+    ("edi", "rdi"),
+    ("ebp", "rbp"),
+    ("esp", "rsp"),
+    ("r8d", "r8"),
+    ("r9d", "r9"),
+    ("r10d", "r10"),
+    ("r11d", "r11"),
+    ("r12d", "r12"),
+    ("r13d", "r13"),
+    ("r14d", "r14"),
+    ("r15d", "r15"),
+    ("eip", "rip"),
+];
+
+/// The registers of a stopped thread, looked up by name.
+pub struct RegisterFile {
+    regs: user_regs_struct,
+}
+
+impl RegisterFile {
+    pub fn new(regs: user_regs_struct) -> RegisterFile {
+        RegisterFile { regs }
+    }
+
+    /// Returns the value of the register called `name` (without the leading `$`). 32-bit names
+    /// like `eax` or `r8d` read the low half of the 64-bit register.
+    pub fn get(&self, name: &str) -> Result<u64, String> {
+        let (field, mask) = RegisterFile::lookup(name)?;
+        let mut regs = self.regs;
+        Ok(*field(&mut regs) & mask)
+    }
+
+    /// Sets the register called `name` to `value`. Setting a 32-bit name leaves the upper half of
+    /// the 64-bit register alone.
+    pub fn set(&mut self, name: &str, value: u64) -> Result<(), String> {
+        let (field, mask) = RegisterFile::lookup(name)?;
+        let register = field(&mut self.regs);
+        *register = (*register & !mask) | (value & mask);
+        Ok(())
+    }
+
+    /// Returns the registers, e.g. to write them back with PTRACE_SETREGS.
+    pub fn regs(&self) -> user_regs_struct {
+        self.regs
+    }
+
+    /// Finds the register called `name`, returning how to get at its 64-bit register and which
+    /// bits of that belong to it.
+    fn lookup(name: &str) -> Result<(Field, u64), String> {
+        let (full_name, mask) = match HALF_REGISTERS.iter().find(|(half, _)| *half == name) {
+            Some(&(_, full_name)) => (full_name, u32::MAX as u64),
+            None => (name, u64::MAX),
+        };
+        REGISTERS
+            .iter()
+            .find(|(register, _)| *register == full_name)
+            .map(|&(_, field)| (field, mask))
+            .ok_or_else(|| {
+                let names: Vec<&str> = REGISTERS.iter().map(|(register, _)| *register).collect();
+                format!(
+                    "Unknown register ${} (valid registers: ${}, and $eax etc. for the low 32 \
+                     bits)",
+                    name,
+                    names.join(", $")
+                )
+            })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn registers() -> RegisterFile {
+        let mut regs: user_regs_struct = unsafe { std::mem::zeroed() };
+        regs.rax = 0x1234_5678_9abc_def0;
+        regs.r8 = u64::MAX;
+        regs.eflags = 0x246;
+        RegisterFile::new(regs)
+    }
+
+    #[test]
+    fn test_get() {
+        let registers = registers();
+        assert_eq!(registers.get("rax"), Ok(0x1234_5678_9abc_def0));
+        assert_eq!(registers.get("eax"), Ok(0x9abc_def0));
+        assert_eq!(registers.get("r8d"), Ok(0xffff_ffff));
+        assert_eq!(registers.get("eflags"), Ok(0x246));
+        let err = registers.get("rax2").unwrap_err();
+        assert!(err.contains("$rax2") && err.contains("$rip"), "{}", err);
+    }
+
+    #[test]
+    fn test_set() {
+        let mut registers = registers();
+        registers.set("eax", 1).unwrap();
+        assert_eq!(registers.get("rax"), Ok(0x1234_5678_0000_0001));
+        registers.set("r8", 5).unwrap();
+        assert_eq!(registers.regs().r8, 5);
+        assert!(registers.set("xyz", 0).is_err());
+    }
+}
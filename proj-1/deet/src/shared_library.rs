@@ -0,0 +1,174 @@
+//! Shared objects (libc, the dynamic loader, ...) mapped into a process. The executable's debug
+//! info says nothing about them, so addresses inside them are resolved with the symbol tables
+//! (`.symtab` if the library wasn't stripped, otherwise the exported symbols in `.dynsym`) and
+//! their frames unwound with their own call frame information.
+
+use crate::demangle;
+use crate::inferior::MemoryRegion;
+use crate::unwind::{CallFrameInfo, FrameRules};
+use object::{Object, ObjectSegment, ObjectSymbol, SymbolFlags, SymbolKind};
+use std::fs;
+
+/// A function symbol, at a link-time address of its library
+struct Symbol {
+    name: String,
+    address: usize,
+    size: usize,
+    /// True for GNU indirect functions, whose code picks the implementation to use (e.g. the
+    /// `strlen` for the CPU) and returns its address
+    indirect: bool,
+}
+
+pub struct SharedLibrary {
+    /// Path of the mapped file, as shown in the process's mappings
+    pub path: String,
+    /// Offset between the library's run-time and link-time addresses
+    pub base: usize,
+    /// Function symbols, sorted by address
+    symbols: Vec<Symbol>,
+    call_frame_info: CallFrameInfo,
+}
+
+/// Returns true if `path` names a shared object, like `/lib/x86_64-linux-gnu/libc.so.6`.
+fn is_shared_object(path: &str) -> bool {
+    path.starts_with('/') && file_name(path).contains(".so")
+}
+
+fn file_name(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+impl SharedLibrary {
+    /// Loads the symbols and unwinding rules of the library at `path`, which `regions` (a
+    /// process's mappings) show where it was loaded.
+    fn load(path: &str, regions: &[MemoryRegion]) -> Option<SharedLibrary> {
+        // The lowest mapping of the file holds its first loadable segment
+        let lowest = regions
+            .iter()
+            .filter(|region| region.path == path)
+            .map(|region| region.start)
+            .min()?;
+        let file = fs::File::open(path).ok()?;
+        let mmap = unsafe { memmap2::Mmap::map(&file).ok()? };
+        let object = object::File::parse(&*mmap).ok()?;
+        let first_segment = object
+            .segments()
+            .map(|segment| segment.address() as usize)
+            .min()?;
+        let base = lowest.wrapping_sub(first_segment & !0xfff);
+        let mut symbols: Vec<Symbol> = object
+            .symbols()
+            .chain(object.dynamic_symbols())
+            .filter(|symbol| symbol.kind() == SymbolKind::Text && symbol.address() != 0)
+            .filter_map(|symbol| {
+                Some(Symbol {
+                    name: symbol.name().ok()?.to_string(),
+                    address: symbol.address() as usize,
+                    size: symbol.size() as usize,
+                    indirect: matches!(
+                        symbol.flags(),
+                        SymbolFlags::Elf { st_info, .. }
+                            if st_info & 0xf == object::elf::STT_GNU_IFUNC
+                    ),
+                })
+            })
+            .collect();
+        // One name per address: functions are often in both tables, and under aliases, of which
+        // the one without leading underscores (puts rather than _IO_puts) reads best
+        symbols.sort_by(|a, b| {
+            let underscores =
+                |symbol: &Symbol| symbol.name.len() - symbol.name.trim_start_matches('_').len();
+            (a.address, underscores(a), &a.name).cmp(&(b.address, underscores(b), &b.name))
+        });
+        symbols.dedup_by(|next, kept| {
+            let alias = next.address == kept.address;
+            if alias {
+                kept.indirect |= next.indirect;
+            }
+            alias
+        });
+        let endian = if object.is_little_endian() {
+            gimli::RunTimeEndian::Little
+        } else {
+            gimli::RunTimeEndian::Big
+        };
+        Some(SharedLibrary {
+            path: path.to_string(),
+            base,
+            symbols,
+            call_frame_info: CallFrameInfo::load(&object, endian),
+        })
+    }
+
+    /// Loads the library mapped at run-time address `addr`, or returns None if `addr` isn't in
+    /// a shared object.
+    pub fn containing(regions: &[MemoryRegion], addr: usize) -> Option<SharedLibrary> {
+        let region = regions.iter().find(|region| region.contains(addr))?;
+        if !is_shared_object(&region.path) {
+            return None;
+        }
+        SharedLibrary::load(&region.path, regions)
+    }
+
+    /// Loads the library whose file name is `name` or starts with `name.`, so that `libc`
+    /// and `libc.so` both find `/lib/x86_64-linux-gnu/libc.so.6`.
+    pub fn named(regions: &[MemoryRegion], name: &str) -> Option<SharedLibrary> {
+        let region = regions.iter().find(|region| {
+            is_shared_object(&region.path) && {
+                let file = file_name(&region.path);
+                file == name || file.starts_with(&format!("{}.", name))
+            }
+        })?;
+        SharedLibrary::load(&region.path, regions)
+    }
+
+    /// Returns the name of the function containing run-time address `addr` and how far into it
+    /// `addr` is. Symbols without a size are assumed to extend to the next symbol.
+    pub fn symbolize(&self, addr: usize) -> Option<(&str, usize)> {
+        let addr = addr.wrapping_sub(self.base);
+        let index = self
+            .symbols
+            .partition_point(|symbol| symbol.address <= addr)
+            .checked_sub(1)?;
+        let symbol = &self.symbols[index];
+        if symbol.size != 0 && addr >= symbol.address + symbol.size {
+            return None;
+        }
+        Some((&symbol.name, addr - symbol.address))
+    }
+
+    /// Returns the run-time address of the function `name`, which may be mangled or not.
+    pub fn symbol_address(&self, name: &str) -> Option<usize> {
+        let symbol = self
+            .symbols
+            .iter()
+            .find(|symbol| demangle::matches(&symbol.name, name))?;
+        Some(symbol.address.wrapping_add(self.base))
+    }
+
+    /// Returns true if the function at run-time address `addr` is a GNU indirect function.
+    pub fn is_indirect_function(&self, addr: usize) -> bool {
+        let addr = addr.wrapping_sub(self.base);
+        self.symbols
+            .iter()
+            .any(|symbol| symbol.address == addr && symbol.indirect)
+    }
+
+    /// Returns the rules for unwinding out of a frame stopped at run-time address `addr`.
+    pub fn frame_rules(&self, addr: usize) -> Option<FrameRules> {
+        self.call_frame_info
+            .rules_for_address(addr.wrapping_sub(self.base))
+    }
+
+    /// Returns true if run-time address `addr` is in one of the library's mappings in `regions`.
+    pub fn contains(&self, regions: &[MemoryRegion], addr: usize) -> bool {
+        regions
+            .iter()
+            .any(|region| region.path == self.path && region.contains(addr))
+    }
+
+    /// Returns the library's file name, e.g. `libc.so.6`.
+    pub fn file_name(&self) -> &str {
+        file_name(&self.path)
+    }
+}
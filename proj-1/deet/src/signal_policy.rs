@@ -0,0 +1,171 @@
+//! What deet does when the inferior receives a signal: whether to stop, whether to say so, and
+//! whether the program gets the signal when it is resumed. Set with `handle`, shown by
+//! `info signals`.
+
+use nix::sys::signal::Signal;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SignalPolicy {
+    pub stop: bool,
+    pub print: bool,
+    pub pass: bool,
+}
+
+impl SignalPolicy {
+    /// gdb's defaults: signals programs commonly use for their own purposes are passed on
+    /// quietly, SIGINT (Ctrl-C) stops the program without reaching it, and everything else
+    /// stops the program and is delivered when it continues.
+    fn default_for(signal: Signal) -> SignalPolicy {
+        let (stop, print, pass) = match signal {
+            Signal::SIGINT | Signal::SIGTRAP => (true, true, false),
+            Signal::SIGALRM
+            | Signal::SIGCHLD
+            | Signal::SIGURG
+            | Signal::SIGWINCH
+            | Signal::SIGIO
+            | Signal::SIGVTALRM
+            | Signal::SIGPROF => (false, false, true),
+            _ => (true, true, true),
+        };
+        SignalPolicy { stop, print, pass }
+    }
+
+    /// Applies a `handle` keyword. As in gdb, stopping implies printing, and not printing
+    /// implies not stopping.
+    fn apply(&mut self, action: &str) -> Result<(), String> {
+        match action {
+            "stop" => (self.stop, self.print) = (true, true),
+            "nostop" => self.stop = false,
+            "print" => self.print = true,
+            "noprint" => (self.stop, self.print) = (false, false),
+            "pass" => self.pass = true,
+            "nopass" => self.pass = false,
+            _ => {
+                return Err(format!(
+                    "Unknown action \"{}\"; expected stop, nostop, print, noprint, pass or nopass.",
+                    action
+                ))
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parses a signal given as `SIGALRM`, `alrm` or `14`.
+pub fn parse_signal(name: &str) -> Result<Signal, String> {
+    if let Ok(num) = name.parse::<i32>() {
+        return Signal::try_from(num).map_err(|_| format!("No signal numbered {}.", num));
+    }
+    let upper = name.to_ascii_uppercase();
+    let full = if upper.starts_with("SIG") {
+        upper
+    } else {
+        format!("SIG{}", upper)
+    };
+    Signal::from_str(&full).map_err(|_| format!("Unknown signal \"{}\".", name))
+}
+
+/// The header of the table printed by `info signals` and `handle`.
+pub const TABLE_HEADER: &str = "Signal      Stop  Print  Pass to program";
+
+/// Formats one row of the table printed by `info signals` and `handle`.
+pub fn format_row(signal: Signal, policy: SignalPolicy) -> String {
+    let yes_no = |flag: bool| if flag { "Yes" } else { "No" };
+    format!(
+        "{:<10}  {:<4}  {:<5}  {}",
+        signal.as_str(),
+        yes_no(policy.stop),
+        yes_no(policy.print),
+        yes_no(policy.pass)
+    )
+}
+
+/// The policy for every signal.
+pub struct SignalTable {
+    policies: BTreeMap<Signal, SignalPolicy>,
+}
+
+impl SignalTable {
+    pub fn new() -> SignalTable {
+        SignalTable {
+            policies: Signal::iterator()
+                .map(|signal| (signal, SignalPolicy::default_for(signal)))
+                .collect(),
+        }
+    }
+
+    pub fn get(&self, signal: Signal) -> SignalPolicy {
+        self.policies[&signal]
+    }
+
+    /// Applies `handle <signal> <actions...>`. Nothing changes if any action is invalid.
+    pub fn handle(&mut self, signal: Signal, actions: &[String]) -> Result<(), String> {
+        match signal {
+            Signal::SIGTRAP => {
+                return Err("SIGTRAP is used by deet for breakpoints and stepping.".to_string())
+            }
+            Signal::SIGKILL => return Err("SIGKILL can't be intercepted.".to_string()),
+            _ => {}
+        }
+        let mut policy = self.get(signal);
+        for action in actions {
+            policy.apply(action)?;
+        }
+        self.policies.insert(signal, policy);
+        Ok(())
+    }
+
+    /// Returns every signal and its policy, in signal number order.
+    pub fn iter(&self) -> impl Iterator<Item = (Signal, SignalPolicy)> + '_ {
+        self.policies
+            .iter()
+            .map(|(&signal, &policy)| (signal, policy))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_signal() {
+        assert_eq!(parse_signal("SIGALRM"), Ok(Signal::SIGALRM));
+        assert_eq!(parse_signal("usr1"), Ok(Signal::SIGUSR1));
+        assert_eq!(parse_signal("11"), Ok(Signal::SIGSEGV));
+        assert!(parse_signal("SIGNOPE").is_err());
+        assert!(parse_signal("0").is_err());
+    }
+
+    #[test]
+    fn test_handle() {
+        let mut table = SignalTable::new();
+        assert!(table.get(Signal::SIGSEGV).stop);
+        assert!(!table.get(Signal::SIGINT).pass);
+        assert!(!table.get(Signal::SIGALRM).stop);
+
+        let actions = |words: &[&str]| words.iter().map(|w| w.to_string()).collect::<Vec<_>>();
+        table
+            .handle(Signal::SIGUSR1, &actions(&["noprint", "nopass"]))
+            .unwrap();
+        assert_eq!(
+            table.get(Signal::SIGUSR1),
+            SignalPolicy {
+                stop: false,
+                print: false,
+                pass: false
+            }
+        );
+        table.handle(Signal::SIGUSR1, &actions(&["stop"])).unwrap();
+        assert!(table.get(Signal::SIGUSR1).print);
+        // A bad action leaves the policy as it was
+        assert!(table
+            .handle(Signal::SIGUSR1, &actions(&["nostop", "sometimes"]))
+            .is_err());
+        assert!(table.get(Signal::SIGUSR1).stop);
+        assert!(table
+            .handle(Signal::SIGTRAP, &actions(&["nostop"]))
+            .is_err());
+    }
+}
@@ -0,0 +1,127 @@
+//! Reading of source files (for `list` and stop messages), cached for the whole session.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+#[derive(Default)]
+pub struct SourceCache {
+    /// File contents split into lines, or None if the file could not be read
+    files: HashMap<String, Option<Vec<String>>>,
+    /// Directories given to `directory`, searched (most recent first) for files that can't be
+    /// found at the path recorded in the debugging information
+    directories: Vec<String>,
+}
+
+impl SourceCache {
+    pub fn new() -> SourceCache {
+        SourceCache::default()
+    }
+
+    /// Adds `dir` to the directories searched for source files. Files that couldn't be found
+    /// before are looked for again.
+    pub fn add_directory(&mut self, dir: &str) {
+        self.directories.retain(|existing| existing != dir);
+        self.directories.insert(0, dir.to_string());
+        self.files.retain(|_, lines| lines.is_some());
+    }
+
+    pub fn directories(&self) -> &[String] {
+        &self.directories
+    }
+
+    /// Returns the lines of the file at `path`, reading it from disk the first time it is
+    /// requested. If `path` can't be read, it is looked for in the source directories, both as
+    /// given (if relative) and by its file name alone. Returns None if the file can't be found.
+    pub fn lines(&mut self, path: &str) -> Option<&[String]> {
+        let directories = &self.directories;
+        self.files
+            .entry(path.to_string())
+            .or_insert_with(|| {
+                let relative = Path::new(path);
+                let candidates = directories.iter().flat_map(|dir| {
+                    let dir = Path::new(dir);
+                    [
+                        relative.is_relative().then(|| dir.join(relative)),
+                        relative.file_name().map(|name| dir.join(name)),
+                    ]
+                    .into_iter()
+                    .flatten()
+                });
+                let bytes = std::iter::once(relative.to_path_buf())
+                    .chain(candidates)
+                    .find_map(|candidate| fs::read(candidate).ok())?;
+                Some(
+                    String::from_utf8_lossy(&bytes)
+                        .lines()
+                        .map(String::from)
+                        .collect(),
+                )
+            })
+            .as_deref()
+    }
+}
+
+/// Lines longer than this (after tab expansion) are truncated when displayed
+const MAX_DISPLAY_WIDTH: usize = 160;
+
+/// Prepares a source line for display: tabs are expanded to 8-column tab stops, and overly long
+/// lines are cut short with a trailing `...`.
+pub fn render_line(text: &str) -> String {
+    let mut rendered = String::new();
+    let mut width = 0;
+    for c in text.chars() {
+        if width >= MAX_DISPLAY_WIDTH {
+            rendered.push_str("...");
+            break;
+        }
+        if c == '\t' {
+            let spaces = 8 - width % 8;
+            rendered.extend(std::iter::repeat_n(' ', spaces));
+            width += spaces;
+        } else {
+            rendered.push(c);
+            width += 1;
+        }
+    }
+    rendered
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_lines() {
+        let mut cache = SourceCache::new();
+        let lines = cache
+            .lines("samples/hello.c")
+            .expect("Failed to read samples/hello.c");
+        assert_eq!(lines[0], "#include <stdio.h>");
+        assert!(cache.lines("samples/does_not_exist.c").is_none());
+    }
+
+    #[test]
+    fn test_directories() {
+        let mut cache = SourceCache::new();
+        assert!(cache.lines("/elsewhere/hello.c").is_none());
+        assert!(cache.lines("hello.c").is_none());
+        cache.add_directory("samples");
+        assert_eq!(cache.directories(), ["samples"]);
+        let lines = cache
+            .lines("/elsewhere/hello.c")
+            .expect("Failed to find hello.c in samples");
+        assert_eq!(lines[0], "#include <stdio.h>");
+        assert!(cache.lines("hello.c").is_some());
+    }
+
+    #[test]
+    fn test_render_line() {
+        assert_eq!(render_line("\tx = 1;"), "        x = 1;");
+        assert_eq!(render_line("ab\tc"), "ab      c");
+        let long = "x".repeat(MAX_DISPLAY_WIDTH + 10);
+        let rendered = render_line(&long);
+        assert!(rendered.ends_with("..."));
+        assert_eq!(rendered.len(), MAX_DISPLAY_WIDTH + 3);
+    }
+}
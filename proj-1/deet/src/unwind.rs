@@ -0,0 +1,252 @@
+//! Stack unwinding with the DWARF call frame information (CFI) in `.eh_frame` and
+//! `.debug_frame`, which describes for every instruction where the caller's registers were saved.
+//! Unlike following the chain of saved %rbp values, this also works in code compiled with
+//! `-fomit-frame-pointer`.
+
+use gimli::{CfaRule, UnwindSection, X86_64};
+use object::{Object, ObjectSection};
+use std::rc::Rc;
+
+type Reader = gimli::EndianRcSlice<gimli::RunTimeEndian>;
+
+/// The registers needed to walk from a frame to its caller, as run-time values.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UnwindRegisters {
+    pub rip: usize,
+    pub rsp: usize,
+    pub rbp: usize,
+}
+
+/// The register the canonical frame address (CFA) is computed from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CfaRegister {
+    Rsp,
+    Rbp,
+}
+
+/// How to recover one of the caller's registers in a frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RegisterRule {
+    /// The register wasn't changed by the callee
+    SameValue,
+    /// The register was saved at the CFA plus this offset
+    SavedAt(i64),
+    /// The register's value is the CFA plus this offset
+    CfaPlus(i64),
+    /// The register can't be recovered
+    Undefined,
+}
+
+/// The rules for unwinding out of a frame stopped at one particular instruction.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameRules {
+    pub cfa_register: CfaRegister,
+    pub cfa_offset: i64,
+    pub rbp: RegisterRule,
+    pub return_address: RegisterRule,
+}
+
+impl FrameRules {
+    /// Returns the canonical frame address: the value %rsp had in the caller just before the call
+    /// into this frame.
+    pub fn cfa(&self, regs: &UnwindRegisters) -> usize {
+        let base = match self.cfa_register {
+            CfaRegister::Rsp => regs.rsp,
+            CfaRegister::Rbp => regs.rbp,
+        };
+        base.wrapping_add(self.cfa_offset as usize)
+    }
+
+    /// Computes the caller's registers, reading saved values through `read_word`. Its rip is the
+    /// return address, and its rsp the CFA.
+    pub fn unwind<F>(&self, regs: &UnwindRegisters, read_word: F) -> Result<UnwindRegisters, String>
+    where
+        F: Fn(usize) -> Option<usize>,
+    {
+        let cfa = self.cfa(regs);
+        let recover = |rule: RegisterRule, current: usize, name: &str| match rule {
+            RegisterRule::SameValue => Ok(current),
+            RegisterRule::SavedAt(offset) => {
+                let addr = cfa.wrapping_add(offset as usize);
+                read_word(addr).ok_or_else(|| format!("cannot access memory at {:#x}", addr))
+            }
+            RegisterRule::CfaPlus(offset) => Ok(cfa.wrapping_add(offset as usize)),
+            RegisterRule::Undefined => Err(format!("caller's {} is not recoverable", name)),
+        };
+        Ok(UnwindRegisters {
+            rip: recover(self.return_address, regs.rip, "return address")?,
+            rsp: cfa,
+            rbp: recover(self.rbp, regs.rbp, "%rbp")?,
+        })
+    }
+
+    /// Returns true if this is the outermost frame (e.g. `_start`), whose return address is marked
+    /// as undefined.
+    pub fn is_outermost(&self) -> bool {
+        self.return_address == RegisterRule::Undefined
+    }
+}
+
+/// The call frame information of an executable.
+pub struct CallFrameInfo {
+    eh_frame: Option<(gimli::EhFrame<Reader>, gimli::BaseAddresses)>,
+    debug_frame: Option<gimli::DebugFrame<Reader>>,
+}
+
+impl CallFrameInfo {
+    /// Loads `.eh_frame` and `.debug_frame` from `object`. Either or both may be missing.
+    pub fn load(object: &object::File, endian: gimli::RunTimeEndian) -> CallFrameInfo {
+        let section = |name: &str| {
+            let section = object.section_by_name(name)?;
+            let data = section.uncompressed_data().ok()?;
+            Some((Reader::new(Rc::from(&*data), endian), section.address()))
+        };
+        let eh_frame = section(".eh_frame").map(|(data, address)| {
+            let mut bases = gimli::BaseAddresses::default().set_eh_frame(address);
+            if let Some(text) = object.section_by_name(".text") {
+                bases = bases.set_text(text.address());
+            }
+            if let Some(got) = object.section_by_name(".got") {
+                bases = bases.set_got(got.address());
+            }
+            (gimli::EhFrame::from(data), bases)
+        });
+        let debug_frame = section(".debug_frame").map(|(data, _)| gimli::DebugFrame::from(data));
+        CallFrameInfo {
+            eh_frame,
+            debug_frame,
+        }
+    }
+
+    /// Returns the rules for unwinding out of a frame stopped at link-time address `addr`, or None
+    /// if there is no (usable) CFI for it.
+    pub fn rules_for_address(&self, addr: usize) -> Option<FrameRules> {
+        let addr = addr as u64;
+        let mut ctx = gimli::UnwindContext::new();
+        if let Some((eh_frame, bases)) = &self.eh_frame {
+            if let Ok(row) = eh_frame.unwind_info_for_address(
+                bases,
+                &mut ctx,
+                addr,
+                gimli::EhFrame::cie_from_offset,
+            ) {
+                return frame_rules(row);
+            }
+        }
+        let debug_frame = self.debug_frame.as_ref()?;
+        let bases = gimli::BaseAddresses::default();
+        let row = debug_frame
+            .unwind_info_for_address(&bases, &mut ctx, addr, gimli::DebugFrame::cie_from_offset)
+            .ok()?;
+        frame_rules(row)
+    }
+}
+
+/// Converts an unwind table row into `FrameRules`, or None if it uses rules we don't support
+/// (DWARF expressions, or registers other than %rsp and %rbp).
+fn frame_rules(row: &gimli::UnwindTableRow<Reader>) -> Option<FrameRules> {
+    let (cfa_register, cfa_offset) = match row.cfa() {
+        CfaRule::RegisterAndOffset { register, offset } if *register == X86_64::RSP => {
+            (CfaRegister::Rsp, *offset)
+        }
+        CfaRule::RegisterAndOffset { register, offset } if *register == X86_64::RBP => {
+            (CfaRegister::Rbp, *offset)
+        }
+        _ => return None,
+    };
+    let register_rule = |register| match row.register(register) {
+        // Callee-saved registers without a rule keep their value
+        gimli::RegisterRule::Undefined if register == X86_64::RBP => Some(RegisterRule::SameValue),
+        gimli::RegisterRule::Undefined => Some(RegisterRule::Undefined),
+        gimli::RegisterRule::SameValue => Some(RegisterRule::SameValue),
+        gimli::RegisterRule::Offset(offset) => Some(RegisterRule::SavedAt(offset)),
+        gimli::RegisterRule::ValOffset(offset) => Some(RegisterRule::CfaPlus(offset)),
+        _ => None,
+    };
+    Some(FrameRules {
+        cfa_register,
+        cfa_offset,
+        rbp: register_rule(X86_64::RBP)?,
+        return_address: register_rule(X86_64::RA)?,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// The rules at the start of a function body compiled with frame pointers, after
+    /// `push %rbp; mov %rsp,%rbp`
+    const AFTER_PROLOGUE: FrameRules = FrameRules {
+        cfa_register: CfaRegister::Rbp,
+        cfa_offset: 16,
+        rbp: RegisterRule::SavedAt(-16),
+        return_address: RegisterRule::SavedAt(-8),
+    };
+
+    fn read_word(addr: usize) -> Option<usize> {
+        match addr {
+            0x7000 => Some(0x7100),
+            0x7008 => Some(0x401234),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_unwind() {
+        let regs = UnwindRegisters {
+            rip: 0x401150,
+            rsp: 0x6ff0,
+            rbp: 0x7000,
+        };
+        assert_eq!(AFTER_PROLOGUE.cfa(&regs), 0x7010);
+        assert_eq!(
+            AFTER_PROLOGUE.unwind(&regs, read_word),
+            Ok(UnwindRegisters {
+                rip: 0x401234,
+                rsp: 0x7010,
+                rbp: 0x7100,
+            })
+        );
+
+        // At the first instruction of a function, only the return address has been pushed
+        let entry = FrameRules {
+            cfa_register: CfaRegister::Rsp,
+            cfa_offset: 8,
+            rbp: RegisterRule::SameValue,
+            return_address: RegisterRule::SavedAt(-8),
+        };
+        let regs = UnwindRegisters {
+            rip: 0x401140,
+            rsp: 0x7008,
+            rbp: 0x7100,
+        };
+        assert_eq!(
+            entry.unwind(&regs, read_word),
+            Ok(UnwindRegisters {
+                rip: 0x401234,
+                rsp: 0x7010,
+                rbp: 0x7100,
+            })
+        );
+    }
+
+    #[test]
+    fn test_unwind_errors() {
+        let regs = UnwindRegisters {
+            rip: 0x401150,
+            rsp: 0x8000,
+            rbp: 0x9000,
+        };
+        assert!(AFTER_PROLOGUE
+            .unwind(&regs, read_word)
+            .unwrap_err()
+            .contains("cannot access memory"));
+        let outermost = FrameRules {
+            return_address: RegisterRule::Undefined,
+            ..AFTER_PROLOGUE
+        };
+        assert!(outermost.is_outermost());
+        assert!(outermost.unwind(&regs, read_word).is_err());
+    }
+}
@@ -0,0 +1,507 @@
+//! Formatting of raw bytes read from the inferior according to their DWARF type.
+
+use crate::dwarf_data::{Encoding, Member, Type, TypeKind};
+
+/// How deeply nested structs and arrays are printed before their contents are elided as `{...}`
+const MAX_PRINT_DEPTH: usize = 8;
+
+/// How many elements of an array (or characters of a char array) are printed unless told
+/// otherwise
+pub const DEFAULT_MAX_ELEMENTS: usize = 200;
+
+/// Interprets up to 8 little-endian bytes as an integer, sign-extending if `signed` is set.
+pub fn to_integer(bytes: &[u8], signed: bool) -> i64 {
+    let size = bytes.len().min(8);
+    let mut buf = [0u8; 8];
+    buf[..size].copy_from_slice(&bytes[..size]);
+    let value = u64::from_le_bytes(buf);
+    if signed && size > 0 {
+        let shift = 64 - 8 * size as u32;
+        ((value << shift) as i64) >> shift
+    } else {
+        value as i64
+    }
+}
+
+/// Returns true if values of this type should be sign-extended when read as integers.
+pub fn is_signed(entity_type: &Type) -> bool {
+    matches!(
+        entity_type.kind,
+        TypeKind::Base(Encoding::Signed) | TypeKind::Base(Encoding::SignedChar)
+    )
+}
+
+/// Renders a character the way it would appear in a C character literal.
+pub fn escape_char(byte: u8) -> String {
+    match byte {
+        0 => "\\0".to_string(),
+        b'\n' => "\\n".to_string(),
+        b'\t' => "\\t".to_string(),
+        b'\r' => "\\r".to_string(),
+        b'\\' => "\\\\".to_string(),
+        b'\'' => "\\'".to_string(),
+        0x20..=0x7e => (byte as char).to_string(),
+        _ => format!("\\{:03o}", byte),
+    }
+}
+
+/// Renders bytes the way they would appear inside a C string literal (without the quotes).
+pub fn escape_string(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|&byte| match byte {
+            b'\'' => "'".to_string(),
+            b'"' => "\\\"".to_string(),
+            _ => escape_char(byte),
+        })
+        .collect()
+}
+
+/// Renders a string read from memory as a C string literal. A string of `max_len` bytes is
+/// assumed to have been cut short, and is marked with a trailing `...`.
+pub fn format_cstring(bytes: &[u8], max_len: usize) -> String {
+    let ellipsis = if bytes.len() >= max_len { "..." } else { "" };
+    format!("\"{}\"{}", escape_string(bytes), ellipsis)
+}
+
+fn format_raw_bytes(bytes: &[u8]) -> String {
+    let hex: Vec<String> = bytes.iter().map(|b| format!("{:#04x}", b)).collect();
+    format!("{{{}}}", hex.join(", "))
+}
+
+/// Formats `bytes` (the in-memory representation of a value) according to `entity_type`.
+pub fn format_value(bytes: &[u8], entity_type: &Type) -> String {
+    format_value_limited(bytes, entity_type, Some(DEFAULT_MAX_ELEMENTS))
+}
+
+/// Like `format_value`, but shows at most `max_elements` elements of every array (all of them if
+/// None), marking the rest with `...`.
+pub fn format_value_limited(
+    bytes: &[u8],
+    entity_type: &Type,
+    max_elements: Option<usize>,
+) -> String {
+    format_nested_value(bytes, entity_type, 0, max_elements)
+}
+
+/// Formats a value nested `depth` structs or arrays deep.
+fn format_nested_value(
+    bytes: &[u8],
+    entity_type: &Type,
+    depth: usize,
+    max_elements: Option<usize>,
+) -> String {
+    match &entity_type.kind {
+        &TypeKind::Base(encoding) => match encoding {
+            Encoding::Signed => to_integer(bytes, true).to_string(),
+            Encoding::Unsigned => (to_integer(bytes, false) as u64).to_string(),
+            Encoding::SignedChar | Encoding::UnsignedChar => {
+                let value = to_integer(bytes, encoding == Encoding::SignedChar);
+                format!("{} '{}'", value, escape_char(bytes[0]))
+            }
+            Encoding::Boolean => (to_integer(bytes, false) != 0).to_string(),
+            Encoding::Float => match bytes.len() {
+                4 => f32::from_le_bytes(bytes.try_into().unwrap()).to_string(),
+                8 => f64::from_le_bytes(bytes.try_into().unwrap()).to_string(),
+                _ => format_raw_bytes(bytes),
+            },
+            Encoding::Other => format_raw_bytes(bytes),
+        },
+        TypeKind::Pointer(_) => {
+            format!("({}) {:#x}", entity_type.name, to_integer(bytes, false))
+        }
+        TypeKind::Struct(members) => format_struct(bytes, members, depth, max_elements),
+        TypeKind::Array(element, _) => format_array(bytes, element, depth, max_elements),
+        TypeKind::Unknown => format_raw_bytes(bytes),
+    }
+}
+
+/// Formats a struct as `{x = 3, y = 7}`.
+fn format_struct(
+    bytes: &[u8],
+    members: &[Member],
+    depth: usize,
+    max_elements: Option<usize>,
+) -> String {
+    if depth >= MAX_PRINT_DEPTH {
+        return "{...}".to_string();
+    }
+    let members: Vec<String> = members
+        .iter()
+        .map(|member| {
+            let value = match bytes.get(member.offset..member.offset + member.entity_type.size) {
+                Some(member_bytes) => {
+                    format_nested_value(member_bytes, &member.entity_type, depth + 1, max_elements)
+                }
+                None => "<unavailable>".to_string(),
+            };
+            format!("{} = {}", member.name, value)
+        })
+        .collect();
+    format!("{{{}}}", members.join(", "))
+}
+
+/// Formats the elements in `bytes` as `{1, 2, 3}`, or as a string literal if they are chars (up
+/// to the first NUL).
+fn format_array(bytes: &[u8], element: &Type, depth: usize, max_elements: Option<usize>) -> String {
+    if element.size == 0 {
+        return format_raw_bytes(bytes);
+    }
+    let count = bytes.len() / element.size;
+    let shown = max_elements.map_or(count, |max| count.min(max));
+    let ellipsis = if shown < count { "..." } else { "" };
+    if let (TypeKind::Base(Encoding::SignedChar | Encoding::UnsignedChar), 1) =
+        (&element.kind, element.size)
+    {
+        let len = bytes.iter().position(|&byte| byte == 0).unwrap_or(count);
+        let ellipsis = if shown < len { "..." } else { "" };
+        return format!(
+            "\"{}\"{}",
+            escape_string(&bytes[..len.min(shown)]),
+            ellipsis
+        );
+    }
+    if depth >= MAX_PRINT_DEPTH {
+        return "{...}".to_string();
+    }
+    let elements: Vec<String> = bytes
+        .chunks_exact(element.size)
+        .take(shown)
+        .map(|element_bytes| format_nested_value(element_bytes, element, depth + 1, max_elements))
+        .collect();
+    format!("{{{}{}}}", elements.join(", "), ellipsis)
+}
+
+/// The output formats `print/F` and `x/F` accept: x (hex), d (signed decimal), u (unsigned
+/// decimal), c (character), t (binary) and a (address, with the symbol it points into)
+pub const FORMAT_LETTERS: &str = "xducta";
+
+/// Formats `bytes`, the in-memory representation of a value of type `entity_type`, in the
+/// output format `format` (one of `FORMAT_LETTERS`) instead of the way its type would have it.
+/// Scalars (floats included) are shown from their raw bits; structs and arrays (char arrays
+/// too) have each element formatted. `symbolize` names the code an address points into, for
+/// `a`.
+pub fn format_value_as(
+    bytes: &[u8],
+    entity_type: &Type,
+    format: char,
+    max_elements: Option<usize>,
+    symbolize: &dyn Fn(u64) -> Option<String>,
+) -> String {
+    format_nested_value_as(bytes, entity_type, format, 0, max_elements, symbolize)
+}
+
+fn format_nested_value_as(
+    bytes: &[u8],
+    entity_type: &Type,
+    format: char,
+    depth: usize,
+    max_elements: Option<usize>,
+    symbolize: &dyn Fn(u64) -> Option<String>,
+) -> String {
+    let nested = |bytes: &[u8], entity_type: &Type| {
+        format_nested_value_as(
+            bytes,
+            entity_type,
+            format,
+            depth + 1,
+            max_elements,
+            symbolize,
+        )
+    };
+    match &entity_type.kind {
+        TypeKind::Struct(members) => {
+            if depth >= MAX_PRINT_DEPTH {
+                return "{...}".to_string();
+            }
+            let members: Vec<String> = members
+                .iter()
+                .map(|member| {
+                    let end = member.offset + member.entity_type.size;
+                    let value = match bytes.get(member.offset..end) {
+                        Some(member_bytes) => nested(member_bytes, &member.entity_type),
+                        None => "<unavailable>".to_string(),
+                    };
+                    format!("{} = {}", member.name, value)
+                })
+                .collect();
+            format!("{{{}}}", members.join(", "))
+        }
+        TypeKind::Array(element, _) if element.size > 0 => {
+            if depth >= MAX_PRINT_DEPTH {
+                return "{...}".to_string();
+            }
+            let count = bytes.len() / element.size;
+            let shown = max_elements.map_or(count, |max| count.min(max));
+            let ellipsis = if shown < count { "..." } else { "" };
+            let elements: Vec<String> = bytes
+                .chunks_exact(element.size)
+                .take(shown)
+                .map(|element_bytes| nested(element_bytes, element))
+                .collect();
+            format!("{{{}{}}}", elements.join(", "), ellipsis)
+        }
+        _ if bytes.is_empty() || bytes.len() > 8 => format_raw_bytes(bytes),
+        _ => format_scalar(bytes, format, symbolize),
+    }
+}
+
+/// Formats a scalar of at most 8 bytes in the output format `format`.
+fn format_scalar(bytes: &[u8], format: char, symbolize: &dyn Fn(u64) -> Option<String>) -> String {
+    let unsigned = to_integer(bytes, false) as u64;
+    match format {
+        'd' => to_integer(bytes, true).to_string(),
+        'u' => unsigned.to_string(),
+        'c' => format!(
+            "{} '{}'",
+            to_integer(&bytes[..1], true),
+            escape_char(bytes[0])
+        ),
+        't' => format!("{:b}", unsigned),
+        'a' => match symbolize(unsigned) {
+            Some(symbol) => format!("{:#x} {}", unsigned, symbol),
+            None => format!("{:#x}", unsigned),
+        },
+        _ => format!("{:#x}", unsigned),
+    }
+}
+
+/// Formats one unit of memory for the `x` command. `format` is one of x (hex), d (signed),
+/// u (unsigned), c (character), t (binary) or a (address, named by `symbolize`). Hex and binary
+/// are padded to the unit size.
+pub fn format_memory_unit(
+    bytes: &[u8],
+    format: char,
+    symbolize: &dyn Fn(u64) -> Option<String>,
+) -> String {
+    match format {
+        'x' => format!(
+            "0x{:0width$x}",
+            to_integer(bytes, false) as u64,
+            width = bytes.len() * 2
+        ),
+        't' => format!(
+            "{:0width$b}",
+            to_integer(bytes, false) as u64,
+            width = bytes.len() * 8
+        ),
+        _ => format_scalar(bytes, format, symbolize),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn base(name: &str, size: usize, encoding: Encoding) -> Type {
+        Type::new(name.to_string(), size, TypeKind::Base(encoding))
+    }
+
+    #[test]
+    fn test_format_integers() {
+        let int = base("int", 4, Encoding::Signed);
+        assert_eq!(format_value(&(-5i32).to_le_bytes(), &int), "-5");
+        let unsigned = base("unsigned int", 4, Encoding::Unsigned);
+        assert_eq!(
+            format_value(&u32::MAX.to_le_bytes(), &unsigned),
+            "4294967295"
+        );
+        let short = base("short", 2, Encoding::Signed);
+        assert_eq!(format_value(&(-2i16).to_le_bytes(), &short), "-2");
+        let long = base("long", 8, Encoding::Signed);
+        assert_eq!(
+            format_value(&i64::MIN.to_le_bytes(), &long),
+            i64::MIN.to_string()
+        );
+    }
+
+    #[test]
+    fn test_format_chars_bools_and_floats() {
+        let char_type = base("char", 1, Encoding::SignedChar);
+        assert_eq!(format_value(b"A", &char_type), "65 'A'");
+        assert_eq!(format_value(b"\n", &char_type), "10 '\\n'");
+        assert_eq!(format_value(&[0xff], &char_type), "-1 '\\377'");
+        let bool_type = base("_Bool", 1, Encoding::Boolean);
+        assert_eq!(format_value(&[1], &bool_type), "true");
+        let double = base("double", 8, Encoding::Float);
+        assert_eq!(format_value(&1.5f64.to_le_bytes(), &double), "1.5");
+        let float = base("float", 4, Encoding::Float);
+        assert_eq!(format_value(&(-0.25f32).to_le_bytes(), &float), "-0.25");
+    }
+
+    #[test]
+    fn test_escape_string() {
+        assert_eq!(
+            escape_string(b"don't \"quote\"\n"),
+            "don't \\\"quote\\\"\\n"
+        );
+    }
+
+    #[test]
+    fn test_format_cstring() {
+        assert_eq!(format_cstring(b"hi\n", 200), "\"hi\\n\"");
+        assert_eq!(format_cstring(b"abc", 3), "\"abc\"...");
+        assert_eq!(format_cstring(b"", 200), "\"\"");
+    }
+
+    #[test]
+    fn test_format_memory_unit() {
+        let unit = |bytes: &[u8], format| format_memory_unit(bytes, format, &|_| None);
+        assert_eq!(unit(&[0x2a, 0, 0, 0], 'x'), "0x0000002a");
+        assert_eq!(unit(&[0xfe], 'x'), "0xfe");
+        assert_eq!(unit(&[0xfe, 0xff], 'd'), "-2");
+        assert_eq!(unit(&[0xfe, 0xff], 'u'), "65534");
+        assert_eq!(unit(b"a", 'c'), "97 'a'");
+        assert_eq!(unit(&[5], 't'), "00000101");
+    }
+
+    #[test]
+    fn test_format_value_as() {
+        let symbolize = |addr: u64| (addr == 0x401000).then(|| "<main+4>".to_string());
+        let format = |bytes: &[u8], entity_type: &Type, format| {
+            format_value_as(bytes, entity_type, format, None, &symbolize)
+        };
+        let int = base("int", 4, Encoding::Signed);
+        let minus_two = (-2i32).to_le_bytes();
+        assert_eq!(format(&minus_two, &int, 'x'), "0xfffffffe");
+        assert_eq!(format(&minus_two, &int, 'd'), "-2");
+        assert_eq!(format(&minus_two, &int, 'u'), "4294967294");
+        assert_eq!(format(&10i32.to_le_bytes(), &int, 't'), "1010");
+        assert_eq!(format(&65i32.to_le_bytes(), &int, 'c'), "65 'A'");
+        let pointer = Type::new("void *".to_string(), 8, TypeKind::Pointer(None));
+        assert_eq!(
+            format(&0x401000u64.to_le_bytes(), &pointer, 'a'),
+            "0x401000 <main+4>"
+        );
+        assert_eq!(format(&0x10u64.to_le_bytes(), &pointer, 'a'), "0x10");
+        // Floats are shown from their bits
+        let float = base("float", 4, Encoding::Float);
+        assert_eq!(format(&1.0f32.to_le_bytes(), &float, 'x'), "0x3f800000");
+        // Every element of an aggregate is formatted, chars included
+        let chars = Type::new(
+            "char [2]".to_string(),
+            2,
+            TypeKind::Array(Box::new(base("char", 1, Encoding::SignedChar)), Some(2)),
+        );
+        assert_eq!(format(b"hi", &chars, 'x'), "{0x68, 0x69}");
+        let point = Type::new(
+            "struct Point".to_string(),
+            8,
+            TypeKind::Struct(vec![
+                Member {
+                    name: "x".to_string(),
+                    offset: 0,
+                    entity_type: int.clone(),
+                },
+                Member {
+                    name: "y".to_string(),
+                    offset: 4,
+                    entity_type: int,
+                },
+            ]),
+        );
+        let bytes: Vec<u8> = [15i32, 16].iter().flat_map(|n| n.to_le_bytes()).collect();
+        assert_eq!(format(&bytes, &point, 'x'), "{x = 0xf, y = 0x10}");
+    }
+
+    #[test]
+    fn test_format_struct() {
+        let member = |name: &str, offset, entity_type| Member {
+            name: name.to_string(),
+            offset,
+            entity_type,
+        };
+        let int = base("int", 4, Encoding::Signed);
+        let point = Type::new(
+            "struct Point".to_string(),
+            8,
+            TypeKind::Struct(vec![member("x", 0, int.clone()), member("y", 4, int)]),
+        );
+        let bytes: Vec<u8> = [3i32, 7, -1, 2]
+            .iter()
+            .flat_map(|n| n.to_le_bytes())
+            .collect();
+        assert_eq!(format_value(&bytes[..8], &point), "{x = 3, y = 7}");
+        let line = Type::new(
+            "struct Line".to_string(),
+            16,
+            TypeKind::Struct(vec![
+                member("from", 0, point.clone()),
+                member("to", 8, point.clone()),
+            ]),
+        );
+        assert_eq!(
+            format_value(&bytes, &line),
+            "{from = {x = 3, y = 7}, to = {x = -1, y = 2}}"
+        );
+        // Members past the end of what was read
+        assert_eq!(
+            format_value(&bytes[..12], &line),
+            "{from = {x = 3, y = 7}, to = <unavailable>}"
+        );
+
+        let mut nested = point;
+        for _ in 0..MAX_PRINT_DEPTH {
+            nested = Type::new(
+                "struct Wrapper".to_string(),
+                8,
+                TypeKind::Struct(vec![member("inner", 0, nested)]),
+            );
+        }
+        assert_eq!(
+            format_value(&bytes[..8], &nested),
+            format!(
+                "{}{{...}}{}",
+                "{inner = ".repeat(MAX_PRINT_DEPTH),
+                "}".repeat(MAX_PRINT_DEPTH)
+            )
+        );
+    }
+
+    #[test]
+    fn test_format_array() {
+        let int = base("int", 4, Encoding::Signed);
+        let array = |element: Type, count| {
+            Type::new(
+                format!("{} [{}]", element.name, count),
+                element.size * count,
+                TypeKind::Array(Box::new(element), Some(count)),
+            )
+        };
+        let bytes: Vec<u8> = (1..=6i32).flat_map(|n| n.to_le_bytes()).collect();
+        assert_eq!(
+            format_value(&bytes, &array(int.clone(), 6)),
+            "{1, 2, 3, 4, 5, 6}"
+        );
+        assert_eq!(
+            format_value_limited(&bytes, &array(int.clone(), 6), Some(4)),
+            "{1, 2, 3, 4...}"
+        );
+        assert_eq!(
+            format_value(&bytes, &array(array(int, 3), 2)),
+            "{{1, 2, 3}, {4, 5, 6}}"
+        );
+
+        let char_type = base("char", 1, Encoding::SignedChar);
+        let chars = array(char_type, 8);
+        assert_eq!(format_value(b"say \"hi\"", &chars), "\"say \\\"hi\\\"\"");
+        assert_eq!(format_value(b"ab\0cdefg", &chars), "\"ab\"");
+        assert_eq!(
+            format_value_limited(b"abcdefgh", &chars, Some(3)),
+            "\"abc\"..."
+        );
+        assert_eq!(
+            format_value_limited(b"abcdefgh", &chars, None),
+            "\"abcdefgh\""
+        );
+    }
+
+    #[test]
+    fn test_format_pointer() {
+        let pointer = Type::new("int *".to_string(), 8, TypeKind::Pointer(None));
+        assert_eq!(
+            format_value(&0x7ffc1234usize.to_le_bytes(), &pointer),
+            "(int *) 0x7ffc1234"
+        );
+    }
+}
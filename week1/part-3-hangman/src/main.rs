@@ -1,6 +1,6 @@
 // Simple Hangman Program
-// User gets five incorrect guesses
-// Word chosen randomly from words.txt
+// User gets five incorrect guesses (or as many as --guesses says)
+// Word chosen randomly from words.txt (or the file given with --words)
 // Inspiration from: https://doc.rust-lang.org/book/ch02-00-guessing-game-tutorial.html
 // This assignment will introduce you to some fundamental syntax in Rust:
 // - variable declaration
@@ -12,78 +12,1065 @@
 // - user input
 // We've tried to limit/hide Rust's quirks since we'll discuss those details
 // more in depth in the coming lectures.
+extern crate libc;
 extern crate rand;
+mod stats;
+
 use rand::Rng;
+use stats::Stats;
+use std::env;
+use std::fmt;
 use std::fs;
 use std::io;
-use std::io::Write;
+use std::io::{BufRead, IsTerminal, Write};
 use std::iter::FromIterator;
+use std::mem;
+use std::process;
 
+// Defaults, used when the command line doesn't say otherwise
 const NUM_INCORRECT_GUESSES: u32 = 5;
 const WORDS_PATH: &str = "words.txt";
 
-fn pick_a_random_word() -> String {
-    let file_string = fs::read_to_string(WORDS_PATH).expect("Unable to read file.");
-    let words: Vec<&str> = file_string.split('\n').collect();
-    String::from(words[rand::thread_rng().gen_range(0, words.len())].trim())
+// The gallows, drawn with more and more of the figure: head, body, arms and legs
+const GALLOWS_STAGES: [&str; 7] = [
+    "  +---+
+  |   |
+      |
+      |
+      |
+      |
+=========",
+    "  +---+
+  |   |
+  O   |
+      |
+      |
+      |
+=========",
+    "  +---+
+  |   |
+  O   |
+  |   |
+      |
+      |
+=========",
+    "  +---+
+  |   |
+  O   |
+ /|   |
+      |
+      |
+=========",
+    "  +---+
+  |   |
+  O   |
+ /|\\  |
+      |
+      |
+=========",
+    "  +---+
+  |   |
+  O   |
+ /|\\  |
+ /    |
+      |
+=========",
+    "  +---+
+  |   |
+  O   |
+ /|\\  |
+ / \\  |
+      |
+=========",
+];
+
+// Returns which stage of the gallows to draw for `incorrect_guesses` mistakes out of
+// `num_incorrect_guesses` allowed. The parts of the figure are spread over the allowed mistakes,
+// rounding up so that the first mistake already shows something, and the figure is complete
+// exactly when the player runs out of guesses.
+fn gallows_stage(incorrect_guesses: u32, num_incorrect_guesses: u32) -> usize {
+    let num_parts = (GALLOWS_STAGES.len() - 1) as u32;
+    let mistakes = incorrect_guesses.min(num_incorrect_guesses);
+    (mistakes * num_parts).div_ceil(num_incorrect_guesses) as usize
 }
 
-fn main() {
-    let secret_word = pick_a_random_word();
-    // Note: given what you know about Rust so far, it's easier to pull characters out of a
-    // vector than it is to pull them out of a string. You can get the ith character of
-    // secret_word by doing secret_word_chars[i].
-    let secret_word_chars: Vec<char> = secret_word.chars().collect();
-    // Uncomment for debugging:
-    println!("random word: {}", secret_word);
-
-    // Your code here! :)
-    let mut guessed_word: String = "-".repeat(secret_word.len());
-    let mut guessed_chars: Vec<char> = Vec::new();
-    let mut incorrect_guesses: u32 = 0;
-
-    println!("Welcome to CS110L Hangman!");
-
-    while incorrect_guesses < NUM_INCORRECT_GUESSES && guessed_word != secret_word {
-        println!("The word so far is {}", guessed_word);
-        println!(
-            "You have guessed the following letters: {}",
-            String::from_iter(guessed_chars.iter())
-        );
-        println!(
-            "You have {} guesses left",
-            NUM_INCORRECT_GUESSES - incorrect_guesses
-        );
-        print!("Please guess a letter: ");
+fn gallows(incorrect_guesses: u32, num_incorrect_guesses: u32) -> &'static str {
+    GALLOWS_STAGES[gallows_stage(incorrect_guesses, num_incorrect_guesses)]
+}
+
+// How many incorrect guesses a wrong guess of the whole word counts as
+const WRONG_WORD_PENALTY: u32 = 1;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Difficulty {
+    Easy,
+    Medium,
+    Hard,
+}
+
+impl Difficulty {
+    fn parse(name: &str) -> Option<Difficulty> {
+        match name {
+            "easy" => Some(Difficulty::Easy),
+            "medium" => Some(Difficulty::Medium),
+            "hard" => Some(Difficulty::Hard),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "easy",
+            Difficulty::Medium => "medium",
+            Difficulty::Hard => "hard",
+        }
+    }
+
+    // Returns whether `word` has the right number of letters: 4–6 for easy, 7–9 for medium and
+    // 10 or more for hard. Spaces and punctuation don't count.
+    fn suits(self, word: &str) -> bool {
+        let num_letters = word.chars().filter(|c| c.is_alphabetic()).count();
+        match self {
+            Difficulty::Easy => (4..=6).contains(&num_letters),
+            Difficulty::Medium => (7..=9).contains(&num_letters),
+            Difficulty::Hard => num_letters >= 10,
+        }
+    }
+
+    fn num_incorrect_guesses(self) -> u32 {
+        match self {
+            Difficulty::Easy => 7,
+            Difficulty::Medium => 5,
+            Difficulty::Hard => 4,
+        }
+    }
+}
+
+// Settings for a game, taken from the command line
+struct Config {
+    num_incorrect_guesses: u32,
+    words_path: String,
+    // Whether one player types the secret word for the other to guess, instead of it being
+    // picked from the word list
+    two_player: bool,
+    // Limits the words picked by length, and sets the number of incorrect guesses unless
+    // --guesses is given
+    difficulty: Option<Difficulty>,
+    // Print the statistics saved from earlier games, or clear them, instead of playing
+    show_stats: bool,
+    reset_stats: bool,
+}
+
+fn usage(program: &str) -> String {
+    format!(
+        "Usage: {} [options]
+
+Options:
+  -g, --guesses N    Number of incorrect guesses allowed (default {})
+  -w, --words PATH   File to pick the secret word from, one word per line (default {})
+  -d, --difficulty LEVEL
+                     easy (4-6 letters, 7 guesses), medium (7-9 letters, 5 guesses)
+                     or hard (10+ letters, 4 guesses)
+  --two-player       Have one player type the secret word for the other to guess
+  --stats            Print the statistics saved from earlier games and exit
+  --reset-stats      Clear the saved statistics (after asking) and exit
+  -h, --help         Print this help and exit",
+        program, NUM_INCORRECT_GUESSES, WORDS_PATH
+    )
+}
+
+// Parses the command-line arguments (not including the program name). Returns None if the
+// player asked for help.
+fn parse_args(args: &[String]) -> Result<Option<Config>, String> {
+    let mut config = Config {
+        num_incorrect_guesses: NUM_INCORRECT_GUESSES,
+        words_path: WORDS_PATH.to_string(),
+        two_player: false,
+        difficulty: None,
+        show_stats: false,
+        reset_stats: false,
+    };
+    let mut num_incorrect_guesses = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "-g" | "--guesses" => {
+                let value = iter
+                    .next()
+                    .ok_or(format!("{} needs a number of guesses", arg))?;
+                num_incorrect_guesses = match value.parse() {
+                    Ok(n) if n >= 1 => Some(n),
+                    _ => {
+                        return Err(format!(
+                            "Invalid number of guesses \"{}\" (must be at least 1)",
+                            value
+                        ))
+                    }
+                };
+            }
+            "-w" | "--words" => {
+                config.words_path = iter
+                    .next()
+                    .ok_or(format!("{} needs the path of a word list", arg))?
+                    .clone();
+            }
+            "-d" | "--difficulty" => {
+                let value = iter
+                    .next()
+                    .ok_or(format!("{} needs a difficulty level", arg))?;
+                config.difficulty = Some(Difficulty::parse(value).ok_or(format!(
+                    "Invalid difficulty \"{}\" (must be easy, medium or hard)",
+                    value
+                ))?);
+            }
+            "--two-player" => config.two_player = true,
+            "--stats" => config.show_stats = true,
+            "--reset-stats" => config.reset_stats = true,
+            _ => return Err(format!("Unrecognized argument \"{}\"", arg)),
+        }
+    }
+    config.num_incorrect_guesses = num_incorrect_guesses
+        .or(config.difficulty.map(Difficulty::num_incorrect_guesses))
+        .unwrap_or(NUM_INCORRECT_GUESSES);
+    Ok(Some(config))
+}
+
+// Reads the word list at `path`, skipping blank lines. It's an error for the list to be empty.
+fn load_words(path: &str) -> Result<Vec<String>, String> {
+    let file_string =
+        fs::read_to_string(path).map_err(|err| format!("Unable to read {}: {}", path, err))?;
+    let words: Vec<String> = file_string
+        .lines()
+        .map(|line| line.trim().to_string())
+        .filter(|word| !word.is_empty())
+        .collect();
+    if words.is_empty() {
+        return Err(format!("{} doesn't contain any words", path));
+    }
+    Ok(words)
+}
+
+// Characters besides letters that may appear in a secret phrase. They're shown from the start
+// and never need to be guessed.
+const PHRASE_PUNCTUATION: [char; 3] = [' ', '-', '\''];
+
+fn is_phrase_char(c: char) -> bool {
+    c.is_alphabetic() || PHRASE_PUNCTUATION.contains(&c)
+}
+
+// Returns `phrase` with runs of whitespace turned into single spaces, and without whitespace at
+// either end.
+fn squeeze_whitespace(phrase: &str) -> String {
+    phrase.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+// Why a line typed by the player isn't a valid guess
+#[derive(Debug, PartialEq)]
+enum InputError {
+    Empty,
+    // More than one character, not all of them letters
+    NotAWord(String),
+    NotALetter(char),
+}
+
+impl fmt::Display for InputError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InputError::Empty => write!(f, "You didn't enter anything.")?,
+            InputError::NotAWord(line) => write!(f, "\"{}\" is not a word.", line)?,
+            InputError::NotALetter(c) => write!(f, "'{}' is not a letter.", c)?,
+        }
+        write!(f, " Please enter a single letter a–z, or the whole word")
+    }
+}
+
+// What the player guessed
+#[derive(Debug, PartialEq)]
+enum Guess {
+    Letter(char),
+    // The whole word (or phrase)
+    Word(String),
+}
+
+// Checks that a line typed by the player is a single letter, or a word or phrase made of letters
+// and PHRASE_PUNCTUATION, ignoring surrounding whitespace.
+fn parse_guess(line: &str) -> Result<Guess, InputError> {
+    let line = line.trim();
+    let mut chars = line.chars();
+    match (chars.next(), chars.next()) {
+        (None, _) => Err(InputError::Empty),
+        (Some(c), None) if !c.is_alphabetic() => Err(InputError::NotALetter(c)),
+        (Some(c), None) => Ok(Guess::Letter(c)),
+        _ if !line.chars().all(is_phrase_char) || !line.chars().any(char::is_alphabetic) => {
+            Err(InputError::NotAWord(line.to_string()))
+        }
+        _ => Ok(Guess::Word(squeeze_whitespace(line))),
+    }
+}
+
+// Asks for a guess until the player enters a valid one. Returns None if the input ends first.
+fn read_guess<R: BufRead>(input: &mut R) -> Option<Guess> {
+    loop {
+        print!("Please guess a letter (or the whole word): ");
         io::stdout().flush().expect("Error flushing stdout.");
         let mut guess_line = String::new();
-        io::stdin()
+        if input
             .read_line(&mut guess_line)
-            .expect("Error reading line.");
-        let guess_char: char = guess_line.trim().chars().next().unwrap();
-        guessed_chars.push(guess_char);
-
-        if secret_word_chars.contains(&guess_char) {
-            for i in 0..secret_word.len() {
-                if secret_word_chars[i] == guess_char {
-                    guessed_word.replace_range(i..=i, &guess_char.to_string());
+            .expect("Error reading line.")
+            == 0
+        {
+            println!();
+            return None;
+        }
+        match parse_guess(&guess_line) {
+            Ok(guess) => return Some(guess),
+            Err(err) => println!("{}", err),
+        }
+    }
+}
+
+// Returns the lowercase form of `c`, so that letters match regardless of case. Letters whose
+// lowercase form is more than one character (such as 'İ') are left as they are.
+fn normalize(c: char) -> char {
+    let mut lower = c.to_lowercase();
+    match (lower.next(), lower.next()) {
+        (Some(lower), None) => lower,
+        _ => c,
+    }
+}
+
+// Normalizes every letter of `phrase` and squeezes its whitespace, for comparing whole words.
+fn normalize_phrase(phrase: &str) -> String {
+    squeeze_whitespace(phrase).chars().map(normalize).collect()
+}
+
+// What became of a guess
+#[derive(Debug, PartialEq)]
+enum GuessResult {
+    Correct,
+    Incorrect,
+    // The letter had been guessed before, so nothing changed
+    AlreadyGuessed,
+}
+
+// The state of a game in progress
+// Words are handled as vectors of characters rather than strings, so that positions count
+// characters, not bytes (which differ for letters outside ASCII, like the 'ï' in "naïve").
+struct Game {
+    secret_word_chars: Vec<char>,
+    // The secret word with the letters not guessed yet replaced by dashes. Anything else in it
+    // (the spaces and punctuation of a phrase) is shown as it is.
+    guessed_word: Vec<char>,
+    // Every letter guessed so far (normalized), once each, in the order guessed
+    guessed_chars: Vec<char>,
+    // Wrong guesses of the whole word (normalized), once each
+    wrong_words: Vec<String>,
+    incorrect_guesses: u32,
+    num_incorrect_guesses: u32,
+}
+
+impl Game {
+    fn new(secret_word: &str, num_incorrect_guesses: u32) -> Game {
+        Game {
+            secret_word_chars: secret_word.chars().collect(),
+            guessed_word: secret_word
+                .chars()
+                .map(|c| if c.is_alphabetic() { '-' } else { c })
+                .collect(),
+            guessed_chars: Vec::new(),
+            wrong_words: Vec::new(),
+            incorrect_guesses: 0,
+            num_incorrect_guesses,
+        }
+    }
+
+    // Reveals `guess_char` wherever it appears in the word, in either case, or counts it
+    // against the player. Guessing a letter again changes nothing.
+    fn guess(&mut self, guess_char: char) -> GuessResult {
+        let guess_char = normalize(guess_char);
+        if self.guessed_chars.contains(&guess_char) {
+            return GuessResult::AlreadyGuessed;
+        }
+        self.guessed_chars.push(guess_char);
+
+        let in_word = |&secret_char: &char| normalize(secret_char) == guess_char;
+        if self.secret_word_chars.iter().any(in_word) {
+            for (i, &secret_char) in self.secret_word_chars.iter().enumerate() {
+                if in_word(&secret_char) {
+                    // Reveal the letter as it's written in the word, whatever case was typed
+                    self.guessed_word[i] = secret_char;
                     // keep doing this until all occurrences of guess_char are replaced
                 }
             }
+            GuessResult::Correct
         } else {
-            println!("Sorry, that letter is not in the word");
-            incorrect_guesses += 1;
+            self.incorrect_guesses += 1;
+            GuessResult::Incorrect
+        }
+    }
+
+    // Guesses the whole word, in any case. Getting it right wins the game; getting it wrong costs
+    // WRONG_WORD_PENALTY guesses, but only the first time.
+    fn guess_word(&mut self, word: &str) -> GuessResult {
+        let word = normalize_phrase(word);
+        let secret_word = normalize_phrase(&self.secret_word_chars.iter().collect::<String>());
+        if word == secret_word {
+            self.guessed_word = self.secret_word_chars.clone();
+            return GuessResult::Correct;
+        }
+        if self.wrong_words.contains(&word) {
+            return GuessResult::AlreadyGuessed;
+        }
+        self.wrong_words.push(word);
+        self.incorrect_guesses += WRONG_WORD_PENALTY;
+        GuessResult::Incorrect
+    }
+
+    // Returns how many letters the secret word has (not counting spaces and punctuation).
+    fn num_letters(&self) -> usize {
+        self.secret_word_chars
+            .iter()
+            .filter(|c| c.is_alphabetic())
+            .count()
+    }
+
+    // Returns the word as far as it has been guessed, for display.
+    fn word_so_far(&self) -> String {
+        self.guessed_word.iter().collect()
+    }
+
+    fn guesses_left(&self) -> u32 {
+        self.num_incorrect_guesses
+            .saturating_sub(self.incorrect_guesses)
+    }
+
+    fn is_won(&self) -> bool {
+        self.guessed_word == self.secret_word_chars
+    }
+
+    fn is_lost(&self) -> bool {
+        self.incorrect_guesses >= self.num_incorrect_guesses
+    }
+}
+
+// Checks that a secret word typed by a player is something that can be guessed: letters, maybe
+// with PHRASE_PUNCTUATION between them.
+fn validate_secret(line: &str) -> Result<String, String> {
+    let secret = squeeze_whitespace(line);
+    if secret.is_empty() {
+        return Err("The secret word can't be empty".to_string());
+    }
+    if let Some(c) = secret.chars().find(|&c| !is_phrase_char(c)) {
+        return Err(format!("The secret word can't contain '{}'", c));
+    }
+    if !secret.chars().any(char::is_alphabetic) {
+        return Err("The secret word needs at least one letter".to_string());
+    }
+    Ok(secret)
+}
+
+// Turns echo back on for stdin's terminal. Only makes calls that are safe in a signal handler.
+fn restore_echo() {
+    unsafe {
+        let mut settings: libc::termios = mem::zeroed();
+        if libc::tcgetattr(libc::STDIN_FILENO, &mut settings) == 0 {
+            settings.c_lflag |= libc::ECHO;
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &settings);
+        }
+    }
+}
+
+// Ctrl-C while input is hidden: Drop won't run, so put echo back before dying of the signal
+extern "C" fn interrupted(signal: libc::c_int) {
+    restore_echo();
+    unsafe {
+        libc::signal(signal, libc::SIG_DFL);
+        libc::raise(signal);
+    }
+}
+
+// While this exists, what's typed at the terminal isn't shown
+struct HiddenInput {
+    settings: libc::termios,
+    sigint_handler: libc::sighandler_t,
+}
+
+impl HiddenInput {
+    // Fails if stdin isn't a terminal
+    fn new() -> io::Result<HiddenInput> {
+        unsafe {
+            let mut settings: libc::termios = mem::zeroed();
+            if libc::tcgetattr(libc::STDIN_FILENO, &mut settings) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let sigint_handler = libc::signal(
+                libc::SIGINT,
+                interrupted as extern "C" fn(libc::c_int) as libc::sighandler_t,
+            );
+            let mut hidden = settings;
+            hidden.c_lflag &= !libc::ECHO;
+            if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &hidden) != 0 {
+                let err = io::Error::last_os_error();
+                libc::signal(libc::SIGINT, sigint_handler);
+                return Err(err);
+            }
+            Ok(HiddenInput {
+                settings,
+                sigint_handler,
+            })
         }
+    }
+}
 
+impl Drop for HiddenInput {
+    fn drop(&mut self) {
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.settings);
+            libc::signal(libc::SIGINT, self.sigint_handler);
+        }
+    }
+}
+
+// Prints `prompt` and reads a line, without showing what's typed if `hide_input` is set (and the
+// terminal allows it). Returns None if the input has ended.
+fn read_secret_line<R: BufRead>(input: &mut R, prompt: &str, hide_input: bool) -> Option<String> {
+    print!("{}", prompt);
+    io::stdout().flush().expect("Error flushing stdout.");
+    let hidden = if hide_input {
+        HiddenInput::new()
+            .map_err(|err| println!("(Can't hide what you type: {})", err))
+            .ok()
+    } else {
+        None
+    };
+    let mut line = String::new();
+    let num_read = input.read_line(&mut line).expect("Error reading line.");
+    if hidden.is_some() {
+        // The newline that was typed wasn't shown either
         println!();
     }
+    if num_read == 0 {
+        return None;
+    }
+    Some(line)
+}
 
-    if guessed_word == secret_word {
+// Has player one type the secret word twice (to catch typos, since it can't be seen), until it
+// is valid and both entries match. Returns None if the input ends first.
+fn read_secret_word<R: BufRead>(input: &mut R, hide_input: bool) -> Option<String> {
+    loop {
+        let line = read_secret_line(input, "Player one, enter the secret word: ", hide_input)?;
+        let secret = match validate_secret(&line) {
+            Ok(secret) => secret,
+            Err(err) => {
+                println!("{}", err);
+                continue;
+            }
+        };
+        let confirmation = read_secret_line(input, "Enter it again to confirm: ", hide_input)?;
+        if squeeze_whitespace(&confirmation) == secret {
+            return Some(secret);
+        }
+        println!("The two entries don't match, please start over");
+    }
+}
+
+// Picks a word from `words` with a number of letters that suits the difficulty, if one is set.
+fn pick_a_random_word(config: &Config, words: &[String]) -> Result<String, String> {
+    let candidates: Vec<&String> = match config.difficulty {
+        Some(difficulty) => words.iter().filter(|word| difficulty.suits(word)).collect(),
+        None => words.iter().collect(),
+    };
+    if candidates.is_empty() {
+        return Err(format!(
+            "No word in {} suits the difficulty \"{}\"",
+            config.words_path,
+            config.difficulty.unwrap().name()
+        ));
+    }
+    Ok(candidates[rand::thread_rng().gen_range(0, candidates.len())].clone())
+}
+
+// How a round ended
+#[derive(Debug, PartialEq)]
+enum RoundResult {
+    Won,
+    Lost,
+    // The input ended before the round did
+    Abandoned,
+}
+
+// Plays a round with a newly picked word, reading guesses from `input`, and counts it in
+// `stats` if it's finished. Fails if no word in the list suits the difficulty.
+fn play_round<R: BufRead>(
+    config: &Config,
+    words: &[String],
+    input: &mut R,
+    stats: &mut Stats,
+) -> Result<RoundResult, String> {
+    let secret_word = if config.two_player {
+        let at_terminal = io::stdin().is_terminal();
+        let secret_word = match read_secret_word(input, at_terminal) {
+            Some(secret_word) => secret_word,
+            None => return Ok(RoundResult::Abandoned),
+        };
+        if at_terminal {
+            // Clear the screen, so that player two can't scroll back to the secret word
+            print!("\x1B[2J\x1B[3J\x1B[H");
+        }
+        println!("Player two, it's your turn to guess!");
+        secret_word
+    } else {
+        let secret_word = pick_a_random_word(config, words)?;
+        // Uncomment for debugging:
+        println!("random word: {}", secret_word);
+        secret_word
+    };
+
+    let mut game = Game::new(&secret_word, config.num_incorrect_guesses);
+
+    while !game.is_won() && !game.is_lost() {
+        println!(
+            "{}",
+            gallows(game.incorrect_guesses, game.num_incorrect_guesses)
+        );
+        println!("The word so far is {}", game.word_so_far());
+        println!(
+            "You have guessed the following letters: {}",
+            String::from_iter(game.guessed_chars.iter())
+        );
+        if !game.wrong_words.is_empty() {
+            println!(
+                "You have wrongly guessed the words: {}",
+                game.wrong_words.join(", ")
+            );
+        }
+        println!("You have {} guesses left", game.guesses_left());
+        let (guess, result) = loop {
+            let guess = match read_guess(input) {
+                Some(guess) => guess,
+                None => {
+                    println!("Goodbye! The word was {}", secret_word);
+                    return Ok(RoundResult::Abandoned);
+                }
+            };
+            let result = match guess {
+                Guess::Letter(guess_char) => game.guess(guess_char),
+                Guess::Word(ref word) => game.guess_word(word),
+            };
+            match (result, &guess) {
+                (GuessResult::AlreadyGuessed, Guess::Letter(guess_char)) => {
+                    println!("You already guessed '{}'", guess_char)
+                }
+                (GuessResult::AlreadyGuessed, Guess::Word(word)) => {
+                    println!("You already guessed \"{}\"", word)
+                }
+                (result, _) => break (guess, result),
+            }
+        };
+        match (result, guess) {
+            (GuessResult::Incorrect, Guess::Letter(_)) => {
+                println!("Sorry, that letter is not in the word")
+            }
+            (GuessResult::Incorrect, Guess::Word(_)) => println!("Sorry, that's not the word"),
+            _ => {}
+        }
+
+        println!();
+    }
+
+    stats.record(
+        game.is_won(),
+        game.num_letters(),
+        game.guessed_chars.len(),
+        config.difficulty,
+    );
+    if game.is_won() {
         println!(
             "Congratulations you guessed the secret word: {}!",
             secret_word
         );
+        Ok(RoundResult::Won)
+    } else {
+        println!(
+            "{}",
+            gallows(game.incorrect_guesses, game.num_incorrect_guesses)
+        );
+        println!(
+            "Sorry, you ran out of guesses! The word was {}",
+            secret_word
+        );
+        Ok(RoundResult::Lost)
+    }
+}
+
+// Wins and losses over the rounds played in this session
+#[derive(Debug, Default, PartialEq)]
+struct Tally {
+    played: u32,
+    wins: u32,
+    losses: u32,
+    // Wins since the last loss
+    streak: u32,
+    // The difficulty the rounds were played at, if one was chosen
+    difficulty: Option<Difficulty>,
+}
+
+impl Tally {
+    fn record(&mut self, result: &RoundResult, difficulty: Option<Difficulty>) {
+        match result {
+            RoundResult::Won => {
+                self.wins += 1;
+                self.streak += 1;
+            }
+            RoundResult::Lost => {
+                self.losses += 1;
+                self.streak = 0;
+            }
+            RoundResult::Abandoned => return,
+        }
+        self.played += 1;
+        self.difficulty = difficulty;
+    }
+}
+
+impl fmt::Display for Tally {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Games played: {}, wins: {}, losses: {}, current streak: {}",
+            self.played, self.wins, self.losses, self.streak
+        )?;
+        if let Some(difficulty) = self.difficulty {
+            write!(f, " (difficulty: {})", difficulty.name())?;
+        }
+        Ok(())
+    }
+}
+
+// Asks `question` until the answer is yes or no. The end of the input counts as no.
+fn ask_yes_no<R: BufRead>(input: &mut R, question: &str) -> bool {
+    loop {
+        print!("{} (y/n) ", question);
+        io::stdout().flush().expect("Error flushing stdout.");
+        let mut answer = String::new();
+        if input.read_line(&mut answer).expect("Error reading line.") == 0 {
+            println!();
+            return false;
+        }
+        match answer.trim().to_lowercase().as_str() {
+            "y" | "yes" => return true,
+            "n" | "no" => return false,
+            _ => println!("Please answer y or n"),
+        }
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let config = match parse_args(&args[1..]) {
+        Ok(Some(config)) => config,
+        Ok(None) => {
+            println!("{}", usage(&args[0]));
+            return;
+        }
+        Err(err) => {
+            eprintln!("{}\n{}", err, usage(&args[0]));
+            process::exit(1);
+        }
+    };
+    let stdin = io::stdin();
+    let mut input = stdin.lock();
+    // Statistics are kept in the home directory, if there is one
+    let stats_path = stats::stats_path()
+        .map_err(|err| println!("WARNING: statistics won't be saved ({})", err))
+        .ok();
+    let mut stats = match stats_path {
+        Some(ref path) => Stats::load(path).unwrap_or_else(|err| {
+            println!("WARNING: {}; starting with fresh statistics", err);
+            Stats::default()
+        }),
+        None => Stats::default(),
+    };
+    if config.show_stats {
+        println!("{}", stats);
+        return;
+    }
+    if config.reset_stats {
+        if ask_yes_no(&mut input, "Really clear all your statistics?") {
+            // Without a home directory there's no file to clear
+            if let Err(err) = stats::stats_path().and_then(|path| Stats::default().save(&path)) {
+                eprintln!("{}", err);
+                process::exit(1);
+            }
+            println!("Statistics cleared");
+        }
+        return;
+    }
+
+    let words = if config.two_player {
+        if !io::stdin().is_terminal() {
+            println!("WARNING: not reading from a terminal, so the secret word will be visible");
+        }
+        Vec::new()
     } else {
-        println!("Sorry, you ran out of guesses!");
+        load_words(&config.words_path).unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            process::exit(1);
+        })
+    };
+
+    match config.difficulty {
+        Some(difficulty) => println!(
+            "Welcome to CS110L Hangman! (difficulty: {})",
+            difficulty.name()
+        ),
+        None => println!("Welcome to CS110L Hangman!"),
+    }
+
+    let mut tally = Tally::default();
+    loop {
+        let result = play_round(&config, &words, &mut input, &mut stats).unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            process::exit(1);
+        });
+        if result == RoundResult::Abandoned {
+            break;
+        }
+        tally.record(&result, config.difficulty);
+        if let Some(ref path) = stats_path {
+            if let Err(err) = stats.save(path) {
+                println!("WARNING: {}", err);
+            }
+        }
+        println!("{}", tally);
+        if !ask_yes_no(&mut input, "Play again?") {
+            break;
+        }
+        println!();
+    }
+    println!("Thanks for playing! {}", tally);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_guess() {
+        assert_eq!(parse_guess("e\n"), Ok(Guess::Letter('e')));
+        assert_eq!(parse_guess("  Q \n"), Ok(Guess::Letter('Q')));
+        assert_eq!(parse_guess("\n"), Err(InputError::Empty));
+        assert_eq!(parse_guess("   "), Err(InputError::Empty));
+        assert_eq!(
+            parse_guess("  Lobster \n"),
+            Ok(Guess::Word("Lobster".to_string()))
+        );
+        assert_eq!(
+            parse_guess("ab3\n"),
+            Err(InputError::NotAWord("ab3".to_string()))
+        );
+        assert_eq!(parse_guess("7\n"), Err(InputError::NotALetter('7')));
+        assert_eq!(parse_guess("?"), Err(InputError::NotALetter('?')));
+    }
+
+    #[test]
+    fn test_repeated_guesses() {
+        let mut game = Game::new("shared", 5);
+        assert_eq!(game.guess('e'), GuessResult::Correct);
+        assert_eq!(game.guess('e'), GuessResult::AlreadyGuessed);
+        assert_eq!(game.guess('z'), GuessResult::Incorrect);
+        assert_eq!(game.guess('z'), GuessResult::AlreadyGuessed);
+        assert_eq!(game.guesses_left(), 4);
+        assert_eq!(game.guessed_chars, vec!['e', 'z']);
+        assert_eq!(game.word_so_far(), "----e-");
+    }
+
+    #[test]
+    fn test_case_insensitive_guesses() {
+        let mut game = Game::new("Paris", 5);
+        assert_eq!(game.guess('p'), GuessResult::Correct);
+        assert_eq!(game.guess('A'), GuessResult::Correct);
+        assert_eq!(game.guess('a'), GuessResult::AlreadyGuessed);
+        assert_eq!(game.word_so_far(), "Pa---");
+        assert_eq!(game.guessed_chars, vec!['p', 'a']);
+        for c in "RIS".chars() {
+            game.guess(c);
+        }
+        assert!(game.is_won());
+        assert_eq!(normalize('É'), 'é');
+        assert_eq!(normalize('İ'), 'İ');
+    }
+
+    #[test]
+    fn test_gallows_stage() {
+        let stages = |limit| {
+            (0..=limit)
+                .map(|n| gallows_stage(n, limit))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(stages(5), vec![0, 2, 3, 4, 5, 6]);
+        assert_eq!(stages(6), vec![0, 1, 2, 3, 4, 5, 6]);
+        assert_eq!(stages(12), vec![0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6]);
+        assert_eq!(stages(1), vec![0, 6]);
+        assert_eq!(gallows(5, 5), GALLOWS_STAGES[6]);
+    }
+
+    #[test]
+    fn test_tally() {
+        let mut tally = Tally::default();
+        for result in &[
+            RoundResult::Won,
+            RoundResult::Lost,
+            RoundResult::Won,
+            RoundResult::Won,
+            RoundResult::Abandoned,
+        ] {
+            tally.record(result, Some(Difficulty::Hard));
+        }
+        assert_eq!(
+            tally,
+            Tally {
+                played: 4,
+                wins: 3,
+                losses: 1,
+                streak: 2,
+                difficulty: Some(Difficulty::Hard),
+            }
+        );
+        assert_eq!(
+            tally.to_string(),
+            "Games played: 4, wins: 3, losses: 1, current streak: 2 (difficulty: hard)"
+        );
+        assert!(!Tally::default().to_string().contains("difficulty"));
+    }
+
+    #[test]
+    fn test_ask_yes_no() {
+        let ask = |answers: &str| ask_yes_no(&mut io::Cursor::new(answers), "Play again?");
+        assert!(ask("maybe\nY\n"));
+        assert!(!ask("no\n"));
+        assert!(!ask(""));
+    }
+
+    #[test]
+    fn test_word_guesses() {
+        let mut game = Game::new("Lobster", 5);
+        assert_eq!(game.guess_word("lobsters"), GuessResult::Incorrect);
+        assert_eq!(game.guess_word(" LOBSTERS "), GuessResult::AlreadyGuessed);
+        assert_eq!(game.guesses_left(), 5 - WRONG_WORD_PENALTY);
+        assert_eq!(game.wrong_words, vec!["lobsters".to_string()]);
+        assert!(!game.is_won());
+        assert_eq!(game.guess_word(" lObStEr\n"), GuessResult::Correct);
+        assert!(game.is_won());
+        assert_eq!(game.word_so_far(), "Lobster");
+    }
+
+    #[test]
+    fn test_multi_byte_words() {
+        let mut game = Game::new("naïve", 5);
+        assert_eq!(game.word_so_far(), "-----");
+        assert_eq!(game.guess('Ï'), GuessResult::Correct);
+        assert_eq!(game.guess('e'), GuessResult::Correct);
+        assert_eq!(game.word_so_far(), "--ï-e");
+        for c in "nav".chars() {
+            game.guess(c);
+        }
+        assert!(game.is_won());
+
+        let mut game = Game::new("東京都", 5);
+        assert_eq!(game.word_so_far(), "---");
+        assert_eq!(game.guess('京'), GuessResult::Correct);
+        assert_eq!(game.guess('大'), GuessResult::Incorrect);
+        assert_eq!(game.word_so_far(), "-京-");
+        assert_eq!(game.guess_word("東京都"), GuessResult::Correct);
+        assert!(game.is_won());
+    }
+
+    #[test]
+    fn test_phrases() {
+        let mut game = Game::new("don't panic", 5);
+        assert_eq!(game.word_so_far(), "---'- -----");
+        for c in "dontpaic".chars() {
+            assert_eq!(game.guess(c), GuessResult::Correct);
+        }
+        assert!(game.is_won());
+
+        let mut game = Game::new("New  York", 5);
+        assert_eq!(game.word_so_far(), "---  ----");
+        assert_eq!(
+            parse_guess(" new   york\n"),
+            Ok(Guess::Word("new york".to_string()))
+        );
+        assert_eq!(game.guess_word("new york"), GuessResult::Correct);
+        assert!(game.is_won());
+
+        assert_eq!(parse_guess("-"), Err(InputError::NotALetter('-')));
+        assert_eq!(parse_guess("'"), Err(InputError::NotALetter('\'')));
+        assert_eq!(
+            parse_guess("- -"),
+            Err(InputError::NotAWord("- -".to_string()))
+        );
+        assert_eq!(
+            parse_guess("well-known"),
+            Ok(Guess::Word("well-known".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_validate_secret() {
+        assert_eq!(validate_secret("  Lobster\n"), Ok("Lobster".to_string()));
+        assert_eq!(
+            validate_secret("don't  panic\n"),
+            Ok("don't panic".to_string())
+        );
+        assert!(validate_secret("\n").is_err());
+        assert!(validate_secret("r2d2\n").is_err());
+        assert!(validate_secret("- '\n").is_err());
+    }
+
+    #[test]
+    fn test_read_secret_word() {
+        let mut input = io::Cursor::new("\nsecret\nsecert\nsecret\nsecret\n");
+        assert_eq!(
+            read_secret_word(&mut input, false),
+            Some("secret".to_string())
+        );
+        assert_eq!(read_secret_word(&mut input, false), None);
+    }
+
+    #[test]
+    fn test_difficulty() {
+        let args = |line: &str| {
+            line.split_whitespace()
+                .map(String::from)
+                .collect::<Vec<_>>()
+        };
+        let config = parse_args(&args("--difficulty easy")).unwrap().unwrap();
+        assert_eq!(config.difficulty, Some(Difficulty::Easy));
+        assert_eq!(config.num_incorrect_guesses, 7);
+        let config = parse_args(&args("-g 3 -d hard")).unwrap().unwrap();
+        assert_eq!(config.num_incorrect_guesses, 3);
+        assert!(parse_args(&args("-d impossible")).is_err());
+
+        let words = args("lobster aluminum immutable new-york-city");
+        let mut config = parse_args(&[]).unwrap().unwrap();
+        config.difficulty = Some(Difficulty::Medium);
+        for _ in 0..10 {
+            let word = pick_a_random_word(&config, &words).unwrap();
+            assert!(word == "lobster" || word == "aluminum" || word == "immutable");
+        }
+        config.difficulty = Some(Difficulty::Easy);
+        assert!(pick_a_random_word(&config, &words).is_err());
+        assert!(Difficulty::Hard.suits("don't panic now"));
+    }
+
+    #[test]
+    fn test_read_guess() {
+        let mut input = io::Cursor::new("\nab?\n3\nk\nzip\n");
+        assert_eq!(read_guess(&mut input), Some(Guess::Letter('k')));
+        assert_eq!(read_guess(&mut input), Some(Guess::Word("zip".to_string())));
+        assert_eq!(read_guess(&mut input), None);
     }
 }
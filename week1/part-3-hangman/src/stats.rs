@@ -0,0 +1,261 @@
+// Statistics kept across sessions in ~/.hangman_stats
+//
+// The file has one statistic per line, a name followed by numbers:
+//
+//     wins 3
+//     losses 2
+//     current_streak 1
+//     best_streak 2
+//     letters_guessed 40
+//     length 7 2 3
+//     difficulty hard 1 2
+//
+// where each `length` line gives a word length (in letters), and how many games with a word of
+// that length were won and played, and each `difficulty` line does the same for games played at
+// a `--difficulty`.
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use Difficulty;
+
+const STATS_FILE_NAME: &str = ".hangman_stats";
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Stats {
+    pub wins: u32,
+    pub losses: u32,
+    // Wins since the last loss
+    pub current_streak: u32,
+    pub best_streak: u32,
+    // Letters guessed over all games, right or wrong
+    pub letters_guessed: u32,
+    // For each length of word (in letters), how many games were won and how many were played
+    pub by_length: BTreeMap<usize, (u32, u32)>,
+    // The same for each difficulty, for games played with one
+    pub by_difficulty: BTreeMap<Difficulty, (u32, u32)>,
+}
+
+// Returns the path of the stats file, in the home directory.
+pub fn stats_path() -> Result<String, String> {
+    let home = env::var("HOME").map_err(|_| "HOME is not set".to_string())?;
+    Ok(format!("{}/{}", home, STATS_FILE_NAME))
+}
+
+impl Stats {
+    // Reads the stats in `path`. A file that doesn't exist yet holds no stats.
+    pub fn load(path: &str) -> Result<Stats, String> {
+        match fs::read_to_string(path) {
+            Ok(contents) => Stats::parse(&contents).map_err(|err| format!("{}: {}", path, err)),
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => Ok(Stats::default()),
+            Err(err) => Err(format!("Unable to read {}: {}", path, err)),
+        }
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        fs::write(path, self.to_file_format())
+            .map_err(|err| format!("Unable to write {}: {}", path, err))
+    }
+
+    fn parse(contents: &str) -> Result<Stats, String> {
+        let mut stats = Stats::default();
+        for (i, line) in contents.lines().enumerate() {
+            let mut fields = line.split_whitespace();
+            let name = match fields.next() {
+                Some(name) => name,
+                None => continue,
+            };
+            let unexpected = || format!("line {}: unexpected \"{}\"", i + 1, line);
+            // The only field that isn't a number is the name on a `difficulty` line
+            let difficulty = if name == "difficulty" {
+                Some(
+                    fields
+                        .next()
+                        .and_then(Difficulty::parse)
+                        .ok_or_else(unexpected)?,
+                )
+            } else {
+                None
+            };
+            let numbers = fields
+                .map(|field| field.parse::<u32>())
+                .collect::<Result<Vec<u32>, _>>()
+                .map_err(|_| format!("line {}: bad number in \"{}\"", i + 1, line))?;
+            match (name, numbers.as_slice(), difficulty) {
+                ("wins", &[n], _) => stats.wins = n,
+                ("losses", &[n], _) => stats.losses = n,
+                ("current_streak", &[n], _) => stats.current_streak = n,
+                ("best_streak", &[n], _) => stats.best_streak = n,
+                ("letters_guessed", &[n], _) => stats.letters_guessed = n,
+                ("length", &[length, wins, played], _) if wins <= played => {
+                    stats.by_length.insert(length as usize, (wins, played));
+                }
+                ("difficulty", &[wins, played], Some(difficulty)) if wins <= played => {
+                    stats.by_difficulty.insert(difficulty, (wins, played));
+                }
+                _ => return Err(unexpected()),
+            }
+        }
+        Ok(stats)
+    }
+
+    fn to_file_format(&self) -> String {
+        let mut contents = format!(
+            "wins {}\nlosses {}\ncurrent_streak {}\nbest_streak {}\nletters_guessed {}\n",
+            self.wins, self.losses, self.current_streak, self.best_streak, self.letters_guessed
+        );
+        for (length, &(wins, played)) in &self.by_length {
+            contents.push_str(&format!("length {} {} {}\n", length, wins, played));
+        }
+        for (difficulty, &(wins, played)) in &self.by_difficulty {
+            contents.push_str(&format!(
+                "difficulty {} {} {}\n",
+                difficulty.name(),
+                wins,
+                played
+            ));
+        }
+        contents
+    }
+
+    // Counts a finished game with a word of `word_length` letters, in which the player guessed
+    // `letters_guessed` letters, at `difficulty` if one was chosen.
+    pub fn record(
+        &mut self,
+        won: bool,
+        word_length: usize,
+        letters_guessed: usize,
+        difficulty: Option<Difficulty>,
+    ) {
+        count_game(self.by_length.entry(word_length).or_insert((0, 0)), won);
+        if let Some(difficulty) = difficulty {
+            count_game(self.by_difficulty.entry(difficulty).or_insert((0, 0)), won);
+        }
+        if won {
+            self.wins = self.wins.saturating_add(1);
+            self.current_streak = self.current_streak.saturating_add(1);
+            self.best_streak = self.best_streak.max(self.current_streak);
+        } else {
+            self.losses = self.losses.saturating_add(1);
+            self.current_streak = 0;
+        }
+        let letters_guessed = letters_guessed.min(u32::MAX as usize) as u32;
+        self.letters_guessed = self.letters_guessed.saturating_add(letters_guessed);
+    }
+}
+
+// Adds a game to a count of games won and played.
+fn count_game(counts: &mut (u32, u32), won: bool) {
+    counts.1 = counts.1.saturating_add(1);
+    if won {
+        counts.0 = counts.0.saturating_add(1);
+    }
+}
+
+// Returns `wins` out of `played` as a whole percentage. The sums are done in u64 so they can't
+// overflow.
+fn percentage(wins: u64, played: u64) -> u64 {
+    (wins * 100 + played / 2).checked_div(played).unwrap_or(0)
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let played = u64::from(self.wins) + u64::from(self.losses);
+        writeln!(f, "Games played: {}", played)?;
+        writeln!(
+            f,
+            "Wins: {} ({}%)",
+            self.wins,
+            percentage(u64::from(self.wins), played)
+        )?;
+        writeln!(f, "Losses: {}", self.losses)?;
+        writeln!(f, "Current streak: {}", self.current_streak)?;
+        writeln!(f, "Best streak: {}", self.best_streak)?;
+        write!(f, "Letters guessed: {}", self.letters_guessed)?;
+        if !self.by_length.is_empty() {
+            write!(f, "\nWin rate by word length:")?;
+        }
+        for (length, &(wins, played)) in &self.by_length {
+            write!(
+                f,
+                "\n  {:>2} letters: {} of {} ({}%)",
+                length,
+                wins,
+                played,
+                percentage(u64::from(wins), u64::from(played))
+            )?;
+        }
+        if !self.by_difficulty.is_empty() {
+            write!(f, "\nWin rate by difficulty:")?;
+        }
+        for (difficulty, &(wins, played)) in &self.by_difficulty {
+            write!(
+                f,
+                "\n  {:>6}: {} of {} ({}%)",
+                difficulty.name(),
+                wins,
+                played,
+                percentage(u64::from(wins), u64::from(played))
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_record() {
+        let mut stats = Stats::default();
+        stats.record(true, 7, 9, None);
+        stats.record(true, 7, 8, Some(Difficulty::Medium));
+        stats.record(false, 5, 10, Some(Difficulty::Easy));
+        stats.record(true, 5, 6, Some(Difficulty::Easy));
+        assert_eq!(stats.wins, 3);
+        assert_eq!(stats.losses, 1);
+        assert_eq!(stats.current_streak, 1);
+        assert_eq!(stats.best_streak, 2);
+        assert_eq!(stats.letters_guessed, 33);
+        assert_eq!(stats.by_length[&7], (2, 2));
+        assert_eq!(stats.by_length[&5], (1, 2));
+        assert_eq!(stats.by_difficulty[&Difficulty::Easy], (1, 2));
+        assert_eq!(stats.by_difficulty[&Difficulty::Medium], (1, 1));
+        assert!(!stats.by_difficulty.contains_key(&Difficulty::Hard));
+    }
+
+    #[test]
+    fn test_file_format() {
+        let mut stats = Stats::default();
+        stats.record(true, 7, 9, None);
+        stats.record(false, 12, 4, Some(Difficulty::Hard));
+        assert_eq!(Stats::parse(&stats.to_file_format()), Ok(stats));
+        assert_eq!(Stats::parse(""), Ok(Stats::default()));
+        assert!(Stats::parse("wins lots\n").is_err());
+        assert!(Stats::parse("wins 1 2\n").is_err());
+        assert!(Stats::parse("length 7 3 2\n").is_err());
+        assert!(Stats::parse("difficulty extreme 1 2\n").is_err());
+        assert!(Stats::parse("difficulty 1 2\n").is_err());
+        assert!(Stats::parse("{\"wins\": 1}\n").is_err());
+    }
+
+    #[test]
+    fn test_large_counts() {
+        let mut stats = Stats::parse(&format!(
+            "wins {0}\nlosses {0}\ncurrent_streak {0}\nletters_guessed {0}\nlength 7 {0} {0}\n",
+            u32::MAX
+        ))
+        .unwrap();
+        stats.record(true, 7, 9, None);
+        assert_eq!(stats.wins, u32::MAX);
+        assert_eq!(stats.current_streak, u32::MAX);
+        assert_eq!(stats.letters_guessed, u32::MAX);
+        assert_eq!(stats.by_length[&7], (u32::MAX, u32::MAX));
+        let shown = stats.to_string();
+        assert!(shown.contains(&format!("Games played: {}", 2 * u64::from(u32::MAX))));
+        assert!(shown.contains("Wins: 4294967295 (50%)"));
+        assert!(shown.contains("of 4294967295 (100%)"));
+    }
+}